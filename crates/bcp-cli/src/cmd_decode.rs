@@ -1,8 +1,8 @@
 /// Implementation of `bcp decode`.
 ///
-/// Reads a BCP file, decodes all blocks with `BcpDecoder`, then passes
-/// the block slice to `DefaultDriver::render` for model-ready text
-/// output. The output is written to stdout or to `-o <file>`.
+/// Reads a BCP file and hands it to `DefaultDriver::decode`, which decodes
+/// the blocks and renders them as model-ready text in one call. The
+/// output is written to stdout or to `-o <file>`.
 ///
 /// # Output modes
 ///
@@ -13,6 +13,8 @@
 /// │ xml      │ <code lang="rust" path="...">...</code>   (default)          │
 /// │ markdown │ ```rust\n// src/main.rs\n...\n```                            │
 /// │ minimal  │ --- src/main.rs [rust] ---\n...                              │
+/// │ raw      │ code b64 AQAgAQABAgELc3JjL21h...  (alias raw-b64)            │
+/// │ raw-b85  │ code b85 0RSKY009C43v+T~FK...     (RFC 1924 base85)          │
 /// └──────────┴──────────────────────────────────────────────────────────────┘
 /// ```
 ///
@@ -33,24 +35,90 @@
 /// is exhausted (RFC §5.5). Without `--budget`, adaptive mode behaves like
 /// `full`.
 ///
+/// `--budget-strategy optimal` shares the budget out across all blocks at
+/// once instead of first-come-first-served within each priority tier, so
+/// one large block cannot crowd out several smaller ones.
+///
+/// `--query "why does the client time out?"` scores every block against
+/// the task text (BM25 over content, plus path and tool-name matches)
+/// and, within each priority tier, keeps the best matches and degrades
+/// the rest first.
+///
+/// `--keep-turns 4` budgets conversation turns as a conversation: the
+/// system turn and the last four turns always render in full, and older
+/// turns degrade to summaries, then placeholders, newest first. A tool
+/// call and its results stay together.
+///
+/// `--group` keeps related blocks together under the budget: a diff and
+/// the code of the same path, a tool call and its output, an embedding
+/// reference and its source, or two blocks joined by a link all render
+/// in full, or are all cut down.
+///
+/// `--dedup` renders repeated content once: two fragments of
+/// `src/main.rs` with overlapping line ranges are merged, and a tool
+/// result that prints lines already shown becomes
+/// `[see code src/main.rs L10-40]`.
+///
+/// `--order edges` puts the most important blocks (by priority, then
+/// relevance) at the start and end of the output, where models attend
+/// best; `--order priority` and `--order type` sort by priority or
+/// group by block type. Each block keeps its budget decision.
+///
+/// `--path-prefix factor` declares the directory every code and diff
+/// path shares once, as `<context root="services/billing/src">`, and
+/// shows paths relative to it; `full` keeps full paths. The default,
+/// `auto`, factors in minimal mode only.
+///
 /// # Type filtering
 ///
 /// `--include code,conversation` limits rendering to those block types.
 /// All other blocks are silently excluded from the output.
+///
+/// # Tag filtering
+///
+/// `--tag test` keeps only blocks with a `test` Tag annotation;
+/// `--exclude-tag generated,vendored` drops blocks carrying either tag.
+/// Exclusion wins when a block matches both.
+///
+/// # Token estimation
+///
+/// `--estimator heuristic` prices blocks at 4 chars per token instead of
+/// the default code-aware estimate; `--vocab cl100k_base.tiktoken` counts
+/// them exactly with a local BPE vocabulary. Only affects `--budget`
+/// decisions.
+///
+/// # File tree markers
+///
+/// `--recent-within 3600` marks FILE_TREE entries whose mtime falls in the
+/// last hour as `modified recently`.
+///
+/// # Render report
+///
+/// `--report` prints one line per block to stderr — how it was rendered,
+/// why, its estimated tokens and its byte range in the output — followed
+/// by the total against `--budget`. With `--query`, a Score column shows
+/// each block's match. The rendered text is unchanged.
 use std::fs;
 use std::io::{self, Write as _};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{Context, Result, anyhow};
-use bcp_decoder::BcpDecoder;
-use bcp_driver::{DefaultDriver, DriverConfig, BcpDriver, OutputMode, Verbosity};
+use bcp_driver::{
+    BlockOrdering, BpeEstimator, BudgetStrategy, CodeAwareEstimator, ConversationPolicy,
+    DecodeConfig, DefaultDriver, DriverConfig, HeuristicEstimator, ModelFamily, OutputMode,
+    PathPrefix, PayloadError, RawEncoding, RenderDecision, RenderReport, TokenEstimator, Verbosity,
+};
 use bcp_types::block_type::BlockType;
 
 use crate::DecodeArgs;
+use crate::cmd_stats::block_type_label;
 
 /// Run the `bcp decode` command.
 ///
-/// Decodes the BCP file, applies the mode / verbosity / budget / include
-/// configuration, renders the blocks via [`DefaultDriver`], and writes the
+/// Builds the mode / verbosity / budget / include / tag configuration,
+/// decodes and renders the file via [`DefaultDriver::decode`], and writes the
 /// result to stdout or an output file.
 ///
 /// # Errors
@@ -62,29 +130,63 @@ pub fn run(args: &DecodeArgs) -> Result<()> {
     let bytes =
         fs::read(&args.file).with_context(|| format!("cannot read {}", args.file.display()))?;
 
-    let decoded = BcpDecoder::decode(&bytes)
-        .with_context(|| format!("failed to decode {}", args.file.display()))?;
-
-    let mode = parse_output_mode(&args.mode)?;
+    let mode = args.mode.as_deref().map(parse_output_mode).transpose()?;
+    let target_model = args.model.as_deref().map(parse_model_family).transpose()?;
     let verbosity = parse_verbosity(&args.verbosity)?;
+    let budget_strategy = parse_budget_strategy(&args.budget_strategy)?;
+    let ordering = parse_ordering(&args.order)?;
+    let path_prefix = parse_path_prefix(&args.path_prefix)?;
     let include_types = args
         .include
         .as_deref()
         .map(parse_include_types)
         .transpose()?;
+    let recent_since = args.recent_within.map(recent_cutoff);
+    // Leave the estimator unset unless asked for, so a --model profile's
+    // token ratio applies.
+    let estimator = if args.estimator.is_some() || args.vocab.is_some() {
+        let name = args.estimator.as_deref().unwrap_or("code-aware");
+        Some(resolve_estimator(name, args.vocab.as_deref())?)
+    } else {
+        None
+    };
 
-    let config = DriverConfig {
+    let driver = DriverConfig {
         mode,
         verbosity,
+        budget_strategy,
+        query: args.query.clone(),
+        conversation: args
+            .keep_turns
+            .map(|recent_turns| ConversationPolicy { recent_turns }),
+        group_dependencies: args.group,
+        dedup: args.dedup,
+        ordering,
+        path_prefix,
         token_budget: args.budget,
         include_types,
-        target_model: None,
+        target_model,
+        recent_since,
+        include_tags: (!args.tags.is_empty()).then(|| args.tags.clone()),
+        exclude_tags: args.exclude_tags.clone(),
+        current_time: None,
+        estimator,
+    };
+    let config = DecodeConfig {
+        driver,
+        content_store: None,
     };
 
-    let driver = DefaultDriver;
-    let rendered = driver
-        .render(&decoded.blocks, &config)
-        .with_context(|| "driver render failed")?;
+    let out = DefaultDriver::decode(&bytes, &config).map_err(|err| match err {
+        PayloadError::Decode(err) => {
+            anyhow!(err).context(format!("failed to decode {}", args.file.display()))
+        }
+        PayloadError::Render(err) => anyhow!(err).context("driver render failed"),
+    })?;
+    if args.report {
+        print_report(&out.report);
+    }
+    let rendered = out.text;
 
     if let Some(path) = &args.output {
         fs::write(path, rendered.as_bytes())
@@ -103,6 +205,81 @@ pub fn run(args: &DecodeArgs) -> Result<()> {
     Ok(())
 }
 
+/// Prints the `--report` table to stderr.
+///
+/// ```text
+/// Block  Type             Decision     Reason              Tokens  Bytes
+/// ──────────────────────────────────────────────────────────────────────────
+/// 0      CODE             full         critical                 3  10..43     src/main.rs
+/// 2      CODE             summary      over-budget              4  45..98     src/lib.rs
+/// ──────────────────────────────────────────────────────────────────────────
+/// Total: ~7 tokens of 10 budget (3 left)
+/// ```
+///
+/// When `--dedup` saved tokens, a Saved column follows Tokens; when the
+/// render had a query, a Score column follows them.
+fn print_report(report: &RenderReport) {
+    let scored = report.blocks.iter().any(|b| b.query_score.is_some());
+    let deduped = report.blocks.iter().any(|b| b.saved_tokens > 0);
+    let sep = "─".repeat(74 + if scored { 7 } else { 0 } + if deduped { 7 } else { 0 });
+    eprintln!(
+        "Block  Type             Decision     Reason              Tokens  {}{}Bytes",
+        if deduped { "Saved  " } else { "" },
+        if scored { "Score  " } else { "" },
+    );
+    eprintln!("{sep}");
+    for b in &report.blocks {
+        let decision = match b.decision {
+            RenderDecision::Full => "full",
+            RenderDecision::Summary => "summary",
+            RenderDecision::Outline => "outline",
+            RenderDecision::Truncated { .. } => "truncated",
+            RenderDecision::Placeholder { .. } => "placeholder",
+            RenderDecision::Omit => "omit",
+        };
+        let bytes = format!("{}..{}", b.range.start, b.range.end);
+        let saved = if deduped {
+            format!("{:>5}  ", b.saved_tokens)
+        } else {
+            String::new()
+        };
+        let score = match b.query_score {
+            Some(score) => format!("{score:>5.2}  "),
+            None if scored => "    -  ".to_string(),
+            None => String::new(),
+        };
+        eprintln!(
+            "{:<7}{:<17}{decision:<13}{:<19}{:>7}  {saved}{score}{bytes:<10} {}",
+            b.block_index,
+            block_type_label(&b.block_type),
+            b.reason.label(),
+            b.estimated_tokens,
+            b.description,
+        );
+    }
+    eprintln!("{sep}");
+    match (report.token_budget, report.headroom()) {
+        (Some(budget), Some(left)) if left >= 0 => eprintln!(
+            "Total: ~{} tokens of {budget} budget ({left} left)",
+            report.total_tokens
+        ),
+        (Some(budget), Some(left)) => eprintln!(
+            "Total: ~{} tokens of {budget} budget ({} over)",
+            report.total_tokens, -left
+        ),
+        _ => eprintln!("Total: ~{} tokens (no budget)", report.total_tokens),
+    }
+}
+
+/// Converts a `--recent-within` window into an absolute Unix timestamp
+/// cutoff for [`DriverConfig::recent_since`].
+fn recent_cutoff(window_secs: u64) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    now.saturating_sub(window_secs)
+}
+
 // ── Flag parsers ──────────────────────────────────────────────────────────────
 
 /// Parses the `--mode` string to an [`OutputMode`].
@@ -115,8 +292,27 @@ fn parse_output_mode(s: &str) -> Result<OutputMode> {
         "xml" => Ok(OutputMode::Xml),
         "markdown" | "md" => Ok(OutputMode::Markdown),
         "minimal" => Ok(OutputMode::Minimal),
+        "raw" | "raw-b64" => Ok(OutputMode::Raw(RawEncoding::Base64)),
+        "raw-b85" => Ok(OutputMode::Raw(RawEncoding::Base85)),
+        _ => Err(anyhow!(
+            "unknown mode {s:?} — expected xml|markdown|minimal|raw|raw-b85"
+        )),
+    }
+}
+
+/// Parses the `--model` string to a [`ModelFamily`].
+///
+/// # Errors
+///
+/// Returns an error for unrecognised family names.
+fn parse_model_family(s: &str) -> Result<ModelFamily> {
+    match s.to_lowercase().as_str() {
+        "claude" => Ok(ModelFamily::Claude),
+        "gpt" => Ok(ModelFamily::Gpt),
+        "gemini" => Ok(ModelFamily::Gemini),
+        "generic" => Ok(ModelFamily::Generic),
         _ => Err(anyhow!(
-            "unknown mode {s:?} — expected xml|markdown|minimal"
+            "unknown model {s:?} — expected claude|gpt|gemini|generic"
         )),
     }
 }
@@ -137,6 +333,78 @@ fn parse_verbosity(s: &str) -> Result<Verbosity> {
     }
 }
 
+/// Parses the `--budget-strategy` string to a [`BudgetStrategy`].
+///
+/// # Errors
+///
+/// Returns an error for unrecognised strategy names.
+fn parse_budget_strategy(s: &str) -> Result<BudgetStrategy> {
+    match s.to_lowercase().as_str() {
+        "greedy" => Ok(BudgetStrategy::Greedy),
+        "optimal" => Ok(BudgetStrategy::Optimal),
+        _ => Err(anyhow!(
+            "unknown budget strategy {s:?} — expected greedy|optimal"
+        )),
+    }
+}
+
+/// Parses the `--order` string to a [`BlockOrdering`].
+///
+/// # Errors
+///
+/// Returns an error for unrecognised ordering names.
+fn parse_ordering(s: &str) -> Result<BlockOrdering> {
+    match s.to_lowercase().as_str() {
+        "payload" => Ok(BlockOrdering::Payload),
+        "priority" => Ok(BlockOrdering::PriorityFirst),
+        "edges" => Ok(BlockOrdering::Edges),
+        "type" => Ok(BlockOrdering::ByType),
+        _ => Err(anyhow!(
+            "unknown order {s:?} — expected payload|priority|edges|type"
+        )),
+    }
+}
+
+/// Parses the `--path-prefix` string to a [`PathPrefix`].
+///
+/// # Errors
+///
+/// Returns an error for unrecognised names.
+fn parse_path_prefix(s: &str) -> Result<PathPrefix> {
+    match s.to_lowercase().as_str() {
+        "auto" => Ok(PathPrefix::Auto),
+        "factor" => Ok(PathPrefix::Factor),
+        "full" => Ok(PathPrefix::Full),
+        _ => Err(anyhow!(
+            "unknown path prefix {s:?} — expected auto|factor|full"
+        )),
+    }
+}
+
+/// Resolves `--estimator` / `--vocab` to a shared [`TokenEstimator`].
+///
+/// A vocabulary path wins: it loads a [`BpeEstimator`] for exact counts.
+/// Otherwise the estimator is chosen by name.
+///
+/// # Errors
+///
+/// Returns an error if the vocabulary cannot be loaded, or for
+/// unrecognised estimator names.
+pub(crate) fn resolve_estimator(s: &str, vocab: Option<&Path>) -> Result<Arc<dyn TokenEstimator>> {
+    if let Some(path) = vocab {
+        let bpe = BpeEstimator::load(path)
+            .with_context(|| format!("cannot load vocabulary {}", path.display()))?;
+        return Ok(Arc::new(bpe));
+    }
+    match s.to_lowercase().as_str() {
+        "code-aware" | "code_aware" => Ok(Arc::new(CodeAwareEstimator)),
+        "heuristic" => Ok(Arc::new(HeuristicEstimator)),
+        _ => Err(anyhow!(
+            "unknown estimator {s:?} — expected code-aware|heuristic"
+        )),
+    }
+}
+
 /// Parses a comma-separated `--include` string to a list of [`BlockType`]s.
 ///
 /// # Errors
@@ -53,7 +53,7 @@
 /// ┌──────────────────┬──────────────────────────────────────────────────────┐
 /// │ Type             │ Required fields                                      │
 /// ├──────────────────┼──────────────────────────────────────────────────────┤
-/// │ code             │ lang, path, content (or content_file)                │
+/// │ code             │ path, content (or content_file); lang optional       │
 /// │ conversation     │ role, content (or content_file)                      │
 /// │ tool_result      │ name, content (or content_file)                      │
 /// │ document         │ title, content (or content_file)                     │
@@ -64,6 +64,21 @@
 /// Optional fields for all blocks: `summary` (string), `priority`
 /// (`critical` | `high` | `normal` | `low` | `background`).
 ///
+/// Optional annotation fields for all blocks:
+///
+/// ```text
+/// ┌────────────┬──────────────────────────────────────────────────────┐
+/// │ Field      │ Value                                                │
+/// ├────────────┼──────────────────────────────────────────────────────┤
+/// │ relevance  │ score from 0.0 to 1.0                                │
+/// │ source     │ provenance: "tool:…", "url:…" or "commit:…"          │
+/// │ expires_at │ Unix seconds after which the block is dropped        │
+/// │ ttl        │ seconds from now after which the block is dropped    │
+/// │ links      │ [{"kind": "tests", "target": 0}, …] — kind is        │
+/// │            │ depends-on | tests | explains, target a blocks index │
+/// └────────────┴──────────────────────────────────────────────────────┘
+/// ```
+///
 /// # Flags
 ///
 /// ```text
@@ -73,15 +88,18 @@
 /// │ --compress-blocks    │ zstd-compress each block body individually  │
 /// │ --compress-payload   │ zstd-compress all blocks as one stream      │
 /// │ --dedup              │ BLAKE3 dedup via in-memory content store    │
+/// │ --validate           │ Parse structured_data (json/ndjson/xml)     │
 /// └──────────────────────┴─────────────────────────────────────────────┘
 /// ```
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Context, Result, anyhow};
 use bcp_encoder::{BcpEncoder, MemoryContentStore};
-use bcp_types::enums::{DataFormat, FormatHint, Lang, Priority, Role, Status};
+use bcp_types::annotation::Provenance;
+use bcp_types::enums::{DataFormat, FormatHint, Lang, LinkKind, Priority, Role, Status};
 
 use crate::EncodeArgs;
 
@@ -100,9 +118,11 @@ struct Manifest {
 #[derive(serde::Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 enum ManifestBlock {
-    /// Source code block. `lang` must be a recognised language name.
+    /// Source code block. `lang` is a language name or alias; when
+    /// omitted, the language is detected from `path` and the content.
+    /// Unrecognised names map to [`Lang::Unknown`].
     Code {
-        lang: String,
+        lang: Option<String>,
         path: String,
         /// Inline text content. Mutually exclusive with `content_file`.
         content: Option<String>,
@@ -110,6 +130,8 @@ enum ManifestBlock {
         content_file: Option<String>,
         summary: Option<String>,
         priority: Option<String>,
+        #[serde(flatten)]
+        annotations: ManifestAnnotations,
     },
     /// Conversation turn (system / user / assistant / tool).
     Conversation {
@@ -118,6 +140,8 @@ enum ManifestBlock {
         content_file: Option<String>,
         summary: Option<String>,
         priority: Option<String>,
+        #[serde(flatten)]
+        annotations: ManifestAnnotations,
     },
     /// Tool invocation result.
     ToolResult {
@@ -126,6 +150,8 @@ enum ManifestBlock {
         status: Option<String>,
         content: Option<String>,
         content_file: Option<String>,
+        #[serde(flatten)]
+        annotations: ManifestAnnotations,
     },
     /// Free-form document (prose, references, specs).
     Document {
@@ -136,6 +162,8 @@ enum ManifestBlock {
         content_file: Option<String>,
         summary: Option<String>,
         priority: Option<String>,
+        #[serde(flatten)]
+        annotations: ManifestAnnotations,
     },
     /// Structured / tabular data.
     StructuredData {
@@ -143,9 +171,43 @@ enum ManifestBlock {
         format: String,
         content: Option<String>,
         content_file: Option<String>,
+        #[serde(flatten)]
+        annotations: ManifestAnnotations,
     },
 }
 
+/// Annotation fields accepted on every manifest block.
+#[derive(serde::Deserialize)]
+struct ManifestAnnotations {
+    relevance: Option<f32>,
+    source: Option<String>,
+    expires_at: Option<u64>,
+    ttl: Option<u64>,
+    #[serde(default)]
+    links: Vec<ManifestLink>,
+}
+
+/// A link from the enclosing block to another manifest block.
+#[derive(serde::Deserialize)]
+struct ManifestLink {
+    /// `depends-on` | `tests` | `explains`.
+    kind: String,
+    /// Zero-based position of the linked block in `blocks`.
+    target: usize,
+}
+
+impl ManifestBlock {
+    fn annotations(&self) -> &ManifestAnnotations {
+        match self {
+            Self::Code { annotations, .. }
+            | Self::Conversation { annotations, .. }
+            | Self::ToolResult { annotations, .. }
+            | Self::Document { annotations, .. }
+            | Self::StructuredData { annotations, .. } => annotations,
+        }
+    }
+}
+
 // ── Public entry point ────────────────────────────────────────────────────────
 
 /// Run the `bcp encode` command.
@@ -185,11 +247,23 @@ pub fn run(args: &EncodeArgs) -> Result<()> {
     if args.compress_payload {
         encoder.compress_payload();
     }
+    if args.validate {
+        encoder.validate_structured_data();
+    }
 
+    // Annotations shift stream indices, so record each manifest block's
+    // stream id for resolving links once every block is in place.
+    let mut block_ids = Vec::with_capacity(manifest.blocks.len());
     for (idx, block) in manifest.blocks.iter().enumerate() {
+        block_ids.push(encoder.next_block_id());
         apply_block(&mut encoder, block, manifest_dir)
+            .and_then(|()| apply_annotations(&mut encoder, block.annotations()))
             .with_context(|| format!("block {idx}: failed to apply"))?;
     }
+    for (idx, block) in manifest.blocks.iter().enumerate() {
+        apply_links(&mut encoder, &block.annotations().links, idx, &block_ids)
+            .with_context(|| format!("block {idx}: invalid link"))?;
+    }
 
     let bytes = encoder
         .encode()
@@ -215,6 +289,7 @@ fn apply_block(encoder: &mut BcpEncoder, block: &ManifestBlock, manifest_dir: &P
             content_file,
             summary,
             priority,
+            ..
         } => {
             let bytes = resolve_content(
                 content.as_deref(),
@@ -222,8 +297,10 @@ fn apply_block(encoder: &mut BcpEncoder, block: &ManifestBlock, manifest_dir: &P
                 manifest_dir,
                 "code",
             )?;
-            let lang_val = parse_lang(lang);
-            encoder.add_code(lang_val, path, &bytes);
+            match lang {
+                Some(name) => encoder.add_code(Lang::from_name(name), path, &bytes),
+                None => encoder.add_code_auto(path, &bytes),
+            };
             apply_meta(encoder, summary.as_deref(), priority.as_deref())?;
         }
 
@@ -233,6 +310,7 @@ fn apply_block(encoder: &mut BcpEncoder, block: &ManifestBlock, manifest_dir: &P
             content_file,
             summary,
             priority,
+            ..
         } => {
             let bytes = resolve_content(
                 content.as_deref(),
@@ -250,6 +328,7 @@ fn apply_block(encoder: &mut BcpEncoder, block: &ManifestBlock, manifest_dir: &P
             status,
             content,
             content_file,
+            ..
         } => {
             let bytes = resolve_content(
                 content.as_deref(),
@@ -268,6 +347,7 @@ fn apply_block(encoder: &mut BcpEncoder, block: &ManifestBlock, manifest_dir: &P
             content_file,
             summary,
             priority,
+            ..
         } => {
             let bytes = resolve_content(
                 content.as_deref(),
@@ -286,6 +366,7 @@ fn apply_block(encoder: &mut BcpEncoder, block: &ManifestBlock, manifest_dir: &P
             format,
             content,
             content_file,
+            ..
         } => {
             let bytes = resolve_content(
                 content.as_deref(),
@@ -316,6 +397,53 @@ fn apply_meta(
     Ok(())
 }
 
+/// Applies relevance, provenance and expiry annotations to the most
+/// recently added block. Links are applied separately by [`apply_links`].
+fn apply_annotations(encoder: &mut BcpEncoder, ann: &ManifestAnnotations) -> Result<()> {
+    if let Some(score) = ann.relevance {
+        if !(0.0..=1.0).contains(&score) {
+            return Err(anyhow!("relevance {score} is outside 0.0..=1.0"));
+        }
+        encoder.with_relevance(score)?;
+    }
+    if let Some(ref source) = ann.source {
+        let source = Provenance::parse(source).ok_or_else(|| {
+            anyhow!("unknown source {source:?} — expected tool:…, url:… or commit:…")
+        })?;
+        encoder.with_provenance(&source)?;
+    }
+    if let Some(at) = ann.expires_at {
+        encoder.with_expiry(at)?;
+    }
+    if let Some(secs) = ann.ttl {
+        encoder.with_ttl(Duration::from_secs(secs))?;
+    }
+    Ok(())
+}
+
+/// Adds `Link` annotations from manifest block `from` to the blocks its
+/// `links` name, translating manifest positions into stream ids.
+fn apply_links(
+    encoder: &mut BcpEncoder,
+    links: &[ManifestLink],
+    from: usize,
+    block_ids: &[u32],
+) -> Result<()> {
+    for link in links {
+        let kind = LinkKind::from_name(&link.kind).ok_or_else(|| {
+            anyhow!(
+                "unknown link kind {:?} — expected depends-on|tests|explains",
+                link.kind
+            )
+        })?;
+        let to = block_ids
+            .get(link.target)
+            .ok_or_else(|| anyhow!("link target {} is out of range", link.target))?;
+        encoder.add_link(block_ids[from], kind, *to);
+    }
+    Ok(())
+}
+
 // ── Content resolution ────────────────────────────────────────────────────────
 
 /// Returns the UTF-8 bytes for a block's content field.
@@ -343,33 +471,6 @@ fn resolve_content(
 
 // ── Enum parsers ──────────────────────────────────────────────────────────────
 
-/// Maps a language name string to a [`Lang`] variant.
-///
-/// Unrecognised names map to [`Lang::Unknown`] rather than erroring, so
-/// arbitrary language tags in manifests produce a valid (if opaque) block.
-fn parse_lang(s: &str) -> Lang {
-    match s.to_lowercase().as_str() {
-        "rust" => Lang::Rust,
-        "typescript" | "ts" => Lang::TypeScript,
-        "javascript" | "js" => Lang::JavaScript,
-        "python" | "py" => Lang::Python,
-        "go" => Lang::Go,
-        "java" => Lang::Java,
-        "c" => Lang::C,
-        "cpp" | "c++" => Lang::Cpp,
-        "ruby" | "rb" => Lang::Ruby,
-        "shell" | "sh" | "bash" => Lang::Shell,
-        "sql" => Lang::Sql,
-        "html" => Lang::Html,
-        "css" => Lang::Css,
-        "json" => Lang::Json,
-        "yaml" | "yml" => Lang::Yaml,
-        "toml" => Lang::Toml,
-        "markdown" | "md" => Lang::Markdown,
-        _ => Lang::Unknown,
-    }
-}
-
 /// Parses a conversation role name.
 ///
 /// # Errors
@@ -447,8 +548,10 @@ fn parse_data_format(s: &str) -> Result<DataFormat> {
         "yaml" | "yml" => Ok(DataFormat::Yaml),
         "toml" => Ok(DataFormat::Toml),
         "csv" => Ok(DataFormat::Csv),
+        "xml" => Ok(DataFormat::Xml),
+        "ndjson" | "jsonl" | "jsonlines" => Ok(DataFormat::Ndjson),
         _ => Err(anyhow!(
-            "unknown data format {s:?} — expected json|yaml|toml|csv"
+            "unknown data format {s:?} — expected json|yaml|toml|csv|xml|ndjson"
         )),
     }
 }
@@ -21,6 +21,7 @@ use std::fs;
 
 use anyhow::{Context, Result};
 use bcp_decoder::BcpDecoder;
+use bcp_types::annotation::AnnotationBlock;
 use bcp_types::block::BlockContent;
 use bcp_types::enums::AnnotationKind;
 
@@ -159,13 +160,16 @@ fn block_detail(content: &BlockContent) -> String {
         BlockContent::Diff(d) => format!(" path={:?}", d.path),
         BlockContent::Annotation(a) => {
             let kind = annotation_kind_label(a.kind);
-            let value = format_annotation_value(a.kind, &a.value);
+            let value = format_annotation_value(a);
             format!(" target={} kind={kind} value={value:?}", a.target_block_id)
         }
         BlockContent::EmbeddingRef(_) => " [embedding ref]".to_string(),
         BlockContent::Image(i) => {
             let media = format!("{:?}", i.media_type).to_lowercase();
-            format!(" [{media}] alt={:?}", i.alt_text)
+            match i.uri {
+                Some(ref uri) => format!(" [{media}] alt={:?} uri={uri:?}", i.alt_text),
+                None => format!(" [{media}] alt={:?}", i.alt_text),
+            }
         }
         BlockContent::Extension(e) => {
             format!(" namespace={:?} type={:?}", e.namespace, e.type_name)
@@ -225,21 +229,35 @@ fn annotation_kind_label(kind: AnnotationKind) -> &'static str {
         AnnotationKind::Priority => "priority",
         AnnotationKind::Summary => "summary",
         AnnotationKind::Tag => "tag",
+        AnnotationKind::Relevance => "relevance",
+        AnnotationKind::Provenance => "provenance",
+        AnnotationKind::Expiry => "expiry",
+        AnnotationKind::Link => "link",
     }
 }
 
 /// Formats an annotation value for display.
 ///
-/// For priority annotations, decodes the single byte to a priority name.
-/// For all others, formats as UTF-8 lossy text.
-fn format_annotation_value(kind: AnnotationKind, value: &[u8]) -> String {
+/// Priority bytes decode to a priority name, relevance to its score,
+/// expiry to its Unix timestamp and links to `kind -> #target`. All
+/// others (and malformed binary values) format as UTF-8 lossy text.
+fn format_annotation_value(a: &AnnotationBlock) -> String {
     use bcp_types::enums::Priority;
 
-    if kind == AnnotationKind::Priority
-        && let Some(&byte) = value.first()
+    if a.kind == AnnotationKind::Priority
+        && let Some(&byte) = a.value.first()
         && let Ok(p) = Priority::from_wire_byte(byte)
     {
         return format!("{p:?}").to_lowercase();
     }
-    String::from_utf8_lossy(value).into_owned()
+    if let Some(score) = a.as_relevance() {
+        return score.to_string();
+    }
+    if let Some(at) = a.as_expiry() {
+        return at.to_string();
+    }
+    if let Some((kind, to)) = a.as_link() {
+        return format!("{} -> #{to}", kind.name());
+    }
+    String::from_utf8_lossy(&a.value).into_owned()
 }
@@ -29,21 +29,26 @@
 /// ```
 ///
 /// The token estimates use [`HeuristicEstimator`] (4 chars ≈ 1 token) on the
-/// rendered output of each mode. These are rough lower-bound estimates; actual
-/// tokenisation varies by model and content.
+/// rendered output of each mode, or the code-aware estimator with
+/// `--estimator code-aware`. These are rough estimates; actual tokenisation
+/// varies by model and content. With `--vocab <FILE>` the counts are exact
+/// for that BPE vocabulary and the report says `bpe, exact`.
+///
+/// [`HeuristicEstimator`]: bcp_driver::HeuristicEstimator
 use std::collections::HashMap;
 use std::fs;
 
 use anyhow::{Context, Result};
 use bcp_decoder::BcpDecoder;
 use bcp_driver::{
-    DefaultDriver, DriverConfig, HeuristicEstimator, BcpDriver, OutputMode, TokenEstimator,
+    BcpDriver, BlockOrdering, BudgetStrategy, DefaultDriver, DriverConfig, OutputMode, PathPrefix,
     Verbosity,
 };
 use bcp_types::block::BlockContent;
 use bcp_types::block_type::BlockType;
 
 use crate::StatsArgs;
+use crate::cmd_decode::resolve_estimator;
 
 /// Run the `bcp stats` command.
 ///
@@ -100,14 +105,26 @@ pub fn run(args: &StatsArgs) -> Result<()> {
 
     // ── Token estimates ───────────────────────────────────────────────────────
 
-    let estimator = HeuristicEstimator;
+    let estimator = resolve_estimator(&args.estimator, args.vocab.as_deref())?;
     let est = |mode: OutputMode| -> u32 {
         let config = DriverConfig {
-            mode,
+            mode: Some(mode),
             verbosity: Verbosity::Full,
+            budget_strategy: BudgetStrategy::Greedy,
+            query: None,
+            conversation: None,
+            group_dependencies: false,
+            dedup: false,
+            ordering: BlockOrdering::Payload,
+            path_prefix: PathPrefix::Auto,
             token_budget: None,
             include_types: None,
             target_model: None,
+            recent_since: None,
+            include_tags: None,
+            exclude_tags: Vec::new(),
+            current_time: None,
+            estimator: None,
         };
         DefaultDriver
             .render(&decoded.blocks, &config)
@@ -151,10 +168,17 @@ pub fn run(args: &StatsArgs) -> Result<()> {
     println!("{:<20}{:>6}{:>8}", "Total", total_blocks, total_bytes);
 
     println!();
-    println!("Tokens (heuristic estimate):");
-    println!("  xml mode      ~{xml_tokens} tokens");
-    println!("  markdown mode ~{md_tokens} tokens");
-    println!("  minimal mode  ~{min_tokens} tokens");
+    // Exact counts drop the `~` approximation marker.
+    let approx = if args.vocab.is_some() {
+        println!("Tokens (bpe, exact):");
+        ""
+    } else {
+        println!("Tokens ({} estimate):", args.estimator.to_lowercase());
+        "~"
+    };
+    println!("  xml mode      {approx}{xml_tokens} tokens");
+    println!("  markdown mode {approx}{md_tokens} tokens");
+    println!("  minimal mode  {approx}{min_tokens} tokens");
 
     Ok(())
 }
@@ -182,7 +206,7 @@ fn content_size(content: &BlockContent) -> usize {
 
 /// Returns the uppercase display label for a block type, matching the format
 /// used by `bcp inspect`.
-fn block_type_label(bt: &BlockType) -> &'static str {
+pub(crate) fn block_type_label(bt: &BlockType) -> &'static str {
     match bt {
         BlockType::Code => "CODE",
         BlockType::Conversation => "CONVERSATION",
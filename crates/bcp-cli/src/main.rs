@@ -62,6 +62,9 @@ struct Cli {
 
 // ── Sub-commands ──────────────────────────────────────────────────────────────
 
+// Parsed once per run, so the size of the largest argument struct does
+// not matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Print a human-readable summary of each block in a BCP file.
@@ -149,6 +152,7 @@ pub struct ValidateArgs {
 /// │ --compress-blocks  │ zstd-compress each block body individually   │
 /// │ --compress-payload │ zstd-compress everything after the header    │
 /// │ --dedup            │ BLAKE3 content-addressing + deduplication    │
+/// │ --validate         │ reject malformed structured_data content     │
 /// └──────────────────┴────────────────────────────────────────────────┘
 /// ```
 #[derive(clap::Args)]
@@ -171,6 +175,11 @@ pub struct EncodeArgs {
     /// Enable BLAKE3 content-addressed deduplication.
     #[arg(long)]
     pub dedup: bool,
+
+    /// Parse JSON, NDJSON and XML structured data blocks and fail on
+    /// malformed content.
+    #[arg(long)]
+    pub validate: bool,
 }
 
 /// Arguments for `bcp decode`.
@@ -180,24 +189,45 @@ pub struct EncodeArgs {
 /// type filter are all configurable.
 ///
 /// ```text
-/// ┌─────────────┬──────────────────────────────────────────────────────┐
-/// │ Flag        │ Values / default                                     │
-/// ├─────────────┼──────────────────────────────────────────────────────┤
-/// │ --mode      │ xml (default) | markdown | minimal                   │
-/// │ --verbosity │ full | summary | adaptive (default)                  │
-/// │ --budget    │ approximate token count (none = no limit)            │
-/// │ --include   │ comma-separated block types to render                │
-/// │ -o / --output │ write to file instead of stdout                   │
-/// └─────────────┴──────────────────────────────────────────────────────┘
+/// ┌───────────────────┬────────────────────────────────────────────┐
+/// │ Flag              │ Values / default                           │
+/// ├───────────────────┼────────────────────────────────────────────┤
+/// │ --mode            │ xml (default) | markdown | minimal | raw   │
+/// │ --model           │ claude | gpt | gemini | generic            │
+/// │ --verbosity       │ full | summary | adaptive (default)        │
+/// │ --budget          │ approximate token count (none = no limit)  │
+/// │ --budget-strategy │ greedy (default) | optimal                 │
+/// │ --query           │ task text; rank blocks by relevance to it  │
+/// │ --keep-turns      │ conversation turns kept in full under      │
+/// │                   │ --budget; older turns degrade first        │
+/// │ --group           │ budget related blocks (same path, tool     │
+/// │                   │ call, link) together                       │
+/// │ --dedup           │ render content repeated between blocks once│
+/// │ --order           │ payload (default) | priority | edges | type│
+/// │ --path-prefix     │ auto (default) | factor | full             │
+/// │ --include         │ comma-separated block types to render      │
+/// │ --recent-within   │ seconds; mark newer FILE_TREE entries      │
+/// │                   │ recent                                     │
+/// │ -o / --output     │ write to file instead of stdout            │
+/// └───────────────────┴────────────────────────────────────────────┘
 /// ```
 #[derive(clap::Args)]
 pub struct DecodeArgs {
     /// Path to the `.bcp` file to decode.
     pub file: PathBuf,
 
-    /// Output format: `xml`, `markdown`, or `minimal`.
-    #[arg(long, default_value = "xml")]
-    pub mode: String,
+    /// Output format: `xml`, `markdown`, `minimal`, `raw` (base64 frames)
+    /// or `raw-b85`. Defaults to the `--model` family's preferred mode,
+    /// or `xml`.
+    #[arg(long)]
+    pub mode: Option<String>,
+
+    /// Target model family: `claude`, `gpt`, `gemini`, or `generic`.
+    ///
+    /// Selects a rendering profile: default output mode, XML tag names and
+    /// attribute style, and the token ratio used for `--budget`.
+    #[arg(long)]
+    pub model: Option<String>,
 
     /// Token budget for adaptive rendering.
     ///
@@ -211,6 +241,51 @@ pub struct DecodeArgs {
     #[arg(long, default_value = "adaptive")]
     pub verbosity: String,
 
+    /// How `--budget` is shared between blocks: `greedy` (default, first
+    /// fit in priority order) or `optimal` (maximize total utility).
+    #[arg(long, default_value = "greedy")]
+    pub budget_strategy: String,
+
+    /// Task the context is for, e.g. the user's question. Blocks that
+    /// match it best keep their content under `--budget`; `--report`
+    /// shows each block's score.
+    #[arg(long)]
+    pub query: Option<String>,
+
+    /// Keep the system turn and the last N conversation turns in full
+    /// under `--budget`; older turns degrade to summaries, then
+    /// placeholders. Without it, turns are budgeted like other blocks.
+    #[arg(long, value_name = "N")]
+    pub keep_turns: Option<usize>,
+
+    /// Budget related blocks as one unit under `--budget`: a diff with
+    /// the code of its path, a tool call with its output, an embedding
+    /// reference with its source, and blocks joined by a link. Members
+    /// of a group are kept or degraded together.
+    #[arg(long)]
+    pub group: bool,
+
+    /// Render content repeated between full blocks once: overlapping
+    /// code fragments of a path are merged, and long runs of lines that
+    /// repeat an earlier block become a back-reference to it. `--report`
+    /// shows the tokens saved.
+    #[arg(long)]
+    pub dedup: bool,
+
+    /// Block order in the output: `payload` (default, as encoded),
+    /// `priority` (highest priority first), `edges` (most important
+    /// blocks at the start and end) or `type` (trees, code, diffs, tool
+    /// output, then the rest).
+    #[arg(long, default_value = "payload")]
+    pub order: String,
+
+    /// Paths in block headers: `factor` declares the directory every
+    /// code and diff path shares once and shows paths relative to it,
+    /// `full` keeps full paths, and `auto` (default) factors in minimal
+    /// mode only.
+    #[arg(long, default_value = "auto")]
+    pub path_prefix: String,
+
     /// Comma-separated list of block types to include (e.g. `code,conversation`).
     ///
     /// When set, only blocks of matching types appear in the output.
@@ -219,6 +294,36 @@ pub struct DecodeArgs {
     #[arg(long)]
     pub include: Option<String>,
 
+    /// Mark FILE_TREE entries modified within this many seconds of now
+    /// as recently modified (e.g. `3600` for the last hour).
+    #[arg(long, value_name = "SECONDS")]
+    pub recent_within: Option<u64>,
+
+    /// Only render blocks carrying this tag. Repeatable or comma-separated;
+    /// a block matching any listed tag is kept.
+    #[arg(long = "tag", value_name = "TAG", value_delimiter = ',')]
+    pub tags: Vec<String>,
+
+    /// Skip blocks carrying this tag (e.g. `--exclude-tag generated,vendored`).
+    /// Repeatable or comma-separated; wins over `--tag`.
+    #[arg(long = "exclude-tag", value_name = "TAG", value_delimiter = ',')]
+    pub exclude_tags: Vec<String>,
+
+    /// Token estimator for `--budget`: `code-aware` (default without
+    /// `--model`) or `heuristic`. Overrides the model profile's ratio.
+    #[arg(long)]
+    pub estimator: Option<String>,
+
+    /// Count `--budget` tokens exactly with this tiktoken-format BPE
+    /// vocabulary (e.g. `cl100k_base.tiktoken`). Overrides `--estimator`.
+    #[arg(long, value_name = "FILE")]
+    pub vocab: Option<PathBuf>,
+
+    /// Print a per-block render report to stderr: decision, reason,
+    /// estimated tokens and byte range of every block.
+    #[arg(long)]
+    pub report: bool,
+
     /// Write rendered output to this file instead of stdout.
     #[arg(short, long)]
     pub output: Option<PathBuf>,
@@ -233,6 +338,16 @@ pub struct DecodeArgs {
 pub struct StatsArgs {
     /// Path to the `.bcp` file to analyse.
     pub file: PathBuf,
+
+    /// Token estimator for the per-mode totals: `heuristic` (default) or
+    /// `code-aware`.
+    #[arg(long, default_value = "heuristic")]
+    pub estimator: String,
+
+    /// Report exact token counts using this tiktoken-format BPE
+    /// vocabulary (e.g. `cl100k_base.tiktoken`). Overrides `--estimator`.
+    #[arg(long, value_name = "FILE")]
+    pub vocab: Option<PathBuf>,
 }
 
 // ── Entry point ───────────────────────────────────────────────────────────────
@@ -288,7 +288,7 @@ mod tests {
     use bcp_types::enums::{
         AnnotationKind, DataFormat, FormatHint, Lang, MediaType, Priority, Role, Status,
     };
-    use bcp_types::file_tree::{FileEntry, FileEntryKind};
+    use bcp_types::file_tree::FileEntry;
     use bcp_wire::block_frame::{BlockFlags, BlockFrame};
 
     // ── Round-trip helpers ────────────────────────────────────────────────
@@ -422,12 +422,7 @@ mod tests {
                 .add_conversation(Role::User, b"hello")
                 .add_file_tree(
                     "/project",
-                    vec![FileEntry {
-                        name: "lib.rs".to_string(),
-                        kind: FileEntryKind::File,
-                        size: 100,
-                        children: vec![],
-                    }],
+                    vec![FileEntry::file("lib.rs", 100)],
                 )
                 .add_tool_result("rg", Status::Ok, b"3 matches")
                 .add_document("README", b"# Title", FormatHint::Markdown)
@@ -436,7 +431,9 @@ mod tests {
                     "src/lib.rs",
                     vec![DiffHunk {
                         old_start: 1,
+                        old_lines: 0,
                         new_start: 1,
+                        new_lines: 1,
                         lines: b"+new line\n".to_vec(),
                     }],
                 )
@@ -507,23 +504,8 @@ mod tests {
                 .add_file_tree(
                     "/project/src",
                     vec![
-                        FileEntry {
-                            name: "main.rs".to_string(),
-                            kind: FileEntryKind::File,
-                            size: 512,
-                            children: vec![],
-                        },
-                        FileEntry {
-                            name: "lib".to_string(),
-                            kind: FileEntryKind::Directory,
-                            size: 0,
-                            children: vec![FileEntry {
-                                name: "utils.rs".to_string(),
-                                kind: FileEntryKind::File,
-                                size: 128,
-                                children: vec![],
-                            }],
-                        },
+                        FileEntry::file("main.rs", 512),
+                        FileEntry::dir("lib", vec![FileEntry::file("utils.rs", 128)]),
                     ],
                 )
                 .add_diff(
@@ -531,12 +513,16 @@ mod tests {
                     vec![
                         DiffHunk {
                             old_start: 5,
+                            old_lines: 0,
                             new_start: 5,
+                            new_lines: 1,
                             lines: b"+tokio = \"1\"\n".to_vec(),
                         },
                         DiffHunk {
                             old_start: 20,
+                            old_lines: 1,
                             new_start: 21,
+                            new_lines: 1,
                             lines: b"-old_dep = \"0.1\"\n+new_dep = \"0.2\"\n".to_vec(),
                         },
                     ],
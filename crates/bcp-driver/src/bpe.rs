@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use regex::Regex;
+
+use crate::budget::TokenEstimator;
+use crate::error::VocabError;
+
+/// Most cached counts [`BpeEstimator`] holds before starting afresh.
+///
+/// A render prices a few hundred distinct bodies at most, so this keeps
+/// every hit within one render while bounding a long-lived estimator
+/// that sees an unbounded stream of payloads.
+const CACHE_CAPACITY: usize = 4096;
+
+/// The `cl100k_base` pre-tokenization pattern, minus its one lookahead.
+///
+/// tiktoken's final alternatives are `\s+(?!\S)|\s+`: a whitespace run
+/// followed by text gives up its last character so the next piece can
+/// carry a leading space. The `regex` crate has no lookaround, so that
+/// backoff is applied by hand in [`BpeEstimator::count`].
+const PRETOKENIZE: &str = r"(?i:'s|'t|'re|'ve|'m|'ll|'d)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+";
+
+/// Exact token counter for byte-pair-encoding vocabularies.
+///
+/// Loads a tiktoken-format vocabulary — one `<base64 token> <rank>` pair
+/// per line, as in `cl100k_base.tiktoken` — from local disk and counts
+/// tokens the way the model's tokenizer would. No network access is
+/// involved; the vocabulary file must already be present.
+///
+/// ```text
+/// ┌──────────────┬──────────────────────────────────────────────────┐
+/// │ Stage        │ Work                                             │
+/// ├──────────────┼──────────────────────────────────────────────────┤
+/// │ Pre-tokenize │ Split text into words, numbers, punctuation runs │
+/// │ Lookup       │ A piece found whole in the vocabulary is 1 token │
+/// │ Merge        │ Otherwise merge byte pairs, lowest rank first    │
+/// └──────────────┴──────────────────────────────────────────────────┘
+/// ```
+///
+/// Counts are cached by BLAKE3 hash of the text, so re-estimating the
+/// same block body — the budget engine prices each block more than once,
+/// and payloads often repeat content — skips the merge loop. The cache
+/// holds at most 4096 counts and is cleared when it fills. Special
+/// tokens (`<|endoftext|>` and friends) are counted as ordinary text.
+pub struct BpeEstimator {
+    ranks: HashMap<Vec<u8>, u32>,
+    pretokenizer: Regex,
+    cache: Mutex<HashMap<blake3::Hash, u32>>,
+}
+
+impl BpeEstimator {
+    /// Load a tiktoken-format vocabulary file from `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocabError::Io`] if the file cannot be read, or any
+    /// error from [`BpeEstimator::from_tiktoken`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, VocabError> {
+        Self::from_tiktoken(&fs::read_to_string(path)?)
+    }
+
+    /// Parse a tiktoken-format vocabulary from memory.
+    ///
+    /// Blank lines are skipped. Every other line must be a base64 token
+    /// followed by a single space and its decimal rank.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VocabError::Malformed`] for a line that does not parse,
+    /// or [`VocabError::Empty`] if the vocabulary has no entries.
+    ///
+    /// # Panics
+    ///
+    /// Never in practice: the only regex compiled is the constant
+    /// pre-tokenization pattern.
+    pub fn from_tiktoken(data: &str) -> Result<Self, VocabError> {
+        let mut ranks = HashMap::new();
+        for (idx, line) in data.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let malformed = |reason| VocabError::Malformed {
+                line: idx + 1,
+                reason,
+            };
+            let (token, rank) = line
+                .split_once(' ')
+                .ok_or_else(|| malformed("expected `<base64 token> <rank>`"))?;
+            let token = STANDARD
+                .decode(token)
+                .map_err(|_| malformed("token is not valid base64"))?;
+            let rank = rank
+                .trim()
+                .parse()
+                .map_err(|_| malformed("rank is not an unsigned integer"))?;
+            ranks.insert(token, rank);
+        }
+        if ranks.is_empty() {
+            return Err(VocabError::Empty);
+        }
+        Ok(Self {
+            ranks,
+            pretokenizer: Regex::new(PRETOKENIZE).expect("pre-tokenization pattern is valid"),
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Number of tokens in the loaded vocabulary.
+    #[must_use]
+    pub fn vocab_size(&self) -> usize {
+        self.ranks.len()
+    }
+
+    /// Count the tokens in `text` without consulting the cache.
+    #[must_use]
+    pub fn count(&self, text: &str) -> usize {
+        let mut total = 0;
+        let mut start = 0;
+        while let Some(m) = self.pretokenizer.find_at(text, start) {
+            let mut end = m.end();
+            let piece = m.as_str();
+            // Emulate `\s+(?!\S)`: leave the run's last character to
+            // prefix the text that follows it.
+            if end < text.len()
+                && piece.chars().all(char::is_whitespace)
+                && !piece.ends_with(['\r', '\n'])
+                && let Some((last, _)) = piece.char_indices().last()
+                && last > 0
+            {
+                end = m.start() + last;
+            }
+            total += self.merge_count(&text.as_bytes()[m.start()..end]);
+            start = end;
+        }
+        total
+    }
+
+    /// Count the tokens a single pre-tokenized piece encodes to.
+    ///
+    /// Repeatedly merges the adjacent pair with the lowest rank, leftmost
+    /// on ties, until no adjacent pair is in the vocabulary. Bytes the
+    /// vocabulary lacks count as one token each.
+    fn merge_count(&self, piece: &[u8]) -> usize {
+        if piece.len() <= 1 || self.ranks.contains_key(piece) {
+            return piece.len().min(1);
+        }
+        // Boundaries between the current parts; part i is
+        // piece[bounds[i]..bounds[i + 1]].
+        let mut bounds: Vec<usize> = (0..=piece.len()).collect();
+        loop {
+            let mut best: Option<(u32, usize)> = None;
+            for i in 0..bounds.len() - 2 {
+                if let Some(&rank) = self.ranks.get(&piece[bounds[i]..bounds[i + 2]])
+                    && best.is_none_or(|(lowest, _)| rank < lowest)
+                {
+                    best = Some((rank, i));
+                }
+            }
+            match best {
+                Some((_, i)) => {
+                    bounds.remove(i + 1);
+                }
+                None => return bounds.len() - 1,
+            }
+        }
+    }
+}
+
+impl TokenEstimator for BpeEstimator {
+    fn estimate(&self, text: &str) -> u32 {
+        if text.is_empty() {
+            return 0;
+        }
+        let key = blake3::hash(text.as_bytes());
+        if let Some(hit) = self.cache.lock().ok().and_then(|c| c.get(&key).copied()) {
+            return hit;
+        }
+        let tokens = u32::try_from(self.count(text)).unwrap_or(u32::MAX);
+        if let Ok(mut cache) = self.cache.lock() {
+            if cache.len() >= CACHE_CAPACITY {
+                cache.clear();
+            }
+            cache.insert(key, tokens);
+        }
+        tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write as _;
+
+    use super::*;
+
+    /// Builds a tiktoken-format vocabulary from `(token, rank)` pairs.
+    fn vocab(entries: &[(&str, u32)]) -> String {
+        let mut out = String::new();
+        for (token, rank) in entries {
+            writeln!(out, "{} {rank}", STANDARD.encode(token)).unwrap();
+        }
+        out
+    }
+
+    fn estimator() -> BpeEstimator {
+        BpeEstimator::from_tiktoken(&vocab(&[
+            ("a", 0),
+            ("b", 1),
+            (" ", 2),
+            ("ab", 3),
+            (" ab", 4),
+            ("  ", 5),
+            ("\n", 6),
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn whole_pieces_and_merges() {
+        let bpe = estimator();
+        assert_eq!(bpe.vocab_size(), 7);
+        // "ab" and " ab" are both whole entries.
+        assert_eq!(bpe.count("ab ab"), 2);
+        // "aab" is not: "a"+"b" merge, leaving "a", "ab".
+        assert_eq!(bpe.count("aab"), 2);
+        // "ba" has no merge, so it stays two single-byte tokens.
+        assert_eq!(bpe.count("ba"), 2);
+        assert_eq!(bpe.estimate(""), 0);
+    }
+
+    #[test]
+    fn whitespace_run_leaves_last_space_for_next_word() {
+        let bpe = estimator();
+        // "a", " ", " b" — not "a", "  ", "b", which would count 3.
+        assert_eq!(bpe.count("a  b"), 4);
+        // Trailing whitespace has nothing to prefix and stays whole.
+        assert_eq!(bpe.count("a  "), 2);
+    }
+
+    #[test]
+    fn unknown_bytes_count_one_each() {
+        let bpe = estimator();
+        assert_eq!(bpe.count("xyz"), 3);
+    }
+
+    #[test]
+    fn estimates_are_cached_by_hash() {
+        let bpe = estimator();
+        assert_eq!(bpe.estimate("ab ab"), 2);
+        assert_eq!(bpe.estimate("ab ab"), 2);
+        assert_eq!(bpe.cache.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn cache_is_bounded() {
+        let bpe = estimator();
+        for i in 0..=CACHE_CAPACITY {
+            bpe.estimate(&i.to_string());
+        }
+        assert!(bpe.cache.lock().unwrap().len() <= CACHE_CAPACITY);
+    }
+
+    #[test]
+    fn malformed_vocabularies_rejected() {
+        assert!(matches!(
+            BpeEstimator::from_tiktoken(""),
+            Err(VocabError::Empty)
+        ));
+        assert!(matches!(
+            BpeEstimator::from_tiktoken("YQ== 0\nnot-a-pair\n"),
+            Err(VocabError::Malformed { line: 2, .. })
+        ));
+        assert!(matches!(
+            BpeEstimator::from_tiktoken("!!! 0"),
+            Err(VocabError::Malformed { line: 1, .. })
+        ));
+        assert!(matches!(
+            BpeEstimator::from_tiktoken("YQ== first"),
+            Err(VocabError::Malformed { line: 1, .. })
+        ));
+    }
+}
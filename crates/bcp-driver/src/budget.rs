@@ -4,7 +4,16 @@ use bcp_types::BlockType;
 use bcp_types::block::{Block, BlockContent};
 use bcp_types::enums::{AnnotationKind, Priority};
 
+use crate::config::{BudgetStrategy, DriverConfig, OutputMode};
+use crate::conversation::{Turn, classify_turns, decide_turn, turn_members};
+use crate::driver::{render_item, separator};
+use crate::group::{decide_group, dependency_groups, group_members};
+use crate::optimize::allocate_optimal;
+use crate::placeholder::render_elision;
+use crate::render_context::RenderContext;
 use crate::render_xml::{content_to_string, role_display_name};
+use crate::report::DecisionReason;
+use crate::truncate::plan_truncation;
 
 /// How to render a single block under budget constraints.
 ///
@@ -20,6 +29,9 @@ use crate::render_xml::{content_to_string, role_display_name};
 /// ├─────────────┼──────────────────────────────────────────────────┤
 /// │ Full        │ Render complete block content (ignore summary)   │
 /// │ Summary     │ Render summary text only                         │
+/// │ Outline     │ Render code declarations with bodies elided      │
+/// │ Truncated   │ Render a line window of the content plus an      │
+/// │             │ elision marker for the lines dropped             │
 /// │ Placeholder │ Emit a compact omission notice with metadata     │
 /// │ Omit        │ Skip the block entirely (no output)              │
 /// └─────────────┴──────────────────────────────────────────────────┘
@@ -33,9 +45,12 @@ use crate::render_xml::{content_to_string, role_display_name};
 /// │ Priority   │ Degradation path                                 │
 /// ├────────────┼──────────────────────────────────────────────────┤
 /// │ Critical   │ Full (always, even over budget)                  │
-/// │ High       │ Full → Summary → Full (forced, over budget)      │
-/// │ Normal     │ Full → Summary → Placeholder                     │
-/// │ Low        │ Summary → Placeholder                            │
+/// │ High       │ Full → Summary → Outline →                       │
+/// │            │ Full (forced, over budget)                       │
+/// │ Normal     │ Full → Truncated (most fits) → Summary →         │
+/// │            │ Outline → Truncated → Placeholder → Omit         │
+/// │ Low        │ Summary → Outline → Truncated → Placeholder →    │
+/// │            │ Omit                                             │
 /// │ Background │ Placeholder → Omit                               │
 /// └────────────┴──────────────────────────────────────────────────┘
 /// ```
@@ -48,6 +63,25 @@ pub enum RenderDecision {
     /// the renderer falls back to full content.
     Summary,
 
+    /// Render a code block's outline — its signatures, type definitions,
+    /// imports and doc comments, with function bodies elided (see
+    /// [`bcp_types::outline::outline`]). Blocks without an outline fall
+    /// back to full content.
+    Outline,
+
+    /// Render the first `head` and last `tail` lines of the content,
+    /// with an elision marker in place of the lines between. Only
+    /// text-bodied blocks (code, documents, data, tool output,
+    /// conversation) are truncated.
+    Truncated {
+        /// Lines kept from the start of the content.
+        head: usize,
+        /// Lines kept from the end of the content.
+        tail: usize,
+        /// Estimated tokens of the elided lines.
+        omitted_tokens: u32,
+    },
+
     /// Render a compact placeholder showing the block's type,
     /// description, and estimated omitted token count.
     Placeholder {
@@ -163,6 +197,39 @@ impl TokenEstimator for CodeAwareEstimator {
     }
 }
 
+/// Code-aware estimator with configurable character-per-token ratios.
+///
+/// Classifies text the same way as [`CodeAwareEstimator`], then divides
+/// the byte length by `code_chars_per_token` or `prose_chars_per_token`.
+/// Model profiles use it to approximate each family's tokenizer; with
+/// ratios of 3.0 and 4.0 it matches [`CodeAwareEstimator`] exactly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RatioEstimator {
+    /// Characters per token for code-like text.
+    pub code_chars_per_token: f32,
+    /// Characters per token for prose.
+    pub prose_chars_per_token: f32,
+}
+
+impl TokenEstimator for RatioEstimator {
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn estimate(&self, text: &str) -> u32 {
+        if text.is_empty() {
+            return 0;
+        }
+        let ratio = if CodeAwareEstimator::is_code_like(text) {
+            self.code_chars_per_token
+        } else {
+            self.prose_chars_per_token
+        };
+        ((text.len() as f32 / ratio) as u32).max(1)
+    }
+}
+
 /// Per-block budget metadata computed during the scan pass.
 ///
 /// One `BlockBudgetInfo` is created for every renderable (non-Annotation,
@@ -171,20 +238,43 @@ impl TokenEstimator for CodeAwareEstimator {
 /// make render decisions.
 ///
 /// ```text
-/// ┌────────────────┬──────────────────────────────────────────────────┐
-/// │ Field          │ Purpose                                          │
-/// ├────────────────┼──────────────────────────────────────────────────┤
-/// │ priority       │ Resolved from ANNOTATION blocks or Normal        │
-/// │ full_tokens    │ Estimated tokens for full content rendering      │
-/// │ summary_tokens │ Estimated tokens for summary (None if no summary)│
-/// │ has_summary    │ Whether the block has an attached summary        │
-/// └────────────────┴──────────────────────────────────────────────────┘
+/// ┌─────────────────────┬─────────────────────────────────────────────┐
+/// │ Field               │ Purpose                                     │
+/// ├─────────────────────┼─────────────────────────────────────────────┤
+/// │ priority            │ Resolved from ANNOTATION blocks or Normal   │
+/// │ full_tokens         │ Rendered cost of the full block             │
+/// │ summary_tokens      │ Rendered cost of the summary (None if no    │
+/// │                     │ summary)                                    │
+/// │ placeholder_tokens  │ Rendered cost of the block's placeholder    │
+/// │ content_tokens      │ Estimated tokens of the content alone       │
+/// │ truncation_overhead │ Framing plus elision marker of a truncation │
+/// │ has_summary         │ Whether the block has an attached summary   │
+/// │ relevance           │ Relevance annotation score, or              │
+/// │                     │ NEUTRAL_RELEVANCE                           │
+/// │ turn                │ Part in the conversation policy, if any     │
+/// │ group               │ First member of its dependency group, if    │
+/// │                     │ any                                         │
+/// └─────────────────────┴─────────────────────────────────────────────┘
 /// ```
+///
+/// The rendered costs come from a [`BlockPricer`]: they include the
+/// block's tags, fences or headers and the separator before it, so
+/// charging them against the budget charges what the output will hold.
+/// `content_tokens` is what placeholders and elision markers report as
+/// omitted.
 pub(crate) struct BlockBudgetInfo {
     pub priority: Priority,
+    pub relevance: f32,
     pub full_tokens: u32,
     pub summary_tokens: Option<u32>,
+    /// Cost of the block's outline; `None` if it has none.
+    pub outline_tokens: Option<u32>,
+    pub placeholder_tokens: u32,
+    pub content_tokens: u32,
+    pub truncation_overhead: u32,
     pub has_summary: bool,
+    pub turn: Option<Turn>,
+    pub group: Option<usize>,
 }
 
 // ── Priority Resolution ──────────────────────────────────────────────
@@ -197,8 +287,8 @@ pub(crate) struct BlockBudgetInfo {
 /// target block in the original (unfiltered) block stream.
 ///
 /// If multiple annotations target the same block, the last one wins.
-/// Non-priority annotations (`Summary`, `Tag`) are ignored — they are
-/// handled elsewhere in the pipeline.
+/// Non-priority annotations (`Summary`, `Tag`, `Relevance`, …) are
+/// ignored — they are handled elsewhere in the pipeline.
 ///
 /// Blocks without a priority annotation default to `Priority::Normal`
 /// (applied by the caller, not this function).
@@ -216,6 +306,44 @@ pub(crate) fn resolve_priorities(blocks: &[Block]) -> HashMap<u32, Priority> {
     map
 }
 
+/// Relevance assumed for blocks without a `Relevance` annotation — the
+/// midpoint, so scored blocks can rank either side of unscored ones.
+pub(crate) const NEUTRAL_RELEVANCE: f32 = 0.5;
+
+/// Resolve relevance scores from ANNOTATION blocks.
+///
+/// Like [`resolve_priorities`], keyed by `target_block_id` with the last
+/// annotation winning. Malformed scores (wrong length, outside `0..=1`)
+/// are ignored.
+pub(crate) fn resolve_relevance(blocks: &[Block]) -> HashMap<u32, f32> {
+    let mut map = HashMap::new();
+    for block in blocks {
+        if let BlockContent::Annotation(ann) = &block.content
+            && let Some(score) = ann.as_relevance()
+        {
+            map.insert(ann.target_block_id, score);
+        }
+    }
+    map
+}
+
+/// [`resolve_relevance`], with each filtered block's query score filling
+/// in where it has no `Relevance` annotation.
+pub(crate) fn resolve_relevance_with_query(
+    all_blocks: &[Block],
+    original_indices: &[usize],
+    query_scores: Option<&[f32]>,
+) -> HashMap<u32, f32> {
+    let mut relevance = resolve_relevance(all_blocks);
+    if let Some(scores) = query_scores {
+        #[allow(clippy::cast_possible_truncation)]
+        for (&orig, &score) in original_indices.iter().zip(scores) {
+            relevance.entry(orig as u32).or_insert(score);
+        }
+    }
+    relevance
+}
+
 // ── Text Extraction for Estimation ──────────────────────────────────
 
 /// Extract the text content from a block for token estimation.
@@ -244,16 +372,12 @@ pub(crate) fn estimate_block_text(block: &Block) -> String {
         BlockContent::StructuredData(d) => {
             content_to_string(&d.content, 0).unwrap_or_else(|_| "x".repeat(d.content.len()))
         }
-        BlockContent::Diff(d) => {
-            let mut text = String::new();
-            for hunk in &d.hunks {
-                text.push_str(&String::from_utf8_lossy(&hunk.lines));
-            }
-            text
-        }
-        BlockContent::Image(i) => {
-            content_to_string(&i.data, 0).unwrap_or_else(|_| "x".repeat(i.data.len()))
-        }
+        BlockContent::Diff(d) => crate::render_xml::render_unified_hunks(&d.hunks),
+        BlockContent::Image(i) => match i.uri {
+            Some(ref uri) => uri.clone(),
+            None => crate::render_xml::image_inline_text(i, 0)
+                .unwrap_or_else(|_| "x".repeat(i.data.len())),
+        },
         BlockContent::Extension(e) => {
             content_to_string(&e.content, 0).unwrap_or_else(|_| "x".repeat(e.content.len()))
         }
@@ -264,14 +388,98 @@ pub(crate) fn estimate_block_text(block: &Block) -> String {
     }
 }
 
+// ── Pricing ──────────────────────────────────────────────────────────
+
+/// Prices blocks as the renderer for one output mode will draw them.
+///
+/// Content alone undercounts the output: every block also carries its
+/// XML element, Markdown header and fence, or Minimal delimiter line,
+/// plus the separator before it. The pricer renders each candidate
+/// decision with the real renderer and estimates the result, so every
+/// mode is charged its own framing.
+///
+/// ```text
+/// ┌──────────┬──────────────────────────────┬────────────────────────┐
+/// │ Mode     │ Per-block framing            │ Once per output        │
+/// ├──────────┼──────────────────────────────┼────────────────────────┤
+/// │ Xml      │ Element tags and attributes  │ <context> … </context> │
+/// │ Markdown │ Header line and code fence   │ —                      │
+/// │ Minimal  │ --- path [lang] --- line     │ —                      │
+/// │ Raw      │ Frame encoding               │ —                      │
+/// └──────────┴──────────────────────────────┴────────────────────────┘
+/// ```
+pub(crate) struct BlockPricer<'a> {
+    mode: OutputMode,
+    ctx: &'a RenderContext,
+    estimator: &'a dyn TokenEstimator,
+}
+
+impl<'a> BlockPricer<'a> {
+    pub fn new(
+        mode: OutputMode,
+        ctx: &'a RenderContext,
+        estimator: &'a dyn TokenEstimator,
+    ) -> Self {
+        Self {
+            mode,
+            ctx,
+            estimator,
+        }
+    }
+
+    pub fn estimator(&self) -> &'a dyn TokenEstimator {
+        self.estimator
+    }
+
+    /// Tokens `block` adds to the output when rendered under `decision`
+    /// at output position `index`, separator included.
+    ///
+    /// A block the renderer rejects (non-UTF-8 content) is priced by its
+    /// content alone; the render itself will fail on it.
+    pub fn cost(&self, block: &Block, decision: &RenderDecision, index: usize) -> u32 {
+        match render_item(self.mode, block, decision, index, self.ctx) {
+            Ok(Some(text)) => self
+                .estimator
+                .estimate(&format!("{}{text}", separator(self.mode))),
+            Ok(None) => 0,
+            Err(_) => self.estimator.estimate(&estimate_block_text(block)),
+        }
+    }
+
+    /// Tokens a truncation adds on top of its kept lines: the block's
+    /// framing and the elision marker.
+    ///
+    /// The marker is priced with the widest line numbers the block can
+    /// produce and the full content as its omitted count.
+    pub fn truncation_overhead(&self, block: &Block, full_tokens: u32, content_tokens: u32) -> u32 {
+        #[allow(clippy::cast_possible_truncation)]
+        let lines = estimate_block_text(block).lines().count().max(1) as u32;
+        let marker = render_elision(self.mode, lines, lines, content_tokens);
+        full_tokens.saturating_sub(content_tokens) + self.estimator.estimate(&format!("{marker}\n"))
+    }
+
+    /// Tokens of the framing around the whole output: the XML root
+    /// element and any declared path root.
+    pub fn wrapper_tokens(&self) -> u32 {
+        let opening = self.ctx.opening(self.mode);
+        let closing = self.ctx.closing(self.mode);
+        if opening.is_empty() && closing.is_empty() {
+            return 0;
+        }
+        self.estimator.estimate(&format!("{opening}{closing}"))
+    }
+}
+
 // ── Scan Pass ────────────────────────────────────────────────────────
 
 /// Scan pass: compute token estimates and resolve priorities.
 ///
-/// Produces one [`BlockBudgetInfo`] per filtered block. The caller
-/// provides the priority map from [`resolve_priorities`] and the
-/// original indices mapping (filtered index → original block index)
-/// so that annotation targets resolve correctly.
+/// Produces one [`BlockBudgetInfo`] per filtered block, priced by
+/// `pricer` at the block's filtered position. The caller provides the
+/// priority map from [`resolve_priorities`], the relevance map from
+/// [`resolve_relevance`], and the original indices mapping (filtered
+/// index → original block index) so that annotation targets resolve
+/// correctly.
 ///
 /// ```text
 /// filtered[0] → original_indices[0] = 2 → priorities.get(2) → High
@@ -280,7 +488,8 @@ pub(crate) fn estimate_block_text(block: &Block) -> String {
 pub(crate) fn scan_blocks(
     filtered: &[&Block],
     priorities: &HashMap<u32, Priority>,
-    estimator: &dyn TokenEstimator,
+    relevance: &HashMap<u32, f32>,
+    pricer: &BlockPricer<'_>,
     original_indices: &[usize],
 ) -> Vec<BlockBudgetInfo> {
     filtered
@@ -293,21 +502,40 @@ pub(crate) fn scan_blocks(
                 .get(&(orig_idx as u32))
                 .copied()
                 .unwrap_or(Priority::Normal);
+            #[allow(clippy::cast_possible_truncation)]
+            let relevance = relevance
+                .get(&(orig_idx as u32))
+                .copied()
+                .unwrap_or(NEUTRAL_RELEVANCE);
 
-            let full_text = estimate_block_text(block);
-            let full_tokens = estimator.estimate(&full_text);
+            let content_tokens = pricer.estimator.estimate(&estimate_block_text(block));
+            let full_tokens = pricer.cost(block, &RenderDecision::Full, i);
 
-            let (summary_tokens, has_summary) = if let Some(ref summary) = block.summary {
-                (Some(estimator.estimate(&summary.text)), true)
+            let (summary_tokens, has_summary) = if block.summary.is_some() {
+                (Some(pricer.cost(block, &RenderDecision::Summary, i)), true)
             } else {
                 (None, false)
             };
+            let outline_tokens = match &block.content {
+                BlockContent::Code(c) if c.outline().is_some() => {
+                    Some(pricer.cost(block, &RenderDecision::Outline, i))
+                }
+                _ => None,
+            };
+            let placeholder = make_placeholder(block, content_tokens);
 
             BlockBudgetInfo {
                 priority,
+                relevance,
                 full_tokens,
                 summary_tokens,
+                outline_tokens,
+                placeholder_tokens: pricer.cost(block, &placeholder, i),
+                content_tokens,
+                truncation_overhead: pricer.truncation_overhead(block, full_tokens, content_tokens),
                 has_summary,
+                turn: None,
+                group: None,
             }
         })
         .collect()
@@ -377,118 +605,252 @@ pub(crate) fn block_type_label(bt: &BlockType) -> &'static str {
 
 // ── Allocation Pass ──────────────────────────────────────────────────
 
-/// Estimated token cost of a placeholder line.
-///
-/// Placeholders are short strings like `[omitted: code src/main.rs ~823tok]`
-/// which typically consume around 10-15 tokens. We use a conservative
-/// fixed estimate rather than measuring each one.
-const PLACEHOLDER_TOKEN_COST: u32 = 10;
-
 /// Budget allocation pass — assign a [`RenderDecision`] to each block.
 ///
 /// Takes the scanned block metadata, the total token budget, and the
 /// filtered block slice. Returns a `Vec<RenderDecision>` parallel to
-/// the filtered slice (same length, same order).
+/// the filtered slice (same length, same order), and the
+/// [`DecisionReason`] behind each decision.
 ///
 /// The algorithm:
 /// 1. Create an index list sorted by priority (ascending: Critical first).
-///    Within the same priority, original order is preserved (stable sort).
+///    Within the same priority, higher relevance goes first; ties keep
+///    original order (stable sort).
 /// 2. Walk sorted indices, greedily subtracting from remaining budget:
 ///    - **Critical**: always `Full` (never degraded, even over budget).
 ///    - **High**: `Full` if budget allows, else `Summary` if available,
-///      else `Full` anyway (high-priority content is too important to omit).
-///    - **Normal**: `Full` if budget allows, else `Summary` if available,
-///      else `Placeholder`.
-///    - **Low**: `Summary` if budget allows, else `Placeholder`.
+///      else `Outline` if the block has one, else `Full` anyway
+///      (high-priority content is too important to omit).
+///    - **Normal**: `Full` if budget allows, else `Truncated` if at least
+///      half the content fits, else `Summary` if available, else
+///      `Outline`, else `Truncated` to whatever is left, else
+///      `Placeholder`, else `Omit`.
+///    - **Low**: `Summary` if budget allows, else `Outline`, else
+///      `Truncated` (at most half the content), else `Placeholder`, else
+///      `Omit`.
 ///    - **Background**: `Placeholder` if budget allows, else `Omit`.
+///
+///    Every step is charged its rendered cost, placeholders included, so
+///    only Critical and forced High blocks can take the total over
+///    `budget`.
 /// 3. Return decisions reordered to match the original block sequence.
+///
+/// Under a [`ConversationPolicy`](crate::config::ConversationPolicy),
+/// kept turns have Critical priority. Older turns follow the other
+/// blocks of their tier, newest first, and each is decided as a whole
+/// by [`decide_turn`]. A dependency group is decided as a whole by
+/// [`decide_group`] when its first member comes up.
 pub(crate) fn allocate_budget(
     infos: &[BlockBudgetInfo],
     budget: u32,
     filtered: &[&Block],
-) -> Vec<RenderDecision> {
+    estimator: &dyn TokenEstimator,
+) -> (Vec<RenderDecision>, Vec<DecisionReason>) {
     let mut decisions = vec![RenderDecision::Omit; infos.len()];
+    let mut reasons = vec![DecisionReason::OverBudget; infos.len()];
     let mut remaining = budget;
 
-    // Sort indices by priority (stable: preserves original order within
-    // the same priority level). Priority::Critical < Priority::High < ...
+    // Sort indices by priority, older turns last, then by descending
+    // relevance and newest turn first (stable: preserves original order
+    // for equal keys).
+    // Priority::Critical < Priority::High < ...
+    let older = |i: usize| match infos[i].turn {
+        Some(Turn::Older(n)) => (true, std::cmp::Reverse(n)),
+        _ => (false, std::cmp::Reverse(0)),
+    };
     let mut sorted: Vec<usize> = (0..infos.len()).collect();
-    sorted.sort_by(|&a, &b| infos[a].priority.cmp(&infos[b].priority));
-
+    sorted.sort_by(|&a, &b| {
+        infos[a]
+            .priority
+            .cmp(&infos[b].priority)
+            .then_with(|| older(a).0.cmp(&older(b).0))
+            .then_with(|| infos[b].relevance.total_cmp(&infos[a].relevance))
+            .then_with(|| older(a).1.cmp(&older(b).1))
+    });
+
+    let turns: Vec<Option<Turn>> = infos.iter().map(|info| info.turn).collect();
+    let mut decided = vec![false; infos.len()];
     for idx in sorted {
-        let info = &infos[idx];
-        let block = filtered[idx];
-
-        match info.priority {
-            Priority::Critical => {
-                decisions[idx] = RenderDecision::Full;
-                remaining = remaining.saturating_sub(info.full_tokens);
+        if decided[idx] {
+            continue;
+        }
+        if let Some(Turn::Older(n)) = infos[idx].turn {
+            let members = turn_members(&turns, n);
+            let turn = decide_turn(&members, infos, filtered, &mut remaining);
+            for (&member, (decision, reason)) in members.iter().zip(turn) {
+                decisions[member] = decision;
+                reasons[member] = reason;
+                decided[member] = true;
             }
-            Priority::High => {
-                if info.full_tokens <= remaining {
-                    decisions[idx] = RenderDecision::Full;
-                    remaining -= info.full_tokens;
-                } else if info.has_summary {
-                    let stok = info.summary_tokens.unwrap_or(0);
-                    if stok <= remaining {
-                        decisions[idx] = RenderDecision::Summary;
-                        remaining -= stok;
-                    } else {
-                        // High-priority: render full anyway, over budget
-                        decisions[idx] = RenderDecision::Full;
-                        remaining = 0;
-                    }
-                } else {
-                    // No summary available, render full over budget
-                    decisions[idx] = RenderDecision::Full;
-                    remaining = 0;
-                }
+            continue;
+        }
+        if let Some(first) = infos[idx].group {
+            let members = group_members(infos, first);
+            let group = decide_group(&members, infos, filtered, &mut remaining);
+            for (&member, (decision, reason)) in members.iter().zip(group) {
+                decisions[member] = decision;
+                reasons[member] = reason;
+                decided[member] = true;
             }
-            Priority::Normal => {
-                if info.full_tokens <= remaining {
-                    decisions[idx] = RenderDecision::Full;
-                    remaining -= info.full_tokens;
-                } else if info.has_summary {
-                    let stok = info.summary_tokens.unwrap_or(0);
-                    if stok <= remaining {
-                        decisions[idx] = RenderDecision::Summary;
-                        remaining -= stok;
-                    } else {
-                        decisions[idx] = make_placeholder(block, info.full_tokens);
-                    }
-                } else {
-                    decisions[idx] = make_placeholder(block, info.full_tokens);
-                }
+            continue;
+        }
+        (decisions[idx], reasons[idx]) =
+            decide_block(&infos[idx], filtered[idx], &mut remaining, estimator);
+    }
+
+    (decisions, reasons)
+}
+
+/// Decide how to render one block and charge its cost to `remaining`.
+///
+/// Applies the block's priority degradation path against whatever
+/// budget is left, and says which step of the path it landed on.
+/// [`allocate_budget`] calls this in priority order; the streaming
+/// renderer calls it in arrival order.
+///
+/// An outline, for code without a summary that fits, keeps the API
+/// surface of a file where a placeholder would keep only its path.
+/// Truncation spends leftover budget on a slice of the content where
+/// the block would otherwise lose all of it. A Normal block whose full
+/// content is at most twice the remaining budget is truncated before
+/// its summary is considered; a Low block is truncated to at most half
+/// its content, so it never renders more than its priority allows.
+///
+/// A block that cannot even afford its placeholder is omitted.
+pub(crate) fn decide_block(
+    info: &BlockBudgetInfo,
+    block: &Block,
+    remaining: &mut u32,
+    estimator: &dyn TokenEstimator,
+) -> (RenderDecision, DecisionReason) {
+    match info.priority {
+        Priority::Critical => {
+            *remaining = remaining.saturating_sub(info.full_tokens);
+            let reason = if info.turn == Some(Turn::Kept) {
+                DecisionReason::KeptTurn
+            } else {
+                DecisionReason::Critical
+            };
+            (RenderDecision::Full, reason)
+        }
+        Priority::High | Priority::Normal if info.full_tokens <= *remaining => {
+            *remaining -= info.full_tokens;
+            (RenderDecision::Full, DecisionReason::WithinBudget)
+        }
+        Priority::High => {
+            if let Some(decision) =
+                try_summary(info, remaining).or_else(|| try_outline(info, remaining))
+            {
+                (decision, DecisionReason::OverBudget)
+            } else {
+                // High-priority content is too important to omit: render
+                // full anyway, over budget.
+                *remaining = 0;
+                (RenderDecision::Full, DecisionReason::ForcedOverBudget)
             }
-            Priority::Low => {
-                if info.has_summary {
-                    let stok = info.summary_tokens.unwrap_or(0);
-                    if stok <= remaining {
-                        decisions[idx] = RenderDecision::Summary;
-                        remaining -= stok;
-                    } else {
-                        decisions[idx] = make_placeholder(block, info.full_tokens);
-                    }
-                } else {
-                    decisions[idx] = make_placeholder(block, info.full_tokens);
-                }
+        }
+        Priority::Normal => {
+            let mostly_fits = info.content_tokens <= remaining.saturating_mul(2);
+            let decision = mostly_fits
+                .then(|| try_truncate(info, block, remaining, u32::MAX, estimator))
+                .flatten()
+                .or_else(|| try_summary(info, remaining))
+                .or_else(|| try_outline(info, remaining))
+                .or_else(|| try_truncate(info, block, remaining, u32::MAX, estimator))
+                .or_else(|| try_placeholder(info, block, remaining))
+                .unwrap_or(RenderDecision::Omit);
+            (decision, DecisionReason::OverBudget)
+        }
+        Priority::Low => {
+            if let Some(decision) = try_summary(info, remaining) {
+                return (decision, DecisionReason::LowPriority);
             }
-            Priority::Background => {
-                if PLACEHOLDER_TOKEN_COST <= remaining {
-                    decisions[idx] = make_placeholder(block, info.full_tokens);
-                    remaining = remaining.saturating_sub(PLACEHOLDER_TOKEN_COST);
+            // Without a summary, the outline is the most a Low block
+            // may render.
+            if let Some(decision) = try_outline(info, remaining) {
+                let reason = if info.has_summary {
+                    DecisionReason::OverBudget
                 } else {
-                    decisions[idx] = RenderDecision::Omit;
-                }
+                    DecisionReason::LowPriority
+                };
+                return (decision, reason);
             }
+            let decision = try_truncate(info, block, remaining, info.content_tokens / 2, estimator)
+                .or_else(|| try_placeholder(info, block, remaining))
+                .unwrap_or(RenderDecision::Omit);
+            // A Low block without a summary or outline has nothing
+            // between full content and a cut-down form: its priority put
+            // it there.
+            let reason = if info.has_summary || info.outline_tokens.is_some() {
+                DecisionReason::OverBudget
+            } else {
+                DecisionReason::LowPriority
+            };
+            (decision, reason)
         }
+        Priority::Background => match try_placeholder(info, block, remaining) {
+            Some(decision) => (decision, DecisionReason::LowPriority),
+            None => (RenderDecision::Omit, DecisionReason::OverBudget),
+        },
+    }
+}
+
+/// `Summary` if the block has one that fits in `remaining`, charging it.
+fn try_summary(info: &BlockBudgetInfo, remaining: &mut u32) -> Option<RenderDecision> {
+    let tokens = info.summary_tokens.filter(|_| info.has_summary)?;
+    if tokens > *remaining {
+        return None;
+    }
+    *remaining -= tokens;
+    Some(RenderDecision::Summary)
+}
+
+/// `Outline` if the block has one that fits in `remaining`, charging it.
+fn try_outline(info: &BlockBudgetInfo, remaining: &mut u32) -> Option<RenderDecision> {
+    let tokens = info.outline_tokens?;
+    if tokens > *remaining {
+        return None;
     }
+    *remaining -= tokens;
+    Some(RenderDecision::Outline)
+}
+
+/// `Truncated` if a line window of the block fits in `remaining` (less
+/// the block's framing and elision marker) and in `cap`, charging the
+/// kept lines and the overhead.
+fn try_truncate(
+    info: &BlockBudgetInfo,
+    block: &Block,
+    remaining: &mut u32,
+    cap: u32,
+    estimator: &dyn TokenEstimator,
+) -> Option<RenderDecision> {
+    let allowance = remaining.checked_sub(info.truncation_overhead)?.min(cap);
+    let truncation = plan_truncation(block, allowance, estimator)?;
+    *remaining -= truncation.kept_tokens + info.truncation_overhead;
+    Some(RenderDecision::Truncated {
+        head: truncation.head,
+        tail: truncation.tail,
+        omitted_tokens: info.content_tokens.saturating_sub(truncation.kept_tokens),
+    })
+}
 
-    decisions
+/// `Placeholder` if the block's placeholder fits in `remaining`,
+/// charging it.
+fn try_placeholder(
+    info: &BlockBudgetInfo,
+    block: &Block,
+    remaining: &mut u32,
+) -> Option<RenderDecision> {
+    if info.placeholder_tokens > *remaining {
+        return None;
+    }
+    *remaining -= info.placeholder_tokens;
+    Some(make_placeholder(block, info.content_tokens))
 }
 
 /// Build a `RenderDecision::Placeholder` for a block.
-fn make_placeholder(block: &Block, omitted_tokens: u32) -> RenderDecision {
+pub(crate) fn make_placeholder(block: &Block, omitted_tokens: u32) -> RenderDecision {
     RenderDecision::Placeholder {
         block_type: block.block_type.clone(),
         description: block_description(block),
@@ -500,6 +862,15 @@ fn make_placeholder(block: &Block, omitted_tokens: u32) -> RenderDecision {
 
 /// Run the complete budget engine: resolve priorities, scan, allocate.
 ///
+/// `config.budget_strategy` selects the allocation pass:
+/// [`allocate_budget`] for [`BudgetStrategy::Greedy`],
+/// [`allocate_optimal`] for [`BudgetStrategy::Optimal`].
+/// `config.conversation`, when set, marks each conversation turn kept
+/// or older first (see [`classify_turns`]); turns with a `Priority`
+/// annotation are left out. `config.group_dependencies` then joins the
+/// remaining blocks into [`dependency_groups`], each member raised to
+/// the priority of the group's most important one.
+///
 /// This is the main entry point called by `DefaultDriver::render()`.
 /// It ties together the three pipeline stages:
 ///
@@ -510,105 +881,114 @@ fn make_placeholder(block: &Block, omitted_tokens: u32) -> RenderDecision {
 ///                                               │
 ///                                         Vec<BlockBudgetInfo>
 ///                                               │
-///                                        allocate_budget()
+///                              allocate_budget() / allocate_optimal()
 ///                                               │
 ///                                        Vec<RenderDecision>
 /// ```
 ///
 /// Returns a `Vec<RenderDecision>` parallel to `filtered` — each entry
-/// tells the renderer how to handle the corresponding block.
+/// tells the renderer how to handle the corresponding block — and the
+/// matching `Vec<DecisionReason>`. `budget` covers the blocks alone;
+/// the caller subtracts [`BlockPricer::wrapper_tokens`] first.
+///
+/// `query_scores`, parallel to `filtered`, gives the relevance of each
+/// block without a `Relevance` annotation — an explicit annotation
+/// always wins over the query.
 pub(crate) fn compute_budget_decisions(
     all_blocks: &[Block],
     filtered: &[&Block],
     original_indices: &[usize],
     budget: u32,
-    estimator: &dyn TokenEstimator,
-) -> Vec<RenderDecision> {
+    config: &DriverConfig,
+    pricer: &BlockPricer<'_>,
+    query_scores: Option<&[f32]>,
+) -> (Vec<RenderDecision>, Vec<DecisionReason>) {
     let priorities = resolve_priorities(all_blocks);
-    let infos = scan_blocks(filtered, &priorities, estimator, original_indices);
-    allocate_budget(&infos, budget, filtered)
+    let relevance = resolve_relevance_with_query(all_blocks, original_indices, query_scores);
+    let mut infos = scan_blocks(filtered, &priorities, &relevance, pricer, original_indices);
+    if let Some(policy) = config.conversation {
+        let turns = classify_turns(filtered, policy);
+        for ((info, turn), &orig) in infos.iter_mut().zip(turns).zip(original_indices) {
+            #[allow(clippy::cast_possible_truncation)]
+            if priorities.contains_key(&(orig as u32)) {
+                continue;
+            }
+            if turn == Some(Turn::Kept) {
+                info.priority = Priority::Critical;
+            }
+            info.turn = turn;
+        }
+    }
+    if config.group_dependencies {
+        for members in dependency_groups(all_blocks, filtered, original_indices) {
+            let members: Vec<usize> = members
+                .into_iter()
+                .filter(|&m| infos[m].turn.is_none())
+                .collect();
+            if members.len() < 2 {
+                continue;
+            }
+            let priority = members
+                .iter()
+                .map(|&m| infos[m].priority)
+                .min()
+                .unwrap_or(Priority::Normal);
+            for &m in &members {
+                infos[m].priority = priority;
+                infos[m].group = Some(members[0]);
+            }
+        }
+    }
+    let estimator = pricer.estimator();
+    match config.budget_strategy {
+        BudgetStrategy::Greedy => allocate_budget(&infos, budget, filtered, estimator),
+        BudgetStrategy::Optimal => allocate_optimal(&infos, budget, filtered, estimator),
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bcp_types::annotation::AnnotationBlock;
-    use bcp_types::code::CodeBlock;
-    use bcp_types::conversation::ConversationBlock;
+    use crate::test_support::{
+        code_block, conversation_block, priority_annotation, tag_annotation, tool_result_block,
+        with_summary,
+    };
     use bcp_types::enums::{Lang, Role};
-    use bcp_types::summary::Summary;
-    use bcp_types::tool_result::ToolResultBlock;
-    use bcp_wire::block_frame::BlockFlags;
 
     // ── Test Helpers ────────────────────────────────────────────────
 
-    fn code_block(path: &str, content: &[u8]) -> Block {
-        Block {
-            block_type: BlockType::Code,
-            flags: BlockFlags::NONE,
-            summary: None,
-            content: BlockContent::Code(CodeBlock {
-                lang: Lang::Rust,
-                path: path.to_string(),
-                content: content.to_vec(),
-                line_range: None,
-            }),
-        }
-    }
-
-    fn code_block_with_summary(path: &str, content: &[u8], summary: &str) -> Block {
-        Block {
-            block_type: BlockType::Code,
-            flags: BlockFlags::HAS_SUMMARY,
-            summary: Some(Summary {
-                text: summary.to_string(),
-            }),
-            content: BlockContent::Code(CodeBlock {
-                lang: Lang::Rust,
-                path: path.to_string(),
-                content: content.to_vec(),
-                line_range: None,
-            }),
-        }
-    }
-
-    fn conversation_block(role: Role, content: &[u8]) -> Block {
-        Block {
-            block_type: BlockType::Conversation,
-            flags: BlockFlags::NONE,
-            summary: None,
-            content: BlockContent::Conversation(ConversationBlock {
-                role,
-                content: content.to_vec(),
-                tool_call_id: None,
-            }),
-        }
-    }
-
-    fn priority_annotation(target: u32, priority: Priority) -> Block {
-        Block {
-            block_type: BlockType::Annotation,
-            flags: BlockFlags::NONE,
-            summary: None,
-            content: BlockContent::Annotation(AnnotationBlock {
-                target_block_id: target,
-                kind: AnnotationKind::Priority,
-                value: vec![priority.to_wire_byte()],
-            }),
-        }
+    /// Greedy decisions, priced as Minimal output with the heuristic
+    /// estimator.
+    fn decide(
+        blocks: &[Block],
+        filtered: &[&Block],
+        original_indices: &[usize],
+        budget: u32,
+    ) -> (Vec<RenderDecision>, Vec<DecisionReason>) {
+        let ctx = RenderContext::default();
+        let pricer = BlockPricer::new(OutputMode::Minimal, &ctx, &HeuristicEstimator);
+        compute_budget_decisions(
+            blocks,
+            filtered,
+            original_indices,
+            budget,
+            &DriverConfig::default(),
+            &pricer,
+            None,
+        )
     }
 
-    fn tag_annotation(target: u32, tag: &str) -> Block {
-        Block {
-            block_type: BlockType::Annotation,
-            flags: BlockFlags::NONE,
-            summary: None,
-            content: BlockContent::Annotation(AnnotationBlock {
-                target_block_id: target,
-                kind: AnnotationKind::Tag,
-                value: tag.as_bytes().to_vec(),
-            }),
-        }
+    /// Unannotated scan infos, priced as in [`decide`].
+    fn scan(filtered: &[&Block], original_indices: &[usize]) -> Vec<BlockBudgetInfo> {
+        let ctx = RenderContext::default();
+        let pricer = BlockPricer::new(OutputMode::Minimal, &ctx, &HeuristicEstimator);
+        scan_blocks(
+            filtered,
+            &HashMap::new(),
+            &HashMap::new(),
+            &pricer,
+            original_indices,
+        )
     }
 
     // ── HeuristicEstimator tests ────────────────────────────────────
@@ -679,7 +1059,7 @@ mod tests {
     #[test]
     fn resolve_priorities_single() {
         let blocks = vec![
-            code_block("main.rs", b"fn main() {}"),
+            code_block(Lang::Rust, "main.rs", b"fn main() {}"),
             priority_annotation(0, Priority::Critical),
         ];
         let map = resolve_priorities(&blocks);
@@ -689,8 +1069,8 @@ mod tests {
     #[test]
     fn resolve_priorities_multiple_targets() {
         let blocks = vec![
-            code_block("a.rs", b"a"),
-            code_block("b.rs", b"b"),
+            code_block(Lang::Rust, "a.rs", b"a"),
+            code_block(Lang::Rust, "b.rs", b"b"),
             priority_annotation(0, Priority::High),
             priority_annotation(1, Priority::Low),
         ];
@@ -702,7 +1082,7 @@ mod tests {
     #[test]
     fn resolve_priorities_last_wins() {
         let blocks = vec![
-            code_block("main.rs", b"fn main() {}"),
+            code_block(Lang::Rust, "main.rs", b"fn main() {}"),
             priority_annotation(0, Priority::Low),
             priority_annotation(0, Priority::Critical),
         ];
@@ -713,7 +1093,7 @@ mod tests {
     #[test]
     fn resolve_priorities_ignores_non_priority() {
         let blocks = vec![
-            code_block("main.rs", b"fn main() {}"),
+            code_block(Lang::Rust, "main.rs", b"fn main() {}"),
             tag_annotation(0, "security"),
         ];
         let map = resolve_priorities(&blocks);
@@ -726,19 +1106,13 @@ mod tests {
     fn allocate_budget_unlimited() {
         // Large budget: all blocks should get Full
         let blocks = vec![
-            code_block("a.rs", &"a".repeat(100).into_bytes()),
-            code_block("b.rs", &"b".repeat(100).into_bytes()),
+            code_block(Lang::Rust, "a.rs", "a".repeat(100)),
+            code_block(Lang::Rust, "b.rs", "b".repeat(100)),
         ];
         let filtered: Vec<&Block> = blocks.iter().collect();
         let original_indices = vec![0, 1];
 
-        let decisions = compute_budget_decisions(
-            &blocks,
-            &filtered,
-            &original_indices,
-            100_000,
-            &HeuristicEstimator,
-        );
+        let (decisions, _) = decide(&blocks, &filtered, &original_indices, 100_000);
         assert_eq!(decisions.len(), 2);
         assert_eq!(decisions[0], RenderDecision::Full);
         assert_eq!(decisions[1], RenderDecision::Full);
@@ -748,7 +1122,7 @@ mod tests {
     fn allocate_budget_critical_always_full() {
         // Budget of 0, but Critical block still gets Full
         let blocks = vec![
-            code_block("main.rs", &"x".repeat(400).into_bytes()),
+            code_block(Lang::Rust, "main.rs", "x".repeat(400)),
             priority_annotation(0, Priority::Critical),
         ];
         let filtered: Vec<&Block> = blocks
@@ -757,13 +1131,7 @@ mod tests {
             .collect();
         let original_indices = vec![0];
 
-        let decisions = compute_budget_decisions(
-            &blocks,
-            &filtered,
-            &original_indices,
-            0,
-            &HeuristicEstimator,
-        );
+        let (decisions, _) = decide(&blocks, &filtered, &original_indices, 0);
         assert_eq!(decisions[0], RenderDecision::Full);
     }
 
@@ -771,22 +1139,16 @@ mod tests {
     fn allocate_budget_normal_degrades_to_summary() {
         // Normal block with summary, tight budget → Summary
         let content = "x".repeat(400); // ~100 tokens
-        let blocks = vec![code_block_with_summary(
-            "main.rs",
-            content.as_bytes(),
-            "Entry point.", // ~3 tokens
+        let blocks = vec![with_summary(
+            code_block(Lang::Rust, "main.rs", content.as_bytes()),
+            "Entry point.",
         )];
         let filtered: Vec<&Block> = blocks.iter().collect();
         let original_indices = vec![0];
 
-        // Budget of 10: not enough for full (100 tokens) but enough for summary (3)
-        let decisions = compute_budget_decisions(
-            &blocks,
-            &filtered,
-            &original_indices,
-            10,
-            &HeuristicEstimator,
-        );
+        // Budget of 20: not enough for full (100 tokens) but enough for
+        // the summary with its header line (~12)
+        let (decisions, _) = decide(&blocks, &filtered, &original_indices, 20);
         assert_eq!(decisions[0], RenderDecision::Summary);
     }
 
@@ -795,7 +1157,7 @@ mod tests {
         // Low priority block without summary, tight budget → Placeholder
         let content = "x".repeat(400);
         let blocks = vec![
-            code_block("main.rs", content.as_bytes()),
+            code_block(Lang::Rust, "main.rs", content.as_bytes()),
             priority_annotation(0, Priority::Low),
         ];
         let filtered: Vec<&Block> = blocks
@@ -804,13 +1166,7 @@ mod tests {
             .collect();
         let original_indices = vec![0];
 
-        let decisions = compute_budget_decisions(
-            &blocks,
-            &filtered,
-            &original_indices,
-            5,
-            &HeuristicEstimator,
-        );
+        let (decisions, _) = decide(&blocks, &filtered, &original_indices, 15);
         assert!(
             matches!(decisions[0], RenderDecision::Placeholder { .. }),
             "Low priority without summary should be Placeholder, got {:?}",
@@ -822,7 +1178,7 @@ mod tests {
     fn allocate_budget_background_omit() {
         // Background block with zero budget → Omit
         let blocks = vec![
-            code_block("bg.rs", b"background stuff"),
+            code_block(Lang::Rust, "bg.rs", b"background stuff"),
             priority_annotation(0, Priority::Background),
         ];
         let filtered: Vec<&Block> = blocks
@@ -831,25 +1187,128 @@ mod tests {
             .collect();
         let original_indices = vec![0];
 
-        let decisions = compute_budget_decisions(
-            &blocks,
-            &filtered,
-            &original_indices,
-            0,
-            &HeuristicEstimator,
-        );
+        let (decisions, _) = decide(&blocks, &filtered, &original_indices, 0);
         assert_eq!(decisions[0], RenderDecision::Omit);
     }
 
+    #[test]
+    fn allocate_budget_reports_reasons() {
+        // High without a summary is forced full and spends the budget.
+        // Low without a summary is cut down for its priority, Normal for
+        // lack of budget — both all the way to Omit, since not even a
+        // placeholder fits.
+        let content = "x".repeat(400);
+        let blocks = vec![
+            code_block(Lang::Rust, "high.rs", content.as_bytes()),
+            priority_annotation(0, Priority::High),
+            code_block(Lang::Rust, "low.rs", b"fn low() {}"),
+            priority_annotation(2, Priority::Low),
+            code_block(Lang::Rust, "normal.rs", content.as_bytes()),
+        ];
+        let filtered: Vec<&Block> = blocks
+            .iter()
+            .filter(|b| b.block_type != BlockType::Annotation)
+            .collect();
+        let original_indices = vec![0, 2, 4];
+
+        let (decisions, reasons) = decide(&blocks, &filtered, &original_indices, 20);
+        assert_eq!(decisions[0], RenderDecision::Full);
+        assert_eq!(decisions[1], RenderDecision::Omit);
+        assert_eq!(decisions[2], RenderDecision::Omit);
+        assert_eq!(
+            reasons,
+            [
+                DecisionReason::ForcedOverBudget,
+                DecisionReason::LowPriority,
+                DecisionReason::OverBudget,
+            ]
+        );
+    }
+
+    #[test]
+    fn allocate_budget_truncates_mostly_fitting_block() {
+        // 100 lines of ~2 tokens: 10% over budget keeps the top of the
+        // file rather than swapping it for the summary.
+        let content = (1..=100)
+            .map(|n| format!("x{n:06}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let blocks = vec![with_summary(
+            code_block(Lang::Rust, "big.rs", content.as_bytes()),
+            "Big.",
+        )];
+        let filtered: Vec<&Block> = blocks.iter().collect();
+        let infos = scan(&filtered, &[0]);
+        let budget = infos[0].full_tokens * 9 / 10;
+
+        let (decisions, reasons) = decide(&blocks, &filtered, &[0], budget);
+        let RenderDecision::Truncated {
+            head,
+            tail,
+            omitted_tokens,
+        } = decisions[0]
+        else {
+            panic!("expected Truncated, got {:?}", decisions[0]);
+        };
+        assert_eq!(tail, 0);
+        assert!(head > 50 && head < 100, "head = {head}");
+        assert!(omitted_tokens > 0);
+        assert_eq!(reasons[0], DecisionReason::OverBudget);
+
+        // Far over budget: the summary wins.
+        let (decisions, _) = decide(&blocks, &filtered, &[0], budget / 4);
+        assert_eq!(decisions[0], RenderDecision::Summary);
+    }
+
+    #[test]
+    fn allocate_budget_truncates_instead_of_placeholder() {
+        // Tool output without a summary keeps head and tail lines.
+        let content = (1..=40)
+            .map(|n| format!("out {n:04}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let blocks = vec![
+            tool_result_block("cargo test", content.as_bytes()),
+            code_block(Lang::Rust, "low.rs", content.as_bytes()),
+            priority_annotation(1, Priority::Low),
+        ];
+        let filtered: Vec<&Block> = blocks
+            .iter()
+            .filter(|b| b.block_type != BlockType::Annotation)
+            .collect();
+
+        let (decisions, reasons) = decide(&blocks, &filtered, &[0, 1], 60);
+        assert!(
+            matches!(decisions[0], RenderDecision::Truncated { head, tail, .. } if head > 0 && tail > 0),
+            "got {:?}",
+            decisions[0]
+        );
+        assert_eq!(reasons[0], DecisionReason::OverBudget);
+
+        // Low priority never gets more than half of its content.
+        let (decisions, reasons) = decide(&blocks, &filtered, &[0, 1], 10_000);
+        assert_eq!(decisions[0], RenderDecision::Full);
+        let RenderDecision::Truncated { head, tail, .. } = decisions[1] else {
+            panic!("expected Truncated, got {:?}", decisions[1]);
+        };
+        let low_full = scan(&filtered[1..], &[1])[0].full_tokens;
+        assert_eq!(tail, 0);
+        assert!(head > 0 && u32::try_from(head).unwrap() * 2 <= low_full / 2);
+        assert_eq!(reasons[1], DecisionReason::LowPriority);
+    }
+
     #[test]
     fn allocate_budget_respects_priority_ordering() {
         // Critical block consumes budget before Normal block
         let big_content = "x".repeat(400); // ~100 tokens each
         let blocks = vec![
             // Block 0: Normal priority (default)
-            code_block_with_summary("normal.rs", big_content.as_bytes(), "Normal summary."),
+            with_summary(
+                code_block(Lang::Rust, "normal.rs", big_content.as_bytes()),
+                "Normal summary.",
+            ),
             // Block 1: Critical priority
-            code_block("critical.rs", big_content.as_bytes()),
+            code_block(Lang::Rust, "critical.rs", big_content.as_bytes()),
             priority_annotation(1, Priority::Critical),
         ];
         let filtered: Vec<&Block> = blocks
@@ -859,13 +1318,7 @@ mod tests {
         let original_indices = vec![0, 1];
 
         // Budget = 120: enough for one full (100) + one summary (4), not two fulls
-        let decisions = compute_budget_decisions(
-            &blocks,
-            &filtered,
-            &original_indices,
-            120,
-            &HeuristicEstimator,
-        );
+        let (decisions, _) = decide(&blocks, &filtered, &original_indices, 120);
         // Critical block should be Full (processed first due to priority)
         assert_eq!(
             decisions[1],
@@ -885,20 +1338,20 @@ mod tests {
         // Without annotations, all blocks default to Normal
         let content = "x".repeat(400);
         let blocks = vec![
-            code_block_with_summary("a.rs", content.as_bytes(), "Summary A."),
-            code_block_with_summary("b.rs", content.as_bytes(), "Summary B."),
+            with_summary(
+                code_block(Lang::Rust, "a.rs", content.as_bytes()),
+                "Summary A.",
+            ),
+            with_summary(
+                code_block(Lang::Rust, "b.rs", content.as_bytes()),
+                "Summary B.",
+            ),
         ];
         let filtered: Vec<&Block> = blocks.iter().collect();
         let original_indices = vec![0, 1];
 
         // Budget enough for one full + one summary
-        let decisions = compute_budget_decisions(
-            &blocks,
-            &filtered,
-            &original_indices,
-            120,
-            &HeuristicEstimator,
-        );
+        let (decisions, _) = decide(&blocks, &filtered, &original_indices, 120);
         // First Normal block should get Full (has budget), second should degrade
         assert_eq!(decisions[0], RenderDecision::Full);
         assert_eq!(decisions[1], RenderDecision::Summary);
@@ -906,7 +1359,7 @@ mod tests {
 
     #[test]
     fn block_description_code() {
-        let block = code_block("src/main.rs", b"fn main() {}");
+        let block = code_block(Lang::Rust, "src/main.rs", b"fn main() {}");
         assert_eq!(block_description(&block), "src/main.rs");
     }
 
@@ -920,9 +1373,9 @@ mod tests {
     fn zero_budget_all_critical() {
         // Zero budget, all Critical → all Full (Critical is never degraded)
         let blocks = vec![
-            code_block("a.rs", &"a".repeat(400).into_bytes()),
+            code_block(Lang::Rust, "a.rs", "a".repeat(400)),
             priority_annotation(0, Priority::Critical),
-            code_block("b.rs", &"b".repeat(400).into_bytes()),
+            code_block(Lang::Rust, "b.rs", "b".repeat(400)),
             priority_annotation(2, Priority::Critical),
         ];
         let filtered: Vec<&Block> = blocks
@@ -931,13 +1384,7 @@ mod tests {
             .collect();
         let original_indices = vec![0, 2];
 
-        let decisions = compute_budget_decisions(
-            &blocks,
-            &filtered,
-            &original_indices,
-            0,
-            &HeuristicEstimator,
-        );
+        let (decisions, _) = decide(&blocks, &filtered, &original_indices, 0);
         assert_eq!(decisions[0], RenderDecision::Full);
         assert_eq!(decisions[1], RenderDecision::Full);
     }
@@ -948,18 +1395,24 @@ mod tests {
         let content = "x".repeat(400); // ~100 tokens each
         let blocks = vec![
             // 0: Background
-            code_block("bg.rs", content.as_bytes()),
+            code_block(Lang::Rust, "bg.rs", content.as_bytes()),
             priority_annotation(0, Priority::Background),
             // 2: Critical
-            code_block("crit.rs", content.as_bytes()),
+            code_block(Lang::Rust, "crit.rs", content.as_bytes()),
             priority_annotation(2, Priority::Critical),
             // 4: Normal with summary
-            code_block_with_summary("normal.rs", content.as_bytes(), "Normal summary."),
+            with_summary(
+                code_block(Lang::Rust, "normal.rs", content.as_bytes()),
+                "Normal summary.",
+            ),
             // 5: Low
-            code_block("low.rs", content.as_bytes()),
+            code_block(Lang::Rust, "low.rs", content.as_bytes()),
             priority_annotation(5, Priority::Low),
             // 7: High with summary
-            code_block_with_summary("high.rs", content.as_bytes(), "High summary."),
+            with_summary(
+                code_block(Lang::Rust, "high.rs", content.as_bytes()),
+                "High summary.",
+            ),
             priority_annotation(7, Priority::High),
         ];
         let filtered: Vec<&Block> = blocks
@@ -969,13 +1422,7 @@ mod tests {
         let original_indices = vec![0, 2, 4, 5, 7];
 
         // Budget = 150: enough for Critical (100) + some leftovers
-        let decisions = compute_budget_decisions(
-            &blocks,
-            &filtered,
-            &original_indices,
-            150,
-            &HeuristicEstimator,
-        );
+        let (decisions, _) = decide(&blocks, &filtered, &original_indices, 150);
 
         // Critical → Full (always)
         assert_eq!(
@@ -1004,37 +1451,26 @@ mod tests {
     fn block_without_summary_at_normal_gets_placeholder() {
         // Normal block without summary, tight budget → Placeholder (not Summary)
         let content = "x".repeat(400); // ~100 tokens
-        let blocks = vec![code_block("nosummary.rs", content.as_bytes())];
+        let blocks = vec![code_block(Lang::Rust, "nosummary.rs", content.as_bytes())];
         let filtered: Vec<&Block> = blocks.iter().collect();
         let original_indices = vec![0];
 
-        let decisions = compute_budget_decisions(
-            &blocks,
-            &filtered,
-            &original_indices,
-            5, // way too small
-            &HeuristicEstimator,
-        );
+        // Room for the placeholder line only
+        let (decisions, _) = decide(&blocks, &filtered, &original_indices, 15);
         assert!(
             matches!(decisions[0], RenderDecision::Placeholder { .. }),
             "Normal without summary should be Placeholder, got {:?}",
             decisions[0]
         );
+
+        // Not even that: the block is dropped
+        let (decisions, _) = decide(&blocks, &filtered, &original_indices, 5);
+        assert_eq!(decisions[0], RenderDecision::Omit);
     }
 
     #[test]
     fn block_description_tool_result() {
-        let block = Block {
-            block_type: BlockType::ToolResult,
-            flags: BlockFlags::NONE,
-            summary: None,
-            content: BlockContent::ToolResult(ToolResultBlock {
-                tool_name: "ripgrep".to_string(),
-                status: bcp_types::enums::Status::Ok,
-                content: b"results".to_vec(),
-                schema_hint: None,
-            }),
-        };
+        let block = tool_result_block("ripgrep", b"results");
         assert_eq!(block_description(&block), "ripgrep");
     }
 }
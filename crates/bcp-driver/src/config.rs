@@ -1,5 +1,11 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use bcp_types::BlockType;
 
+use crate::budget::{CodeAwareEstimator, TokenEstimator};
+use crate::profile::ModelProfile;
+
 /// Configuration for the BCP driver.
 ///
 /// Controls how decoded blocks are rendered into model-ready text.
@@ -8,34 +14,96 @@ use bcp_types::BlockType;
 /// rendered output, and manage token budget constraints.
 ///
 /// ```text
-/// ┌────────────────┬────────────────────────────────────────────────────┐
-/// │ Field          │ Purpose                                            │
-/// ├────────────────┼────────────────────────────────────────────────────┤
-/// │ mode           │ Selects XML, Markdown, or Minimal output format    │
-/// │ target_model   │ Hints for model-specific formatting adjustments    │
-/// │ include_types  │ Optional allowlist — only render matching blocks   │
-/// │ token_budget   │ Approximate token limit for rendered output        │
-/// │ verbosity      │ Full / Summary / Adaptive rendering mode           │
-/// └────────────────┴────────────────────────────────────────────────────┘
+/// ┌────────────────────┬────────────────────────────────────────────────────┐
+/// │ Field              │ Purpose                                            │
+/// ├────────────────────┼────────────────────────────────────────────────────┤
+/// │ mode               │ Xml / Markdown / Minimal / Raw; None = profile     │
+/// │ target_model       │ Model family whose rendering profile applies       │
+/// │ include_types      │ Optional allowlist — only render matching blocks   │
+/// │ token_budget       │ Approximate token limit for rendered output        │
+/// │ verbosity          │ Full / Summary / Adaptive rendering mode           │
+/// │ budget_strategy    │ Greedy / Optimal allocation under token_budget     │
+/// │ query              │ Task query that ranks blocks by relevance          │
+/// │ conversation       │ Keep recent turns, degrade older ones first        │
+/// │ group_dependencies │ Budget related blocks as one unit                  │
+/// │ dedup              │ Render content repeated between blocks once        │
+/// │ ordering           │ Payload / PriorityFirst / Edges / ByType           │
+/// │ path_prefix        │ Declare the common directory of paths once         │
+/// │ recent_since       │ Mark FILE_TREE entries modified at/after this time │
+/// │ include_tags       │ Optional allowlist — only render blocks so tagged  │
+/// │ exclude_tags       │ Never render blocks carrying any of these tags     │
+/// │ current_time       │ Clock for Expiry annotations (default: system)     │
+/// │ estimator          │ Token counter for the budget engine                │
+/// └────────────────────┴────────────────────────────────────────────────────┘
 /// ```
 ///
+/// When `target_model` is set, its [`ModelProfile`] supplies the output
+/// mode (if `mode` is `None`), the token estimator (if `estimator` is
+/// `None`), and the XML element names and attribute style. Explicit
+/// fields always win over the profile.
+///
 /// When `include_types` is `None`, all block types are rendered (except
 /// `Annotation`, which is metadata-only and never produces visible output).
 /// When `Some(vec)`, only blocks whose `BlockType` is in the list are
 /// rendered; all others are silently skipped.
 ///
+/// Tags come from `AnnotationKind::Tag` annotations. `include_tags` keeps
+/// only blocks carrying at least one of the listed tags; `exclude_tags`
+/// drops any block carrying one of its tags, and wins over `include_tags`.
+/// Both filters compose with `include_types`.
+///
 /// When `token_budget` is `Some(n)`, the driver uses a two-pass algorithm
 /// (RFC §5.5) to fit blocks within the budget: high-priority blocks get
 /// full content, while lower-priority blocks degrade to summaries or
-/// placeholders. When `None`, all blocks render with full content.
+/// placeholders. When `None`, all blocks render with full content. Token
+/// costs come from `estimator`, which defaults to [`CodeAwareEstimator`].
+/// `budget_strategy` picks how the budget is shared out; see
+/// [`BudgetStrategy`].
+///
+/// When `query` is set, every rendered block is scored against it
+/// locally (BM25 over the content, plus matches in paths and tool
+/// names). Within a priority tier the budget engine keeps the
+/// best-matching blocks and degrades the rest first; a `Relevance`
+/// annotation on a block overrides its query score.
+///
+/// When `conversation` is set, conversation turns are budgeted as a
+/// conversation rather than in payload order: the system turn and the
+/// most recent turns always render in full, and older turns degrade to
+/// summaries, then placeholders. See [`ConversationPolicy`].
+///
+/// When `group_dependencies` is set, blocks that only make sense
+/// together are budgeted as one unit: a DIFF with the CODE of the same
+/// path, a tool call with its output, an `EMBEDDING_REF` with the block
+/// it was computed from, and blocks joined by a `Link` annotation. All
+/// members of a group render at the same level — all full, all
+/// summarized, all placeholders or all omitted — at the priority of
+/// the most important member.
+///
+/// When `dedup` is set, content repeated between blocks rendered in
+/// full is rendered once: overlapping CODE fragments of the same path
+/// are merged, and long runs of lines that repeat an earlier block —
+/// a tool printing a file, say — become a back-reference such as
+/// `[see code src/main.rs L10-40]`. The report shows the tokens saved.
+/// It runs after the budget, so it never drops the lines a reference
+/// points at, and Raw mode skips it.
+///
+/// `ordering` picks the order blocks are rendered in; see
+/// [`BlockOrdering`].
+///
+/// `path_prefix` factors the directory every rendered path shares out
+/// of the block headers and declares it once, as in
+/// `<context root="services/billing/src">`. It is on by default in
+/// Minimal mode only; see [`PathPrefix`].
+///
+/// [`CodeAwareEstimator`]: crate::budget::CodeAwareEstimator
 pub struct DriverConfig {
     /// Output format mode. Determines the textual structure of the
-    /// rendered output.
-    pub mode: OutputMode,
+    /// rendered output. When `None`, the `target_model` profile's mode
+    /// is used, falling back to XML.
+    pub mode: Option<OutputMode>,
 
-    /// Model family hint. Affects minor formatting choices (e.g., XML
-    /// attribute ordering for Claude compatibility, markdown header
-    /// depth for GPT).
+    /// Model family whose [`ModelProfile`] fills in the output mode,
+    /// token estimator, and XML tag vocabulary and style.
     pub target_model: Option<ModelFamily>,
 
     /// Block type filter. When set, only blocks of these types are
@@ -51,11 +119,72 @@ pub struct DriverConfig {
     /// driver renders full content, summaries, or auto-selects per
     /// block based on budget and priority.
     pub verbosity: Verbosity,
+
+    /// How the budget engine shares `token_budget` between blocks.
+    /// Defaults to [`BudgetStrategy::Greedy`].
+    pub budget_strategy: BudgetStrategy,
+
+    /// Task the payload is rendered for, such as the user's question.
+    /// When set, blocks are ranked by how well they match it and the
+    /// budget engine degrades poor matches first. Scores appear in the
+    /// render report. When `None`, only `Relevance` annotations rank
+    /// blocks.
+    pub query: Option<String>,
+
+    /// Conversation policy for the budget engine. When `None`, turns
+    /// are budgeted like any other block.
+    pub conversation: Option<ConversationPolicy>,
+
+    /// Budget related blocks as one unit, so the budget engine never
+    /// keeps one half of a pair and drops the other. Blocks in a
+    /// `conversation` turn are left to the turn. For streaming, see
+    /// [`StreamStrategy`](crate::StreamStrategy).
+    pub group_dependencies: bool,
+
+    /// Replace content repeated between full blocks with
+    /// back-references to where it already appears. Defaults to off.
+    pub dedup: bool,
+
+    /// Order of the blocks in the output. Defaults to
+    /// [`BlockOrdering::Payload`].
+    pub ordering: BlockOrdering,
+
+    /// Whether paths are shown relative to their common directory.
+    /// Defaults to [`PathPrefix::Auto`]: factored in Minimal mode only.
+    pub path_prefix: PathPrefix,
+
+    /// Recency cutoff for `FILE_TREE` entries, in seconds since the Unix
+    /// epoch. Entries whose `mtime` is at or after this instant are
+    /// marked as recently modified in the rendered tree. When `None`,
+    /// no recency markers are emitted.
+    pub recent_since: Option<u64>,
+
+    /// Tag allowlist. When set, only blocks tagged with at least one of
+    /// these tags are rendered; untagged blocks are skipped.
+    pub include_tags: Option<Vec<String>>,
+
+    /// Tag denylist. Blocks tagged with any of these are skipped, even
+    /// if they also match `include_tags`.
+    pub exclude_tags: Vec<String>,
+
+    /// Reference time for `Expiry` annotations, in seconds since the
+    /// Unix epoch. Blocks whose expiry is at or before this instant are
+    /// dropped. When `None`, the system clock is used.
+    pub current_time: Option<u64>,
+
+    /// Token estimator used by the budget engine. When `None`, the
+    /// driver uses [`CodeAwareEstimator`](crate::budget::CodeAwareEstimator).
+    /// Swap in a tokenizer-backed estimator for tighter budgets.
+    pub estimator: Option<Arc<dyn TokenEstimator>>,
 }
 
 impl Default for DriverConfig {
-    /// Default configuration: XML mode, no model hint, no type filter,
-    /// no token budget, adaptive verbosity.
+    /// Default configuration: mode from the (absent) model profile, so
+    /// XML; no model hint, no type filter, no token budget, adaptive
+    /// verbosity, greedy budget allocation, no conversation policy, no
+    /// dependency groups, no dedup, payload order, paths factored in
+    /// Minimal mode only, no recency markers, no tag filters, expiry
+    /// checked against the system clock, code-aware token estimation.
     ///
     /// XML mode is the default because it produces the most semantically
     /// structured output — Claude-family models parse it natively, and
@@ -64,15 +193,77 @@ impl Default for DriverConfig {
     /// and render everything in full otherwise.
     fn default() -> Self {
         Self {
-            mode: OutputMode::Xml,
+            mode: None,
             target_model: None,
             include_types: None,
             token_budget: None,
             verbosity: Verbosity::default(),
+            budget_strategy: BudgetStrategy::default(),
+            query: None,
+            conversation: None,
+            group_dependencies: false,
+            dedup: false,
+            ordering: BlockOrdering::default(),
+            path_prefix: PathPrefix::default(),
+            recent_since: None,
+            include_tags: None,
+            exclude_tags: Vec::new(),
+            current_time: None,
+            estimator: None,
         }
     }
 }
 
+impl DriverConfig {
+    /// The rendering profile for `target_model`, if one is set.
+    #[must_use]
+    pub fn profile(&self) -> Option<ModelProfile> {
+        self.target_model.map(ModelFamily::profile)
+    }
+
+    /// The output mode to render with: `mode` if set, otherwise the
+    /// model profile's mode, otherwise XML.
+    #[must_use]
+    pub fn output_mode(&self) -> OutputMode {
+        self.mode
+            .or_else(|| self.profile().map(|p| p.mode))
+            .unwrap_or(OutputMode::Xml)
+    }
+
+    /// The token estimator for the budget engine: `estimator` if set,
+    /// otherwise the model profile's ratios, otherwise
+    /// [`CodeAwareEstimator`].
+    #[must_use]
+    pub fn token_estimator(&self) -> Arc<dyn TokenEstimator> {
+        match (&self.estimator, self.profile()) {
+            (Some(estimator), _) => Arc::clone(estimator),
+            (None, Some(profile)) => Arc::new(profile.estimator()),
+            (None, None) => Arc::new(CodeAwareEstimator),
+        }
+    }
+
+    /// Whether the output declares a common path root: per
+    /// `path_prefix`, and never in Raw mode, whose frames carry the
+    /// payload's own paths.
+    pub(crate) fn factors_paths(&self) -> bool {
+        match (self.path_prefix, self.output_mode()) {
+            (_, OutputMode::Raw(_)) | (PathPrefix::Full, _) => false,
+            (PathPrefix::Auto, mode) => mode == OutputMode::Minimal,
+            (PathPrefix::Factor, _) => true,
+        }
+    }
+
+    /// The reference time for expiry checks: `current_time` if set,
+    /// otherwise the system clock.
+    pub(crate) fn now(&self) -> u64 {
+        self.current_time.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        })
+    }
+}
+
 /// Verbosity modes for budget-aware rendering (RFC §5.5).
 ///
 /// Controls how the driver decides between full content and summary
@@ -102,6 +293,149 @@ pub enum Verbosity {
     Adaptive,
 }
 
+/// How the budget engine allocates `token_budget` across blocks.
+///
+/// ```text
+/// ┌─────────┬──────────────────────────────────────────────────────────┐
+/// │ Name    │ Allocation                                               │
+/// ├─────────┼──────────────────────────────────────────────────────────┤
+/// │ Greedy  │ Walk blocks by priority, then relevance; each takes the  │
+/// │         │ fullest form that fits what is left. Fast, but one large │
+/// │         │ block can crowd out many small ones of the same tier.    │
+/// │ Optimal │ Choose every block's form at once to maximize a weighted │
+/// │         │ utility (priority, relevance, recency), then spend any   │
+/// │         │ leftover budget widening what was cut down.              │
+/// └─────────┴──────────────────────────────────────────────────────────┘
+/// ```
+///
+/// Both strategies keep the priority rules: Critical blocks render in
+/// full, Low blocks never do, and every tier below High is omitted when
+/// not even its placeholder fits.
+/// `Optimal` falls back to the greedy allocation whenever that scores at
+/// least as well, so it never does worse by its own measure.
+///
+/// For streaming, see [`StreamStrategy`](crate::StreamStrategy).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BudgetStrategy {
+    /// Priority-order first fit (RFC §5.5). This is the default.
+    #[default]
+    Greedy,
+    /// Utility-maximizing allocation over all blocks at once.
+    Optimal,
+}
+
+/// The order blocks appear in the rendered output.
+///
+/// RFC open question 2 asks whether block order is significant. Models
+/// attend best to the start and end of a long context, so the order can
+/// be chosen:
+///
+/// ```text
+/// ┌───────────────┬──────────────────────────────────────────────────┐
+/// │ Name          │ Output order                                     │
+/// ├───────────────┼──────────────────────────────────────────────────┤
+/// │ Payload       │ As encoded. This is the default.                 │
+/// │ PriorityFirst │ Critical, High, Normal, Low, then Background;    │
+/// │               │ most relevant first within a tier                │
+/// │ Edges         │ Most important first, next most important last,  │
+/// │               │ and so on inwards: the least important blocks    │
+/// │               │ end up in the middle                             │
+/// │ ByType        │ File trees, code, diffs, tool output, then the   │
+/// │               │ rest                                             │
+/// └───────────────┴──────────────────────────────────────────────────┘
+/// ```
+///
+/// Importance is a block's `Priority` annotation, then its relevance (a
+/// `Relevance` annotation, else its score against `query`). Ties keep
+/// payload order.
+///
+/// Budget decisions are made in payload order as usual, so a
+/// conversation's turns and the optimal allocator's recency still
+/// follow the payload. Each decision then moves with its block, and the
+/// output is checked against the budget in its final order. For
+/// streaming, see [`StreamStrategy`](crate::StreamStrategy).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlockOrdering {
+    /// Payload order. This is the default.
+    #[default]
+    Payload,
+    /// By importance, highest first.
+    PriorityFirst,
+    /// Important blocks at both ends, against "lost in the middle".
+    Edges,
+    /// Grouped by block type.
+    ByType,
+}
+
+/// How CODE and DIFF paths appear in the rendered output.
+///
+/// Deep monorepo paths repeat in every block header. Factoring finds
+/// the deepest directory holding every rendered path, declares it once
+/// and shows each path relative to it:
+///
+/// ```text
+/// ┌──────────┬──────────────────────────────────────────────────────┐
+/// │ Mode     │ Declaration                                          │
+/// ├──────────┼──────────────────────────────────────────────────────┤
+/// │ Xml      │ <context root="services/billing/src">                │
+/// │ Markdown │ # Root: services/billing/src                         │
+/// │ Minimal  │ --- root: services/billing/src ---                   │
+/// └──────────┴──────────────────────────────────────────────────────┘
+/// ```
+///
+/// A root is only declared when stripping it saves more characters than
+/// the declaration costs, so one or two short paths stay as they are.
+/// Placeholders keep full paths. For streaming, see
+/// [`StreamStrategy`](crate::StreamStrategy).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PathPrefix {
+    /// Factor in Minimal mode, where every token counts. This is the
+    /// default.
+    #[default]
+    Auto,
+    /// Factor in every text mode.
+    Factor,
+    /// Always show full paths.
+    Full,
+}
+
+/// How the budget engine treats CONVERSATION turns.
+///
+/// A turn here is a system, user or assistant message together with the
+/// tool outputs that answer it: the `Tool` turns and `TOOL_RESULT`
+/// blocks that follow an assistant turn before the next message. A
+/// tool call and its results are always decided together.
+///
+/// ```text
+/// ┌──────────────────────────────┬───────────────────────────────────┐
+/// │ Turn                         │ Under budget pressure             │
+/// ├──────────────────────────────┼───────────────────────────────────┤
+/// │ System                       │ Full, regardless of budget        │
+/// │ Last `recent_turns` turns    │ Full, regardless of budget        │
+/// │ Older turns                  │ Full → Summary → Placeholder →    │
+/// │                              │ Omit, newest first, after the     │
+/// │                              │ other blocks of their tier        │
+/// └──────────────────────────────┴───────────────────────────────────┘
+/// ```
+///
+/// Older turns are never truncated: a message cut mid-way reads as if
+/// the speaker said less. A `Priority` annotation on a turn takes it out
+/// of the policy. For streaming, see
+/// [`StreamStrategy`](crate::StreamStrategy).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConversationPolicy {
+    /// Number of turns at the end of the conversation kept in full.
+    pub recent_turns: usize,
+}
+
+impl Default for ConversationPolicy {
+    /// Keep the last four turns: the current exchange and the one
+    /// before it.
+    fn default() -> Self {
+        Self { recent_turns: 4 }
+    }
+}
+
 /// Output format modes per RFC §5.4.
 ///
 /// Each mode represents a different tradeoff between semantic structure,
@@ -121,6 +455,10 @@ pub enum Verbosity {
 /// ├──────────┼─────────────────────────────────────────────────────────┤
 /// │ Minimal  │ --- src/main.rs [rust] ---\ncontent                     │
 /// │          │ Maximum token efficiency, fewest structural tokens.     │
+/// ├──────────┼─────────────────────────────────────────────────────────┤
+/// │ Raw      │ code b64 AQAgAQABAgELc3JjL21...                         │
+/// │          │ Binary frames as text, for native-ingestion experiments │
+/// │          │ (RFC §8). Lossless; parse back with RawRenderer::parse. │
 /// └──────────┴─────────────────────────────────────────────────────────┘
 /// ```
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -128,27 +466,69 @@ pub enum OutputMode {
     Xml,
     Markdown,
     Minimal,
+    /// Each block's wire frame, base-encoded with the given alphabet.
+    Raw(RawEncoding),
+}
+
+/// Text encodings for [`OutputMode::Raw`] frames.
+///
+/// ```text
+/// ┌────────┬───────┬──────────────────────────────────────────────┐
+/// │ Name   │ Label │ Alphabet                                     │
+/// ├────────┼───────┼──────────────────────────────────────────────┤
+/// │ Base64 │ b64   │ RFC 4648 standard, padded (4 chars/3 bytes)  │
+/// │ Base85 │ b85   │ RFC 1924 (5 chars/4 bytes, ~7% shorter)      │
+/// └────────┴───────┴──────────────────────────────────────────────┘
+/// ```
+///
+/// Neither alphabet contains whitespace, so a frame always stays on
+/// one line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RawEncoding {
+    #[default]
+    Base64,
+    Base85,
+}
+
+impl RawEncoding {
+    /// The label written before each encoded frame.
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Base64 => "b64",
+            Self::Base85 => "b85",
+        }
+    }
+
+    /// Look up an encoding by its label.
+    #[must_use]
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label {
+            "b64" => Some(Self::Base64),
+            "b85" => Some(Self::Base85),
+            _ => None,
+        }
+    }
 }
 
-/// Model family hints for output tuning.
+/// Model families with their own rendering profile.
 ///
-/// The driver may adjust minor formatting choices based on the target
-/// model. For example, Claude models have strong XML comprehension, so
-/// the XML renderer can use Claude-optimized attribute ordering. GPT
-/// models handle markdown well, so the markdown renderer can use
-/// GPT-friendly header conventions.
+/// Each family maps to a [`ModelProfile`] via [`ModelFamily::profile`]:
+/// a default output mode, XML tag vocabulary and style, and token
+/// estimation ratios. Claude models have strong XML comprehension and get
+/// compact attribute-style XML; GPT models handle markdown well and
+/// default to it.
 ///
-/// This is a hint, not a hard requirement — the output is valid regardless
-/// of the target model setting.
+/// The output is valid regardless of the target model setting.
 ///
 /// ```text
 /// ┌─────────┬────────────────────────────────────────────────────┐
 /// │ Family  │ Notes                                              │
 /// ├─────────┼────────────────────────────────────────────────────┤
-/// │ Claude  │ Strong XML parsing, prefers semantic tags          │
-/// │ Gpt     │ Strong markdown parsing, prefers fenced blocks     │
-/// │ Gemini  │ Handles both XML and markdown well                 │
-/// │ Generic │ No model-specific tuning applied                   │
+/// │ Claude  │ XML, compact tags as attributes                    │
+/// │ Gpt     │ Markdown; descriptive nested tags if XML forced    │
+/// │ Gemini  │ XML, descriptive tags as nested elements           │
+/// │ Generic │ Same output as no family                           │
 /// └─────────┴────────────────────────────────────────────────────┘
 /// ```
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
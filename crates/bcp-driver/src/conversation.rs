@@ -0,0 +1,280 @@
+use bcp_types::block::{Block, BlockContent};
+use bcp_types::enums::Role;
+
+use crate::budget::{BlockBudgetInfo, RenderDecision, make_placeholder};
+use crate::config::ConversationPolicy;
+use crate::report::DecisionReason;
+
+/// A block's part in the [`ConversationPolicy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Turn {
+    /// A system turn, or one of the recent turns: rendered in full.
+    Kept,
+    /// A member of older turn `n`. Turns are numbered in stream order,
+    /// so a higher `n` is a newer turn.
+    Older(usize),
+}
+
+/// Sort the conversation in `filtered` into kept and older turns.
+///
+/// Returns one entry per block: `None` for blocks outside the
+/// conversation. Each user or assistant message opens a turn. `Tool`
+/// turns and `TOOL_RESULT` blocks join the turn of the assistant message
+/// before them, if no other message came in between; a `Tool` turn that
+/// answers nothing opens a turn of its own, and a `TOOL_RESULT` that
+/// answers nothing is not part of the conversation. System turns are
+/// always kept and do not count towards `recent_turns`.
+///
+/// ```text
+/// system   "You are…"            Kept
+/// user     "Fix the test"        Older(0)
+/// assistant call: cargo test     Older(1)  ┐ one turn: decided
+/// TOOL_RESULT cargo test         Older(1)  ┘ together
+/// user     "Still failing"       Kept      ┐ recent_turns = 2
+/// assistant "The fixture…"       Kept      ┘
+/// ```
+pub(crate) fn classify_turns(filtered: &[&Block], policy: ConversationPolicy) -> Vec<Option<Turn>> {
+    let mut turns: Vec<Option<Turn>> = vec![None; filtered.len()];
+    let mut count = 0;
+    let mut open_call: Option<usize> = None;
+    for (i, block) in filtered.iter().enumerate() {
+        match &block.content {
+            BlockContent::Conversation(c) if c.role == Role::System => {
+                turns[i] = Some(Turn::Kept);
+                open_call = None;
+            }
+            BlockContent::Conversation(c) if c.role == Role::Tool && open_call.is_some() => {
+                turns[i] = open_call.map(Turn::Older);
+            }
+            BlockContent::Conversation(c) => {
+                turns[i] = Some(Turn::Older(count));
+                open_call = (c.role == Role::Assistant).then_some(count);
+                count += 1;
+            }
+            BlockContent::ToolResult(_) => turns[i] = open_call.map(Turn::Older),
+            _ => {}
+        }
+    }
+
+    let first_recent = count.saturating_sub(policy.recent_turns);
+    for turn in &mut turns {
+        if let Some(Turn::Older(n)) = *turn
+            && n >= first_recent
+        {
+            *turn = Some(Turn::Kept);
+        }
+    }
+    turns
+}
+
+/// Indices of the blocks in older turn `n`.
+pub(crate) fn turn_members(turns: &[Option<Turn>], n: usize) -> Vec<usize> {
+    turns
+        .iter()
+        .enumerate()
+        .filter(|(_, turn)| **turn == Some(Turn::Older(n)))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// One way to render an older turn: a decision per member, in member
+/// order, and what they cost together.
+pub(crate) struct TurnLevel {
+    pub decisions: Vec<RenderDecision>,
+    pub cost: u32,
+}
+
+/// Every way an older turn may render, from Omit up to Full.
+///
+/// ```text
+/// ┌─────────────┬────────────────────────────────────────────────┐
+/// │ Level       │ Members                                        │
+/// ├─────────────┼────────────────────────────────────────────────┤
+/// │ Omit        │ all omitted                                    │
+/// │ Placeholder │ all placeholders                               │
+/// │ Summary     │ summaries, outlines for code without one,      │
+/// │             │ placeholders for the rest (skipped when no     │
+/// │             │ member has a summary or outline)               │
+/// │ Full        │ all in full                                    │
+/// └─────────────┴────────────────────────────────────────────────┘
+/// ```
+///
+/// Costs are the members' rendered costs from `infos`. Dependency
+/// groups (see [`group_levels`](crate::group::group_levels)) render at
+/// the same levels.
+pub(crate) fn turn_levels(
+    members: &[usize],
+    infos: &[BlockBudgetInfo],
+    filtered: &[&Block],
+) -> Vec<TurnLevel> {
+    let level = |pick: &dyn Fn(&BlockBudgetInfo, &Block) -> (RenderDecision, u32)| {
+        let (decisions, costs): (Vec<_>, Vec<_>) = members
+            .iter()
+            .map(|&i| pick(&infos[i], filtered[i]))
+            .unzip();
+        TurnLevel {
+            decisions,
+            cost: costs.iter().sum(),
+        }
+    };
+    let placeholder = |info: &BlockBudgetInfo, block: &Block| {
+        (
+            make_placeholder(block, info.content_tokens),
+            info.placeholder_tokens,
+        )
+    };
+
+    let mut levels = vec![
+        level(&|_, _| (RenderDecision::Omit, 0)),
+        level(&placeholder),
+    ];
+    if members
+        .iter()
+        .any(|&i| infos[i].has_summary || infos[i].outline_tokens.is_some())
+    {
+        levels.push(level(
+            &|info, block| match (info.summary_tokens, info.outline_tokens) {
+                (Some(tokens), _) if info.has_summary => (RenderDecision::Summary, tokens),
+                (_, Some(tokens)) => (RenderDecision::Outline, tokens),
+                _ => placeholder(info, block),
+            },
+        ));
+    }
+    levels.push(level(&|info, _| (RenderDecision::Full, info.full_tokens)));
+    levels
+}
+
+/// Decide an older turn for the greedy allocator: the fullest level
+/// that fits `remaining`, charging it. Returns one decision per member.
+pub(crate) fn decide_turn(
+    members: &[usize],
+    infos: &[BlockBudgetInfo],
+    filtered: &[&Block],
+    remaining: &mut u32,
+) -> Vec<(RenderDecision, DecisionReason)> {
+    let levels = turn_levels(members, infos, filtered);
+    let full = levels.len() - 1;
+    let (index, level) = levels
+        .into_iter()
+        .enumerate()
+        .rev()
+        .find(|(_, level)| level.cost <= *remaining)
+        .expect("omitting a turn is free");
+    *remaining -= level.cost;
+    let reason = if index == full {
+        DecisionReason::WithinBudget
+    } else {
+        DecisionReason::OverBudget
+    };
+    level.decisions.into_iter().map(|d| (d, reason)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::budget::{BlockPricer, HeuristicEstimator, scan_blocks};
+    use crate::config::OutputMode;
+    use crate::render_context::RenderContext;
+    use crate::test_support::{conversation_block, tool_result_block};
+
+    #[test]
+    fn recent_turns_and_system_are_kept() {
+        let blocks = [
+            tool_result_block("ls", "ok"),
+            conversation_block(Role::System, "You are terse."),
+            conversation_block(Role::User, "Fix the test"),
+            conversation_block(Role::Assistant, "Running it."),
+            tool_result_block("cargo test", "ok"),
+            conversation_block(Role::Tool, "1 failed"),
+            conversation_block(Role::User, "Still failing"),
+            conversation_block(Role::Assistant, "The fixture is stale."),
+        ];
+        let refs: Vec<&Block> = blocks.iter().collect();
+
+        let turns = classify_turns(&refs, ConversationPolicy { recent_turns: 2 });
+        assert_eq!(
+            turns,
+            [
+                None,
+                Some(Turn::Kept),
+                Some(Turn::Older(0)),
+                Some(Turn::Older(1)),
+                Some(Turn::Older(1)),
+                Some(Turn::Older(1)),
+                Some(Turn::Kept),
+                Some(Turn::Kept),
+            ]
+        );
+        assert_eq!(turn_members(&turns, 1), [3, 4, 5]);
+
+        // The tool call is kept with its results.
+        let turns = classify_turns(&refs, ConversationPolicy { recent_turns: 3 });
+        assert_eq!(turns[2], Some(Turn::Older(0)));
+        assert!(turns[3..].iter().all(|t| *t == Some(Turn::Kept)));
+    }
+
+    #[test]
+    fn tool_output_without_call_stands_alone() {
+        let blocks = [
+            conversation_block(Role::User, "Run it"),
+            conversation_block(Role::Tool, "done"),
+            tool_result_block("rg", "ok"),
+            conversation_block(Role::User, "Thanks"),
+        ];
+        let refs: Vec<&Block> = blocks.iter().collect();
+        let turns = classify_turns(&refs, ConversationPolicy { recent_turns: 1 });
+        assert_eq!(
+            turns,
+            [
+                Some(Turn::Older(0)),
+                Some(Turn::Older(1)),
+                None,
+                Some(Turn::Kept),
+            ]
+        );
+    }
+
+    #[test]
+    fn tool_call_and_result_degrade_together() {
+        let blocks = [
+            conversation_block(
+                Role::Assistant,
+                "Running the tests to see which one fails, then reading the parser.",
+            ),
+            conversation_block(Role::Tool, "test parser::empty ... FAILED\n".repeat(4)),
+        ];
+        let refs: Vec<&Block> = blocks.iter().collect();
+        let ctx = RenderContext::default();
+        let pricer = BlockPricer::new(OutputMode::Minimal, &ctx, &HeuristicEstimator);
+        let mut infos = scan_blocks(&refs, &HashMap::new(), &HashMap::new(), &pricer, &[0, 1]);
+        for info in &mut infos {
+            info.turn = Some(Turn::Older(0));
+        }
+
+        // Room for the call in full, but not for its result too.
+        let mut remaining = infos[0].full_tokens + infos[1].placeholder_tokens;
+        let decided = decide_turn(&[0, 1], &infos, &refs, &mut remaining);
+        assert!(
+            decided
+                .iter()
+                .all(|(d, _)| matches!(d, RenderDecision::Placeholder { .. }))
+        );
+        assert_eq!(
+            remaining,
+            infos[0].full_tokens - infos[0].placeholder_tokens
+        );
+
+        let mut remaining = infos[0].full_tokens + infos[1].full_tokens;
+        let decided = decide_turn(&[0, 1], &infos, &refs, &mut remaining);
+        assert_eq!(
+            decided,
+            [
+                (RenderDecision::Full, DecisionReason::WithinBudget),
+                (RenderDecision::Full, DecisionReason::WithinBudget),
+            ]
+        );
+        assert_eq!(remaining, 0);
+    }
+}
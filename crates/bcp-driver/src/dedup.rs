@@ -0,0 +1,417 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use bcp_types::BlockType;
+use bcp_types::block::{Block, BlockContent};
+
+use crate::budget::{RenderDecision, TokenEstimator, block_description};
+use crate::config::OutputMode;
+use crate::group::text_body;
+use crate::placeholder::render_reference;
+use crate::render_context::normalize_path;
+
+/// Fewest consecutive lines a repeated span needs before it is replaced
+/// by a back-reference. Shorter runs — a closing brace, a blank line
+/// and a common import — repeat by chance.
+const MIN_SPAN_LINES: usize = 8;
+
+/// A full block's text, line by line, as the passes rewrite it.
+struct Body {
+    lines: Vec<String>,
+    /// Line number of each entry of `lines` in the block's own
+    /// numbering (a CODE fragment counts from its `line_range` start);
+    /// `None` for back-reference lines.
+    numbers: Vec<Option<u32>>,
+    /// `line_range` of a CODE fragment whose range matches its lines.
+    range: Option<(u32, u32)>,
+    changed: bool,
+    /// The whole block became a back-reference.
+    reference: bool,
+}
+
+impl Body {
+    fn new(block: &Block) -> Option<Self> {
+        let text = std::str::from_utf8(text_body(block)?).ok()?;
+        let lines: Vec<String> = text.lines().map(str::to_string).collect();
+        let line_range = match &block.content {
+            BlockContent::Code(c) => c.line_range,
+            _ => None,
+        };
+        let first = line_range.map_or(1, |(start, _)| start);
+        #[allow(clippy::cast_possible_truncation)]
+        let numbers = (0..lines.len()).map(|n| Some(first + n as u32)).collect();
+        #[allow(clippy::cast_possible_truncation)]
+        let range = line_range
+            .filter(|&(start, end)| end >= start && (end - start) as usize + 1 == lines.len());
+        Some(Self {
+            lines,
+            numbers,
+            range,
+            changed: false,
+            reference: false,
+        })
+    }
+}
+
+/// Rewrite `blocks` so content repeated between them renders once.
+///
+/// `blocks` are in output order, and only blocks rendered in full take
+/// part, as sources or as duplicates, so a back-reference always points
+/// at lines earlier in the output.
+///
+/// 1. Merge: CODE fragments of the same path whose line ranges overlap
+///    or touch, and agree where they overlap, are joined into the first
+///    of them. Each later fragment keeps only a back-reference to its
+///    lines.
+/// 2. Spans: a run of at least [`MIN_SPAN_LINES`] lines that repeats
+///    lines of an earlier block — a tool printing a file that is also a
+///    CODE block, say — is replaced by a back-reference to them.
+///
+/// ```text
+/// CODE src/main.rs L1-40     ──▶  CODE src/main.rs L1-60
+/// CODE src/main.rs L30-60    ──▶  [see code src/main.rs L30-60]
+/// TOOL_RESULT cat src/main.rs ─▶  [see code src/main.rs L1-60]
+/// ```
+///
+/// Lines compare with trailing whitespace trimmed. Returns the blocks,
+/// rewritten ones owned, and the tokens saved on each: its content
+/// tokens before less after, less what its lines added to a merged
+/// fragment.
+pub(crate) fn dedup_blocks<'b>(
+    blocks: &[&'b Block],
+    decisions: &[RenderDecision],
+    mode: OutputMode,
+    estimator: &dyn TokenEstimator,
+) -> (Vec<Cow<'b, Block>>, Vec<u32>) {
+    let mut bodies: Vec<Option<Body>> = blocks
+        .iter()
+        .zip(decisions)
+        .map(|(block, decision)| {
+            if *decision == RenderDecision::Full {
+                Body::new(block)
+            } else {
+                None
+            }
+        })
+        .collect();
+    let mut saved = vec![0; blocks.len()];
+
+    merge_fragments(blocks, &mut bodies, &mut saved, mode, estimator);
+    replace_spans(blocks, &mut bodies, &mut saved, mode, estimator);
+
+    let out = blocks
+        .iter()
+        .zip(bodies)
+        .map(|(&block, body)| match body {
+            Some(body) if body.changed => Cow::Owned(rewrite(block, &body)),
+            _ => Cow::Borrowed(block),
+        })
+        .collect();
+    (out, saved)
+}
+
+/// Pass 1: join overlapping CODE fragments of one path.
+fn merge_fragments(
+    blocks: &[&Block],
+    bodies: &mut [Option<Body>],
+    saved: &mut [u32],
+    mode: OutputMode,
+    estimator: &dyn TokenEstimator,
+) {
+    let mut fragments: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, block) in blocks.iter().enumerate() {
+        let BlockContent::Code(code) = &block.content else {
+            continue;
+        };
+        let Some((start, end)) = bodies[i].as_ref().and_then(|b| b.range) else {
+            continue;
+        };
+        let kept = fragments.entry(normalize_path(&code.path)).or_default();
+        let target = kept.iter().copied().find(|&k| {
+            let (a, b) = (bodies[k].as_ref(), bodies[i].as_ref());
+            a.zip(b).is_some_and(|(a, b)| mergeable(a, b))
+        });
+        let Some(k) = target else {
+            kept.push(i);
+            continue;
+        };
+
+        let before = bodies[i]
+            .as_ref()
+            .map(|b| estimator.estimate(&b.lines.join("\n")))
+            .unwrap_or_default();
+        let added = merge_into(bodies, k, i);
+        let marker = render_reference(
+            mode,
+            &BlockType::Code,
+            &block_description(blocks[k]),
+            start,
+            end,
+        );
+        let after = estimator.estimate(&marker);
+        saved[i] = before
+            .saturating_sub(after)
+            .saturating_sub(estimator.estimate(&added));
+        bodies[i] = Some(Body {
+            lines: vec![marker],
+            numbers: vec![None],
+            range: None,
+            changed: true,
+            reference: true,
+        });
+    }
+}
+
+/// Whether fragment `b` overlaps or touches fragment `a`, with the
+/// lines they share equal.
+fn mergeable(a: &Body, b: &Body) -> bool {
+    let (Some((sa, ea)), Some((sb, eb))) = (a.range, b.range) else {
+        return false;
+    };
+    if sb > ea.saturating_add(1) || sa > eb.saturating_add(1) {
+        return false;
+    }
+    (sa.max(sb)..=ea.min(eb))
+        .all(|n| same_line(&a.lines[(n - sa) as usize], &b.lines[(n - sb) as usize]))
+}
+
+/// Join fragment `from` into fragment `into`, returning the text of the
+/// lines it added.
+fn merge_into(bodies: &mut [Option<Body>], into: usize, from: usize) -> String {
+    let (Some(a), Some(b)) = (bodies[into].as_ref(), bodies[from].as_ref()) else {
+        return String::new();
+    };
+    let (Some((sa, ea)), Some((sb, eb))) = (a.range, b.range) else {
+        return String::new();
+    };
+    let (start, end) = (sa.min(sb), ea.max(eb));
+    let mut lines = Vec::new();
+    let mut added = Vec::new();
+    for n in start..=end {
+        if (sa..=ea).contains(&n) {
+            lines.push(a.lines[(n - sa) as usize].clone());
+        } else {
+            let line = b.lines[(n - sb) as usize].clone();
+            added.push(line.clone());
+            lines.push(line);
+        }
+    }
+    let a = bodies[into].as_mut().expect("merge target has a body");
+    a.numbers = (start..=end).map(Some).collect();
+    a.lines = lines;
+    a.range = Some((start, end));
+    a.changed = true;
+    added.join("\n")
+}
+
+/// Pass 2: replace runs of lines repeated from earlier blocks.
+fn replace_spans(
+    blocks: &[&Block],
+    bodies: &mut [Option<Body>],
+    saved: &mut [u32],
+    mode: OutputMode,
+    estimator: &dyn TokenEstimator,
+) {
+    // Window hash → (block, line) of every window of MIN_SPAN_LINES
+    // original lines in the blocks seen so far.
+    let mut windows: HashMap<u64, Vec<(usize, usize)>> = HashMap::new();
+    for i in 0..blocks.len() {
+        let Some(body) = bodies[i].as_ref().filter(|b| !b.reference) else {
+            continue;
+        };
+
+        let mut spans = Vec::new();
+        let mut at = 0;
+        while at + MIN_SPAN_LINES <= body.lines.len() {
+            let best = windows
+                .get(&window_hash(&body.lines[at..at + MIN_SPAN_LINES]))
+                .into_iter()
+                .flatten()
+                .filter_map(|&(j, from)| {
+                    let source = bodies[j].as_ref()?;
+                    let len = common_run(body, at, source, from);
+                    (len >= MIN_SPAN_LINES).then_some((len, j, from))
+                })
+                .max_by_key(|&(len, _, _)| len);
+            if let Some((len, j, from)) = best {
+                spans.push((at, len, j, from));
+                at += len;
+            } else {
+                at += 1;
+            }
+        }
+
+        let markers: Vec<(usize, usize, String)> = spans
+            .into_iter()
+            .filter_map(|(at, len, j, from)| {
+                let numbers = &bodies[j].as_ref()?.numbers;
+                let marker = render_reference(
+                    mode,
+                    &blocks[j].block_type,
+                    &block_description(blocks[j]),
+                    numbers[from]?,
+                    numbers[from + len - 1]?,
+                );
+                Some((at, len, marker))
+            })
+            .collect();
+        let body = bodies[i].as_mut().expect("checked above");
+        for (at, len, marker) in markers.into_iter().rev() {
+            let span = body.lines[at..at + len].join("\n");
+            saved[i] += estimator
+                .estimate(&span)
+                .saturating_sub(estimator.estimate(&marker));
+            body.lines.splice(at..at + len, [marker]);
+            body.numbers.splice(at..at + len, [None]);
+            body.changed = true;
+        }
+
+        let body = bodies[i].as_ref().expect("checked above");
+        for at in 0..body.lines.len().saturating_sub(MIN_SPAN_LINES - 1) {
+            let window = at..at + MIN_SPAN_LINES;
+            if body.numbers[window.clone()].iter().all(Option::is_some)
+                && substantial(&body.lines[window.clone()])
+            {
+                windows
+                    .entry(window_hash(&body.lines[window]))
+                    .or_default()
+                    .push((i, at));
+            }
+        }
+    }
+}
+
+/// Number of lines from `at` in `body` equal to those from `from` in
+/// `source`, stopping at back-references.
+fn common_run(body: &Body, at: usize, source: &Body, from: usize) -> usize {
+    body.lines[at..]
+        .iter()
+        .zip(&source.lines[from..])
+        .zip(&source.numbers[from..])
+        .take_while(|((a, b), n)| n.is_some() && same_line(a, b))
+        .count()
+}
+
+fn same_line(a: &str, b: &str) -> bool {
+    a.trim_end() == b.trim_end()
+}
+
+fn window_hash(lines: &[String]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for line in lines {
+        line.trim_end().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Whether at least half of `lines` carry more than a brace or two, so
+/// runs of blank lines and closing braces are not matched.
+fn substantial(lines: &[String]) -> bool {
+    lines.iter().filter(|l| l.trim().len() > 2).count() * 2 >= lines.len()
+}
+
+/// `block` with its text body replaced by `body`.
+fn rewrite(block: &Block, body: &Body) -> Block {
+    let mut out = block.clone();
+    let text = body.lines.join("\n").into_bytes();
+    match &mut out.content {
+        BlockContent::Code(c) => {
+            c.content = text;
+            if body.range.is_some() || body.reference {
+                c.line_range = body.range;
+            }
+        }
+        BlockContent::Document(d) => d.content = text,
+        BlockContent::StructuredData(d) => d.content = text,
+        BlockContent::ToolResult(t) => t.content = text,
+        BlockContent::Conversation(c) => c.content = text,
+        _ => {}
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::HeuristicEstimator;
+    use bcp_decoder::BcpDecoder;
+    use bcp_encoder::BcpEncoder;
+    use bcp_types::enums::{Lang, Status};
+
+    fn numbered(lines: std::ops::RangeInclusive<u32>) -> String {
+        lines
+            .map(|n| format!("    let value_{n} = compute({n});\n"))
+            .collect::<Vec<_>>()
+            .concat()
+    }
+
+    fn dedup(blocks: &[Block]) -> (Vec<Cow<'_, Block>>, Vec<u32>) {
+        let refs: Vec<&Block> = blocks.iter().collect();
+        let decisions = vec![RenderDecision::Full; blocks.len()];
+        dedup_blocks(&refs, &decisions, OutputMode::Minimal, &HeuristicEstimator)
+    }
+
+    fn text(block: &Block) -> &str {
+        std::str::from_utf8(text_body(block).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn overlapping_fragments_merge() {
+        let mut enc = BcpEncoder::new();
+        enc.add_code_range(
+            Lang::Rust,
+            "src/main.rs",
+            numbered(1..=20).as_bytes(),
+            1,
+            20,
+        )
+        .add_code_range(
+            Lang::Rust,
+            "./src/main.rs",
+            numbered(15..=30).as_bytes(),
+            15,
+            30,
+        )
+        .add_code_range(Lang::Rust, "src/lib.rs", b"pub mod parser;\n", 1, 1);
+        let blocks = BcpDecoder::decode(&enc.encode().unwrap()).unwrap().blocks;
+
+        let (out, saved) = dedup(&blocks);
+        let BlockContent::Code(merged) = &out[0].content else {
+            panic!("expected code");
+        };
+        assert_eq!(merged.line_range, Some((1, 30)));
+        assert_eq!(text(&out[0]), numbered(1..=30).trim_end());
+        assert_eq!(text(&out[1]), "[see code src/main.rs L15-30]");
+        assert!(matches!(out[2], Cow::Borrowed(_)));
+        assert!(saved[1] > 0);
+        assert_eq!(saved[2], 0);
+    }
+
+    #[test]
+    fn repeated_span_becomes_reference() {
+        let mut enc = BcpEncoder::new();
+        let output = format!("$ sed -n 5,16p src/main.rs\n{}done", numbered(5..=16));
+        enc.add_code_range(
+            Lang::Rust,
+            "src/main.rs",
+            numbered(1..=20).as_bytes(),
+            1,
+            20,
+        )
+        .add_tool_result("sed", Status::Ok, output.as_bytes())
+        .add_tool_result("echo", Status::Ok, numbered(1..=4).as_bytes());
+        let blocks = BcpDecoder::decode(&enc.encode().unwrap()).unwrap().blocks;
+
+        let (out, saved) = dedup(&blocks);
+        assert!(matches!(out[0], Cow::Borrowed(_)));
+        assert_eq!(
+            text(&out[1]),
+            "$ sed -n 5,16p src/main.rs\n[see code src/main.rs L5-16]\ndone"
+        );
+        assert!(saved[1] > 0);
+        // Fewer than MIN_SPAN_LINES repeated lines are left alone.
+        assert!(matches!(out[2], Cow::Borrowed(_)));
+    }
+}
@@ -1,12 +1,24 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+
 use bcp_types::BlockType;
 use bcp_types::block::Block;
 
-use crate::budget::{CodeAwareEstimator, RenderDecision, compute_budget_decisions};
+use crate::budget::{BlockPricer, RenderDecision, compute_budget_decisions};
 use crate::config::{DriverConfig, OutputMode, Verbosity};
+use crate::dedup::dedup_blocks;
 use crate::error::DriverError;
+use crate::order::{block_order, permute};
+use crate::placeholder::render_placeholder;
+use crate::query::score_blocks;
+use crate::render_context::{BlockAnnotations, RenderContext, resolve_annotations};
 use crate::render_markdown::MarkdownRenderer;
 use crate::render_minimal::MinimalRenderer;
+use crate::render_raw::RawRenderer;
 use crate::render_xml::XmlRenderer;
+use crate::report::{DecisionReason, RenderOutput, RenderReport, build_report};
+use crate::truncate::{outline_block, truncate_block};
 
 /// Core driver interface — renders decoded blocks into model-ready text.
 ///
@@ -35,6 +47,31 @@ pub trait BcpDriver {
     /// Returns `DriverError::EmptyInput` if `blocks` is empty (after
     /// filtering by `config.include_types`, if set).
     fn render(&self, blocks: &[Block], config: &DriverConfig) -> Result<String, DriverError>;
+
+    /// Render like [`render`](Self::render), and report what happened to
+    /// each block: its decision, the reason for it, its estimated tokens
+    /// and its byte range in the output.
+    ///
+    /// The default body delegates to `render` and reports only the
+    /// token totals, with no per-block entries. [`DefaultDriver`] fills
+    /// in every block.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`render`](Self::render).
+    fn render_with_report(
+        &self,
+        blocks: &[Block],
+        config: &DriverConfig,
+    ) -> Result<RenderOutput, DriverError> {
+        let text = self.render(blocks, config)?;
+        let report = RenderReport {
+            blocks: Vec::new(),
+            total_tokens: config.token_estimator().estimate(&text),
+            token_budget: config.token_budget,
+        };
+        Ok(RenderOutput { text, report })
+    }
 }
 
 /// Default driver implementation — filtering, budget allocation, and
@@ -42,13 +79,14 @@ pub trait BcpDriver {
 ///
 /// This is the standard entry point for rendering. It handles:
 ///
-/// 1. **Block filtering** — removes Annotation/End blocks and applies
-///    `config.include_types` to skip non-matching blocks.
+/// 1. **Block filtering** — removes Annotation/End blocks, blocks whose
+///    Expiry annotation has passed, and applies `config.include_types`
+///    and the tag filters to skip non-matching blocks.
 /// 2. **Budget decisions** — based on `config.verbosity` and
 ///    `config.token_budget`, computes a [`RenderDecision`] per block
-///    (Full, Summary, Placeholder, or Omit).
+///    (Full, Summary, Outline, Truncated, Placeholder, or Omit).
 /// 3. **Renderer dispatch** — selects `XmlRenderer`, `MarkdownRenderer`,
-///    or `MinimalRenderer` based on `config.mode`, using the
+///    `MinimalRenderer` or `RawRenderer` based on `config.mode`, using the
 ///    decision-aware rendering path.
 ///
 /// ```text
@@ -56,6 +94,7 @@ pub trait BcpDriver {
 /// │ &[Block]    │────▶│ filter +      │────▶│ XmlRenderer      │
 /// │             │     │ budget engine │     │ MarkdownRenderer │
 /// │             │     │ + dispatch    │     │ MinimalRenderer  │
+/// │             │     │               │     │ RawRenderer      │
 /// └─────────────┘     └───────────────┘     └──────────────────┘
 ///                           │                       │
 ///                     DriverConfig            String output
@@ -69,27 +108,89 @@ impl BcpDriver for DefaultDriver {
     ///
     /// The rendering pipeline:
     ///
-    /// 1. Filter: remove Annotation/End blocks, apply `include_types`.
+    /// 1. Filter: remove Annotation/End blocks and expired blocks, apply
+    ///    `include_types`, `include_tags` and `exclude_tags`.
     /// 2. Decide: compute per-block [`RenderDecision`] based on verbosity
     ///    and token budget.
-    /// 3. Render: dispatch to the appropriate renderer with decisions.
+    /// 3. Order: arrange blocks by `ordering`, each keeping its decision.
+    /// 4. Deduplicate: with `dedup`, replace content repeated between
+    ///    full blocks by back-references to earlier output.
+    /// 5. Render: dispatch to the appropriate renderer with decisions.
     ///
     /// # Errors
     ///
     /// - `DriverError::EmptyInput` if no renderable blocks remain after filtering.
     /// - `DriverError::InvalidContent` if a block contains non-UTF-8 bytes.
     fn render(&self, blocks: &[Block], config: &DriverConfig) -> Result<String, DriverError> {
+        RenderPlan::new(blocks, config)?.render(config.output_mode())
+    }
+
+    /// Render decoded blocks and report per-block decisions.
+    ///
+    /// Runs the same pipeline as [`render`](BcpDriver::render), then
+    /// prices each rendered block with the config's token estimator.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`render`](BcpDriver::render).
+    fn render_with_report(
+        &self,
+        blocks: &[Block],
+        config: &DriverConfig,
+    ) -> Result<RenderOutput, DriverError> {
+        RenderPlan::new(blocks, config)?.render_with_report(config)
+    }
+}
+
+/// The filtered blocks of a payload, each with its render decision, and
+/// the context the renderers need — everything but the final dispatch.
+///
+/// Built once per render by [`RenderPlan::new`]; the streaming renderer
+/// builds one too when it scans the whole stream first.
+pub(crate) struct RenderPlan<'b> {
+    /// Renderable blocks, in output order (see `DriverConfig::ordering`).
+    /// Blocks rewritten by `DriverConfig::dedup` are owned.
+    pub blocks: Vec<Cow<'b, Block>>,
+    /// Index of each entry of `blocks` in the unfiltered stream.
+    pub original_indices: Vec<usize>,
+    /// One decision per entry of `blocks`.
+    pub decisions: Vec<RenderDecision>,
+    /// Why each entry of `decisions` was made.
+    pub reasons: Vec<DecisionReason>,
+    /// Content blocks the filters dropped, with their stream indices.
+    pub filtered_out: Vec<(usize, &'b Block)>,
+    /// One score per entry of `blocks` against `DriverConfig::query`,
+    /// when a query is set.
+    pub query_scores: Option<Vec<f32>>,
+    /// Tokens `DriverConfig::dedup` saved on each entry of `blocks`.
+    pub saved_tokens: Vec<u32>,
+    pub ctx: RenderContext,
+}
+
+impl<'b> RenderPlan<'b> {
+    /// Filter `blocks`, compute render decisions, and build the context.
+    ///
+    /// # Errors
+    ///
+    /// - `DriverError::EmptyInput` if no renderable blocks remain after
+    ///   filtering.
+    /// - `DriverError::InvalidContent` if a token budget applies and a
+    ///   block contains non-UTF-8 bytes: fitting the budget renders the
+    ///   blocks.
+    pub fn new(blocks: &'b [Block], config: &DriverConfig) -> Result<Self, DriverError> {
         // Step 1: Filter blocks, tracking original indices for annotation mapping
         let mut filtered: Vec<&Block> = Vec::new();
         let mut original_indices: Vec<usize> = Vec::new();
+        let mut filtered_out = Vec::new();
+        let annotations = resolve_annotations(blocks);
+        let now = config.now();
 
         for (i, b) in blocks.iter().enumerate() {
-            if b.block_type == BlockType::Annotation || b.block_type == BlockType::End {
-                continue;
-            }
-            if let Some(ref types) = config.include_types
-                && !types.contains(&b.block_type)
-            {
+            #[allow(clippy::cast_possible_truncation)]
+            if !block_allowed(b, annotations.get(&(i as u32)), config, now) {
+                if b.block_type != BlockType::Annotation && b.block_type != BlockType::End {
+                    filtered_out.push((i, b));
+                }
                 continue;
             }
             filtered.push(b);
@@ -100,84 +201,438 @@ impl BcpDriver for DefaultDriver {
             return Err(DriverError::EmptyInput);
         }
 
-        // Step 2: Compute render decisions
-        let decisions = match (config.token_budget, config.verbosity) {
+        // Step 2: Score blocks against the task query, then compute
+        // render decisions
+        let query_scores = config
+            .query
+            .as_deref()
+            .map(|query| score_blocks(query, &filtered));
+        let (decisions, reasons) = match (config.token_budget, config.verbosity) {
             // Summary mode (with or without budget): summaries where available
-            (_, Verbosity::Summary) => filtered
-                .iter()
-                .map(|b| {
-                    if b.summary.is_some() {
-                        RenderDecision::Summary
-                    } else {
-                        RenderDecision::Full
-                    }
-                })
-                .collect(),
+            (_, Verbosity::Summary) => (
+                filtered.iter().map(|b| summary_decision(b)).collect(),
+                vec![DecisionReason::SummaryVerbosity; filtered.len()],
+            ),
             // Budget + Adaptive: run the full budget engine
-            (Some(budget), Verbosity::Adaptive) => compute_budget_decisions(
+            (Some(budget), Verbosity::Adaptive) => fit_budget(
                 blocks,
                 &filtered,
                 &original_indices,
+                &annotations,
                 budget,
-                &CodeAwareEstimator,
-            ),
+                query_scores.as_deref(),
+                config,
+            )?,
             // All other cases: render everything in full
             // (no budget, or Full verbosity regardless of budget)
-            _ => vec![RenderDecision::Full; filtered.len()],
+            (budget, verbosity) => {
+                let reason = if verbosity == Verbosity::Full && budget.is_some() {
+                    DecisionReason::FullVerbosity
+                } else {
+                    DecisionReason::NoBudget
+                };
+                (
+                    vec![RenderDecision::Full; filtered.len()],
+                    vec![reason; filtered.len()],
+                )
+            }
         };
 
-        // Step 3: Build (block, decision) pairs and dispatch to renderer
-        let items: Vec<(&Block, &RenderDecision)> =
-            filtered.iter().copied().zip(decisions.iter()).collect();
+        // Step 3: Put the blocks in output order, each keeping its
+        // decision
+        let order = block_order(
+            config.ordering,
+            blocks,
+            &filtered,
+            &original_indices,
+            query_scores.as_deref(),
+        );
+        let filtered = permute(&filtered, &order);
+        let original_indices = permute(&original_indices, &order);
+        let decisions = permute(&decisions, &order);
+        let reasons = permute(&reasons, &order);
+        let query_scores = query_scores.map(|scores| permute(&scores, &order));
+
+        // Step 4: Replace content repeated between full blocks by
+        // back-references to earlier output. Raw frames carry the
+        // payload's own bytes.
+        let mode = config.output_mode();
+        let (deduped, saved_tokens) = if config.dedup && !matches!(mode, OutputMode::Raw(_)) {
+            let estimator = config.token_estimator();
+            dedup_blocks(&filtered, &decisions, mode, estimator.as_ref())
+        } else {
+            let saved = vec![0; filtered.len()];
+            (filtered.iter().copied().map(Cow::Borrowed).collect(), saved)
+        };
 
-        match config.mode {
-            OutputMode::Xml => XmlRenderer::render_all_with_decisions(&items),
-            OutputMode::Markdown => MarkdownRenderer::render_all_with_decisions(&items),
-            OutputMode::Minimal => MinimalRenderer::render_all_with_decisions(&items),
+        // Step 5: Build the render context for the (block, decision) pairs
+        let ordered: Vec<&Block> = deduped.iter().map(|b| &**b).collect();
+        let ctx = plan_context(
+            &ordered,
+            &decisions,
+            &original_indices,
+            &annotations,
+            config,
+        );
+
+        Ok(Self {
+            blocks: deduped,
+            original_indices,
+            decisions,
+            reasons,
+            filtered_out,
+            query_scores,
+            saved_tokens,
+            ctx,
+        })
+    }
+
+    /// Dispatch the plan to the renderer for `mode`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DriverError::InvalidContent` if a block contains
+    /// non-UTF-8 bytes.
+    pub fn render(&self, mode: OutputMode) -> Result<String, DriverError> {
+        render_items(mode, &self.items(), &self.ctx)
+    }
+
+    /// Render the plan block by block, recording where each block's text
+    /// lands in the output.
+    ///
+    /// Produces the same text as [`render`](Self::render), plus one byte
+    /// range per entry of `blocks` — empty for omitted blocks.
+    ///
+    /// # Errors
+    ///
+    /// - `DriverError::EmptyInput` if every block was omitted.
+    /// - `DriverError::InvalidContent` if a block contains non-UTF-8
+    ///   bytes.
+    pub fn render_ranged(
+        &self,
+        mode: OutputMode,
+    ) -> Result<(String, Vec<Range<usize>>), DriverError> {
+        render_items_ranged(mode, &self.items(), &self.ctx)
+    }
+
+    /// Render the plan and build its [`RenderReport`](crate::RenderReport).
+    ///
+    /// # Errors
+    ///
+    /// Same as [`render_ranged`](Self::render_ranged).
+    pub fn render_with_report(&self, config: &DriverConfig) -> Result<RenderOutput, DriverError> {
+        let (text, ranges) = self.render_ranged(config.output_mode())?;
+        let report = build_report(
+            self,
+            &text,
+            ranges,
+            config.token_estimator().as_ref(),
+            config.token_budget,
+        );
+        Ok(RenderOutput { text, report })
+    }
+
+    /// The (block, decision) pairs the renderers take.
+    pub fn items(&self) -> Vec<(&Block, &RenderDecision)> {
+        self.blocks
+            .iter()
+            .map(|b| &**b)
+            .zip(self.decisions.iter())
+            .collect()
+    }
+}
+
+/// Budget decisions for `filtered` whose rendered output fits `budget`.
+///
+/// Blocks are priced as `mode` renders them, framing included, in a
+/// context where every block renders in full; the output wrapper is
+/// charged up front. Estimators are not additive — the whole text can
+/// estimate above the sum of its blocks — so the result is rendered and
+/// estimated once more. While it overshoots, the blocks' share of the
+/// budget is cut by the overshoot, doubling with each retry, and the
+/// decisions recomputed. Only Critical and forced High blocks, and kept
+/// conversation turns, keep the output over budget once the share
+/// reaches zero.
+///
+/// Decisions are made in stream order; the text is estimated in the
+/// order `config.ordering` renders it. Returns decisions in stream
+/// order.
+///
+/// `query_scores` rank blocks within their tier, as
+/// [`compute_budget_decisions`] describes.
+fn fit_budget(
+    all_blocks: &[Block],
+    filtered: &[&Block],
+    original_indices: &[usize],
+    annotations: &HashMap<u32, BlockAnnotations>,
+    budget: u32,
+    query_scores: Option<&[f32]>,
+    config: &DriverConfig,
+) -> Result<(Vec<RenderDecision>, Vec<DecisionReason>), DriverError> {
+    let mode = config.output_mode();
+    let estimator = config.token_estimator();
+    let full = vec![RenderDecision::Full; filtered.len()];
+    let pricing_ctx = plan_context(filtered, &full, original_indices, annotations, config);
+    let pricer = BlockPricer::new(mode, &pricing_ctx, estimator.as_ref());
+
+    let order = block_order(
+        config.ordering,
+        all_blocks,
+        filtered,
+        original_indices,
+        query_scores,
+    );
+    let ordered = permute(filtered, &order);
+    let ordered_indices = permute(original_indices, &order);
+
+    let mut share = budget.saturating_sub(pricer.wrapper_tokens());
+    let mut step: u32 = 1;
+    loop {
+        let (decisions, reasons) = compute_budget_decisions(
+            all_blocks,
+            filtered,
+            original_indices,
+            share,
+            config,
+            &pricer,
+            query_scores,
+        );
+        let ordered_decisions = permute(&decisions, &order);
+        let ctx = plan_context(
+            &ordered,
+            &ordered_decisions,
+            &ordered_indices,
+            annotations,
+            config,
+        );
+        let items: Vec<(&Block, &RenderDecision)> = ordered
+            .iter()
+            .copied()
+            .zip(ordered_decisions.iter())
+            .collect();
+        let over = match render_items(mode, &items, &ctx) {
+            Ok(text) => estimator.estimate(&text).saturating_sub(budget),
+            Err(DriverError::EmptyInput) => 0,
+            Err(err) => return Err(err),
+        };
+        if over == 0 || share == 0 {
+            return Ok((decisions, reasons));
+        }
+        share = share.saturating_sub(over.saturating_mul(step));
+        step = step.saturating_mul(2);
+    }
+}
+
+/// The render context for `filtered` under `decisions`, with each
+/// block's annotations at its output position and, per
+/// `DriverConfig::path_prefix`, the common root of its paths.
+fn plan_context(
+    filtered: &[&Block],
+    decisions: &[RenderDecision],
+    original_indices: &[usize],
+    annotations: &HashMap<u32, BlockAnnotations>,
+    config: &DriverConfig,
+) -> RenderContext {
+    let items: Vec<(&Block, &RenderDecision)> =
+        filtered.iter().copied().zip(decisions.iter()).collect();
+    let mut ctx = RenderContext::new(&items, Some(config));
+    if config.factors_paths() {
+        ctx.factor_paths(&items, config.output_mode());
+    }
+    #[allow(clippy::cast_possible_truncation)]
+    for (pos, &orig) in original_indices.iter().enumerate() {
+        if let Some(ann) = annotations.get(&(orig as u32)) {
+            ctx.annotations.insert(pos, ann.clone());
+        }
+    }
+    ctx
+}
+
+/// Render (block, decision) pairs in `mode`: each block through
+/// [`render_item`], joined by separators inside the context's wrapper.
+///
+/// # Errors
+///
+/// - `DriverError::EmptyInput` if every block was omitted.
+/// - `DriverError::InvalidContent` if a block contains non-UTF-8 bytes.
+pub(crate) fn render_items(
+    mode: OutputMode,
+    items: &[(&Block, &RenderDecision)],
+    ctx: &RenderContext,
+) -> Result<String, DriverError> {
+    render_items_ranged(mode, items, ctx).map(|(text, _)| text)
+}
+
+/// [`render_items`], plus the byte range of each item's text in the
+/// output — empty for omitted items.
+fn render_items_ranged(
+    mode: OutputMode,
+    items: &[(&Block, &RenderDecision)],
+    ctx: &RenderContext,
+) -> Result<(String, Vec<Range<usize>>), DriverError> {
+    let mut text = ctx.opening(mode);
+    let body_start = text.len();
+    let mut ranges = Vec::with_capacity(items.len());
+    for (pos, &(block, decision)) in items.iter().enumerate() {
+        let Some(part) = render_item(mode, block, decision, pos, ctx)? else {
+            ranges.push(text.len()..text.len());
+            continue;
+        };
+        if text.len() > body_start {
+            text.push_str(separator(mode));
+        }
+        let start = text.len();
+        text.push_str(&part);
+        ranges.push(start..text.len());
+    }
+    if text.len() == body_start {
+        return Err(DriverError::EmptyInput);
+    }
+    text.push_str(&ctx.closing(mode));
+    Ok((text, ranges))
+}
+
+/// The text between two rendered blocks in `mode`.
+pub(crate) fn separator(mode: OutputMode) -> &'static str {
+    match mode {
+        OutputMode::Raw(_) => "\n",
+        _ => "\n\n",
+    }
+}
+
+/// Render one block under its decision, without separators or wrapper.
+///
+/// Returns `None` for `Omit`.
+pub(crate) fn render_item(
+    mode: OutputMode,
+    block: &Block,
+    decision: &RenderDecision,
+    index: usize,
+    ctx: &RenderContext,
+) -> Result<Option<String>, DriverError> {
+    let use_summary = match decision {
+        RenderDecision::Full => false,
+        RenderDecision::Summary => true,
+        RenderDecision::Placeholder {
+            block_type,
+            description,
+            omitted_tokens,
+        } => {
+            return Ok(Some(render_placeholder(
+                mode,
+                block_type,
+                description,
+                *omitted_tokens,
+            )));
+        }
+        RenderDecision::Truncated {
+            head,
+            tail,
+            omitted_tokens,
+        } => {
+            let (block, marker) = truncate_block(block, *head, *tail, *omitted_tokens, mode);
+            let mut text =
+                render_item(mode, &block, &RenderDecision::Full, index, ctx)?.unwrap_or_default();
+            if let Some(marker) = marker {
+                text.push('\n');
+                text.push_str(&marker);
+            }
+            return Ok(Some(text));
+        }
+        RenderDecision::Outline => {
+            return render_item(
+                mode,
+                &outline_block(block),
+                &RenderDecision::Full,
+                index,
+                ctx,
+            );
+        }
+        RenderDecision::Omit => return Ok(None),
+    };
+    let text = match mode {
+        OutputMode::Xml => XmlRenderer::render_block_inner(block, index, use_summary, ctx)?,
+        OutputMode::Markdown => {
+            MarkdownRenderer::render_block_inner(block, index, use_summary, ctx)?
         }
+        OutputMode::Minimal => MinimalRenderer::render_block_inner(block, index, use_summary, ctx)?,
+        OutputMode::Raw(encoding) => RawRenderer::render_block(block, ctx, encoding),
+    };
+    Ok(Some(text))
+}
+
+/// `Summary` for a block that has one, `Full` otherwise — the decision
+/// under `Verbosity::Summary`.
+pub(crate) fn summary_decision(block: &Block) -> RenderDecision {
+    if block.summary.is_some() {
+        RenderDecision::Summary
+    } else {
+        RenderDecision::Full
+    }
+}
+
+/// Whether a block survives the driver's filters: it is not an
+/// Annotation or End block, matches `include_types`, and passes expiry
+/// and the tag filters.
+pub(crate) fn block_allowed(
+    block: &Block,
+    ann: Option<&BlockAnnotations>,
+    config: &DriverConfig,
+    now: u64,
+) -> bool {
+    if block.block_type == BlockType::Annotation || block.block_type == BlockType::End {
+        return false;
+    }
+    if let Some(ref types) = config.include_types
+        && !types.contains(&block.block_type)
+    {
+        return false;
+    }
+    annotations_allowed(ann, config, now)
+}
+
+/// Whether an annotated block passes expiry and the config's tag
+/// filters.
+///
+/// A block expires once `now` reaches its Expiry time. `exclude_tags`
+/// is checked before `include_tags`, so a block matching both lists is
+/// dropped.
+fn annotations_allowed(ann: Option<&BlockAnnotations>, config: &DriverConfig, now: u64) -> bool {
+    if ann.and_then(|a| a.expires_at).is_some_and(|at| at <= now) {
+        return false;
+    }
+    let tags = ann.map_or(&[][..], |a| a.tags.as_slice());
+    if tags.iter().any(|t| config.exclude_tags.contains(t)) {
+        return false;
+    }
+    match config.include_tags {
+        Some(ref include) => tags.iter().any(|t| include.contains(t)),
+        None => true,
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
     use super::*;
-    use bcp_types::annotation::AnnotationBlock;
+    use crate::budget::TokenEstimator;
+    use crate::config::{
+        BlockOrdering, BudgetStrategy, ConversationPolicy, ModelFamily, PathPrefix, RawEncoding,
+    };
+    use crate::test_support::{
+        annotation, block, code_block, conversation_block, priority_annotation, tag_annotation,
+        tool_result_block,
+    };
+    use bcp_types::annotation::{AnnotationBlock, Provenance};
     use bcp_types::block::BlockContent;
     use bcp_types::code::CodeBlock;
-    use bcp_types::conversation::ConversationBlock;
-    use bcp_types::enums::{AnnotationKind, Lang, Role, Status};
-    use bcp_types::file_tree::{FileEntry, FileEntryKind, FileTreeBlock};
+    use bcp_types::enums::{AnnotationKind, Lang, LinkKind, Priority, Role, Status};
+    use bcp_types::file_tree::{FileEntry, FileTreeBlock};
     use bcp_types::summary::Summary;
     use bcp_types::tool_result::ToolResultBlock;
     use bcp_wire::block_frame::BlockFlags;
 
-    fn code_block(lang: Lang, path: &str, content: &[u8]) -> Block {
-        Block {
-            block_type: BlockType::Code,
-            flags: BlockFlags::NONE,
-            summary: None,
-            content: BlockContent::Code(CodeBlock {
-                lang,
-                path: path.to_string(),
-                content: content.to_vec(),
-                line_range: None,
-            }),
-        }
-    }
-
-    fn conversation_block(role: Role, content: &[u8]) -> Block {
-        Block {
-            block_type: BlockType::Conversation,
-            flags: BlockFlags::NONE,
-            summary: None,
-            content: BlockContent::Conversation(ConversationBlock {
-                role,
-                content: content.to_vec(),
-                tool_call_id: None,
-            }),
-        }
-    }
-
     #[test]
     fn empty_input_returns_error() {
         let driver = DefaultDriver;
@@ -186,29 +641,170 @@ mod tests {
         assert!(matches!(result, Err(DriverError::EmptyInput)));
     }
 
+    #[test]
+    fn default_render_with_report_delegates_to_render() {
+        struct Echo;
+        impl BcpDriver for Echo {
+            fn render(&self, _: &[Block], _: &DriverConfig) -> Result<String, DriverError> {
+                Ok("hello world".to_string())
+            }
+        }
+        let config = DriverConfig {
+            token_budget: Some(100),
+            ..DriverConfig::default()
+        };
+        let output = Echo.render_with_report(&[], &config).unwrap();
+        assert_eq!(output.text, "hello world");
+        assert!(output.report.blocks.is_empty());
+        assert!(output.report.total_tokens > 0);
+        assert_eq!(output.report.token_budget, Some(100));
+    }
+
     #[test]
     fn annotation_blocks_filtered_out() {
         let driver = DefaultDriver;
         let config = DriverConfig::default();
-        let blocks = vec![Block {
-            block_type: BlockType::Annotation,
-            flags: BlockFlags::NONE,
-            summary: None,
-            content: BlockContent::Annotation(AnnotationBlock {
+        let blocks = vec![block(
+            BlockType::Annotation,
+            BlockContent::Annotation(AnnotationBlock {
                 target_block_id: 0,
                 kind: AnnotationKind::Priority,
                 value: vec![0x01],
             }),
-        }];
+        )];
         let result = driver.render(&blocks, &config);
         assert!(matches!(result, Err(DriverError::EmptyInput)));
     }
 
+    fn tagged_blocks() -> Vec<Block> {
+        vec![
+            code_block(Lang::Rust, "src/lib.rs", b"pub fn lib() {}"),
+            code_block(Lang::Rust, "tests/lib.rs", b"#[test] fn t() {}"),
+            code_block(Lang::Rust, "src/gen.rs", b"// generated"),
+            code_block(Lang::Rust, "vendor/dep.rs", b"// vendored"),
+            tag_annotation(1, "test"),
+            tag_annotation(2, "generated"),
+            tag_annotation(3, "vendored"),
+            tag_annotation(3, "test"),
+        ]
+    }
+
+    #[test]
+    fn tags_rendered_in_every_mode() {
+        let driver = DefaultDriver;
+        let blocks = tagged_blocks();
+        let render = |mode| {
+            let config = DriverConfig {
+                mode: Some(mode),
+                ..DriverConfig::default()
+            };
+            driver.render(&blocks, &config).unwrap()
+        };
+
+        let xml = render(OutputMode::Xml);
+        assert!(xml.contains("<code lang=\"rust\" path=\"src/lib.rs\">"));
+        assert!(xml.contains("path=\"vendor/dep.rs\" tags=\"vendored,test\">"));
+
+        let md = render(OutputMode::Markdown);
+        assert!(md.contains("## tests/lib.rs #test\n"));
+        assert!(md.contains("## vendor/dep.rs #vendored #test\n"));
+
+        let minimal = render(OutputMode::Minimal);
+        assert!(minimal.contains("--- src/gen.rs [rust] #generated ---"));
+    }
+
+    #[test]
+    fn tag_filters() {
+        let driver = DefaultDriver;
+        let blocks = tagged_blocks();
+        let render = |include_tags, exclude_tags| {
+            let config = DriverConfig {
+                mode: Some(OutputMode::Minimal),
+                include_tags,
+                exclude_tags,
+                ..DriverConfig::default()
+            };
+            driver.render(&blocks, &config).unwrap()
+        };
+
+        let excluded = render(None, vec!["generated".into(), "vendored".into()]);
+        assert!(excluded.contains("src/lib.rs"));
+        assert!(excluded.contains("tests/lib.rs"));
+        assert!(!excluded.contains("src/gen.rs"));
+        assert!(!excluded.contains("vendor/dep.rs"));
+
+        // vendor/dep.rs is tagged "test" too, but exclusion wins.
+        let only_tests = render(Some(vec!["test".into()]), vec!["vendored".into()]);
+        assert_eq!(
+            only_tests,
+            "--- tests/lib.rs [rust] #test ---\n#[test] fn t() {}"
+        );
+    }
+
+    #[test]
+    fn provenance_and_links_rendered_in_every_mode() {
+        let driver = DefaultDriver;
+        let blocks = vec![
+            code_block(Lang::Rust, "src/lib.rs", b"pub fn lib() {}"),
+            code_block(Lang::Rust, "tests/lib.rs", b"#[test] fn t() {}"),
+            annotation(AnnotationBlock::provenance(
+                0,
+                &Provenance::Tool("rg".into()),
+            )),
+            annotation(AnnotationBlock::link(1, LinkKind::Tests, 0)),
+        ];
+        let render = |mode| {
+            let config = DriverConfig {
+                mode: Some(mode),
+                ..DriverConfig::default()
+            };
+            driver.render(&blocks, &config).unwrap()
+        };
+
+        let xml = render(OutputMode::Xml);
+        assert!(xml.contains("path=\"src/lib.rs\" source=\"tool:rg\">"));
+        assert!(xml.contains("path=\"tests/lib.rs\" tests=\"src/lib.rs\">"));
+
+        let md = render(OutputMode::Markdown);
+        assert!(md.contains("## src/lib.rs (source: tool:rg)\n"));
+        assert!(md.contains("## tests/lib.rs (tests: src/lib.rs)\n"));
+
+        let minimal = render(OutputMode::Minimal);
+        assert!(minimal.contains("--- tests/lib.rs [rust] (tests: src/lib.rs) ---"));
+    }
+
+    #[test]
+    fn expired_blocks_dropped() {
+        let driver = DefaultDriver;
+        let blocks = vec![
+            code_block(Lang::Rust, "src/old.rs", b"// stale"),
+            code_block(Lang::Rust, "src/new.rs", b"// fresh"),
+            annotation(AnnotationBlock::expiry(0, 1_000)),
+            annotation(AnnotationBlock::expiry(1, 2_000)),
+        ];
+        let render = |current_time| {
+            let config = DriverConfig {
+                mode: Some(OutputMode::Minimal),
+                current_time: Some(current_time),
+                ..DriverConfig::default()
+            };
+            driver.render(&blocks, &config)
+        };
+
+        let before = render(999).unwrap();
+        assert!(before.contains("src/old.rs") && before.contains("src/new.rs"));
+        assert_eq!(
+            render(1_000).unwrap(),
+            "--- src/new.rs [rust] ---\n// fresh"
+        );
+        assert!(matches!(render(2_000), Err(DriverError::EmptyInput)));
+    }
+
     #[test]
     fn include_types_filter() {
         let driver = DefaultDriver;
         let config = DriverConfig {
-            mode: OutputMode::Minimal,
+            mode: Some(OutputMode::Minimal),
             include_types: Some(vec![BlockType::Code]),
             ..DriverConfig::default()
         };
@@ -225,7 +821,7 @@ mod tests {
     fn include_types_filter_empty_result() {
         let driver = DefaultDriver;
         let config = DriverConfig {
-            mode: OutputMode::Xml,
+            mode: Some(OutputMode::Xml),
             include_types: Some(vec![BlockType::Diff]),
             ..DriverConfig::default()
         };
@@ -238,7 +834,7 @@ mod tests {
     fn xml_mode_wraps_in_context() {
         let driver = DefaultDriver;
         let config = DriverConfig {
-            mode: OutputMode::Xml,
+            mode: Some(OutputMode::Xml),
             ..DriverConfig::default()
         };
         let blocks = vec![code_block(Lang::Rust, "main.rs", b"fn main() {}")];
@@ -251,7 +847,7 @@ mod tests {
     fn markdown_mode_no_context_wrapper() {
         let driver = DefaultDriver;
         let config = DriverConfig {
-            mode: OutputMode::Markdown,
+            mode: Some(OutputMode::Markdown),
             ..DriverConfig::default()
         };
         let blocks = vec![code_block(Lang::Rust, "main.rs", b"fn main() {}")];
@@ -264,7 +860,7 @@ mod tests {
     fn minimal_mode_uses_dashes() {
         let driver = DefaultDriver;
         let config = DriverConfig {
-            mode: OutputMode::Minimal,
+            mode: Some(OutputMode::Minimal),
             ..DriverConfig::default()
         };
         let blocks = vec![code_block(Lang::Rust, "main.rs", b"fn main() {}")];
@@ -272,11 +868,86 @@ mod tests {
         assert!(result.contains("--- main.rs [rust] ---"));
     }
 
+    #[test]
+    fn common_path_prefix_is_declared_once() {
+        let driver = DefaultDriver;
+        let blocks = vec![
+            code_block(
+                Lang::Rust,
+                "services/billing/src/invoice.rs",
+                b"pub struct Invoice;",
+            ),
+            code_block(
+                Lang::Rust,
+                "./services/billing/src/tax/vat.rs",
+                b"pub fn vat() {}",
+            ),
+            code_block(Lang::Rust, "services/billing/src/lib.rs", b"pub mod tax;"),
+        ];
+        let render = |mode, path_prefix| {
+            let config = DriverConfig {
+                mode: Some(mode),
+                path_prefix,
+                ..DriverConfig::default()
+            };
+            driver.render(&blocks, &config).unwrap()
+        };
+
+        // Minimal factors by default.
+        let minimal = render(OutputMode::Minimal, PathPrefix::Auto);
+        assert!(
+            minimal.starts_with("--- root: services/billing/src ---\n\n--- invoice.rs [rust] ---")
+        );
+        assert!(minimal.contains("--- tax/vat.rs [rust] ---"));
+        let full = render(OutputMode::Minimal, PathPrefix::Full);
+        assert!(full.starts_with("--- services/billing/src/invoice.rs [rust] ---"));
+        assert!(minimal.len() < full.len());
+
+        // Other modes opt in.
+        let xml = render(OutputMode::Xml, PathPrefix::Auto);
+        assert!(xml.starts_with("<context>\n"));
+        let xml = render(OutputMode::Xml, PathPrefix::Factor);
+        assert!(xml.starts_with("<context root=\"services/billing/src\">\n"));
+        assert!(xml.contains("<code lang=\"rust\" path=\"lib.rs\">"));
+        let markdown = render(OutputMode::Markdown, PathPrefix::Factor);
+        assert!(markdown.starts_with("# Root: services/billing/src\n\n## invoice.rs"));
+
+        // A root that costs more to declare than it saves is left out.
+        let short = vec![
+            code_block(Lang::Rust, "src/main.rs", b"fn main() {}"),
+            code_block(Lang::Rust, "src/lib.rs", b"pub mod tax;"),
+        ];
+        let config = DriverConfig {
+            mode: Some(OutputMode::Minimal),
+            ..DriverConfig::default()
+        };
+        let result = driver.render(&short, &config).unwrap();
+        assert!(result.starts_with("--- src/main.rs [rust] ---"));
+    }
+
+    #[test]
+    fn raw_mode_roundtrips_rendered_blocks() {
+        let driver = DefaultDriver;
+        let blocks = tagged_blocks();
+        let config = DriverConfig {
+            mode: Some(OutputMode::Raw(RawEncoding::Base85)),
+            target_model: Some(ModelFamily::Gemini),
+            ..DriverConfig::default()
+        };
+        let raw = driver.render(&blocks, &config).unwrap();
+        assert_eq!(raw.lines().count(), 4);
+        assert!(raw.lines().all(|l| l.starts_with("file b85 ")));
+
+        // Annotations are filtered before rendering, so only the code
+        // blocks come back.
+        assert_eq!(RawRenderer::parse(&raw).unwrap(), blocks[..4]);
+    }
+
     #[test]
     fn multiple_blocks_rendered() {
         let driver = DefaultDriver;
         let config = DriverConfig {
-            mode: OutputMode::Xml,
+            mode: Some(OutputMode::Xml),
             ..DriverConfig::default()
         };
         let blocks = vec![
@@ -294,36 +965,19 @@ mod tests {
     fn file_tree_rendering_xml() {
         let driver = DefaultDriver;
         let config = DriverConfig {
-            mode: OutputMode::Xml,
+            mode: Some(OutputMode::Xml),
             ..DriverConfig::default()
         };
-        let blocks = vec![Block {
-            block_type: BlockType::FileTree,
-            flags: BlockFlags::NONE,
-            summary: None,
-            content: BlockContent::FileTree(FileTreeBlock {
+        let blocks = vec![block(
+            BlockType::FileTree,
+            BlockContent::FileTree(FileTreeBlock {
                 root_path: "src/".to_string(),
                 entries: vec![
-                    FileEntry {
-                        name: "main.rs".to_string(),
-                        kind: FileEntryKind::File,
-                        size: 1024,
-                        children: vec![],
-                    },
-                    FileEntry {
-                        name: "utils".to_string(),
-                        kind: FileEntryKind::Directory,
-                        size: 0,
-                        children: vec![FileEntry {
-                            name: "helpers.rs".to_string(),
-                            kind: FileEntryKind::File,
-                            size: 256,
-                            children: vec![],
-                        }],
-                    },
+                    FileEntry::file("main.rs", 1024),
+                    FileEntry::dir("utils", vec![FileEntry::file("helpers.rs", 256)]),
                 ],
             }),
-        }];
+        )];
         let result = driver.render(&blocks, &config).unwrap();
         assert!(result.contains("<tree root=\"src/\">"));
         assert!(result.contains("main.rs (1024 bytes)"));
@@ -331,26 +985,72 @@ mod tests {
         assert!(result.contains("  helpers.rs (256 bytes)"));
     }
 
+    #[test]
+    fn file_tree_marks_recent_executable_and_included_files() {
+        let driver = DefaultDriver;
+        let lib_src = b"pub fn lib() {}";
+        let entry = |name: &str, mode, mtime, content_hash| FileEntry {
+            mode,
+            mtime,
+            content_hash,
+            ..FileEntry::file(name, 10)
+        };
+        let tree = block(
+            BlockType::FileTree,
+            BlockContent::FileTree(FileTreeBlock {
+                root_path: "/repo".to_string(),
+                entries: vec![FileEntry::dir(
+                    "src",
+                    vec![
+                        entry("main.rs", None, Some(2_000), None),
+                        entry(
+                            "lib.rs",
+                            None,
+                            Some(1_000),
+                            Some(*blake3::hash(lib_src).as_bytes()),
+                        ),
+                        entry("run.sh", Some(0o755), None, None),
+                        FileEntry::symlink("current", "releases/v2"),
+                    ],
+                )],
+            }),
+        );
+        let blocks = vec![
+            code_block(Lang::Rust, "./src/main.rs", b"fn main() {}"),
+            tree,
+            // Path differs from the tree entry; matched by content hash.
+            code_block(Lang::Rust, "crate/lib.rs", lib_src),
+        ];
+        let config = DriverConfig {
+            mode: Some(OutputMode::Minimal),
+            recent_since: Some(1_500),
+            ..DriverConfig::default()
+        };
+        let result = driver.render(&blocks, &config).unwrap();
+        assert!(result.contains("  main.rs (10 bytes, modified recently, included above)"));
+        assert!(result.contains("  lib.rs (10 bytes, included below)"));
+        assert!(result.contains("  run.sh (10 bytes, executable)"));
+        assert!(result.contains("  current -> releases/v2"));
+    }
+
     #[test]
     fn tool_result_rendering_all_modes() {
         let driver = DefaultDriver;
-        let blocks = vec![Block {
-            block_type: BlockType::ToolResult,
-            flags: BlockFlags::NONE,
-            summary: None,
-            content: BlockContent::ToolResult(ToolResultBlock {
+        let blocks = vec![block(
+            BlockType::ToolResult,
+            BlockContent::ToolResult(ToolResultBlock {
                 tool_name: "ripgrep".to_string(),
                 status: Status::Ok,
                 content: b"3 matches found.".to_vec(),
                 schema_hint: None,
             }),
-        }];
+        )];
 
         let xml = driver
             .render(
                 &blocks,
                 &DriverConfig {
-                    mode: OutputMode::Xml,
+                    mode: Some(OutputMode::Xml),
                     ..DriverConfig::default()
                 },
             )
@@ -361,7 +1061,7 @@ mod tests {
             .render(
                 &blocks,
                 &DriverConfig {
-                    mode: OutputMode::Markdown,
+                    mode: Some(OutputMode::Markdown),
                     ..DriverConfig::default()
                 },
             )
@@ -372,7 +1072,7 @@ mod tests {
             .render(
                 &blocks,
                 &DriverConfig {
-                    mode: OutputMode::Minimal,
+                    mode: Some(OutputMode::Minimal),
                     ..DriverConfig::default()
                 },
             )
@@ -399,7 +1099,7 @@ mod tests {
 
         for mode in [OutputMode::Xml, OutputMode::Markdown, OutputMode::Minimal] {
             let config = DriverConfig {
-                mode,
+                mode: Some(mode),
                 verbosity: Verbosity::Summary,
                 ..DriverConfig::default()
             };
@@ -434,7 +1134,7 @@ mod tests {
 
         // Adaptive without budget → Full rendering (summary ignored)
         let config = DriverConfig {
-            mode: OutputMode::Xml,
+            mode: Some(OutputMode::Xml),
             ..DriverConfig::default()
         };
         let result = driver.render(&blocks, &config).unwrap();
@@ -444,18 +1144,391 @@ mod tests {
         );
     }
 
+    #[test]
+    fn custom_estimator_drives_budget() {
+        /// Counts every non-empty string as a single token.
+        struct OneToken;
+        impl TokenEstimator for OneToken {
+            fn estimate(&self, text: &str) -> u32 {
+                u32::from(!text.is_empty())
+            }
+        }
+
+        let driver = DefaultDriver;
+        let blocks = vec![
+            code_block(
+                Lang::Rust,
+                "src/a.rs",
+                b"fn a() { /* long enough to cost */ }",
+            ),
+            code_block(
+                Lang::Rust,
+                "src/b.rs",
+                b"fn b() { /* long enough to cost */ }",
+            ),
+        ];
+        let render = |estimator| {
+            let config = DriverConfig {
+                mode: Some(OutputMode::Minimal),
+                token_budget: Some(12),
+                estimator,
+                ..DriverConfig::default()
+            };
+            driver.render(&blocks, &config).unwrap()
+        };
+
+        // The default estimator prices each body well over the budget.
+        let default = render(None);
+        assert!(!default.contains("fn a()") || !default.contains("fn b()"));
+
+        let custom = render(Some(Arc::new(OneToken)));
+        assert!(custom.contains("fn a()") && custom.contains("fn b()"));
+    }
+
+    #[test]
+    fn budget_strategy_selects_allocator() {
+        let driver = DefaultDriver;
+        let big = "let xy = 1;\n".repeat(24);
+        let blocks = vec![
+            code_block(Lang::Rust, "src/big.rs", big.as_bytes()),
+            code_block(Lang::Rust, "src/a.rs", b"fn a() {}"),
+            code_block(Lang::Rust, "src/b.rs", b"fn b() {}"),
+        ];
+        let render = |budget_strategy| {
+            let config = DriverConfig {
+                mode: Some(OutputMode::Minimal),
+                token_budget: Some(90),
+                estimator: Some(Arc::new(crate::budget::HeuristicEstimator)),
+                budget_strategy,
+                ..DriverConfig::default()
+            };
+            driver.render(&blocks, &config).unwrap()
+        };
+
+        // Greedy spends the budget on the first block and has nothing
+        // left for the last; optimal keeps both small ones whole and
+        // cuts the large one down.
+        let greedy = render(BudgetStrategy::Greedy);
+        assert!(!greedy.contains("[elided") && !greedy.contains("fn b()"));
+        let optimal = render(BudgetStrategy::Optimal);
+        assert!(optimal.contains("fn a()") && optimal.contains("fn b()"));
+        assert!(optimal.contains("[elided"));
+    }
+
+    #[test]
+    fn outline_stands_in_for_missing_summary() {
+        let driver = DefaultDriver;
+        let body = "    let step = advance(state);\n".repeat(8);
+        let source = ["parse", "check", "emit"].map(|name| {
+            format!("/// Run the {name} pass.\npub fn {name}(input: &str) -> Ast {{\n{body}}}\n")
+        });
+        let source = source.join("\n");
+        let blocks = vec![code_block(Lang::Rust, "src/passes.rs", source.as_bytes())];
+
+        // Too little budget for the code, or most of it; enough for its
+        // outline.
+        for budget_strategy in [BudgetStrategy::Greedy, BudgetStrategy::Optimal] {
+            let config = DriverConfig {
+                mode: Some(OutputMode::Minimal),
+                token_budget: Some(80),
+                estimator: Some(Arc::new(crate::budget::HeuristicEstimator)),
+                budget_strategy,
+                ..DriverConfig::default()
+            };
+            let output = driver.render_with_report(&blocks, &config).unwrap();
+            assert_eq!(output.report.blocks[0].decision, RenderDecision::Outline);
+            assert!(output.text.contains("/// Run the emit pass."));
+            assert!(
+                output
+                    .text
+                    .contains("pub fn emit(input: &str) -> Ast { ... }")
+            );
+            assert!(!output.text.contains("advance"));
+        }
+    }
+
+    #[test]
+    fn query_ranks_blocks_within_tier() {
+        let driver = DefaultDriver;
+        let blocks = vec![
+            code_block(
+                Lang::Rust,
+                "src/parse.rs",
+                b"fn parse(input: &str) -> Ast { todo!() }",
+            ),
+            code_block(
+                Lang::Rust,
+                "src/retry.rs",
+                b"fn retry(attempts: u32) -> Delay { todo!() }",
+            ),
+        ];
+        let render = |query: Option<&str>| {
+            let config = DriverConfig {
+                mode: Some(OutputMode::Minimal),
+                token_budget: Some(20),
+                estimator: Some(Arc::new(crate::budget::HeuristicEstimator)),
+                query: query.map(str::to_string),
+                ..DriverConfig::default()
+            };
+            driver.render(&blocks, &config).unwrap()
+        };
+
+        // Room for one block in full: stream order picks the first,
+        // the query picks the block it names.
+        let plain = render(None);
+        assert!(plain.contains("fn parse(") && !plain.contains("fn retry("));
+        let queried = render(Some("why do retry attempts never stop?"));
+        assert!(queried.contains("fn retry(") && !queried.contains("fn parse("));
+    }
+
+    #[test]
+    fn dependency_groups_degrade_together() {
+        let driver = DefaultDriver;
+        let blocks = vec![
+            conversation_block(Role::Assistant, b"Running the parser tests."),
+            tool_result_block(
+                "cargo test",
+                "test parser::empty ... FAILED\n".repeat(12).as_bytes(),
+            ),
+            code_block(Lang::Rust, "src/lexer.rs", b"pub fn lex(input: &str) {}"),
+        ];
+        let render = |group_dependencies: bool, budget_strategy: BudgetStrategy| {
+            let config = DriverConfig {
+                mode: Some(OutputMode::Minimal),
+                token_budget: Some(40),
+                estimator: Some(Arc::new(crate::budget::HeuristicEstimator)),
+                budget_strategy,
+                group_dependencies,
+                ..DriverConfig::default()
+            };
+            let output = driver.render_with_report(&blocks, &config).unwrap();
+            output
+                .report
+                .blocks
+                .into_iter()
+                .map(|b| b.decision)
+                .collect::<Vec<_>>()
+        };
+
+        // Alone, the call fits and its output is cut to a window.
+        let decisions = render(false, BudgetStrategy::Greedy);
+        assert_eq!(decisions[0], RenderDecision::Full);
+        assert!(matches!(decisions[1], RenderDecision::Truncated { .. }));
+
+        // Grouped, the pair cannot fit in full and degrades as one,
+        // leaving the budget to the unrelated block.
+        for budget_strategy in [BudgetStrategy::Greedy, BudgetStrategy::Optimal] {
+            let decisions = render(true, budget_strategy);
+            assert!(
+                decisions[..2]
+                    .iter()
+                    .all(|d| matches!(d, RenderDecision::Placeholder { .. }))
+            );
+            assert_eq!(decisions[2], RenderDecision::Full);
+        }
+    }
+
+    #[test]
+    fn dedup_replaces_repeats_and_reports_savings() {
+        let driver = DefaultDriver;
+        let source = (1..=12)
+            .map(|n| format!("pub fn step_{n}(input: &str) -> usize {{ input.len() }}\n"))
+            .collect::<Vec<_>>()
+            .concat();
+        let blocks = vec![
+            code_block(Lang::Rust, "src/steps.rs", source.as_bytes()),
+            tool_result_block("cat", source.as_bytes()),
+        ];
+        let render = |dedup: bool| {
+            let config = DriverConfig {
+                mode: Some(OutputMode::Minimal),
+                estimator: Some(Arc::new(crate::budget::HeuristicEstimator)),
+                dedup,
+                ..DriverConfig::default()
+            };
+            driver.render_with_report(&blocks, &config).unwrap()
+        };
+
+        let plain = render(false);
+        assert!(plain.report.blocks.iter().all(|b| b.saved_tokens == 0));
+
+        let deduped = render(true);
+        assert!(deduped.text.contains("[see code src/steps.rs L1-12]"));
+        assert_eq!(deduped.text.matches("pub fn step_7").count(), 1);
+        assert_eq!(deduped.report.blocks[0].saved_tokens, 0);
+        assert!(deduped.report.blocks[1].saved_tokens > 0);
+        assert!(deduped.report.total_tokens < plain.report.total_tokens);
+    }
+
+    #[test]
+    fn dedup_refers_back_in_output_order() {
+        let source = (1..=12)
+            .map(|n| format!("pub fn step_{n}(input: &str) -> usize {{ input.len() }}\n"))
+            .collect::<Vec<_>>()
+            .concat();
+        let blocks = vec![
+            code_block(Lang::Rust, "src/steps.rs", source.as_bytes()),
+            code_block(Lang::Rust, "src/copy.rs", source.as_bytes()),
+            priority_annotation(1, Priority::Critical),
+        ];
+        let config = DriverConfig {
+            mode: Some(OutputMode::Minimal),
+            estimator: Some(Arc::new(crate::budget::HeuristicEstimator)),
+            dedup: true,
+            ordering: BlockOrdering::PriorityFirst,
+            ..DriverConfig::default()
+        };
+        let out = DefaultDriver.render_with_report(&blocks, &config).unwrap();
+
+        // src/copy.rs renders first, so it keeps the lines and
+        // src/steps.rs refers back to it.
+        assert_eq!(out.text.matches("pub fn step_7").count(), 1);
+        assert!(out.text.contains("[see code src/copy.rs L1-12]"));
+        assert!(!out.text.contains("[see code src/steps.rs"));
+        assert_eq!(out.report.blocks[0].block_index, 1);
+        assert_eq!(out.report.blocks[0].saved_tokens, 0);
+        assert!(out.report.blocks[1].saved_tokens > 0);
+    }
+
+    #[test]
+    fn ordering_keeps_decisions_with_blocks() {
+        let driver = DefaultDriver;
+        let priority = |target, priority: Priority| {
+            annotation(AnnotationBlock {
+                target_block_id: target,
+                kind: AnnotationKind::Priority,
+                value: vec![priority.to_wire_byte()],
+            })
+        };
+        let mut low = code_block(Lang::Rust, "src/low.rs", b"fn low() {}");
+        low.summary = Some(Summary {
+            text: "Low-priority helpers.".to_string(),
+        });
+        let blocks = vec![
+            low,
+            code_block(Lang::Rust, "src/normal.rs", b"fn normal() {}"),
+            code_block(Lang::Rust, "src/high.rs", b"fn high() {}"),
+            priority(0, Priority::Low),
+            priority(2, Priority::High),
+        ];
+        let render = |ordering| {
+            let config = DriverConfig {
+                mode: Some(OutputMode::Minimal),
+                token_budget: Some(1000),
+                ordering,
+                ..DriverConfig::default()
+            };
+            driver.render_with_report(&blocks, &config).unwrap()
+        };
+
+        let payload = render(BlockOrdering::Payload);
+        let by_priority = render(BlockOrdering::PriorityFirst);
+        let order: Vec<usize> = by_priority
+            .report
+            .blocks
+            .iter()
+            .map(|b| b.block_index)
+            .collect();
+        assert_eq!(order, [2, 1, 0]);
+        // Each block keeps the decision it gets in payload order.
+        for block in &by_priority.report.blocks {
+            let same = &payload.report.blocks[block.block_index];
+            assert_eq!(block.decision, same.decision);
+        }
+        assert_eq!(
+            by_priority.report.blocks[2].decision,
+            RenderDecision::Summary
+        );
+        let at = |needle: &str| by_priority.text.find(needle).unwrap();
+        assert!(at("fn high()") < at("fn normal()"));
+        assert!(at("fn normal()") < at("Low-priority helpers."));
+    }
+
+    #[test]
+    fn conversation_policy_keeps_recent_turns() {
+        const BUDGET: u32 = 100;
+        let driver = DefaultDriver;
+        let old = "Here is some background on the parser and its history. ".repeat(3);
+        let blocks = vec![
+            conversation_block(Role::System, b"You review Rust code."),
+            conversation_block(Role::User, old.as_bytes()),
+            conversation_block(Role::Assistant, old.as_bytes()),
+            conversation_block(Role::Assistant, b"Running the tests."),
+            tool_result_block("cargo test", b"test parser::empty ... FAILED"),
+            conversation_block(Role::User, b"Why does the parser panic on empty input?"),
+        ];
+        let render = |conversation, budget_strategy| {
+            let config = DriverConfig {
+                mode: Some(OutputMode::Minimal),
+                token_budget: Some(BUDGET),
+                estimator: Some(Arc::new(crate::budget::HeuristicEstimator)),
+                budget_strategy,
+                conversation,
+                ..DriverConfig::default()
+            };
+            let report = driver.render_with_report(&blocks, &config).unwrap().report;
+            report
+                .blocks
+                .iter()
+                .map(|b| (b.decision.clone(), b.reason))
+                .collect::<Vec<_>>()
+        };
+
+        // Payload order spends the budget on the old turns first.
+        let plain = render(None, BudgetStrategy::Greedy);
+        assert_eq!(plain[1].0, RenderDecision::Full);
+        assert_ne!(plain[5].0, RenderDecision::Full);
+
+        let policy = Some(ConversationPolicy { recent_turns: 1 });
+        for strategy in [BudgetStrategy::Greedy, BudgetStrategy::Optimal] {
+            let kept = render(policy, strategy);
+            for i in [0, 5] {
+                assert_eq!(kept[i], (RenderDecision::Full, DecisionReason::KeptTurn));
+            }
+            // The tool call and its result stay together, and the newest
+            // older turn goes first.
+            assert_eq!(kept[3].0, RenderDecision::Full, "{strategy:?}");
+            assert_eq!(kept[4].0, RenderDecision::Full, "{strategy:?}");
+            assert!(matches!(kept[1].0, RenderDecision::Placeholder { .. }));
+        }
+    }
+
+    #[test]
+    fn target_model_selects_profile() {
+        let driver = DefaultDriver;
+        let blocks = vec![code_block(Lang::Rust, "src/main.rs", b"fn main() {}")];
+        let render = |target_model, mode| {
+            let config = DriverConfig {
+                mode,
+                target_model: Some(target_model),
+                ..DriverConfig::default()
+            };
+            driver.render(&blocks, &config).unwrap()
+        };
+
+        // GPT defaults to markdown; an explicit mode still wins.
+        assert!(render(ModelFamily::Gpt, None).starts_with("## src/main.rs"));
+        assert_eq!(
+            render(ModelFamily::Claude, None),
+            "<context>\n<code lang=\"rust\" path=\"src/main.rs\">\nfn main() {}\n</code>\n</context>"
+        );
+        assert_eq!(
+            render(ModelFamily::Gemini, None),
+            "<context>\n<file>\n<lang>rust</lang>\n<path>src/main.rs</path>\n\
+             <content>\nfn main() {}\n</content>\n</file>\n</context>"
+        );
+        assert!(
+            render(ModelFamily::Gpt, Some(OutputMode::Xml)).contains("<file>\n<lang>rust</lang>")
+        );
+    }
+
     #[test]
     fn end_blocks_filtered_out() {
         let driver = DefaultDriver;
         let config = DriverConfig::default();
         let blocks = vec![
             code_block(Lang::Rust, "main.rs", b"fn main() {}"),
-            Block {
-                block_type: BlockType::End,
-                flags: BlockFlags::NONE,
-                summary: None,
-                content: BlockContent::End,
-            },
+            block(BlockType::End, BlockContent::End),
         ];
         let result = driver.render(&blocks, &config).unwrap();
         assert!(result.contains("fn main()"));
@@ -26,3 +26,84 @@ pub enum DriverError {
     #[error("invalid UTF-8 in block content at index {block_index}")]
     InvalidContent { block_index: usize },
 }
+
+/// Errors that can occur while loading a BPE vocabulary.
+///
+/// ```text
+/// ┌───────────┬──────────────────────────────────────────────────┐
+/// │ Variant   │ Cause                                            │
+/// ├───────────┼──────────────────────────────────────────────────┤
+/// │ Io        │ Vocabulary file missing or unreadable            │
+/// │ Malformed │ Line is not `<base64 token> <rank>`              │
+/// │ Empty     │ File contains no vocabulary entries              │
+/// └───────────┴──────────────────────────────────────────────────┘
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum VocabError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("malformed vocabulary line {line}: {reason}")]
+    Malformed { line: usize, reason: &'static str },
+
+    #[error("vocabulary has no entries")]
+    Empty,
+}
+
+/// Errors that can occur while parsing Raw-mode text back into blocks.
+///
+/// Line numbers are 1-based and count every line of the input,
+/// including comments and blank lines.
+///
+/// ```text
+/// ┌───────────┬──────────────────────────────────────────────────┐
+/// │ Variant   │ Cause                                            │
+/// ├───────────┼──────────────────────────────────────────────────┤
+/// │ Malformed │ Line is not `<tag> <encoding> <frame>`, or the   │
+/// │           │ frame text or its framing is invalid             │
+/// │ Frame     │ Decoded bytes are not a valid block frame        │
+/// │ Content   │ Frame body does not decode as its block type     │
+/// └───────────┴──────────────────────────────────────────────────┘
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum RawParseError {
+    #[error("malformed raw line {line}: {reason}")]
+    Malformed { line: usize, reason: &'static str },
+
+    #[error("invalid block frame on raw line {line}")]
+    Frame {
+        line: usize,
+        #[source]
+        source: bcp_wire::WireError,
+    },
+
+    #[error("invalid block body on raw line {line}")]
+    Content {
+        line: usize,
+        #[source]
+        source: bcp_types::TypeError,
+    },
+}
+
+/// Errors from decoding a payload and rendering it in one call —
+/// [`DefaultDriver::decode`](crate::DefaultDriver::decode) and each
+/// item of a [`RenderStream`](crate::RenderStream).
+///
+/// ```text
+/// ┌─────────┬──────────────────────────────────────────────────────┐
+/// │ Variant │ Cause                                                │
+/// ├─────────┼──────────────────────────────────────────────────────┤
+/// │ Decode  │ The payload could not be read or decoded, or a       │
+/// │         │ reference block has no content store to resolve it   │
+/// │ Render  │ A block could not be rendered, or none survived      │
+/// │         │ filtering (DriverError::EmptyInput)                  │
+/// └─────────┴──────────────────────────────────────────────────────┘
+/// ```
+#[derive(Debug, thiserror::Error)]
+pub enum PayloadError {
+    #[error(transparent)]
+    Decode(#[from] bcp_decoder::DecodeError),
+
+    #[error(transparent)]
+    Render(#[from] DriverError),
+}
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+
+use bcp_types::block::{Block, BlockContent};
+use bcp_types::enums::{Priority, Role};
+
+use crate::budget::{BlockBudgetInfo, RenderDecision};
+use crate::conversation::{TurnLevel, turn_levels};
+use crate::render_context::normalize_path;
+use crate::report::DecisionReason;
+
+/// Blocks of `filtered` that only make sense together, as lists of
+/// positions in `filtered`, each ascending and ordered by first member.
+/// Blocks related to nothing are left out.
+///
+/// ```text
+/// ┌──────────────────┬──────────────────────────────────────────────┐
+/// │ Relation         │ Joins                                        │
+/// ├──────────────────┼──────────────────────────────────────────────┤
+/// │ Shared path      │ CODE and DIFF blocks for the same file (a    │
+/// │                  │ DIFF's old path counts too)                  │
+/// │ Tool call        │ An assistant message and the TOOL_RESULT     │
+/// │                  │ blocks and tool turns after it, up to the    │
+/// │                  │ next message; tool turns sharing a           │
+/// │                  │ tool_call_id                                 │
+/// │ Embedding source │ An EMBEDDING_REF and the block whose body    │
+/// │                  │ hashes to its source_hash                    │
+/// │ Link annotation  │ The annotated block and the block it links   │
+/// └──────────────────┴──────────────────────────────────────────────┘
+/// ```
+///
+/// Relations chain: a DIFF linked to a document joins the document to
+/// the CODE block of the DIFF's path as well. Paths compare after
+/// [`normalize_path`]; links to blocks the filters dropped are ignored.
+pub(crate) fn dependency_groups(
+    all_blocks: &[Block],
+    filtered: &[&Block],
+    original_indices: &[usize],
+) -> Vec<Vec<usize>> {
+    let mut parent: Vec<usize> = (0..filtered.len()).collect();
+    let mut paths: HashMap<&str, usize> = HashMap::new();
+    let mut calls: HashMap<&str, usize> = HashMap::new();
+    let mut bodies: HashMap<[u8; 32], usize> = HashMap::new();
+    let mut open_call: Option<usize> = None;
+
+    for (i, block) in filtered.iter().enumerate() {
+        match &block.content {
+            BlockContent::Code(code) => {
+                join(&mut parent, &mut paths, normalize_path(&code.path), i);
+            }
+            BlockContent::Diff(diff) => {
+                for path in std::iter::once(&diff.path).chain(&diff.old_path) {
+                    join(&mut parent, &mut paths, normalize_path(path), i);
+                }
+            }
+            BlockContent::Conversation(c) if c.role == Role::Tool => {
+                if let Some(call) = open_call {
+                    union(&mut parent, call, i);
+                }
+                if let Some(id) = &c.tool_call_id {
+                    join(&mut parent, &mut calls, id, i);
+                }
+            }
+            BlockContent::Conversation(c) => {
+                open_call = (c.role == Role::Assistant).then_some(i);
+            }
+            BlockContent::ToolResult(_) => {
+                if let Some(call) = open_call {
+                    union(&mut parent, call, i);
+                }
+            }
+            _ => {}
+        }
+        if let Some(body) = text_body(block) {
+            bodies.entry(*blake3::hash(body).as_bytes()).or_insert(i);
+        }
+    }
+
+    for (i, block) in filtered.iter().enumerate() {
+        if let BlockContent::EmbeddingRef(e) = &block.content
+            && let Ok(hash) = <[u8; 32]>::try_from(e.source_hash.as_slice())
+            && let Some(&source) = bodies.get(&hash)
+        {
+            union(&mut parent, source, i);
+        }
+    }
+
+    let position: HashMap<usize, usize> = original_indices
+        .iter()
+        .enumerate()
+        .map(|(i, &orig)| (orig, i))
+        .collect();
+    for block in all_blocks {
+        if let BlockContent::Annotation(ann) = &block.content
+            && let Some((_, to)) = ann.as_link()
+            && let Some(&from) = position.get(&(ann.target_block_id as usize))
+            && let Some(&to) = position.get(&(to as usize))
+        {
+            union(&mut parent, from, to);
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut slot: HashMap<usize, usize> = HashMap::new();
+    for i in 0..filtered.len() {
+        let root = find(&mut parent, i);
+        let at = *slot.entry(root).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[at].push(i);
+    }
+    groups.retain(|members| members.len() > 1);
+    groups
+}
+
+/// The text body of a block: what an `EMBEDDING_REF` may point at and
+/// what render-time dedup compares.
+pub(crate) fn text_body(block: &Block) -> Option<&[u8]> {
+    match &block.content {
+        BlockContent::Code(c) => Some(&c.content),
+        BlockContent::Document(d) => Some(&d.content),
+        BlockContent::StructuredData(d) => Some(&d.content),
+        BlockContent::ToolResult(t) => Some(&t.content),
+        BlockContent::Conversation(c) => Some(&c.content),
+        _ => None,
+    }
+}
+
+/// Join `i` to the first block seen under `key`.
+fn join<'a>(parent: &mut [usize], seen: &mut HashMap<&'a str, usize>, key: &'a str, i: usize) {
+    let first = *seen.entry(key).or_insert(i);
+    union(parent, first, i);
+}
+
+/// Root of `i`'s set: its lowest member.
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    parent[a.max(b)] = a.min(b);
+}
+
+/// Indices of the blocks in the dependency group led by `first`.
+pub(crate) fn group_members(infos: &[BlockBudgetInfo], first: usize) -> Vec<usize> {
+    infos
+        .iter()
+        .enumerate()
+        .filter(|(_, info)| info.group == Some(first))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Every way a dependency group may render, from its floor up to Full.
+///
+/// The levels are those of an older conversation turn (see
+/// [`turn_levels`]), limited by the group's priority the way a single
+/// block's degradation path is:
+///
+/// ```text
+/// ┌────────────┬───────────────────────────────────────────────┐
+/// │ Priority   │ Levels                                        │
+/// ├────────────┼───────────────────────────────────────────────┤
+/// │ Critical   │ Full                                          │
+/// │ High       │ Summary, Full                                 │
+/// │ Normal     │ Omit, Placeholder, Summary, Full              │
+/// │ Low        │ Omit, Placeholder, Summary                    │
+/// │ Background │ Omit, Placeholder                             │
+/// └────────────┴───────────────────────────────────────────────┘
+/// ```
+///
+/// Groups are never truncated: cutting each member to a window would
+/// keep halves of both sides of the relation.
+pub(crate) fn group_levels(
+    members: &[usize],
+    infos: &[BlockBudgetInfo],
+    filtered: &[&Block],
+) -> Vec<TurnLevel> {
+    let mut levels = turn_levels(members, infos, filtered);
+    let full = levels.pop().expect("turn levels end with Full");
+    let summary = (levels.len() > 2).then(|| levels.remove(2));
+    match infos[members[0]].priority {
+        Priority::Critical => vec![full],
+        Priority::High => summary.into_iter().chain([full]).collect(),
+        Priority::Normal => {
+            levels.extend(summary);
+            levels.push(full);
+            levels
+        }
+        Priority::Low => {
+            levels.extend(summary);
+            levels
+        }
+        Priority::Background => levels,
+    }
+}
+
+/// Decide a dependency group for the greedy allocator: the fullest
+/// level that fits `remaining`, charging it, or Full over budget for a
+/// Critical or High group. Returns one decision per member.
+pub(crate) fn decide_group(
+    members: &[usize],
+    infos: &[BlockBudgetInfo],
+    filtered: &[&Block],
+    remaining: &mut u32,
+) -> Vec<(RenderDecision, DecisionReason)> {
+    let priority = infos[members[0]].priority;
+    let mut levels = group_levels(members, infos, filtered);
+    let top = levels.len() - 1;
+    let fits = levels.iter().rposition(|level| level.cost <= *remaining);
+    let index = fits.unwrap_or(top);
+    let level = levels.swap_remove(index);
+    *remaining = remaining.saturating_sub(level.cost);
+    let reason = match priority {
+        Priority::Critical => DecisionReason::Critical,
+        _ if fits.is_none() => DecisionReason::ForcedOverBudget,
+        _ if level.decisions[0] == RenderDecision::Full => DecisionReason::WithinBudget,
+        Priority::Low | Priority::Background if index == top => DecisionReason::LowPriority,
+        _ => DecisionReason::OverBudget,
+    };
+    level.decisions.into_iter().map(|d| (d, reason)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::budget::{BlockPricer, HeuristicEstimator, scan_blocks};
+    use crate::config::OutputMode;
+    use crate::render_context::RenderContext;
+    use bcp_decoder::BcpDecoder;
+    use bcp_encoder::BcpEncoder;
+    use bcp_types::diff::DiffHunk;
+    use bcp_types::enums::{FormatHint, Lang, LinkKind, Status};
+
+    fn filter(blocks: &[Block]) -> (Vec<&Block>, Vec<usize>) {
+        blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| !matches!(b.content, BlockContent::Annotation(_)))
+            .map(|(i, b)| (b, i))
+            .unzip()
+    }
+
+    #[test]
+    fn groups_follow_paths_calls_embeddings_and_links() {
+        let lib = b"pub fn parse() {}";
+        let mut enc = BcpEncoder::new();
+        enc.add_code(Lang::Rust, "src/lib.rs", lib)
+            .add_diff(
+                "./src/lib.rs",
+                vec![DiffHunk::new(
+                    1,
+                    1,
+                    b"-fn parse() {}\n+pub fn parse() {}\n".to_vec(),
+                )],
+            )
+            .add_conversation(Role::Assistant, b"Running the tests.")
+            .add_tool_result("cargo test", Status::Ok, b"ok")
+            .add_conversation(Role::User, b"Thanks.")
+            .add_tool_result("ls", Status::Ok, b"src")
+            .add_code(Lang::Rust, "src/main.rs", b"fn main() {}")
+            .add_document("Notes", b"main calls parse", FormatHint::Markdown)
+            .add_link(6, LinkKind::Explains, 7)
+            .add_embedding_ref(b"vec-1", blake3::hash(lib).as_bytes(), "embed");
+        let blocks = BcpDecoder::decode(&enc.encode().unwrap()).unwrap().blocks;
+        let (filtered, original_indices) = filter(&blocks);
+
+        let groups = dependency_groups(&blocks, &filtered, &original_indices);
+        assert_eq!(groups, [vec![0, 1, 8], vec![2, 3], vec![6, 7]]);
+    }
+
+    #[test]
+    fn group_degrades_together() {
+        let mut enc = BcpEncoder::new();
+        enc.add_code(
+            Lang::Rust,
+            "src/lib.rs",
+            &b"pub fn parse(input: &str) -> Vec<Token> { lex(input) }\n".repeat(4),
+        )
+        .with_summary("Parser entry point.")
+        .unwrap()
+        .add_diff(
+            "src/lib.rs",
+            vec![DiffHunk::new(
+                1,
+                1,
+                b"-fn parse() {}\n+pub fn parse() {}\n".to_vec(),
+            )],
+        );
+        let blocks = BcpDecoder::decode(&enc.encode().unwrap()).unwrap().blocks;
+        let (filtered, original_indices) = filter(&blocks);
+        let ctx = RenderContext::default();
+        let pricer = BlockPricer::new(OutputMode::Minimal, &ctx, &HeuristicEstimator);
+        let mut infos = scan_blocks(
+            &filtered,
+            &HashMap::new(),
+            &HashMap::new(),
+            &pricer,
+            &original_indices,
+        );
+        for info in &mut infos {
+            info.group = Some(0);
+        }
+        assert_eq!(group_members(&infos, 0), [0, 1]);
+
+        // Room for the code in full, but not for its diff too.
+        let mut remaining = infos[0].full_tokens + infos[1].placeholder_tokens;
+        let decided = decide_group(&[0, 1], &infos, &filtered, &mut remaining);
+        assert_eq!(decided[0].0, RenderDecision::Summary);
+        assert!(matches!(decided[1].0, RenderDecision::Placeholder { .. }));
+        assert!(
+            decided
+                .iter()
+                .all(|(_, r)| *r == DecisionReason::OverBudget)
+        );
+
+        // A High group never drops below its summary level.
+        for info in &mut infos {
+            info.priority = Priority::High;
+        }
+        let mut remaining = 0;
+        let decided = decide_group(&[0, 1], &infos, &filtered, &mut remaining);
+        assert_eq!(
+            decided,
+            [
+                (RenderDecision::Full, DecisionReason::ForcedOverBudget),
+                (RenderDecision::Full, DecisionReason::ForcedOverBudget),
+            ]
+        );
+    }
+}
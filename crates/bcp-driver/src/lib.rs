@@ -1,16 +1,45 @@
 #![warn(clippy::pedantic)]
 
+pub mod bpe;
 pub mod budget;
 pub mod config;
 pub mod driver;
 pub mod error;
+pub mod payload;
+pub mod profile;
 pub mod render_markdown;
 pub mod render_minimal;
+pub mod render_raw;
 pub mod render_xml;
+pub mod report;
+#[cfg(feature = "stream")]
+pub mod stream;
 
+mod conversation;
+mod dedup;
+mod group;
+mod optimize;
+mod order;
 mod placeholder;
+mod query;
+mod render_context;
+#[cfg(test)]
+mod test_support;
+mod truncate;
 
-pub use budget::{CodeAwareEstimator, HeuristicEstimator, RenderDecision, TokenEstimator};
-pub use config::{DriverConfig, ModelFamily, OutputMode, Verbosity};
+pub use bpe::BpeEstimator;
+pub use budget::{
+    CodeAwareEstimator, HeuristicEstimator, RatioEstimator, RenderDecision, TokenEstimator,
+};
+pub use config::{
+    BlockOrdering, BudgetStrategy, ConversationPolicy, DriverConfig, ModelFamily, OutputMode,
+    PathPrefix, RawEncoding, Verbosity,
+};
 pub use driver::{DefaultDriver, BcpDriver};
-pub use error::DriverError;
+pub use error::{DriverError, PayloadError, RawParseError, VocabError};
+pub use payload::{DecodeConfig, RenderedPayload};
+pub use profile::{ModelProfile, TagVocabulary, XmlStyle};
+pub use render_raw::RawRenderer;
+pub use report::{BlockReport, DecisionReason, RenderOutput, RenderReport};
+#[cfg(feature = "stream")]
+pub use stream::{RenderStream, StreamStrategy, TextChunk};
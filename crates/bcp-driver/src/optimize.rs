@@ -0,0 +1,628 @@
+use bcp_types::block::Block;
+use bcp_types::enums::Priority;
+
+use crate::budget::{
+    BlockBudgetInfo, RenderDecision, TokenEstimator, allocate_budget, make_placeholder,
+};
+use crate::conversation::{Turn, TurnLevel, turn_levels, turn_members};
+use crate::group::{group_levels, group_members};
+use crate::report::DecisionReason;
+use crate::truncate::plan_truncation;
+
+/// Widest knapsack table, in capacity cells. Budgets larger than this
+/// are priced in coarser units, rounding every cost up so the
+/// allocation never overspends.
+const MAX_CELLS: u32 = 4096;
+
+/// Truncation windows offered to the knapsack, as percentages of a
+/// block's content tokens. The upgrade pass widens whichever is chosen.
+const TRUNCATION_STEPS: [u32; 3] = [25, 50, 75];
+
+/// Utility of each rendering, as a fraction of the block's weight.
+///
+/// ```text
+/// ┌─────────────┬───────────────────────────────────────────────┐
+/// │ Decision    │ Value                                         │
+/// ├─────────────┼───────────────────────────────────────────────┤
+/// │ Full        │ 1.0                                           │
+/// │ Truncated   │ 0.9 × kept tokens / content tokens            │
+/// │ Summary     │ 0.4                                           │
+/// │ Outline     │ 0.3                                           │
+/// │ Placeholder │ 0.05                                          │
+/// │ Omit        │ 0                                             │
+/// └─────────────┴───────────────────────────────────────────────┘
+/// ```
+///
+/// A window holding half the content is worth a little more than a
+/// summary, matching the greedy path's preference for truncating a
+/// mostly-fitting block over summarizing it.
+fn decision_value(decision: &RenderDecision, info: &BlockBudgetInfo) -> f64 {
+    match decision {
+        RenderDecision::Full => 1.0,
+        RenderDecision::Truncated { omitted_tokens, .. } if info.content_tokens > 0 => {
+            let kept = info.content_tokens.saturating_sub(*omitted_tokens);
+            0.9 * f64::from(kept) / f64::from(info.content_tokens)
+        }
+        RenderDecision::Summary => 0.4,
+        RenderDecision::Outline => 0.3,
+        RenderDecision::Placeholder { .. } => 0.05,
+        RenderDecision::Truncated { .. } | RenderDecision::Omit => 0.0,
+    }
+}
+
+/// Weight of each block in the utility the allocator maximizes.
+///
+/// ```text
+/// weight = tier × (0.5 + relevance) × recency
+///
+/// tier:     High 8, Normal 4, Low 2, Background 1 (Critical is fixed)
+/// recency:  0.5 at the first block, rising linearly to 1.0 at the last
+/// ```
+///
+/// A tier outweighs any relevance and recency within the tier below, so
+/// the allocator only trades across tiers when the lower tier gains far
+/// more content for the same tokens.
+fn block_weights(infos: &[BlockBudgetInfo]) -> Vec<f64> {
+    #[allow(clippy::cast_precision_loss)]
+    let n = infos.len().max(1) as f64;
+    infos
+        .iter()
+        .enumerate()
+        .map(|(i, info)| {
+            let tier = match info.priority {
+                Priority::Critical | Priority::High => 8.0,
+                Priority::Normal => 4.0,
+                Priority::Low => 2.0,
+                Priority::Background => 1.0,
+            };
+            #[allow(clippy::cast_precision_loss)]
+            let recency = 0.5 + 0.5 * (i + 1) as f64 / n;
+            tier * (0.5 + f64::from(info.relevance)) * recency
+        })
+        .collect()
+}
+
+/// One way to render a block, with its token cost and value.
+struct Choice {
+    decision: RenderDecision,
+    cost: u32,
+    value: f64,
+}
+
+/// Every rendering a block's priority allows, cheapest first.
+///
+/// ```text
+/// ┌────────────┬─────────────────────────────────────────────────┐
+/// │ Priority   │ Choices                                         │
+/// ├────────────┼─────────────────────────────────────────────────┤
+/// │ Critical   │ Full                                            │
+/// │ High       │ Summary, Outline, Full                          │
+/// │ Normal     │ Omit, Placeholder, Summary, Outline,            │
+/// │            │ Truncated ×3, Full                              │
+/// │ Low        │ Omit, Placeholder, Summary, Outline,            │
+/// │            │ Truncated ≤ half                                │
+/// │ Background │ Omit, Placeholder                               │
+/// └────────────┴─────────────────────────────────────────────────┘
+/// ```
+///
+/// Costs follow the greedy engine: each rendering costs what the
+/// [`BlockPricer`](crate::budget::BlockPricer) measured for it, and a
+/// truncation costs its kept lines plus the block's truncation
+/// overhead. The first choice is the block's floor.
+fn choices(info: &BlockBudgetInfo, block: &Block, estimator: &dyn TokenEstimator) -> Vec<Choice> {
+    let mut out = Vec::new();
+    let mut push = |decision: RenderDecision, cost: u32| {
+        let value = decision_value(&decision, info);
+        out.push(Choice {
+            decision,
+            cost,
+            value,
+        });
+    };
+
+    match info.priority {
+        Priority::Critical => push(RenderDecision::Full, info.full_tokens),
+        Priority::High => {
+            push(RenderDecision::Full, info.full_tokens);
+            if let Some(tokens) = info.summary_tokens.filter(|_| info.has_summary) {
+                push(RenderDecision::Summary, tokens);
+            }
+            if let Some(tokens) = info.outline_tokens {
+                push(RenderDecision::Outline, tokens);
+            }
+        }
+        Priority::Normal | Priority::Low => {
+            push(RenderDecision::Omit, 0);
+            push(
+                make_placeholder(block, info.content_tokens),
+                info.placeholder_tokens,
+            );
+            if let Some(tokens) = info.summary_tokens.filter(|_| info.has_summary) {
+                push(RenderDecision::Summary, tokens);
+            }
+            if let Some(tokens) = info.outline_tokens {
+                push(RenderDecision::Outline, tokens);
+            }
+            let steps: &[u32] = if info.priority == Priority::Low {
+                &TRUNCATION_STEPS[..2]
+            } else {
+                &TRUNCATION_STEPS
+            };
+            let mut last_kept = 0;
+            for step in steps {
+                #[allow(clippy::cast_possible_truncation)]
+                let allowance = (u64::from(info.content_tokens) * u64::from(*step) / 100) as u32;
+                match truncation(info, block, allowance, estimator) {
+                    Some((decision, kept)) if kept > last_kept => {
+                        last_kept = kept;
+                        push(decision, kept + info.truncation_overhead);
+                    }
+                    _ => {}
+                }
+            }
+            if info.priority == Priority::Normal {
+                push(RenderDecision::Full, info.full_tokens);
+            }
+        }
+        Priority::Background => {
+            push(RenderDecision::Omit, 0);
+            push(
+                make_placeholder(block, info.content_tokens),
+                info.placeholder_tokens,
+            );
+        }
+    }
+
+    out.sort_by(|a, b| a.cost.cmp(&b.cost).then(b.value.total_cmp(&a.value)));
+    out
+}
+
+/// A `Truncated` decision keeping at most `allowance` tokens, and the
+/// tokens it keeps.
+fn truncation(
+    info: &BlockBudgetInfo,
+    block: &Block,
+    allowance: u32,
+    estimator: &dyn TokenEstimator,
+) -> Option<(RenderDecision, u32)> {
+    let t = plan_truncation(block, allowance, estimator)?;
+    let decision = RenderDecision::Truncated {
+        head: t.head,
+        tail: t.tail,
+        omitted_tokens: info.content_tokens.saturating_sub(t.kept_tokens),
+    };
+    Some((decision, t.kept_tokens))
+}
+
+/// Optimizing budget allocation — the [`BudgetStrategy::Optimal`] path.
+///
+/// Returns decisions and reasons parallel to `infos`, like
+/// [`allocate_budget`].
+///
+/// 1. List each block's [`choices`]. Charge every block its floor, in
+///    priority order; whatever is left is the knapsack capacity.
+/// 2. Pick one choice per block to maximize the summed
+///    `weight × value` within that capacity: a multiple-choice knapsack
+///    solved by dynamic programming over capacity cells.
+/// 3. Upgrade pass: walking blocks by descending weight, spend the
+///    budget the knapsack left over (rounding and the fixed truncation
+///    steps leave some) on a fuller rendering — `Full` if it now fits,
+///    otherwise a wider truncation.
+/// 4. Score the greedy allocation the same way and return it instead if
+///    it scores at least as well.
+///
+/// An older conversation turn is one knapsack menu: its first member
+/// carries the [`turn_levels`] of the whole turn, priced and valued
+/// across all members, and the other members have none of their own.
+/// A dependency group is one menu of its [`group_levels`] the same
+/// way. The upgrade pass leaves older turns and groups alone.
+///
+/// [`BudgetStrategy::Optimal`]: crate::config::BudgetStrategy::Optimal
+pub(crate) fn allocate_optimal(
+    infos: &[BlockBudgetInfo],
+    budget: u32,
+    filtered: &[&Block],
+    estimator: &dyn TokenEstimator,
+) -> (Vec<RenderDecision>, Vec<DecisionReason>) {
+    let weights = block_weights(infos);
+    let mut menus: Vec<Vec<Choice>> = infos
+        .iter()
+        .zip(filtered)
+        .map(|(info, block)| choices(info, block, estimator))
+        .collect();
+    let units = unit_menus(&mut menus, infos, filtered, &weights);
+
+    // Floors first, Critical down to Background, so a High block whose
+    // floor does not fit is forced over budget in full, the way the
+    // greedy path forces it.
+    let mut order: Vec<usize> = (0..infos.len()).collect();
+    order.sort_by_key(|&i| infos[i].priority);
+    let mut capacity = budget;
+    let mut forced = vec![false; infos.len()];
+    for &i in &order {
+        if infos[i].priority == Priority::High && menus[i][0].cost > capacity {
+            menus[i].retain(|c| c.decision == RenderDecision::Full);
+            forced[i] = true;
+        }
+        capacity = capacity.saturating_sub(menus[i][0].cost);
+    }
+
+    let picks = knapsack(&menus, &weights, capacity);
+    let mut decisions: Vec<RenderDecision> = Vec::with_capacity(infos.len());
+    let mut spent = 0;
+    for (menu, &pick) in menus.iter().zip(&picks) {
+        spent += menu[pick].cost - menu[0].cost;
+        decisions.push(menu[pick].decision.clone());
+    }
+    for (members, mut levels) in units {
+        // A forced High group's menu was cut down to its Full level.
+        let pick = if forced[members[0]] {
+            levels
+                .iter()
+                .position(|level| level.decisions[0] == RenderDecision::Full)
+                .unwrap_or(0)
+        } else {
+            picks[members[0]]
+        };
+        let level = levels.swap_remove(pick);
+        for (&member, decision) in members.iter().zip(level.decisions) {
+            decisions[member] = decision;
+            forced[member] = forced[members[0]];
+        }
+    }
+    let leftover = capacity.saturating_sub(spent);
+    upgrade(
+        &mut decisions,
+        infos,
+        filtered,
+        &weights,
+        leftover,
+        estimator,
+    );
+
+    let reasons = infos
+        .iter()
+        .zip(&decisions)
+        .zip(&forced)
+        .map(|((info, decision), &forced)| reason(info, decision, forced))
+        .collect();
+
+    let greedy = allocate_budget(infos, budget, filtered, estimator);
+    if utility(&greedy.0, infos, &weights) >= utility(&decisions, infos, &weights) {
+        return greedy;
+    }
+    (decisions, reasons)
+}
+
+/// Replace the menus of the members of each older conversation turn and
+/// each dependency group with one menu for the whole unit, on its first
+/// member.
+///
+/// Returns each unit's members with its levels, in menu order, so the
+/// picks can be spread back over the members.
+fn unit_menus(
+    menus: &mut [Vec<Choice>],
+    infos: &[BlockBudgetInfo],
+    filtered: &[&Block],
+    weights: &[f64],
+) -> Vec<(Vec<usize>, Vec<TurnLevel>)> {
+    let turns: Vec<Option<Turn>> = infos.iter().map(|info| info.turn).collect();
+    let mut out = Vec::new();
+    for (i, info) in infos.iter().enumerate() {
+        let members = match (info.turn, info.group) {
+            (Some(Turn::Older(n)), _) => turn_members(&turns, n),
+            (_, Some(first)) if first == i => group_members(infos, first),
+            _ => continue,
+        };
+        if members[0] != i {
+            continue;
+        }
+        // Knapsack menus start at their cheapest choice.
+        let mut levels = if info.group.is_some() {
+            group_levels(&members, infos, filtered)
+        } else {
+            turn_levels(&members, infos, filtered)
+        };
+        levels.sort_by_key(|level| level.cost);
+        menus[i] = levels
+            .iter()
+            .map(|level| {
+                let value: f64 = members
+                    .iter()
+                    .zip(&level.decisions)
+                    .map(|(&m, d)| weights[m] * decision_value(d, &infos[m]))
+                    .sum();
+                Choice {
+                    decision: level.decisions[0].clone(),
+                    cost: level.cost,
+                    value: value / weights[i],
+                }
+            })
+            .collect();
+        for &m in &members[1..] {
+            menus[m] = vec![Choice {
+                decision: RenderDecision::Omit,
+                cost: 0,
+                value: 0.0,
+            }];
+        }
+        out.push((members, levels));
+    }
+    out
+}
+
+/// Multiple-choice knapsack: the index of one choice per menu that
+/// maximizes total weighted value gained over the floors, within
+/// `capacity` tokens of upgrades.
+fn knapsack(menus: &[Vec<Choice>], weights: &[f64], capacity: u32) -> Vec<usize> {
+    let unit = capacity.div_ceil(MAX_CELLS).max(1);
+    let cells = (capacity / unit) as usize;
+    let units = |menu: &[Choice], c: usize| (menu[c].cost - menu[0].cost).div_ceil(unit) as usize;
+
+    // best[w]: highest gain with at most w cells spent on the menus so
+    // far. picks[i][w]: the choice menu i took to reach best[w].
+    let mut best = vec![0.0_f64; cells + 1];
+    let mut picks: Vec<Vec<u8>> = Vec::with_capacity(menus.len());
+    for (menu, weight) in menus.iter().zip(weights) {
+        let mut next = best.clone();
+        let mut pick = vec![0_u8; cells + 1];
+        for c in 1..menu.len() {
+            let gain = weight * (menu[c].value - menu[0].value);
+            let cost = units(menu, c);
+            if gain <= 0.0 || cost > cells {
+                continue;
+            }
+            for w in cost..=cells {
+                let candidate = best[w - cost] + gain;
+                if candidate > next[w] {
+                    next[w] = candidate;
+                    #[allow(clippy::cast_possible_truncation)]
+                    {
+                        pick[w] = c as u8;
+                    }
+                }
+            }
+        }
+        best = next;
+        picks.push(pick);
+    }
+
+    let mut out = vec![0; menus.len()];
+    let mut w = cells;
+    for i in (0..menus.len()).rev() {
+        let c = usize::from(picks[i][w]);
+        out[i] = c;
+        w -= if c == 0 { 0 } else { units(&menus[i], c) };
+    }
+    out
+}
+
+/// Spend `leftover` tokens widening the heaviest cut-down blocks.
+fn upgrade(
+    decisions: &mut [RenderDecision],
+    infos: &[BlockBudgetInfo],
+    filtered: &[&Block],
+    weights: &[f64],
+    mut leftover: u32,
+    estimator: &dyn TokenEstimator,
+) {
+    let mut order: Vec<usize> = (0..decisions.len()).collect();
+    order.sort_by(|&a, &b| weights[b].total_cmp(&weights[a]));
+
+    for i in order {
+        let info = &infos[i];
+        if !matches!(info.priority, Priority::Normal | Priority::Low)
+            || info.turn.is_some()
+            || info.group.is_some()
+            || decisions[i] == RenderDecision::Full
+        {
+            continue;
+        }
+        let cost = decision_cost(&decisions[i], info);
+        let available = cost + leftover;
+        if info.priority == Priority::Normal && info.full_tokens <= available {
+            leftover = available - info.full_tokens;
+            decisions[i] = RenderDecision::Full;
+            continue;
+        }
+        let cap = if info.priority == Priority::Low {
+            info.content_tokens / 2
+        } else {
+            u32::MAX
+        };
+        let Some(allowance) = available.checked_sub(info.truncation_overhead) else {
+            continue;
+        };
+        if let Some((decision, kept)) = truncation(info, filtered[i], allowance.min(cap), estimator)
+            && decision_value(&decision, info) > decision_value(&decisions[i], info)
+        {
+            leftover = available - kept - info.truncation_overhead;
+            decisions[i] = decision;
+        }
+    }
+}
+
+/// Tokens a Normal or Low block's decision is charged, as in
+/// [`choices`].
+fn decision_cost(decision: &RenderDecision, info: &BlockBudgetInfo) -> u32 {
+    match decision {
+        RenderDecision::Full => info.full_tokens,
+        RenderDecision::Summary => info.summary_tokens.unwrap_or(0),
+        RenderDecision::Outline => info.outline_tokens.unwrap_or(0),
+        RenderDecision::Truncated { omitted_tokens, .. } => {
+            info.content_tokens.saturating_sub(*omitted_tokens) + info.truncation_overhead
+        }
+        RenderDecision::Placeholder { .. } => info.placeholder_tokens,
+        RenderDecision::Omit => 0,
+    }
+}
+
+/// Summed `weight × value` of an allocation.
+fn utility(decisions: &[RenderDecision], infos: &[BlockBudgetInfo], weights: &[f64]) -> f64 {
+    decisions
+        .iter()
+        .zip(infos)
+        .zip(weights)
+        .map(|((decision, info), weight)| weight * decision_value(decision, info))
+        .sum()
+}
+
+/// The [`DecisionReason`] for a decision the optimizer chose, using the
+/// same vocabulary as the greedy path.
+fn reason(info: &BlockBudgetInfo, decision: &RenderDecision, forced: bool) -> DecisionReason {
+    // Capped by priority rather than by what was left of the budget.
+    let capped = match info.priority {
+        Priority::Low => {
+            *decision == RenderDecision::Summary
+                || (!info.has_summary
+                    && (*decision == RenderDecision::Outline || info.outline_tokens.is_none()))
+        }
+        Priority::Background => matches!(decision, RenderDecision::Placeholder { .. }),
+        _ => false,
+    };
+    match (info.priority, decision) {
+        (Priority::Critical, _) if info.turn == Some(Turn::Kept) => DecisionReason::KeptTurn,
+        (Priority::Critical, _) => DecisionReason::Critical,
+        (_, RenderDecision::Full) if forced => DecisionReason::ForcedOverBudget,
+        (_, RenderDecision::Full) => DecisionReason::WithinBudget,
+        _ if capped => DecisionReason::LowPriority,
+        _ => DecisionReason::OverBudget,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::HeuristicEstimator;
+    use crate::test_support::code_block;
+    use bcp_types::enums::Lang;
+
+    /// A code block of `lines` 8-char lines: 2 heuristic tokens each.
+    fn code(path: &str, lines: usize) -> Block {
+        let content = (0..lines)
+            .map(|n| format!("x{n:07}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        code_block(Lang::Rust, path, content)
+    }
+
+    /// Content-only costs, with a flat 10 tokens of framing for
+    /// placeholders and truncations.
+    fn info(priority: Priority, block: &Block) -> BlockBudgetInfo {
+        let text = crate::budget::estimate_block_text(block);
+        let tokens = HeuristicEstimator.estimate(&text);
+        BlockBudgetInfo {
+            priority,
+            relevance: crate::budget::NEUTRAL_RELEVANCE,
+            full_tokens: tokens,
+            summary_tokens: None,
+            outline_tokens: None,
+            placeholder_tokens: 10,
+            content_tokens: tokens,
+            truncation_overhead: 10,
+            has_summary: false,
+            turn: None,
+            group: None,
+        }
+    }
+
+    #[test]
+    fn small_blocks_are_not_crowded_out_by_a_large_one() {
+        let blocks = [
+            code("big.rs", 45),
+            code("a.rs", 15),
+            code("b.rs", 15),
+            code("c.rs", 15),
+        ];
+        let filtered: Vec<&Block> = blocks.iter().collect();
+        let infos: Vec<_> = blocks.iter().map(|b| info(Priority::Normal, b)).collect();
+        let budget = infos[0].full_tokens + 5;
+
+        let (greedy, _) = allocate_budget(&infos, budget, &filtered, &HeuristicEstimator);
+        assert_eq!(greedy[0], RenderDecision::Full);
+        assert_eq!(greedy[1], RenderDecision::Omit);
+
+        let (optimal, reasons) = allocate_optimal(&infos, budget, &filtered, &HeuristicEstimator);
+        assert_eq!(optimal[0], RenderDecision::Omit);
+        assert!(optimal[1..].iter().all(|d| *d == RenderDecision::Full));
+        assert_eq!(reasons[1], DecisionReason::WithinBudget);
+        assert_eq!(reasons[0], DecisionReason::OverBudget);
+    }
+
+    #[test]
+    fn leftover_budget_widens_truncation() {
+        let blocks = [code("big.rs", 100)];
+        let filtered: Vec<&Block> = blocks.iter().collect();
+        let infos = [info(Priority::Normal, &blocks[0])];
+        let budget = 150;
+
+        let (decisions, _) = allocate_optimal(&infos, budget, &filtered, &HeuristicEstimator);
+        let RenderDecision::Truncated { omitted_tokens, .. } = decisions[0] else {
+            panic!("expected Truncated, got {:?}", decisions[0]);
+        };
+        let cost = decision_cost(&decisions[0], &infos[0]);
+        assert!(cost <= budget && cost + 2 >= budget, "cost = {cost}");
+        assert!(omitted_tokens > 0);
+    }
+
+    #[test]
+    fn priority_rules_hold() {
+        let blocks = [code("crit.rs", 40), code("high.rs", 40), code("low.rs", 40)];
+        let filtered: Vec<&Block> = blocks.iter().collect();
+        let infos = [
+            info(Priority::Critical, &blocks[0]),
+            info(Priority::High, &blocks[1]),
+            info(Priority::Low, &blocks[2]),
+        ];
+
+        let (decisions, reasons) =
+            allocate_optimal(&infos, infos[0].full_tokens, &filtered, &HeuristicEstimator);
+        assert_eq!(decisions[0], RenderDecision::Full);
+        assert_eq!(reasons[0], DecisionReason::Critical);
+        assert_eq!(decisions[1], RenderDecision::Full);
+        assert_eq!(reasons[1], DecisionReason::ForcedOverBudget);
+        assert_eq!(decisions[2], RenderDecision::Omit);
+
+        // With room to spare, a Low block still gets at most half.
+        let (decisions, _) = allocate_optimal(&infos, 10_000, &filtered, &HeuristicEstimator);
+        let RenderDecision::Truncated { omitted_tokens, .. } = decisions[2] else {
+            panic!("expected Truncated, got {:?}", decisions[2]);
+        };
+        assert!(omitted_tokens >= infos[2].full_tokens / 2);
+    }
+
+    #[test]
+    fn never_scores_below_greedy() {
+        let blocks: Vec<Block> = [30, 5, 60, 12, 8, 25, 40, 3]
+            .iter()
+            .enumerate()
+            .map(|(i, &lines)| code(&format!("f{i}.rs"), lines))
+            .collect();
+        let filtered: Vec<&Block> = blocks.iter().collect();
+        let priorities = [
+            Priority::High,
+            Priority::Normal,
+            Priority::Normal,
+            Priority::Low,
+            Priority::Background,
+            Priority::Normal,
+            Priority::Normal,
+            Priority::Background,
+        ];
+        let infos: Vec<_> = blocks
+            .iter()
+            .zip(priorities)
+            .map(|(b, p)| info(p, b))
+            .collect();
+        let weights = block_weights(&infos);
+
+        for budget in [0, 20, 75, 120, 200, 350, 1_000] {
+            let (greedy, _) = allocate_budget(&infos, budget, &filtered, &HeuristicEstimator);
+            let (optimal, _) = allocate_optimal(&infos, budget, &filtered, &HeuristicEstimator);
+            assert!(
+                utility(&optimal, &infos, &weights) >= utility(&greedy, &infos, &weights),
+                "budget {budget}"
+            );
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use bcp_types::BlockType;
+use bcp_types::block::Block;
+use bcp_types::enums::Priority;
+
+use crate::budget::{NEUTRAL_RELEVANCE, resolve_priorities, resolve_relevance_with_query};
+use crate::config::BlockOrdering;
+
+/// Output order of `filtered` under `ordering`: the positions in
+/// `filtered` of its blocks, in the order they render.
+///
+/// Priorities and relevance come from the ANNOTATION blocks in
+/// `all_blocks`, keyed by `original_indices`, with `query_scores`
+/// filling in for missing `Relevance` annotations as in the budget
+/// engine. Sorts are stable, so ties keep payload order.
+///
+/// `Edges` ranks blocks by importance, then deals them out to the two
+/// ends of the output in turn:
+///
+/// ```text
+/// rank:    0  1  2  3  4  5
+/// output:  0  2  4  5  3  1
+/// ```
+pub(crate) fn block_order(
+    ordering: BlockOrdering,
+    all_blocks: &[Block],
+    filtered: &[&Block],
+    original_indices: &[usize],
+    query_scores: Option<&[f32]>,
+) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..filtered.len()).collect();
+    if ordering == BlockOrdering::Payload {
+        return order;
+    }
+
+    let priorities = resolve_priorities(all_blocks);
+    let relevance = resolve_relevance_with_query(all_blocks, original_indices, query_scores);
+    #[allow(clippy::cast_possible_truncation)]
+    let priority = |i: usize| {
+        priorities
+            .get(&(original_indices[i] as u32))
+            .copied()
+            .unwrap_or(Priority::Normal)
+    };
+    #[allow(clippy::cast_possible_truncation)]
+    let relevance = |i: usize| {
+        relevance
+            .get(&(original_indices[i] as u32))
+            .copied()
+            .unwrap_or(NEUTRAL_RELEVANCE)
+    };
+
+    let by_importance = |&a: &usize, &b: &usize| {
+        priority(a)
+            .cmp(&priority(b))
+            .then_with(|| relevance(b).total_cmp(&relevance(a)))
+    };
+
+    match ordering {
+        BlockOrdering::Payload => {}
+        BlockOrdering::PriorityFirst => order.sort_by(by_importance),
+        BlockOrdering::Edges => {
+            order.sort_by(by_importance);
+            let (front, back): (Vec<_>, Vec<_>) = order
+                .into_iter()
+                .enumerate()
+                .partition(|(rank, _)| rank % 2 == 0);
+            order = front
+                .into_iter()
+                .chain(back.into_iter().rev())
+                .map(|(_, i)| i)
+                .collect();
+        }
+        BlockOrdering::ByType => order.sort_by_key(|&i| type_rank(&filtered[i].block_type)),
+    }
+    order
+}
+
+/// Group of a block type under `BlockOrdering::ByType`: file trees,
+/// code, diffs, tool output, then everything else.
+fn type_rank(block_type: &BlockType) -> u8 {
+    match block_type {
+        BlockType::FileTree => 0,
+        BlockType::Code => 1,
+        BlockType::Diff => 2,
+        BlockType::ToolResult => 3,
+        _ => 4,
+    }
+}
+
+/// `items` rearranged into `order`.
+pub(crate) fn permute<T: Clone>(items: &[T], order: &[usize]) -> Vec<T> {
+    order.iter().map(|&i| items[i].clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{block, code_block, priority_annotation};
+    use bcp_types::block::BlockContent;
+    use bcp_types::diff::DiffBlock;
+    use bcp_types::enums::{DiffStatus, Lang};
+
+    fn code(path: &str) -> Block {
+        code_block(Lang::Rust, path, "fn f() {}")
+    }
+
+    /// Blocks 0..4 with priorities Low, Critical, Normal, High.
+    fn prioritized() -> Vec<Block> {
+        vec![
+            code("a.rs"),
+            code("b.rs"),
+            code("c.rs"),
+            code("d.rs"),
+            priority_annotation(0, Priority::Low),
+            priority_annotation(1, Priority::Critical),
+            priority_annotation(3, Priority::High),
+        ]
+    }
+
+    #[test]
+    fn priority_first_and_edges() {
+        let blocks = prioritized();
+        let filtered: Vec<&Block> = blocks[..4].iter().collect();
+        let order = |ordering| block_order(ordering, &blocks, &filtered, &[0, 1, 2, 3], None);
+
+        assert_eq!(order(BlockOrdering::Payload), [0, 1, 2, 3]);
+        assert_eq!(order(BlockOrdering::PriorityFirst), [1, 3, 2, 0]);
+        // Ranked Critical, High, Normal, Low: the two most important
+        // open and close the output.
+        assert_eq!(order(BlockOrdering::Edges), [1, 2, 0, 3]);
+
+        // Query scores break ties within a tier.
+        let equal = [code("a.rs"), code("b.rs"), code("c.rs")];
+        let filtered: Vec<&Block> = equal.iter().collect();
+        let scores = [0.1, 0.2, 1.0];
+        let edges = block_order(
+            BlockOrdering::Edges,
+            &equal,
+            &filtered,
+            &[0, 1, 2],
+            Some(&scores),
+        );
+        assert_eq!(edges, [2, 0, 1]);
+    }
+
+    #[test]
+    fn priority_first_breaks_ties_by_relevance() {
+        let blocks = [code("a.rs"), code("b.rs"), code("c.rs")];
+        let filtered: Vec<&Block> = blocks.iter().collect();
+        let scores = [0.2, 1.0, 0.5];
+        let order = block_order(
+            BlockOrdering::PriorityFirst,
+            &blocks,
+            &filtered,
+            &[0, 1, 2],
+            Some(&scores),
+        );
+        assert_eq!(order, [1, 2, 0]);
+    }
+
+    #[test]
+    fn by_type_groups_blocks() {
+        let diff = block(
+            BlockType::Diff,
+            BlockContent::Diff(DiffBlock {
+                path: "a.rs".to_string(),
+                hunks: Vec::new(),
+                old_path: None,
+                status: DiffStatus::Modified,
+            }),
+        );
+        let blocks = [diff, code("a.rs"), code("b.rs")];
+        let filtered: Vec<&Block> = blocks.iter().collect();
+        assert_eq!(
+            block_order(BlockOrdering::ByType, &blocks, &filtered, &[0, 1, 2], None),
+            [1, 2, 0]
+        );
+        assert_eq!(permute(&["diff", "a", "b"], &[1, 2, 0]), ["a", "b", "diff"]);
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+use bcp_decoder::BcpDecoder;
+use bcp_types::block::Block;
+use bcp_types::content_store::ContentStore;
+use bcp_wire::header::BcpHeader;
+
+use crate::config::DriverConfig;
+use crate::driver::{DefaultDriver, RenderPlan};
+use crate::error::PayloadError;
+use crate::report::{RenderOutput, RenderReport};
+
+/// Configuration for [`DefaultDriver::decode`] — the RFC §5.3
+/// `DecodeConfig`.
+///
+/// ```text
+/// ┌───────────────┬────────────────────────────────────────────────────┐
+/// │ Field         │ Purpose                                            │
+/// ├───────────────┼────────────────────────────────────────────────────┤
+/// │ driver        │ How the decoded blocks are rendered                │
+/// │ content_store │ Resolves IS_REFERENCE blocks; None = no references │
+/// └───────────────┴────────────────────────────────────────────────────┘
+/// ```
+///
+/// Without a content store, a payload containing reference blocks fails
+/// with `DecodeError::MissingContentStore`.
+#[derive(Default)]
+pub struct DecodeConfig {
+    /// Rendering configuration applied to the decoded blocks.
+    pub driver: DriverConfig,
+
+    /// Store used to resolve content-addressed (`IS_REFERENCE`) blocks.
+    pub content_store: Option<Arc<dyn ContentStore>>,
+}
+
+/// A decoded and rendered payload.
+///
+/// ```text
+/// ┌────────────────────────────────────────────────────────────┐
+/// │ RenderedPayload                                            │
+/// │   text:   String       ← model-ready output                │
+/// │   report: RenderReport ← per-block decisions and tokens    │
+/// │   header: BcpHeader    ← version, flags                    │
+/// │   blocks: Vec<Block>   ← decoded blocks, no END            │
+/// └────────────────────────────────────────────────────────────┘
+/// ```
+#[derive(Debug)]
+pub struct RenderedPayload {
+    /// Rendered text, exactly what [`BcpDriver::render`] produces for
+    /// `blocks`.
+    ///
+    /// [`BcpDriver::render`]: crate::BcpDriver::render
+    pub text: String,
+
+    /// How each block was rendered, as from
+    /// [`BcpDriver::render_with_report`](crate::BcpDriver::render_with_report).
+    pub report: RenderReport,
+
+    /// The payload's file header.
+    pub header: BcpHeader,
+
+    /// Every decoded block in wire order, annotations included,
+    /// excluding the END sentinel.
+    pub blocks: Vec<Block>,
+}
+
+impl DefaultDriver {
+    /// Decode a complete payload and render it in one call — the RFC
+    /// §5.2 `decode`.
+    ///
+    /// Runs [`BcpDecoder::decode_with_store`] when `config.content_store`
+    /// is set and [`BcpDecoder::decode`] otherwise, then renders the
+    /// blocks with `config.driver`.
+    ///
+    /// # Errors
+    ///
+    /// - `PayloadError::Decode` if the payload is malformed, or a
+    ///   reference block cannot be resolved.
+    /// - `PayloadError::Render` if no renderable blocks remain after
+    ///   filtering, or a block contains non-UTF-8 bytes.
+    pub fn decode(payload: &[u8], config: &DecodeConfig) -> Result<RenderedPayload, PayloadError> {
+        let decoded = match &config.content_store {
+            Some(store) => BcpDecoder::decode_with_store(payload, store.as_ref())?,
+            None => BcpDecoder::decode(payload)?,
+        };
+        let RenderOutput { text, report } =
+            RenderPlan::new(&decoded.blocks, &config.driver)?.render_with_report(&config.driver)?;
+        Ok(RenderedPayload {
+            text,
+            report,
+            header: decoded.header,
+            blocks: decoded.blocks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BcpDriver;
+    use crate::error::DriverError;
+    use bcp_decoder::DecodeError;
+    use bcp_encoder::{BcpEncoder, MemoryContentStore};
+    use bcp_types::BlockType;
+    use bcp_types::enums::{Lang, Role};
+
+    #[test]
+    fn decode_matches_decode_then_render() {
+        let payload = BcpEncoder::new()
+            .add_code(Lang::Rust, "src/main.rs", b"fn main() {}")
+            .with_summary("Entry point.")
+            .unwrap()
+            .add_conversation(Role::User, b"Fix the timeout")
+            .encode()
+            .unwrap();
+        let config = DecodeConfig::default();
+
+        let out = DefaultDriver::decode(&payload, &config).unwrap();
+        let blocks = BcpDecoder::decode(&payload).unwrap().blocks;
+        let expected = DefaultDriver.render(&blocks, &config.driver).unwrap();
+
+        assert_eq!(out.text, expected);
+        assert_eq!(out.blocks.len(), 2);
+        assert_eq!(out.report.blocks.len(), 2);
+    }
+
+    #[test]
+    fn decode_resolves_references_through_store() {
+        let store = Arc::new(MemoryContentStore::new());
+        let payload = BcpEncoder::new()
+            .set_content_store(store.clone())
+            .add_code(Lang::Rust, "lib.rs", b"pub fn shared() {}")
+            .with_content_addressing()
+            .unwrap()
+            .encode()
+            .unwrap();
+
+        let err = DefaultDriver::decode(&payload, &DecodeConfig::default()).unwrap_err();
+        assert!(matches!(
+            err,
+            PayloadError::Decode(DecodeError::MissingContentStore)
+        ));
+
+        let config = DecodeConfig {
+            content_store: Some(store),
+            ..DecodeConfig::default()
+        };
+        let out = DefaultDriver::decode(&payload, &config).unwrap();
+        assert!(out.text.contains("pub fn shared() {}"));
+    }
+
+    #[test]
+    fn decode_reports_render_errors() {
+        let payload = BcpEncoder::new()
+            .add_conversation(Role::User, b"hello")
+            .encode()
+            .unwrap();
+        let config = DecodeConfig {
+            driver: DriverConfig {
+                include_types: Some(vec![BlockType::Code]),
+                ..DriverConfig::default()
+            },
+            ..DecodeConfig::default()
+        };
+        let err = DefaultDriver::decode(&payload, &config).unwrap_err();
+        assert!(matches!(err, PayloadError::Render(DriverError::EmptyInput)));
+    }
+}
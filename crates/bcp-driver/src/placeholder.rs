@@ -19,6 +19,7 @@ use crate::config::OutputMode;
 /// │ Xml      │ <omitted type="code" desc="src/main.rs" tokens="823"/>│
 /// │ Markdown │ _[Omitted: code src/main.rs, ~823 tokens]_            │
 /// │ Minimal  │ [omitted: code src/main.rs ~823tok]                   │
+/// │ Raw      │ # omitted: code src/main.rs ~823tok                   │
 /// └──────────┴────────────────────────────────────────────────────────┘
 /// ```
 ///
@@ -42,6 +43,78 @@ pub(crate) fn render_placeholder(
         OutputMode::Minimal => {
             format!("[omitted: {type_label} {description} ~{omitted_tokens}tok]")
         }
+        OutputMode::Raw(_) => {
+            format!("# omitted: {type_label} {description} ~{omitted_tokens}tok")
+        }
+    }
+}
+
+/// Render the marker that stands in for the lines a truncated block
+/// dropped.
+///
+/// `first` and `last` are the 1-based, inclusive line numbers elided.
+/// Text modes place the marker inside the block body; Raw mode emits it
+/// as a comment line after the block's frame.
+///
+/// ```text
+/// ┌──────────┬────────────────────────────────────────────────────────┐
+/// │ Mode     │ Output                                                 │
+/// ├──────────┼────────────────────────────────────────────────────────┤
+/// │ Xml      │ <elided lines="41-1960" tokens="823" />               │
+/// │ Markdown │ [... lines 41-1960 elided, ~823 tokens ...]           │
+/// │ Minimal  │ [elided: lines 41-1960 ~823tok]                       │
+/// │ Raw      │ # elided: lines 41-1960 ~823tok                       │
+/// └──────────┴────────────────────────────────────────────────────────┘
+/// ```
+pub(crate) fn render_elision(
+    mode: OutputMode,
+    first: u32,
+    last: u32,
+    omitted_tokens: u32,
+) -> String {
+    match mode {
+        OutputMode::Xml => {
+            format!("<elided lines=\"{first}-{last}\" tokens=\"{omitted_tokens}\" />")
+        }
+        OutputMode::Markdown => {
+            format!("[... lines {first}-{last} elided, ~{omitted_tokens} tokens ...]")
+        }
+        OutputMode::Minimal => format!("[elided: lines {first}-{last} ~{omitted_tokens}tok]"),
+        OutputMode::Raw(_) => format!("# elided: lines {first}-{last} ~{omitted_tokens}tok"),
+    }
+}
+
+/// Render the back-reference that stands in for lines repeated from
+/// another block (see [`dedup_blocks`](crate::dedup::dedup_blocks)).
+///
+/// `block_type` and `description` name the block the lines can be read
+/// in, and `first` and `last` the 1-based, inclusive lines there.
+///
+/// ```text
+/// ┌──────────┬────────────────────────────────────────────────────────┐
+/// │ Mode     │ Output                                                 │
+/// ├──────────┼────────────────────────────────────────────────────────┤
+/// │ Xml      │ <see ref="code src/main.rs" lines="10-40" />           │
+/// │ Markdown │ [... see code src/main.rs L10-40 ...]                  │
+/// │ Minimal  │ [see code src/main.rs L10-40]                          │
+/// │ Raw      │ # see code src/main.rs L10-40                          │
+/// └──────────┴────────────────────────────────────────────────────────┘
+/// ```
+pub(crate) fn render_reference(
+    mode: OutputMode,
+    block_type: &BlockType,
+    description: &str,
+    first: u32,
+    last: u32,
+) -> String {
+    let type_label = block_type_label(block_type);
+    match mode {
+        OutputMode::Xml => {
+            format!("<see ref=\"{type_label} {description}\" lines=\"{first}-{last}\" />")
+        }
+        OutputMode::Markdown => format!("[... see {type_label} {description} L{first}-{last} ...]"),
+        OutputMode::Minimal => format!("[see {type_label} {description} L{first}-{last}]"),
+        OutputMode::Raw(_) => format!("# see {type_label} {description} L{first}-{last}"),
     }
 }
 
@@ -78,4 +151,13 @@ mod tests {
             "<omitted type=\"tool-result\" desc=\"ripgrep\" tokens=\"150\" />"
         );
     }
+    #[test]
+    fn reference_formats() {
+        let render = |mode| render_reference(mode, &BlockType::Code, "src/main.rs", 10, 40);
+        assert_eq!(
+            render(OutputMode::Xml),
+            "<see ref=\"code src/main.rs\" lines=\"10-40\" />"
+        );
+        assert_eq!(render(OutputMode::Minimal), "[see code src/main.rs L10-40]");
+    }
 }
@@ -0,0 +1,218 @@
+use crate::budget::RatioEstimator;
+use crate::config::{ModelFamily, OutputMode};
+
+/// Per-family rendering defaults, selected by `DriverConfig::target_model`.
+///
+/// A profile fills in whatever the config leaves open: the output mode
+/// when `mode` is `None`, the token estimator when `estimator` is
+/// `None`, and — in XML mode — the element names and whether block
+/// metadata is written as attributes or nested child elements.
+///
+/// ```text
+/// ┌─────────┬──────────┬─────────────┬────────────┬──────────────────┐
+/// │ Family  │ Mode     │ Tags        │ XML style  │ Chars/token      │
+/// │         │          │             │            │ (code / prose)   │
+/// ├─────────┼──────────┼─────────────┼────────────┼──────────────────┤
+/// │ Claude  │ Xml      │ COMPACT     │ Attributes │ 3.0 / 3.5        │
+/// │ Gpt     │ Markdown │ DESCRIPTIVE │ Nested     │ 3.5 / 4.0        │
+/// │ Gemini  │ Xml      │ DESCRIPTIVE │ Nested     │ 3.5 / 4.0        │
+/// │ Generic │ Xml      │ COMPACT     │ Attributes │ 3.0 / 4.0        │
+/// └─────────┴──────────┴─────────────┴────────────┴──────────────────┘
+/// ```
+///
+/// `Generic` reproduces the driver's behavior with no family set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ModelProfile {
+    /// Output mode used when `DriverConfig::mode` is `None`.
+    pub mode: OutputMode,
+    /// Element names for XML output.
+    pub tags: TagVocabulary,
+    /// How XML output carries block metadata.
+    pub xml_style: XmlStyle,
+    /// Characters per token for code-like text.
+    pub code_chars_per_token: f32,
+    /// Characters per token for prose.
+    pub prose_chars_per_token: f32,
+}
+
+impl ModelProfile {
+    /// Token estimator using this profile's character ratios.
+    #[must_use]
+    pub fn estimator(&self) -> RatioEstimator {
+        RatioEstimator {
+            code_chars_per_token: self.code_chars_per_token,
+            prose_chars_per_token: self.prose_chars_per_token,
+        }
+    }
+}
+
+impl ModelFamily {
+    /// The rendering profile for this family.
+    #[must_use]
+    pub fn profile(self) -> ModelProfile {
+        match self {
+            Self::Claude => ModelProfile {
+                mode: OutputMode::Xml,
+                tags: TagVocabulary::COMPACT,
+                xml_style: XmlStyle::Attributes,
+                code_chars_per_token: 3.0,
+                prose_chars_per_token: 3.5,
+            },
+            Self::Gpt => ModelProfile {
+                mode: OutputMode::Markdown,
+                tags: TagVocabulary::DESCRIPTIVE,
+                xml_style: XmlStyle::Nested,
+                code_chars_per_token: 3.5,
+                prose_chars_per_token: 4.0,
+            },
+            Self::Gemini => ModelProfile {
+                mode: OutputMode::Xml,
+                tags: TagVocabulary::DESCRIPTIVE,
+                xml_style: XmlStyle::Nested,
+                code_chars_per_token: 3.5,
+                prose_chars_per_token: 4.0,
+            },
+            Self::Generic => ModelProfile {
+                mode: OutputMode::Xml,
+                tags: TagVocabulary::COMPACT,
+                xml_style: XmlStyle::Attributes,
+                code_chars_per_token: 3.0,
+                prose_chars_per_token: 4.0,
+            },
+        }
+    }
+}
+
+/// How the XML renderer attaches block metadata to an element.
+///
+/// ```text
+/// Attributes:  <code lang="rust" path="src/main.rs">
+///              fn main() {}
+///              </code>
+///
+/// Nested:      <code>
+///              <lang>rust</lang>
+///              <path>src/main.rs</path>
+///              <content>
+///              fn main() {}
+///              </content>
+///              </code>
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum XmlStyle {
+    /// Metadata as attributes on the block element (the default).
+    #[default]
+    Attributes,
+    /// Metadata as child elements, with the body in `<content>`.
+    Nested,
+}
+
+/// Element names the XML renderer uses, one per block type plus the
+/// root wrapper.
+///
+/// ```text
+/// ┌────────────────┬───────────┬─────────────┐
+/// │ Field          │ COMPACT   │ DESCRIPTIVE │
+/// ├────────────────┼───────────┼─────────────┤
+/// │ root           │ context   │ context     │
+/// │ code           │ code      │ file        │
+/// │ conversation   │ turn      │ message     │
+/// │ file_tree      │ tree      │ directory   │
+/// │ tool_result    │ tool      │ tool_output │
+/// │ document       │ doc       │ document    │
+/// │ structured_data│ data      │ data        │
+/// │ diff           │ diff      │ diff        │
+/// │ embedding_ref  │ embed-ref │ embedding   │
+/// │ image          │ image     │ image       │
+/// │ extension      │ ext       │ extension   │
+/// └────────────────┴───────────┴─────────────┘
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TagVocabulary {
+    pub root: &'static str,
+    pub code: &'static str,
+    pub conversation: &'static str,
+    pub file_tree: &'static str,
+    pub tool_result: &'static str,
+    pub document: &'static str,
+    pub structured_data: &'static str,
+    pub diff: &'static str,
+    pub embedding_ref: &'static str,
+    pub image: &'static str,
+    pub extension: &'static str,
+}
+
+impl TagVocabulary {
+    /// Short element names — the driver's default vocabulary.
+    pub const COMPACT: Self = Self {
+        root: "context",
+        code: "code",
+        conversation: "turn",
+        file_tree: "tree",
+        tool_result: "tool",
+        document: "doc",
+        structured_data: "data",
+        diff: "diff",
+        embedding_ref: "embed-ref",
+        image: "image",
+        extension: "ext",
+    };
+
+    /// Spelled-out element names for models that lean on tag semantics.
+    pub const DESCRIPTIVE: Self = Self {
+        root: "context",
+        code: "file",
+        conversation: "message",
+        file_tree: "directory",
+        tool_result: "tool_output",
+        document: "document",
+        structured_data: "data",
+        diff: "diff",
+        embedding_ref: "embedding",
+        image: "image",
+        extension: "extension",
+    };
+}
+
+impl Default for TagVocabulary {
+    fn default() -> Self {
+        Self::COMPACT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::{CodeAwareEstimator, TokenEstimator};
+
+    #[test]
+    fn generic_profile_matches_driver_defaults() {
+        let profile = ModelFamily::Generic.profile();
+        assert_eq!(profile.mode, OutputMode::Xml);
+        assert_eq!(profile.tags, TagVocabulary::default());
+        assert_eq!(profile.xml_style, XmlStyle::default());
+
+        let code = "fn main() {\n    let x = 1;\n    x\n}\n";
+        let prose = "The quick brown fox jumps over the lazy dog.";
+        for text in [code, prose] {
+            assert_eq!(
+                profile.estimator().estimate(text),
+                CodeAwareEstimator.estimate(text)
+            );
+        }
+    }
+
+    #[test]
+    fn families_differ_in_mode_and_ratio() {
+        assert_eq!(ModelFamily::Gpt.profile().mode, OutputMode::Markdown);
+        let prose = "a".repeat(70);
+        assert_eq!(
+            ModelFamily::Claude.profile().estimator().estimate(&prose),
+            20
+        );
+        assert_eq!(
+            ModelFamily::Gemini.profile().estimator().estimate(&prose),
+            17
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::{HashMap, HashSet};
+
+use bcp_types::block::Block;
+
+use crate::budget::{block_description, estimate_block_text};
+
+/// BM25 term-frequency saturation: how quickly repeats of a term stop
+/// adding to a block's score.
+const K1: f32 = 1.2;
+
+/// BM25 length normalization: how much a long block is discounted for
+/// containing more terms by chance.
+const B: f32 = 0.75;
+
+/// Weight of a query term found in a block's name — its path, tool
+/// name, title, … — relative to one BM25-saturated content match.
+const NAME_BOOST: f32 = 2.0;
+
+/// Query words too common to say anything about a block.
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "do", "does", "for", "from", "how", "in",
+    "is", "it", "of", "on", "or", "the", "this", "to", "what", "when", "where", "why", "with",
+];
+
+/// Score each block's relevance to a task query, in `0..=1`.
+///
+/// Runs locally, without model calls. Each block is scored as
+///
+/// ```text
+/// score = Σ over query terms t:
+///           idf(t) × tf × (K1 + 1) / (tf + K1 × (1 − B + B × len / avg_len))
+///         + idf(t) × NAME_BOOST        if t appears in the block's name
+/// ```
+///
+/// — BM25 over the block's content and summary, plus a bonus for terms
+/// in the name [`block_description`] gives it (a path, tool name or
+/// title). `idf` is computed over `blocks` themselves, so terms found
+/// in every block count for little. Scores are divided by the highest,
+/// so the best match scores 1; with no match anywhere, every block
+/// scores 0.
+///
+/// Terms are lowercase alphanumeric runs of two or more characters,
+/// split at `_` and at camelCase humps, so `parseConfig`,
+/// `parse_config` and `parse config` all match the query `config`.
+pub(crate) fn score_blocks(query: &str, blocks: &[&Block]) -> Vec<f32> {
+    let mut query_terms: Vec<String> = terms(query)
+        .into_iter()
+        .filter(|t| !STOPWORDS.contains(&t.as_str()))
+        .collect();
+    query_terms.sort();
+    query_terms.dedup();
+    if query_terms.is_empty() || blocks.is_empty() {
+        return vec![0.0; blocks.len()];
+    }
+
+    let docs: Vec<Vec<String>> = blocks.iter().map(|b| terms(&block_text(b))).collect();
+    let names: Vec<HashSet<String>> = blocks
+        .iter()
+        .map(|b| terms(&block_description(b)).into_iter().collect())
+        .collect();
+
+    #[allow(clippy::cast_precision_loss)]
+    let n = blocks.len() as f32;
+    #[allow(clippy::cast_precision_loss)]
+    let avg_len = (docs.iter().map(Vec::len).sum::<usize>() as f32 / n).max(1.0);
+
+    let idf: HashMap<&str, f32> = query_terms
+        .iter()
+        .map(|t| {
+            let df = docs
+                .iter()
+                .zip(&names)
+                .filter(|(doc, name)| name.contains(t) || doc.contains(t))
+                .count();
+            #[allow(clippy::cast_precision_loss)]
+            let df = df as f32;
+            (t.as_str(), (1.0 + (n - df + 0.5) / (df + 0.5)).ln())
+        })
+        .collect();
+
+    let scores: Vec<f32> = docs
+        .iter()
+        .zip(&names)
+        .map(|(doc, name)| {
+            #[allow(clippy::cast_precision_loss)]
+            let len = doc.len() as f32;
+            query_terms
+                .iter()
+                .map(|t| {
+                    let idf = idf[t.as_str()];
+                    #[allow(clippy::cast_precision_loss)]
+                    let tf = doc.iter().filter(|d| *d == t).count() as f32;
+                    let content = idf * tf * (K1 + 1.0) / (tf + K1 * (1.0 - B + B * len / avg_len));
+                    let boost = if name.contains(t) {
+                        idf * NAME_BOOST
+                    } else {
+                        0.0
+                    };
+                    content + boost
+                })
+                .sum()
+        })
+        .collect();
+
+    let best = scores.iter().copied().fold(0.0_f32, f32::max);
+    if best <= 0.0 {
+        return vec![0.0; blocks.len()];
+    }
+    scores.into_iter().map(|s| s / best).collect()
+}
+
+/// The text a block is searched by: its content and its summary.
+fn block_text(block: &Block) -> String {
+    let mut text = estimate_block_text(block);
+    if let Some(summary) = &block.summary {
+        text.push('\n');
+        text.push_str(&summary.text);
+    }
+    text
+}
+
+/// Split text into lowercase search terms.
+///
+/// Breaks at every non-alphanumeric character and where a lowercase
+/// letter or digit is followed by an uppercase one, then drops
+/// single-character terms.
+fn terms(text: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for c in text.chars() {
+        if !c.is_alphanumeric() || (prev_lower && c.is_uppercase()) {
+            if current.chars().nth(1).is_some() {
+                out.push(std::mem::take(&mut current));
+            }
+            current.clear();
+        }
+        if c.is_alphanumeric() {
+            current.extend(c.to_lowercase());
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+    }
+    if current.chars().nth(1).is_some() {
+        out.push(current);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{code_block, tool_result_block};
+    use bcp_types::enums::Lang;
+
+    #[test]
+    fn terms_split_identifiers() {
+        assert_eq!(
+            terms("fn parseConfig(raw_input: &str) -> HTTPResult<u8>"),
+            [
+                "fn",
+                "parse",
+                "config",
+                "raw",
+                "input",
+                "str",
+                "httpresult",
+                "u8"
+            ]
+        );
+    }
+
+    #[test]
+    fn content_and_name_matches_rank_blocks() {
+        let blocks = [
+            code_block(Lang::Rust, "src/main.rs", "fn main() { run(); }"),
+            code_block(
+                Lang::Rust,
+                "src/net/retry.rs",
+                "pub fn backoff() { sleep(delay); }",
+            ),
+            code_block(
+                Lang::Rust,
+                "src/client.rs",
+                "let timeout = Duration::from_secs(5); // timeout",
+            ),
+            tool_result_block("cargo test", "test client::connects ... ok"),
+        ];
+        let refs: Vec<&Block> = blocks.iter().collect();
+
+        let scores = score_blocks("why does the client timeout on retry?", &refs);
+        assert!(scores[0].abs() < f32::EPSILON, "{scores:?}");
+        assert!((scores[2] - 1.0).abs() < f32::EPSILON, "{scores:?}");
+        assert!(scores[1] > scores[3] && scores[3] > 0.0, "{scores:?}");
+
+        let scores = score_blocks("cargo test", &refs);
+        assert!((scores[3] - 1.0).abs() < f32::EPSILON, "{scores:?}");
+        assert_eq!(scores[..3], [0.0; 3]);
+    }
+
+    #[test]
+    fn empty_or_unmatched_query_scores_zero() {
+        let blocks = [
+            code_block(Lang::Rust, "a.rs", "alpha"),
+            code_block(Lang::Rust, "b.rs", "beta"),
+        ];
+        let refs: Vec<&Block> = blocks.iter().collect();
+        assert_eq!(score_blocks("the of and", &refs), [0.0, 0.0]);
+        assert_eq!(score_blocks("gamma", &refs), [0.0, 0.0]);
+    }
+}
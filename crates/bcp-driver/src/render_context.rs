@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+
+use bcp_types::block::{Block, BlockContent};
+use bcp_types::enums::{AnnotationKind, LinkKind};
+
+use crate::budget::{RenderDecision, block_description};
+use crate::config::{DriverConfig, OutputMode};
+use crate::profile::{TagVocabulary, XmlStyle};
+use crate::render_xml::xml_escape;
+
+/// Cross-block facts a renderer needs while rendering a single block.
+///
+/// Most blocks render in isolation, but some output depends on the rest
+/// of the payload. A `FILE_TREE` entry is annotated "included below" when
+/// the same file is also rendered as a CODE block, which requires knowing
+/// which CODE blocks survived budget allocation and where they sit in
+/// the output order.
+///
+/// ```text
+/// ┌─────────────────┬──────────────────────────────────────────────┐
+/// │ Field           │ Source                                       │
+/// ├─────────────────┼──────────────────────────────────────────────┤
+/// │ recent_since    │ DriverConfig::recent_since                   │
+/// │ included_paths  │ CODE blocks rendered Full/Summary, by path   │
+/// │ included_hashes │ Same blocks, by BLAKE3 hash of their content │
+/// │ annotations     │ Tags, provenance and links, by output pos.   │
+/// │ tags, xml_style │ DriverConfig::target_model profile (XML)     │
+/// │ path_root       │ Common directory of the rendered paths       │
+/// └─────────────────┴──────────────────────────────────────────────┘
+/// ```
+///
+/// Both lookup maps store the block's position in the rendered output so
+/// a tree can say whether the file appears above or below it.
+/// `annotations` is filled in by the driver, which is the only caller
+/// that still sees the ANNOTATION blocks. `path_root` is set by
+/// [`factor_paths`](Self::factor_paths), which needs every block that
+/// will render.
+#[derive(Default)]
+pub(crate) struct RenderContext {
+    pub recent_since: Option<u64>,
+    pub included_paths: HashMap<String, usize>,
+    pub included_hashes: HashMap<[u8; 32], usize>,
+    pub annotations: HashMap<usize, BlockAnnotations>,
+    pub tags: TagVocabulary,
+    pub xml_style: XmlStyle,
+    pub path_root: Option<String>,
+}
+
+/// Annotation-derived facts about one block that affect filtering or
+/// show up in its rendered header.
+///
+/// Priority and relevance are not here — they only steer the budget
+/// engine, which resolves them itself.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct BlockAnnotations {
+    /// `Tag` values in stream order, without duplicates.
+    pub tags: Vec<String>,
+    /// `Provenance` as `scheme:source` text; the last one wins.
+    pub source: Option<String>,
+    /// `Expiry` time in Unix seconds; the last one wins.
+    pub expires_at: Option<u64>,
+    /// `Link` relationships, each with a description of the linked
+    /// block (its path, title, …).
+    pub links: Vec<(LinkKind, String)>,
+}
+
+static NO_ANNOTATIONS: BlockAnnotations = BlockAnnotations {
+    tags: Vec::new(),
+    source: None,
+    expires_at: None,
+    links: Vec::new(),
+};
+
+impl RenderContext {
+    /// Build the context for a decision-annotated block list.
+    ///
+    /// CODE blocks rendered as `Placeholder` or `Omit` are not counted as
+    /// included — their content will not appear in the output. Content
+    /// hashes are only computed when the list contains a `FILE_TREE` block,
+    /// since nothing else consults them.
+    pub fn new(items: &[(&Block, &RenderDecision)], config: Option<&DriverConfig>) -> Self {
+        let has_tree = items
+            .iter()
+            .any(|(b, _)| matches!(b.content, BlockContent::FileTree(_)));
+        let mut ctx = Self {
+            recent_since: config.and_then(|c| c.recent_since),
+            ..Self::default()
+        };
+        if let Some(profile) = config.and_then(DriverConfig::profile) {
+            ctx.tags = profile.tags;
+            ctx.xml_style = profile.xml_style;
+        }
+        if !has_tree {
+            return ctx;
+        }
+        for (i, (block, decision)) in items.iter().enumerate() {
+            ctx.include(i, block, decision);
+        }
+        ctx
+    }
+
+    /// Record the block rendered at output position `position` in the
+    /// included-file lookups, if it is a CODE block whose content will
+    /// appear in the output.
+    pub fn include(&mut self, position: usize, block: &Block, decision: &RenderDecision) {
+        let BlockContent::Code(code) = &block.content else {
+            return;
+        };
+        if !matches!(
+            decision,
+            RenderDecision::Full | RenderDecision::Summary | RenderDecision::Outline
+        ) {
+            return;
+        }
+        self.included_paths
+            .entry(normalize_path(&code.path).to_string())
+            .or_insert(position);
+        self.included_hashes
+            .entry(*blake3::hash(&code.content).as_bytes())
+            .or_insert(position);
+    }
+
+    /// Set `path_root` to the deepest directory holding every CODE and
+    /// DIFF path that `items` render with a header, when stripping it
+    /// from each path saves more characters than declaring it in `mode`
+    /// costs.
+    ///
+    /// ```text
+    /// services/billing/src/invoice.rs  ┐
+    /// services/billing/src/tax/vat.rs  ├─▶ services/billing/src
+    /// services/billing/src/lib.rs      ┘
+    /// ```
+    pub fn factor_paths(&mut self, items: &[(&Block, &RenderDecision)], mode: OutputMode) {
+        self.path_root = None;
+        let paths: Vec<&str> = items
+            .iter()
+            .filter(|(_, decision)| {
+                !matches!(
+                    decision,
+                    RenderDecision::Placeholder { .. } | RenderDecision::Omit
+                )
+            })
+            .flat_map(|(block, _)| block_paths(block))
+            .collect();
+        if paths.len() < 2 {
+            return;
+        }
+        let mut common = dirs(paths[0]);
+        for path in &paths[1..] {
+            let shared = common
+                .iter()
+                .zip(dirs(path))
+                .take_while(|(a, b)| **a == *b)
+                .count();
+            common.truncate(shared);
+        }
+        if common.is_empty() || common.iter().any(|part| part.is_empty()) {
+            return;
+        }
+
+        let root = common.join("/");
+        let saved = paths.len() * (root.len() + 1);
+        let plain = self.opening(mode).len();
+        self.path_root = Some(root);
+        if saved <= self.opening(mode).len() - plain {
+            self.path_root = None;
+        }
+    }
+
+    /// `path` as the output shows it: relative to `path_root` when it
+    /// lies under it, unchanged otherwise.
+    pub fn display_path<'p>(&self, path: &'p str) -> &'p str {
+        self.path_root
+            .as_deref()
+            .and_then(|root| normalize_path(path).strip_prefix(root))
+            .and_then(|rest| rest.strip_prefix('/'))
+            .unwrap_or(path)
+    }
+
+    /// Text before the first block in `mode`: the XML root element,
+    /// carrying `path_root` as its `root` attribute, or a line
+    /// declaring `path_root` in Markdown and Minimal mode.
+    pub fn opening(&self, mode: OutputMode) -> String {
+        let tag = self.tags.root;
+        match (mode, &self.path_root) {
+            (OutputMode::Xml, Some(root)) => format!("<{tag} root=\"{}\">\n", xml_escape(root)),
+            (OutputMode::Xml, None) => format!("<{tag}>\n"),
+            (OutputMode::Markdown, Some(root)) => format!("# Root: {root}\n\n"),
+            (OutputMode::Minimal, Some(root)) => format!("--- root: {root} ---\n\n"),
+            _ => String::new(),
+        }
+    }
+
+    /// Text after the last block in `mode`: the XML closing root tag.
+    pub fn closing(&self, mode: OutputMode) -> String {
+        match mode {
+            OutputMode::Xml => format!("\n</{}>", self.tags.root),
+            _ => String::new(),
+        }
+    }
+
+    /// Annotations attached to the block at output position `index`.
+    pub fn annotations_at(&self, index: usize) -> &BlockAnnotations {
+        self.annotations.get(&index).unwrap_or(&NO_ANNOTATIONS)
+    }
+
+    /// Output position of a CODE block matching a tree entry, looked up
+    /// first by content hash and then by any of the candidate paths.
+    pub fn included_position(&self, hash: Option<&[u8; 32]>, paths: &[String]) -> Option<usize> {
+        hash.and_then(|h| self.included_hashes.get(h).copied())
+            .or_else(|| {
+                paths
+                    .iter()
+                    .find_map(|p| self.included_paths.get(normalize_path(p)).copied())
+            })
+    }
+}
+
+/// Collect Tag, Provenance, Expiry and Link annotations into a map
+/// from target block index to [`BlockAnnotations`].
+///
+/// Malformed values (non-UTF-8 or empty tags, unparsable provenance,
+/// links to a block index outside the stream) are ignored rather than
+/// failing the render.
+pub(crate) fn resolve_annotations(blocks: &[Block]) -> HashMap<u32, BlockAnnotations> {
+    let mut map: HashMap<u32, BlockAnnotations> = HashMap::new();
+    for block in blocks {
+        apply_annotation(&mut map, block, blocks);
+    }
+    map
+}
+
+/// Fold one block into a [`resolve_annotations`] map; anything but a
+/// Tag, Provenance, Expiry or Link annotation is ignored. `blocks` is
+/// the stream so far, used to describe link targets.
+pub(crate) fn apply_annotation(
+    map: &mut HashMap<u32, BlockAnnotations>,
+    block: &Block,
+    blocks: &[Block],
+) {
+    let BlockContent::Annotation(ann) = &block.content else {
+        return;
+    };
+    match ann.kind {
+        AnnotationKind::Tag => {
+            if let Ok(tag) = std::str::from_utf8(&ann.value)
+                && !tag.is_empty()
+            {
+                let tags = &mut map.entry(ann.target_block_id).or_default().tags;
+                if !tags.iter().any(|t| t == tag) {
+                    tags.push(tag.to_string());
+                }
+            }
+        }
+        AnnotationKind::Provenance => {
+            if let Some(source) = ann.as_provenance() {
+                map.entry(ann.target_block_id).or_default().source = Some(source.to_string());
+            }
+        }
+        AnnotationKind::Expiry => {
+            if let Some(at) = ann.as_expiry() {
+                map.entry(ann.target_block_id).or_default().expires_at = Some(at);
+            }
+        }
+        AnnotationKind::Link => {
+            if let Some((kind, to)) = ann.as_link()
+                && let Some(linked) = blocks.get(to as usize)
+            {
+                map.entry(ann.target_block_id)
+                    .or_default()
+                    .links
+                    .push((kind, block_description(linked)));
+            }
+        }
+        AnnotationKind::Priority | AnnotationKind::Summary | AnnotationKind::Relevance => {}
+    }
+}
+
+/// The directories of a path, outermost first.
+fn dirs(path: &str) -> Vec<&str> {
+    let mut parts: Vec<&str> = normalize_path(path).split('/').collect();
+    parts.pop();
+    parts
+}
+
+/// The paths a block shows in its header: a CODE block's path, a
+/// DIFF's path and original path.
+fn block_paths(block: &Block) -> Vec<&str> {
+    match &block.content {
+        BlockContent::Code(code) => vec![code.path.as_str()],
+        BlockContent::Diff(diff) => std::iter::once(diff.path.as_str())
+            .chain(diff.old_path.as_deref())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Strip leading `./` and `/` so `./src/main.rs`, `/src/main.rs` and
+/// `src/main.rs` compare equal.
+pub(crate) fn normalize_path(path: &str) -> &str {
+    path.trim_start_matches("./").trim_start_matches('/')
+}
@@ -1,12 +1,17 @@
 use bcp_types::block::{Block, BlockContent};
+use bcp_types::enums::DiffStatus;
+use bcp_types::image::ImageBlock;
 
 use crate::budget::RenderDecision;
 use crate::config::OutputMode;
+use crate::driver::render_items;
 use crate::error::DriverError;
-use crate::placeholder::render_placeholder;
+use crate::render_context::RenderContext;
 use crate::render_xml::{
-    content_to_string, data_format_display_name, format_hint_display_name, lang_display_name,
-    media_type_display_name, render_file_tree_entries, role_display_name, status_display_name,
+    content_to_string, data_format_display_name, diff_status_display_name, diff_title,
+    format_hint_display_name, header_marker, image_dimensions, image_inline_text,
+    lang_display_name, media_type_display_name, render_file_tree, render_unified_hunks,
+    role_display_name, status_display_name,
 };
 
 /// Markdown renderer — emits conventional fenced code blocks and headers.
@@ -75,131 +80,118 @@ impl MarkdownRenderer {
     pub fn render_all_with_decisions(
         items: &[(&Block, &RenderDecision)],
     ) -> Result<String, DriverError> {
-        let mut parts = Vec::with_capacity(items.len());
-        for (i, (block, decision)) in items.iter().enumerate() {
-            match decision {
-                RenderDecision::Full => {
-                    parts.push(Self::render_block_inner(block, i, false)?);
-                }
-                RenderDecision::Summary => {
-                    parts.push(Self::render_block_inner(block, i, true)?);
-                }
-                RenderDecision::Placeholder {
-                    block_type,
-                    description,
-                    omitted_tokens,
-                } => {
-                    parts.push(render_placeholder(
-                        OutputMode::Markdown,
-                        block_type,
-                        description,
-                        *omitted_tokens,
-                    ));
-                }
-                RenderDecision::Omit => {}
-            }
-        }
-        if parts.is_empty() {
-            return Err(DriverError::EmptyInput);
-        }
-        Ok(parts.join("\n\n"))
+        render_items(
+            OutputMode::Markdown,
+            items,
+            &RenderContext::new(items, None),
+        )
     }
 
     /// Render a single block to its markdown representation.
     fn render_block(block: &Block, index: usize) -> Result<String, DriverError> {
         let use_summary = block.summary.is_some();
-        Self::render_block_inner(block, index, use_summary)
+        Self::render_block_inner(block, index, use_summary, &RenderContext::default())
     }
 
-    /// Inner rendering logic shared by `render_block` and the
-    /// decision-aware path.
-    fn render_block_inner(
+    /// Inner rendering logic shared by `render_block`, the
+    /// decision-aware path and the streaming renderer.
+    pub(crate) fn render_block_inner(
         block: &Block,
         index: usize,
         use_summary: bool,
+        ctx: &RenderContext,
     ) -> Result<String, DriverError> {
-        let use_summary = use_summary && block.summary.is_some();
+        let summary = block
+            .summary
+            .as_ref()
+            .filter(|_| use_summary)
+            .map(|s| s.text.as_str());
+        let marker = header_marker(ctx.annotations_at(index), summary.is_some());
+        let body = |content: &[u8]| match summary {
+            Some(text) => Ok(text.to_string()),
+            None => content_to_string(content, index),
+        };
 
         match &block.content {
             BlockContent::Code(code) => {
                 let lang = lang_display_name(code.lang);
-                let content = if use_summary {
-                    block.summary.as_ref().unwrap().text.clone()
-                } else {
-                    content_to_string(&code.content, index)?
-                };
-                if use_summary {
-                    Ok(format!("## {} (summary)\n\n{content}", code.path))
-                } else {
-                    Ok(format!("## {}\n\n```{lang}\n{content}\n```", code.path))
-                }
+                let content = body(&code.content)?;
+                let header = format!("## {}{marker}", ctx.display_path(&code.path));
+                Ok(fenced(&header, lang, &format!("{content}\n"), summary))
             }
 
             BlockContent::Conversation(conv) => {
                 let role = role_display_name(conv.role);
                 let label = capitalize_first(role);
-                let content = content_to_string(&conv.content, index)?;
-                Ok(format!("**{label}**: {content}"))
+                let content = body(&conv.content)?;
+                Ok(format!("**{label}**{marker}: {content}"))
             }
 
             BlockContent::FileTree(tree) => {
-                let rendered_tree = render_file_tree_entries(&tree.entries, 0);
-                Ok(format!(
-                    "### File Tree: {}\n\n```\n{rendered_tree}```",
-                    tree.root_path
-                ))
+                let rendered_tree = match summary {
+                    Some(_) => String::new(),
+                    None => render_file_tree(tree, index, ctx),
+                };
+                let header = format!("### File Tree: {}{marker}", tree.root_path);
+                Ok(fenced(&header, "", &rendered_tree, summary))
             }
 
             BlockContent::ToolResult(tool) => {
                 let status = status_display_name(tool.status);
-                let content = content_to_string(&tool.content, index)?;
+                let content = body(&tool.content)?;
                 Ok(format!(
-                    "### Tool: {} ({status})\n\n{content}",
+                    "### Tool: {} ({status}){marker}\n\n{content}",
                     tool.tool_name
                 ))
             }
 
             BlockContent::Document(doc) => {
                 let format = format_hint_display_name(doc.format_hint);
-                let content = content_to_string(&doc.content, index)?;
+                let content = body(&doc.content)?;
                 Ok(format!(
-                    "### Document: {} [{format}]\n\n{content}",
+                    "### Document: {} [{format}]{marker}\n\n{content}",
                     doc.title
                 ))
             }
 
             BlockContent::StructuredData(data) => {
                 let format = data_format_display_name(data.format);
-                let content = content_to_string(&data.content, index)?;
-                Ok(format!("```{format}\n{content}\n```"))
+                let content = format!("{}\n", body(&data.content)?);
+                if marker.is_empty() {
+                    return Ok(format!("```{format}\n{content}```"));
+                }
+                let header = format!("### Data [{format}]{marker}");
+                Ok(fenced(&header, format, &content, summary))
             }
 
             BlockContent::Diff(diff) => {
-                let mut lines = String::new();
-                for hunk in &diff.hunks {
-                    let hunk_content = String::from_utf8_lossy(&hunk.lines);
-                    lines.push_str(&hunk_content);
-                }
-                Ok(format!("### Diff: {}\n\n```diff\n{lines}```", diff.path))
+                let title = diff_title(diff, ctx);
+                let status = match diff.status {
+                    DiffStatus::Modified => String::new(),
+                    other => format!(" ({})", diff_status_display_name(other)),
+                };
+                let lines = match summary {
+                    Some(_) => String::new(),
+                    None => render_unified_hunks(&diff.hunks),
+                };
+                let header = format!("### Diff: {title}{status}{marker}");
+                Ok(fenced(&header, "diff", &lines, summary))
             }
 
             BlockContent::EmbeddingRef(emb) => {
-                Ok(format!("*[Embedding ref: model={}]*", emb.model))
+                let label = format!("*[Embedding ref: model={}]*{marker}", emb.model);
+                Ok(match summary {
+                    Some(text) => format!("{label}\n\n{text}"),
+                    None => label,
+                })
             }
 
-            BlockContent::Image(img) => {
-                let media = media_type_display_name(img.media_type);
-                let content = content_to_string(&img.data, index)?;
-                Ok(format!(
-                    "### Image ({media}): {}\n\n{content}",
-                    img.alt_text
-                ))
-            }
+            BlockContent::Image(img) => render_image(img, index, summary, &marker),
 
             BlockContent::Extension(ext) => {
-                let content = content_to_string(&ext.content, index)?;
+                let content = body(&ext.content)?;
                 Ok(format!(
-                    "### Extension: {}/{}\n\n{content}",
+                    "### Extension: {}/{}{marker}\n\n{content}",
                     ext.namespace, ext.type_name
                 ))
             }
@@ -207,7 +199,10 @@ impl MarkdownRenderer {
             BlockContent::Annotation(_) | BlockContent::End => Ok(String::new()),
 
             BlockContent::Unknown { type_id, body } => {
-                let content = String::from_utf8_lossy(body);
+                let content = match summary {
+                    Some(text) => text.into(),
+                    None => String::from_utf8_lossy(body),
+                };
                 Ok(format!(
                     "<!-- unknown block type 0x{type_id:02X} -->\n{content}"
                 ))
@@ -216,6 +211,44 @@ impl MarkdownRenderer {
     }
 }
 
+/// Render an IMAGE block under a `### Image` header.
+///
+/// References render as markdown image syntax; inline images use the
+/// text from [`image_inline_text`]. A summary replaces either body.
+fn render_image(
+    img: &ImageBlock,
+    index: usize,
+    summary: Option<&str>,
+    marker: &str,
+) -> Result<String, DriverError> {
+    let media = match image_dimensions(img) {
+        Some(dims) => format!("{}, {dims}", media_type_display_name(img.media_type)),
+        None => media_type_display_name(img.media_type).to_string(),
+    };
+    let content = match (summary, &img.uri) {
+        (Some(text), _) => text.to_string(),
+        (None, Some(uri)) => format!("![{}]({uri})", img.alt_text),
+        (None, None) => image_inline_text(img, index)?,
+    };
+    Ok(format!(
+        "### Image ({media}): {}{marker}\n\n{content}",
+        img.alt_text
+    ))
+}
+
+/// A header followed by fenced content, or by bare summary prose.
+///
+/// Summaries are not code, so a summarized block drops the fence;
+/// `content` is then ignored. Otherwise `content` must be
+/// newline-terminated so the fence closes on its own line. `header`
+/// already carries any tag and summary markers.
+fn fenced(header: &str, info: &str, content: &str, summary: Option<&str>) -> String {
+    if let Some(text) = summary {
+        return format!("{header}\n\n{text}");
+    }
+    format!("{header}\n\n```{info}\n{content}```")
+}
+
 /// Capitalize the first letter of a string.
 ///
 /// Used to convert role names ("user" → "User") for markdown labels.
@@ -234,7 +267,9 @@ mod tests {
     use bcp_types::block::Block;
     use bcp_types::code::CodeBlock;
     use bcp_types::conversation::ConversationBlock;
-    use bcp_types::enums::{Lang, Role};
+    use bcp_types::diff::{DiffBlock, DiffHunk};
+    use bcp_types::enums::{Lang, MediaType, Role};
+    use bcp_types::image::ImageBlock;
     use bcp_wire::block_frame::BlockFlags;
 
     #[test]
@@ -293,4 +328,74 @@ mod tests {
         assert!(result.contains("Entry point."));
         assert!(!result.contains("long"));
     }
+
+    #[test]
+    fn markdown_diff_unified_headers() {
+        let block = Block {
+            block_type: BlockType::Diff,
+            flags: BlockFlags::NONE,
+            summary: None,
+            content: BlockContent::Diff(DiffBlock {
+                path: "src/new.rs".to_string(),
+                hunks: vec![DiffHunk::new(10, 10, b" a\n-b\n+c\n+d\n".to_vec())],
+                old_path: None,
+                status: DiffStatus::Added,
+            }),
+        };
+        let result = MarkdownRenderer::render_all(&[&block]).unwrap();
+        assert!(result.starts_with("### Diff: src/new.rs (added)\n\n```diff\n@@ -10,2 +10,3 @@\n"));
+    }
+
+    #[test]
+    fn markdown_image_reference_uses_image_syntax() {
+        let block = Block {
+            block_type: BlockType::Image,
+            flags: BlockFlags::NONE,
+            summary: None,
+            content: BlockContent::Image(ImageBlock::from_uri(
+                MediaType::Svg,
+                "Architecture",
+                "blake3:00ff",
+            )),
+        };
+        let result = MarkdownRenderer::render_all(&[&block]).unwrap();
+        assert_eq!(
+            result,
+            "### Image (svg): Architecture\n\n![Architecture](blake3:00ff)"
+        );
+    }
+
+    #[test]
+    fn markdown_summary_for_every_block_type() {
+        let summary = Some(bcp_types::summary::Summary {
+            text: "Asked about retries.".to_string(),
+        });
+        let turn = Block {
+            block_type: BlockType::Conversation,
+            flags: BlockFlags::NONE,
+            summary: summary.clone(),
+            content: BlockContent::Conversation(ConversationBlock {
+                role: Role::User,
+                content: b"long question".to_vec(),
+                tool_call_id: None,
+            }),
+        };
+        let data = Block {
+            block_type: BlockType::StructuredData,
+            flags: BlockFlags::NONE,
+            summary,
+            content: BlockContent::StructuredData(
+                bcp_types::structured_data::StructuredDataBlock {
+                    format: bcp_types::enums::DataFormat::Json,
+                    schema: None,
+                    content: b"{\"retries\": 3}".to_vec(),
+                },
+            ),
+        };
+        let result = MarkdownRenderer::render_all(&[&turn, &data]).unwrap();
+        assert!(result.contains("**User** (summary): Asked about retries."));
+        assert!(result.contains("### Data [json] (summary)\n\nAsked about retries."));
+        assert!(!result.contains("long question"));
+        assert!(!result.contains("retries\": 3"));
+    }
 }
@@ -1,12 +1,15 @@
 use bcp_types::block::{Block, BlockContent};
+use bcp_types::enums::DiffStatus;
 
 use crate::budget::RenderDecision;
 use crate::config::OutputMode;
+use crate::driver::render_items;
 use crate::error::DriverError;
-use crate::placeholder::render_placeholder;
+use crate::render_context::RenderContext;
 use crate::render_xml::{
-    content_to_string, data_format_display_name, lang_display_name, media_type_display_name,
-    render_file_tree_entries, role_display_name, status_display_name,
+    content_to_string, data_format_display_name, diff_status_display_name, diff_title,
+    header_marker, image_dimensions, image_inline_text, lang_display_name, media_type_display_name,
+    render_file_tree, render_unified_hunks, role_display_name, status_display_name,
 };
 
 /// Minimal renderer — single-line delimiters for maximum token efficiency.
@@ -68,121 +71,119 @@ impl MinimalRenderer {
     pub fn render_all_with_decisions(
         items: &[(&Block, &RenderDecision)],
     ) -> Result<String, DriverError> {
-        let mut parts = Vec::with_capacity(items.len());
-        for (i, (block, decision)) in items.iter().enumerate() {
-            match decision {
-                RenderDecision::Full => {
-                    parts.push(Self::render_block_inner(block, i, false)?);
-                }
-                RenderDecision::Summary => {
-                    parts.push(Self::render_block_inner(block, i, true)?);
-                }
-                RenderDecision::Placeholder {
-                    block_type,
-                    description,
-                    omitted_tokens,
-                } => {
-                    parts.push(render_placeholder(
-                        OutputMode::Minimal,
-                        block_type,
-                        description,
-                        *omitted_tokens,
-                    ));
-                }
-                RenderDecision::Omit => {}
-            }
-        }
-        if parts.is_empty() {
-            return Err(DriverError::EmptyInput);
-        }
-        Ok(parts.join("\n\n"))
+        render_items(OutputMode::Minimal, items, &RenderContext::new(items, None))
     }
 
     /// Render a single block using minimal delimiters.
     fn render_block(block: &Block, index: usize) -> Result<String, DriverError> {
         let use_summary = block.summary.is_some();
-        Self::render_block_inner(block, index, use_summary)
+        Self::render_block_inner(block, index, use_summary, &RenderContext::default())
     }
 
-    /// Inner rendering logic shared by `render_block` and the
-    /// decision-aware path.
-    fn render_block_inner(
+    /// Inner rendering logic shared by `render_block`, the
+    /// decision-aware path and the streaming renderer.
+    pub(crate) fn render_block_inner(
         block: &Block,
         index: usize,
         use_summary: bool,
+        ctx: &RenderContext,
     ) -> Result<String, DriverError> {
-        let use_summary = use_summary && block.summary.is_some();
+        let summary = block
+            .summary
+            .as_ref()
+            .filter(|_| use_summary)
+            .map(|s| s.text.as_str());
+        let marker = header_marker(ctx.annotations_at(index), summary.is_some());
+        let body = |content: &[u8]| match summary {
+            Some(text) => Ok(text.to_string()),
+            None => content_to_string(content, index),
+        };
 
         match &block.content {
             BlockContent::Code(code) => {
                 let lang = lang_display_name(code.lang);
-                let content = if use_summary {
-                    block.summary.as_ref().unwrap().text.clone()
-                } else {
-                    content_to_string(&code.content, index)?
-                };
-                if use_summary {
-                    Ok(format!(
-                        "--- {} [{lang}] (summary) ---\n{content}",
-                        code.path
-                    ))
-                } else {
-                    Ok(format!("--- {} [{lang}] ---\n{content}", code.path))
-                }
+                let content = body(&code.content)?;
+                Ok(format!(
+                    "--- {} [{lang}]{marker} ---\n{content}",
+                    ctx.display_path(&code.path)
+                ))
             }
 
             BlockContent::Conversation(conv) => {
                 let role = role_display_name(conv.role);
-                let content = content_to_string(&conv.content, index)?;
-                Ok(format!("[{role}] {content}"))
+                let content = body(&conv.content)?;
+                Ok(format!("[{role}]{marker} {content}"))
             }
 
             BlockContent::FileTree(tree) => {
-                let rendered_tree = render_file_tree_entries(&tree.entries, 0);
-                Ok(format!("--- tree: {} ---\n{rendered_tree}", tree.root_path))
+                let rendered_tree = match summary {
+                    Some(text) => format!("{text}\n"),
+                    None => render_file_tree(tree, index, ctx),
+                };
+                Ok(format!(
+                    "--- tree: {}{marker} ---\n{rendered_tree}",
+                    tree.root_path
+                ))
             }
 
             BlockContent::ToolResult(tool) => {
                 let status = status_display_name(tool.status);
-                let content = content_to_string(&tool.content, index)?;
-                Ok(format!("--- {} [{status}] ---\n{content}", tool.tool_name))
+                let content = body(&tool.content)?;
+                Ok(format!(
+                    "--- {} [{status}]{marker} ---\n{content}",
+                    tool.tool_name
+                ))
             }
 
             BlockContent::Document(doc) => {
-                let content = content_to_string(&doc.content, index)?;
-                Ok(format!("--- {} ---\n{content}", doc.title))
+                let content = body(&doc.content)?;
+                Ok(format!("--- {}{marker} ---\n{content}", doc.title))
             }
 
             BlockContent::StructuredData(data) => {
                 let format = data_format_display_name(data.format);
-                let content = content_to_string(&data.content, index)?;
-                Ok(format!("--- data [{format}] ---\n{content}"))
+                let content = body(&data.content)?;
+                Ok(format!("--- data [{format}]{marker} ---\n{content}"))
             }
 
             BlockContent::Diff(diff) => {
-                let mut lines = String::new();
-                for hunk in &diff.hunks {
-                    let hunk_content = String::from_utf8_lossy(&hunk.lines);
-                    lines.push_str(&hunk_content);
-                }
-                Ok(format!("--- diff: {} ---\n{lines}", diff.path))
+                let lines = match summary {
+                    Some(text) => format!("{text}\n"),
+                    None => render_unified_hunks(&diff.hunks),
+                };
+                let title = diff_title(diff, ctx);
+                let status = match diff.status {
+                    DiffStatus::Modified => String::new(),
+                    other => format!(" [{}]", diff_status_display_name(other)),
+                };
+                Ok(format!("--- diff: {title}{status}{marker} ---\n{lines}"))
             }
 
-            BlockContent::EmbeddingRef(emb) => Ok(format!("[embed-ref: {}]", emb.model)),
+            BlockContent::EmbeddingRef(emb) => match summary {
+                Some(text) => Ok(format!("[embed-ref: {}]{marker}\n{text}", emb.model)),
+                None => Ok(format!("[embed-ref: {}]{marker}", emb.model)),
+            },
 
             BlockContent::Image(img) => {
-                let media = media_type_display_name(img.media_type);
-                let content = content_to_string(&img.data, index)?;
+                let media = match image_dimensions(img) {
+                    Some(dims) => format!("{} {dims}", media_type_display_name(img.media_type)),
+                    None => media_type_display_name(img.media_type).to_string(),
+                };
+                let content = match (summary, &img.uri) {
+                    (Some(text), _) => text.to_string(),
+                    (None, Some(uri)) => uri.clone(),
+                    (None, None) => image_inline_text(img, index)?,
+                };
                 Ok(format!(
-                    "--- image [{media}]: {} ---\n{content}",
+                    "--- image [{media}]: {}{marker} ---\n{content}",
                     img.alt_text
                 ))
             }
 
             BlockContent::Extension(ext) => {
-                let content = content_to_string(&ext.content, index)?;
+                let content = body(&ext.content)?;
                 Ok(format!(
-                    "--- ext: {}/{} ---\n{content}",
+                    "--- ext: {}/{}{marker} ---\n{content}",
                     ext.namespace, ext.type_name
                 ))
             }
@@ -190,8 +191,13 @@ impl MinimalRenderer {
             BlockContent::Annotation(_) | BlockContent::End => Ok(String::new()),
 
             BlockContent::Unknown { type_id, body } => {
-                let content = String::from_utf8_lossy(body);
-                Ok(format!("--- unknown 0x{type_id:02X} ---\n{content}"))
+                let content = match summary {
+                    Some(text) => text.into(),
+                    None => String::from_utf8_lossy(body),
+                };
+                Ok(format!(
+                    "--- unknown 0x{type_id:02X}{marker} ---\n{content}"
+                ))
             }
         }
     }
@@ -204,6 +210,7 @@ mod tests {
     use bcp_types::block::Block;
     use bcp_types::code::CodeBlock;
     use bcp_types::conversation::ConversationBlock;
+    use bcp_types::diff::{DiffBlock, DiffHunk};
     use bcp_types::enums::{Lang, Role};
     use bcp_wire::block_frame::BlockFlags;
 
@@ -261,4 +268,39 @@ mod tests {
         assert!(result.contains("Entry point."));
         assert!(!result.contains("long"));
     }
+
+    #[test]
+    fn minimal_diff_unified_headers() {
+        let block = Block {
+            block_type: BlockType::Diff,
+            flags: BlockFlags::NONE,
+            summary: None,
+            content: BlockContent::Diff(DiffBlock {
+                path: "src/lib.rs".to_string(),
+                hunks: vec![DiffHunk::new(10, 10, b" a\n-b\n+c\n+d\n".to_vec())],
+                old_path: None,
+                status: DiffStatus::Modified,
+            }),
+        };
+        let result = MinimalRenderer::render_all(&[&block]).unwrap();
+        assert!(result.starts_with("--- diff: src/lib.rs ---\n@@ -10,2 +10,3 @@\n a\n"));
+    }
+
+    #[test]
+    fn minimal_summary_for_every_block_type() {
+        let block = Block {
+            block_type: BlockType::Document,
+            flags: BlockFlags::NONE,
+            summary: Some(bcp_types::summary::Summary {
+                text: "Setup instructions.".to_string(),
+            }),
+            content: BlockContent::Document(bcp_types::document::DocumentBlock {
+                title: "README".to_string(),
+                content: b"long readme".to_vec(),
+                format_hint: bcp_types::enums::FormatHint::Markdown,
+            }),
+        };
+        let result = MinimalRenderer::render_all(&[&block]).unwrap();
+        assert_eq!(result, "--- README (summary) ---\nSetup instructions.");
+    }
 }
@@ -0,0 +1,347 @@
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use bcp_types::block::{Block, BlockContent};
+use bcp_types::{BlockType, Summary};
+use bcp_wire::block_frame::{BlockFlags, BlockFrame};
+
+use crate::budget::RenderDecision;
+use crate::config::{OutputMode, RawEncoding};
+use crate::driver::render_items;
+use crate::error::{DriverError, RawParseError};
+use crate::profile::TagVocabulary;
+use crate::render_context::RenderContext;
+
+/// The RFC 1924 base85 alphabet: no quotes, backslash, comma or space.
+const BASE85_ALPHABET: &[u8; 85] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz!#$%&()*+-;<=>?@^_`{|}~";
+
+/// Raw renderer — each block as a type tag plus its base-encoded wire
+/// frame (RFC §5.4).
+///
+/// Raw mode is for native-ingestion experiments (RFC §8): instead of a
+/// human-readable rendering, the model sees the binary block frames
+/// themselves, one per line, with just enough text around them to say
+/// what each one is.
+///
+/// ```text
+/// code b64 AQAgAQABAgELc3JjL21haW4ucnMDAQxmbiBtYWluKCkge30=
+/// turn b64 AgAVAQACAgEPRml4IHRoZSB0aW1lb3V0
+/// # omitted: code src/lib.rs ~823tok
+/// ```
+///
+/// Each line is `<tag> <encoding> <frame>`:
+///
+/// ```text
+/// ┌──────────┬──────────────────────────────────────────────────────┐
+/// │ Part     │ Content                                              │
+/// ├──────────┼──────────────────────────────────────────────────────┤
+/// │ tag      │ Element name from the tag vocabulary (a label only;  │
+/// │          │ the frame's own type byte is authoritative)          │
+/// │ encoding │ b64 or b85, see RawEncoding                          │
+/// │ frame    │ block_type varint, flags, content_len, body — with   │
+/// │          │ the summary prefix if present, never compressed      │
+/// └──────────┴──────────────────────────────────────────────────────┘
+/// ```
+///
+/// Frames are lossless, so `Full` and `Summary` decisions both emit the
+/// whole frame. Placeholders become `#` comment lines, which
+/// [`RawRenderer::parse`] skips. Annotation blocks are filtered by the
+/// driver before rendering and do not appear.
+pub struct RawRenderer;
+
+impl RawRenderer {
+    /// Render a filtered slice of blocks into Raw-mode output.
+    ///
+    /// One line per block, no outer wrapper.
+    ///
+    /// # Errors
+    ///
+    /// Returns `DriverError::EmptyInput` if `blocks` is empty.
+    pub fn render_all(blocks: &[&Block], encoding: RawEncoding) -> Result<String, DriverError> {
+        let items: Vec<(&Block, &RenderDecision)> =
+            blocks.iter().map(|&b| (b, &RenderDecision::Full)).collect();
+        render_items(
+            OutputMode::Raw(encoding),
+            &items,
+            &RenderContext::new(&items, None),
+        )
+    }
+
+    /// Render one block as a `<tag> <encoding> <frame>` line.
+    pub(crate) fn render_block(
+        block: &Block,
+        ctx: &RenderContext,
+        encoding: RawEncoding,
+    ) -> String {
+        let tag = block_tag(&ctx.tags, &block.block_type);
+        let frame = encode(&frame_bytes(block), encoding);
+        format!("{tag} {} {frame}", encoding.label())
+    }
+
+    /// Parse Raw-mode text back into blocks.
+    ///
+    /// Blank lines and `#` comment lines are skipped. Every other line
+    /// must hold exactly one frame; the encoding is read per line, so
+    /// `b64` and `b85` lines may be mixed.
+    ///
+    /// # Errors
+    ///
+    /// - [`RawParseError::Malformed`] if a line does not have three
+    ///   fields, names an unknown encoding, has characters outside the
+    ///   alphabet, holds trailing bytes after its frame, or holds an END
+    ///   or compressed frame.
+    /// - [`RawParseError::Frame`] if the bytes are not a block frame.
+    /// - [`RawParseError::Content`] if the body does not decode as the
+    ///   frame's block type.
+    pub fn parse(text: &str) -> Result<Vec<Block>, RawParseError> {
+        let mut blocks = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line_no = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let malformed = |reason| RawParseError::Malformed {
+                line: line_no,
+                reason,
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [_tag, label, data] = fields[..] else {
+                return Err(malformed("expected `<tag> <encoding> <frame>`"));
+            };
+            let encoding =
+                RawEncoding::from_label(label).ok_or_else(|| malformed("unknown encoding"))?;
+            let bytes = decode(data, encoding)
+                .ok_or_else(|| malformed("frame is not valid for encoding"))?;
+            let (frame, consumed) = BlockFrame::read_from(&bytes)
+                .map_err(|source| RawParseError::Frame {
+                    line: line_no,
+                    source,
+                })?
+                .ok_or_else(|| malformed("END frames are not rendered"))?;
+            if consumed != bytes.len() {
+                return Err(malformed("trailing bytes after frame"));
+            }
+            if frame.flags.is_compressed() || frame.flags.is_reference() {
+                return Err(malformed(
+                    "compressed and reference frames are not rendered",
+                ));
+            }
+            blocks.push(
+                frame_to_block(&frame).map_err(|source| RawParseError::Content {
+                    line: line_no,
+                    source,
+                })?,
+            );
+        }
+        Ok(blocks)
+    }
+}
+
+/// The tag written before a block's frame.
+fn block_tag(tags: &TagVocabulary, block_type: &BlockType) -> String {
+    let tag = match block_type {
+        BlockType::Code => tags.code,
+        BlockType::Conversation => tags.conversation,
+        BlockType::FileTree => tags.file_tree,
+        BlockType::ToolResult => tags.tool_result,
+        BlockType::Document => tags.document,
+        BlockType::StructuredData => tags.structured_data,
+        BlockType::Diff => tags.diff,
+        BlockType::EmbeddingRef => tags.embedding_ref,
+        BlockType::Image => tags.image,
+        BlockType::Extension => tags.extension,
+        BlockType::Annotation => "annotation",
+        BlockType::End => "end",
+        BlockType::Unknown(id) => return format!("0x{id:02x}"),
+    };
+    tag.to_string()
+}
+
+/// Serialize a block to its uncompressed wire frame.
+///
+/// Flags are rebuilt from the block rather than copied: a decoded block
+/// may carry `COMPRESSED` or `IS_REFERENCE` from its original payload,
+/// but its content here is always inline and plain.
+fn frame_bytes(block: &Block) -> Vec<u8> {
+    let mut body = Vec::new();
+    let flags = match block.summary {
+        Some(ref summary) => {
+            summary.encode(&mut body);
+            BlockFlags::HAS_SUMMARY
+        }
+        None => BlockFlags::NONE,
+    };
+    body.extend_from_slice(&block.content.encode_body());
+    let frame = BlockFrame {
+        block_type: block.block_type.wire_id(),
+        flags,
+        body,
+    };
+    let mut out = Vec::new();
+    frame
+        .write_to(&mut out)
+        .expect("writing to a Vec cannot fail");
+    out
+}
+
+/// Rebuild a block from a parsed frame, stripping the summary prefix.
+fn frame_to_block(frame: &BlockFrame) -> Result<Block, bcp_types::TypeError> {
+    let block_type = BlockType::from_wire_id(frame.block_type);
+    let (summary, body) = if frame.flags.has_summary() {
+        let (summary, consumed) = Summary::decode(&frame.body)?;
+        (Some(summary), &frame.body[consumed..])
+    } else {
+        (None, &frame.body[..])
+    };
+    let content = BlockContent::decode_body(&block_type, body)?;
+    Ok(Block {
+        block_type,
+        flags: frame.flags,
+        summary,
+        content,
+    })
+}
+
+fn encode(bytes: &[u8], encoding: RawEncoding) -> String {
+    match encoding {
+        RawEncoding::Base64 => STANDARD.encode(bytes),
+        RawEncoding::Base85 => base85_encode(bytes),
+    }
+}
+
+fn decode(text: &str, encoding: RawEncoding) -> Option<Vec<u8>> {
+    match encoding {
+        RawEncoding::Base64 => STANDARD.decode(text).ok(),
+        RawEncoding::Base85 => base85_decode(text),
+    }
+}
+
+/// Encode bytes as RFC 1924 base85.
+///
+/// Each 4-byte group becomes 5 characters, most significant digit
+/// first. A final partial group of `n` bytes is zero-padded and
+/// truncated to `n + 1` characters, so no padding characters appear.
+fn base85_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(4) * 5);
+    for chunk in bytes.chunks(4) {
+        let mut group = [0u8; 4];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let mut value = u32::from_be_bytes(group);
+        let mut digits = [0u8; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = BASE85_ALPHABET[(value % 85) as usize];
+            value /= 85;
+        }
+        out.extend(digits[..=chunk.len()].iter().map(|&d| char::from(d)));
+    }
+    out
+}
+
+/// Decode RFC 1924 base85, the inverse of [`base85_encode`].
+///
+/// A partial final group is padded with the highest digit before
+/// decoding, which rounds the truncated value back up to the original
+/// bytes. Returns `None` for characters outside the alphabet, a lone
+/// trailing character, or a group that overflows 32 bits.
+fn base85_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() / 5 * 4 + 3);
+    for chunk in text.as_bytes().chunks(5) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut value: u32 = 0;
+        for i in 0..5 {
+            let digit = match chunk.get(i) {
+                Some(c) => BASE85_ALPHABET.iter().position(|a| a == c)?,
+                None => 84,
+            };
+            #[allow(clippy::cast_possible_truncation)]
+            let digit = digit as u32;
+            value = value.checked_mul(85)?.checked_add(digit)?;
+        }
+        out.extend_from_slice(&value.to_be_bytes()[..chunk.len() - 1]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{code_block, conversation_block, with_summary};
+    use bcp_types::enums::{Lang, Role};
+
+    #[test]
+    fn render_then_parse_roundtrips_in_both_encodings() {
+        let blocks = [
+            with_summary(
+                code_block(Lang::Rust, "src/main.rs", b"fn main() {}"),
+                "Entry point",
+            ),
+            conversation_block(Role::User, b"Fix the timeout"),
+        ];
+        let refs: Vec<&Block> = blocks.iter().collect();
+        for encoding in [RawEncoding::Base64, RawEncoding::Base85] {
+            let text = RawRenderer::render_all(&refs, encoding).unwrap();
+            let lines: Vec<&str> = text.lines().collect();
+            assert_eq!(lines.len(), 2);
+            assert!(lines[0].starts_with(&format!("code {} ", encoding.label())));
+            assert!(lines[1].starts_with(&format!("turn {} ", encoding.label())));
+
+            let parsed = RawRenderer::parse(&text).unwrap();
+            assert_eq!(parsed.len(), 2);
+            assert_eq!(parsed[0].summary, blocks[0].summary);
+            assert!(parsed[0].flags.has_summary());
+            assert_eq!(parsed[0].content, blocks[0].content);
+            assert_eq!(parsed[1], blocks[1]);
+        }
+    }
+
+    #[test]
+    fn base85_handles_partial_groups() {
+        for len in 0u8..=9 {
+            let bytes: Vec<u8> = (0..len).map(|i| 0xF0 ^ i.wrapping_mul(37)).collect();
+            let text = base85_encode(&bytes);
+            assert_eq!(text.len(), len as usize + (len as usize).div_ceil(4));
+            assert_eq!(base85_decode(&text).unwrap(), bytes);
+        }
+        // Five zero bytes: one full group, then one padded byte.
+        assert_eq!(base85_encode(&[0; 5]), "0000000");
+        assert_eq!(base85_decode("0"), None);
+        assert_eq!(base85_decode("00 00"), None);
+        assert_eq!(base85_decode("~~~~~"), None);
+    }
+
+    #[test]
+    fn parse_skips_comments_and_rejects_bad_lines() {
+        let text = "# omitted: code src/lib.rs ~823tok\n\n";
+        assert!(RawRenderer::parse(text).unwrap().is_empty());
+
+        let err = |text: &str| RawRenderer::parse(text).unwrap_err();
+        assert!(matches!(
+            err("code b64"),
+            RawParseError::Malformed { line: 1, .. }
+        ));
+        assert!(matches!(
+            err("# header\ncode b32 AAAA"),
+            RawParseError::Malformed { line: 2, .. }
+        ));
+        assert!(matches!(
+            err("code b64 !!!!"),
+            RawParseError::Malformed { line: 1, .. }
+        ));
+        // Type 0x01, no flags, length 5, but only 1 body byte.
+        assert!(matches!(
+            err(&format!(
+                "code b64 {}",
+                STANDARD.encode([0x01, 0x00, 0x05, 0x00])
+            )),
+            RawParseError::Frame { line: 1, .. }
+        ));
+        // A CODE frame with an empty body lacks its required fields.
+        assert!(matches!(
+            err(&format!("code b64 {}", STANDARD.encode([0x01, 0x00, 0x00]))),
+            RawParseError::Content { line: 1, .. }
+        ));
+    }
+}
@@ -1,11 +1,15 @@
 use bcp_types::block::{Block, BlockContent};
-use bcp_types::enums::{DataFormat, FormatHint, MediaType, Role, Status};
-use bcp_types::file_tree::{FileEntry, FileEntryKind};
+use bcp_types::diff::{DiffBlock, DiffHunk};
+use bcp_types::enums::{DataFormat, DiffStatus, FormatHint, LinkKind, MediaType, Role, Status};
+use bcp_types::file_tree::{FileEntry, FileEntryKind, FileTreeBlock};
+use bcp_types::image::ImageBlock;
 
 use crate::budget::RenderDecision;
 use crate::config::OutputMode;
+use crate::driver::render_items;
 use crate::error::DriverError;
-use crate::placeholder::render_placeholder;
+use crate::profile::XmlStyle;
+use crate::render_context::{BlockAnnotations, RenderContext};
 
 /// XML-tagged renderer — emits `<context>`-wrapped XML elements.
 ///
@@ -47,10 +51,11 @@ use crate::placeholder::render_placeholder;
 /// │ ToolResult        │ <tool name="X" status="Y">...</tool>     │
 /// │ Document          │ <doc title="X" format="Y">...</doc>      │
 /// │ StructuredData    │ <data format="X">...</data>              │
-/// │ Diff              │ <diff path="X">...</diff>                │
+/// │ Diff              │ <diff path="X" status="Y">@@..</diff>    │
 /// │ Annotation        │ (not rendered — metadata only)           │
 /// │ EmbeddingRef      │ <embed-ref model="X" />                  │
 /// │ Image             │ <image type="X" alt="Y">...</image>      │
+/// │ Image (reference) │ <image type="X" alt="Y" src="Z" />       │
 /// │ Extension         │ <ext ns="X" type="Y">...</ext>           │
 /// └───────────────────┴──────────────────────────────────────────┘
 /// ```
@@ -81,6 +86,7 @@ impl XmlRenderer {
     /// [`RenderDecision`] that determines how it should be rendered:
     /// - `Full`: render complete content (ignore any attached summary)
     /// - `Summary`: render summary text only
+    /// - `Outline`: render code declarations with bodies elided
     /// - `Placeholder`: emit a compact omission notice
     /// - `Omit`: skip the block entirely
     ///
@@ -94,35 +100,7 @@ impl XmlRenderer {
     pub fn render_all_with_decisions(
         items: &[(&Block, &RenderDecision)],
     ) -> Result<String, DriverError> {
-        let mut parts = Vec::with_capacity(items.len());
-        for (i, (block, decision)) in items.iter().enumerate() {
-            match decision {
-                RenderDecision::Full => {
-                    parts.push(Self::render_block_inner(block, i, false)?);
-                }
-                RenderDecision::Summary => {
-                    parts.push(Self::render_block_inner(block, i, true)?);
-                }
-                RenderDecision::Placeholder {
-                    block_type,
-                    description,
-                    omitted_tokens,
-                } => {
-                    parts.push(render_placeholder(
-                        OutputMode::Xml,
-                        block_type,
-                        description,
-                        *omitted_tokens,
-                    ));
-                }
-                RenderDecision::Omit => {}
-            }
-        }
-        if parts.is_empty() {
-            return Err(DriverError::EmptyInput);
-        }
-        let inner = parts.join("\n\n");
-        Ok(format!("<context>\n{inner}\n</context>"))
+        render_items(OutputMode::Xml, items, &RenderContext::new(items, None))
     }
 
     /// Render a single block to its XML element string.
@@ -132,127 +110,105 @@ impl XmlRenderer {
     /// the root element.
     fn render_block(block: &Block, index: usize) -> Result<String, DriverError> {
         let use_summary = block.summary.is_some();
-        Self::render_block_inner(block, index, use_summary)
+        Self::render_block_inner(block, index, use_summary, &RenderContext::default())
     }
 
-    /// Inner rendering logic shared by `render_block` and the
-    /// decision-aware path.
+    /// Inner rendering logic shared by `render_block`, the
+    /// decision-aware path and the streaming renderer.
     ///
     /// When `use_summary` is true and the block has a summary, the
     /// summary text replaces the block content. When false, the full
     /// content is always rendered regardless of summary presence.
-    fn render_block_inner(
+    pub(crate) fn render_block_inner(
         block: &Block,
         index: usize,
         use_summary: bool,
+        ctx: &RenderContext,
     ) -> Result<String, DriverError> {
-        let use_summary = use_summary && block.summary.is_some();
-
-        match &block.content {
-            BlockContent::Code(code) => {
-                let lang = lang_display_name(code.lang);
-                let content = if use_summary {
-                    block.summary.as_ref().unwrap().text.clone()
-                } else {
-                    content_to_string(&code.content, index)?
-                };
-                if use_summary {
-                    Ok(format!(
-                        "<code lang=\"{lang}\" path=\"{}\" summary=\"true\">\n{content}\n</code>",
-                        xml_escape(&code.path)
-                    ))
-                } else {
-                    Ok(format!(
-                        "<code lang=\"{lang}\" path=\"{}\">\n{content}\n</code>",
-                        xml_escape(&code.path)
-                    ))
-                }
-            }
+        let summary = block
+            .summary
+            .as_ref()
+            .filter(|_| use_summary)
+            .map(|s| s.text.as_str());
+        let extra = annotation_attrs(ctx.annotations_at(index), summary.is_some());
+        let body = |content: &[u8]| match summary {
+            Some(text) => Ok(text.to_string()),
+            None => content_to_string(content, index),
+        };
+        let tags = &ctx.tags;
 
-            BlockContent::Conversation(conv) => {
-                let role = role_display_name(conv.role);
-                let content = content_to_string(&conv.content, index)?;
-                Ok(format!("<turn role=\"{role}\">{content}</turn>"))
-            }
+        let element = match &block.content {
+            BlockContent::Code(code) => Element::new(tags.code)
+                .attr("lang", lang_display_name(code.lang))
+                .attr("path", ctx.display_path(&code.path))
+                .body(format!("\n{}\n", body(&code.content)?)),
+
+            BlockContent::Conversation(conv) => Element::new(tags.conversation)
+                .attr("role", role_display_name(conv.role))
+                .body(body(&conv.content)?),
 
             BlockContent::FileTree(tree) => {
-                let rendered_tree = render_file_tree_entries(&tree.entries, 0);
-                Ok(format!(
-                    "<tree root=\"{}\">\n{rendered_tree}</tree>",
-                    xml_escape(&tree.root_path)
-                ))
+                let rendered_tree = match summary {
+                    Some(text) => format!("{text}\n"),
+                    None => render_file_tree(tree, index, ctx),
+                };
+                Element::new(tags.file_tree)
+                    .attr("root", &tree.root_path)
+                    .body(format!("\n{rendered_tree}"))
             }
 
-            BlockContent::ToolResult(tool) => {
-                let status = status_display_name(tool.status);
-                let content = content_to_string(&tool.content, index)?;
-                Ok(format!(
-                    "<tool name=\"{}\" status=\"{status}\">\n{content}\n</tool>",
-                    xml_escape(&tool.tool_name)
-                ))
-            }
+            BlockContent::ToolResult(tool) => Element::new(tags.tool_result)
+                .attr("name", &tool.tool_name)
+                .attr("status", status_display_name(tool.status))
+                .body(format!("\n{}\n", body(&tool.content)?)),
 
-            BlockContent::Document(doc) => {
-                let format = format_hint_display_name(doc.format_hint);
-                let content = content_to_string(&doc.content, index)?;
-                Ok(format!(
-                    "<doc title=\"{}\" format=\"{format}\">\n{content}\n</doc>",
-                    xml_escape(&doc.title)
-                ))
-            }
+            BlockContent::Document(doc) => Element::new(tags.document)
+                .attr("title", &doc.title)
+                .attr("format", format_hint_display_name(doc.format_hint))
+                .body(format!("\n{}\n", body(&doc.content)?)),
 
-            BlockContent::StructuredData(data) => {
-                let format = data_format_display_name(data.format);
-                let content = content_to_string(&data.content, index)?;
-                Ok(format!("<data format=\"{format}\">\n{content}\n</data>"))
-            }
+            BlockContent::StructuredData(data) => Element::new(tags.structured_data)
+                .attr("format", data_format_display_name(data.format))
+                .body(format!("\n{}\n", body(&data.content)?)),
 
             BlockContent::Diff(diff) => {
-                let mut lines = String::new();
-                for hunk in &diff.hunks {
-                    let hunk_content = String::from_utf8_lossy(&hunk.lines);
-                    lines.push_str(&hunk_content);
-                }
-                Ok(format!(
-                    "<diff path=\"{}\">\n{lines}</diff>",
-                    xml_escape(&diff.path)
-                ))
+                let lines = match summary {
+                    Some(text) => format!("{text}\n"),
+                    None => render_unified_hunks(&diff.hunks),
+                };
+                diff_element(tags.diff, diff, ctx).body(format!("\n{lines}"))
             }
 
-            BlockContent::EmbeddingRef(emb) => Ok(format!(
-                "<embed-ref model=\"{}\" />",
-                xml_escape(&emb.model)
-            )),
-
-            BlockContent::Image(img) => {
-                let media = media_type_display_name(img.media_type);
-                let content = content_to_string(&img.data, index)?;
-                Ok(format!(
-                    "<image type=\"{media}\" alt=\"{}\">\n{content}\n</image>",
-                    xml_escape(&img.alt_text)
-                ))
+            BlockContent::EmbeddingRef(emb) => {
+                let element = Element::new(tags.embedding_ref).attr("model", &emb.model);
+                match summary {
+                    Some(text) => element.body(format!("\n{text}\n")),
+                    None => element,
+                }
             }
 
-            BlockContent::Extension(ext) => {
-                let content = content_to_string(&ext.content, index)?;
-                Ok(format!(
-                    "<ext ns=\"{}\" type=\"{}\">\n{content}\n</ext>",
-                    xml_escape(&ext.namespace),
-                    xml_escape(&ext.type_name)
-                ))
-            }
+            BlockContent::Image(img) => image_element(tags.image, img, index, summary)?,
+
+            BlockContent::Extension(ext) => Element::new(tags.extension)
+                .attr("ns", &ext.namespace)
+                .attr("type", &ext.type_name)
+                .body(format!("\n{}\n", body(&ext.content)?)),
 
             // Annotation and End are filtered out by DefaultDriver before
             // reaching the renderer. Unknown blocks are rendered as comments.
-            BlockContent::Annotation(_) | BlockContent::End => Ok(String::new()),
+            BlockContent::Annotation(_) | BlockContent::End => return Ok(String::new()),
 
             BlockContent::Unknown { type_id, body } => {
-                let content = String::from_utf8_lossy(body);
-                Ok(format!(
+                let content = match summary {
+                    Some(text) => text.into(),
+                    None => String::from_utf8_lossy(body),
+                };
+                return Ok(format!(
                     "<!-- unknown block type 0x{type_id:02X} -->\n{content}"
-                ))
+                ));
             }
-        }
+        };
+        Ok(element.attrs(extra).render(ctx.xml_style))
     }
 }
 
@@ -282,6 +238,20 @@ pub(crate) fn lang_display_name(lang: bcp_types::enums::Lang) -> &'static str {
         Lang::Yaml => "yaml",
         Lang::Toml => "toml",
         Lang::Markdown => "markdown",
+        Lang::Kotlin => "kotlin",
+        Lang::Swift => "swift",
+        Lang::CSharp => "csharp",
+        Lang::Php => "php",
+        Lang::Scala => "scala",
+        Lang::Haskell => "haskell",
+        Lang::Elixir => "elixir",
+        Lang::Zig => "zig",
+        Lang::Lua => "lua",
+        Lang::Dockerfile => "dockerfile",
+        Lang::Makefile => "makefile",
+        Lang::Hcl => "hcl",
+        Lang::Protobuf => "protobuf",
+        Lang::GraphQL => "graphql",
         Lang::Unknown | Lang::Other(_) => "text",
     }
 }
@@ -317,6 +287,8 @@ pub(crate) fn data_format_display_name(format: DataFormat) -> &'static str {
         DataFormat::Yaml => "yaml",
         DataFormat::Toml => "toml",
         DataFormat::Csv => "csv",
+        DataFormat::Xml => "xml",
+        DataFormat::Ndjson => "ndjson",
     }
 }
 
@@ -330,13 +302,248 @@ pub(crate) fn media_type_display_name(media: MediaType) -> &'static str {
     }
 }
 
+pub(crate) fn diff_status_display_name(status: DiffStatus) -> &'static str {
+    match status {
+        DiffStatus::Modified => "modified",
+        DiffStatus::Added => "added",
+        DiffStatus::Deleted => "deleted",
+        DiffStatus::Renamed => "renamed",
+    }
+}
+
+/// A DIFF's path as the Markdown and Minimal headers show it:
+/// `old -> new` for a rename, relative to the context's path root.
+pub(crate) fn diff_title(diff: &DiffBlock, ctx: &RenderContext) -> String {
+    let path = ctx.display_path(&diff.path);
+    match diff.old_path {
+        Some(ref old_path) => format!("{} -> {path}", ctx.display_path(old_path)),
+        None => path.to_string(),
+    }
+}
+
+/// Render diff hunks as unified diff text, each preceded by its
+/// `@@ -old_start,old_lines +new_start,new_lines @@` header.
+///
+/// Follows the `diff -u` convention of omitting a count of 1, so a
+/// single-line change renders as `@@ -42 +42 @@`. Each hunk body is
+/// newline-terminated so consecutive headers always start a fresh line.
+pub(crate) fn render_unified_hunks(hunks: &[DiffHunk]) -> String {
+    use std::fmt::Write;
+    let range = |start: u32, count: u32| {
+        if count == 1 {
+            start.to_string()
+        } else {
+            format!("{start},{count}")
+        }
+    };
+    let mut out = String::new();
+    for hunk in hunks {
+        let _ = writeln!(
+            out,
+            "@@ -{} +{} @@",
+            range(hunk.old_start, hunk.old_lines),
+            range(hunk.new_start, hunk.new_lines)
+        );
+        out.push_str(&String::from_utf8_lossy(&hunk.lines));
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// One block's XML element, before it is written out in an [`XmlStyle`].
+///
+/// Attribute values are stored unescaped and escaped on output. The body
+/// is written verbatim between the open and close tags — each block type
+/// decides its own leading and trailing newlines. An element without a
+/// body is self-closing in attribute style.
+struct Element {
+    name: &'static str,
+    attrs: Vec<(&'static str, String)>,
+    body: Option<String>,
+}
+
+impl Element {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            attrs: Vec::new(),
+            body: None,
+        }
+    }
+
+    fn attr(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.attrs.push((key, value.into()));
+        self
+    }
+
+    fn attrs(mut self, attrs: Vec<(&'static str, String)>) -> Self {
+        self.attrs.extend(attrs);
+        self
+    }
+
+    fn body(mut self, body: String) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Write the element with its metadata as attributes, or as child
+    /// elements followed by a `<content>` element holding the body.
+    fn render(&self, style: XmlStyle) -> String {
+        use std::fmt::Write;
+        let name = self.name;
+        let mut out = format!("<{name}");
+        match style {
+            XmlStyle::Attributes => {
+                for (key, value) in &self.attrs {
+                    let _ = write!(out, " {key}=\"{}\"", xml_escape(value));
+                }
+                match self.body {
+                    Some(ref body) => {
+                        let _ = write!(out, ">{body}</{name}>");
+                    }
+                    None => out.push_str(" />"),
+                }
+            }
+            XmlStyle::Nested => {
+                out.push_str(">\n");
+                for (key, value) in &self.attrs {
+                    let _ = writeln!(out, "<{key}>{}</{key}>", xml_escape(value));
+                }
+                if let Some(ref body) = self.body {
+                    let _ = writeln!(out, "<content>{body}</content>");
+                }
+                let _ = write!(out, "</{name}>");
+            }
+        }
+        out
+    }
+}
+
+/// Trailing attributes shared by every element, from the block's
+/// annotations: `tags`, `source`, one attribute per link kind (e.g.
+/// `tests="src/lib.rs"`), then `summary` for summarized blocks.
+fn annotation_attrs(ann: &BlockAnnotations, summarized: bool) -> Vec<(&'static str, String)> {
+    let mut attrs = Vec::new();
+    if !ann.tags.is_empty() {
+        attrs.push(("tags", ann.tags.join(",")));
+    }
+    if let Some(ref source) = ann.source {
+        attrs.push(("source", source.clone()));
+    }
+    for kind in [LinkKind::DependsOn, LinkKind::Tests, LinkKind::Explains] {
+        let targets: Vec<&str> = ann
+            .links
+            .iter()
+            .filter(|(k, _)| *k == kind)
+            .map(|(_, target)| target.as_str())
+            .collect();
+        if !targets.is_empty() {
+            attrs.push((kind.name(), targets.join(",")));
+        }
+    }
+    if summarized {
+        attrs.push(("summary", "true".to_string()));
+    }
+    attrs
+}
+
+/// A DIFF block's element: the path, plus the status and original path
+/// when they differ from a plain modification.
+fn diff_element(name: &'static str, diff: &DiffBlock, ctx: &RenderContext) -> Element {
+    let mut element = Element::new(name).attr("path", ctx.display_path(&diff.path));
+    if diff.status != DiffStatus::Modified {
+        element = element.attr("status", diff_status_display_name(diff.status));
+    }
+    if let Some(ref old_path) = diff.old_path {
+        element = element.attr("from", ctx.display_path(old_path));
+    }
+    element
+}
+
+/// An IMAGE block's element.
+///
+/// References have a `src` attribute and no body; inline images wrap the
+/// text from [`image_inline_text`]. A summary becomes the body in both
+/// cases.
+fn image_element(
+    name: &'static str,
+    img: &ImageBlock,
+    index: usize,
+    summary: Option<&str>,
+) -> Result<Element, DriverError> {
+    let mut element = Element::new(name)
+        .attr("type", media_type_display_name(img.media_type))
+        .attr("alt", &img.alt_text);
+    if let (Some(w), Some(h)) = (img.width, img.height) {
+        element = element
+            .attr("width", w.to_string())
+            .attr("height", h.to_string());
+    }
+    if let Some(ref uri) = img.uri {
+        element = element.attr("src", uri);
+    }
+    let content = match (summary, &img.uri) {
+        (Some(text), _) => text.to_string(),
+        (None, Some(_)) => return Ok(element),
+        (None, None) => image_inline_text(img, index)?,
+    };
+    Ok(element.body(format!("\n{content}\n")))
+}
+
+/// Text standing in for an inline IMAGE block's pixels.
+///
+/// SVG is markup and renders verbatim. Raster formats cannot be shown
+/// as text, so they render as a size note such as `[png image, 2048 bytes]`.
+pub(crate) fn image_inline_text(
+    img: &ImageBlock,
+    block_index: usize,
+) -> Result<String, DriverError> {
+    if img.media_type == MediaType::Svg {
+        return content_to_string(&img.data, block_index);
+    }
+    Ok(format!(
+        "[{} image, {} bytes]",
+        media_type_display_name(img.media_type),
+        img.data.len()
+    ))
+}
+
+/// Header suffix for the markdown and minimal renderers: ` #tag` per
+/// Tag annotation, ` (source: …)`, ` (kind: target)` per link, then
+/// ` (summary)` for summarized blocks. Empty for an unannotated block
+/// rendered in full.
+pub(crate) fn header_marker(ann: &BlockAnnotations, summarized: bool) -> String {
+    use std::fmt::Write;
+    let mut marker = String::new();
+    for tag in &ann.tags {
+        let _ = write!(marker, " #{tag}");
+    }
+    if let Some(ref source) = ann.source {
+        let _ = write!(marker, " (source: {source})");
+    }
+    for (kind, target) in &ann.links {
+        let _ = write!(marker, " ({}: {target})", kind.name());
+    }
+    if summarized {
+        marker.push_str(" (summary)");
+    }
+    marker
+}
+
+/// `WIDTHxHEIGHT` for an IMAGE block whose dimensions are both known.
+pub(crate) fn image_dimensions(img: &ImageBlock) -> Option<String> {
+    Some(format!("{}x{}", img.width?, img.height?))
+}
+
 /// Convert raw content bytes to a UTF-8 string, returning a
 /// `DriverError::InvalidContent` if the bytes are not valid UTF-8.
 pub(crate) fn content_to_string(content: &[u8], block_index: usize) -> Result<String, DriverError> {
     String::from_utf8(content.to_vec()).map_err(|_| DriverError::InvalidContent { block_index })
 }
 
-/// Render file tree entries with indentation.
+/// Render file tree entries with indentation, without cross-block markers.
 ///
 /// Produces output like:
 /// ```text
@@ -346,26 +553,116 @@ pub(crate) fn content_to_string(content: &[u8], block_index: usize) -> Result<St
 ///   utils/
 ///     helpers.rs (256 bytes)
 /// ```
+///
+/// Used for token estimation, where the payload-dependent annotations
+/// added by [`render_file_tree`] are not yet known.
 pub(crate) fn render_file_tree_entries(entries: &[FileEntry], depth: usize) -> String {
-    use std::fmt::Write;
+    let ctx = RenderContext::default();
+    let writer = TreeWriter {
+        root: "",
+        position: 0,
+        ctx: &ctx,
+    };
     let mut out = String::new();
-    let indent = "  ".repeat(depth);
-    for entry in entries {
-        match entry.kind {
-            FileEntryKind::Directory => {
-                let _ = writeln!(out, "{indent}{}/", entry.name);
-                out.push_str(&render_file_tree_entries(&entry.children, depth + 1));
-            }
-            FileEntryKind::File => {
-                let _ = writeln!(out, "{indent}{} ({} bytes)", entry.name, entry.size);
+    writer.write_entries(&mut out, entries, depth, "");
+    out
+}
+
+/// Render a `FILE_TREE` block's entries, annotating files with metadata.
+///
+/// `position` is the block's index in the rendered output, used to say
+/// whether a matching CODE block appears above or below the tree:
+///
+/// ```text
+/// src/
+///   main.rs (1024 bytes, modified recently, included below)
+///   run.sh (88 bytes, executable)
+///   current -> releases/v2
+/// ```
+pub(crate) fn render_file_tree(
+    tree: &FileTreeBlock,
+    position: usize,
+    ctx: &RenderContext,
+) -> String {
+    let writer = TreeWriter {
+        root: &tree.root_path,
+        position,
+        ctx,
+    };
+    let mut out = String::new();
+    writer.write_entries(&mut out, &tree.entries, 0, "");
+    out
+}
+
+/// Recursive file tree formatter carrying the per-tree render state.
+struct TreeWriter<'a> {
+    root: &'a str,
+    position: usize,
+    ctx: &'a RenderContext,
+}
+
+impl TreeWriter<'_> {
+    fn write_entries(&self, out: &mut String, entries: &[FileEntry], depth: usize, dir: &str) {
+        use std::fmt::Write;
+        let indent = "  ".repeat(depth);
+        for entry in entries {
+            let rel = if dir.is_empty() {
+                entry.name.clone()
+            } else {
+                format!("{dir}/{}", entry.name)
+            };
+            match entry.kind {
+                FileEntryKind::Directory => {
+                    let _ = writeln!(out, "{indent}{}/", entry.name);
+                    self.write_entries(out, &entry.children, depth + 1, &rel);
+                }
+                FileEntryKind::File => {
+                    let notes = self.file_notes(entry, &rel);
+                    let _ = writeln!(out, "{indent}{} ({} bytes{notes})", entry.name, entry.size);
+                }
+                FileEntryKind::Symlink => match entry.link_target {
+                    Some(ref target) => {
+                        let _ = writeln!(out, "{indent}{} -> {target}", entry.name);
+                    }
+                    None => {
+                        let _ = writeln!(out, "{indent}{}@", entry.name);
+                    }
+                },
             }
         }
     }
-    out
+
+    /// Comma-prefixed metadata notes appended after a file's size.
+    fn file_notes(&self, entry: &FileEntry, rel: &str) -> String {
+        let mut notes = String::new();
+        if entry.mode.is_some_and(|mode| mode & 0o111 != 0) {
+            notes.push_str(", executable");
+        }
+        if let (Some(since), Some(mtime)) = (self.ctx.recent_since, entry.mtime)
+            && mtime >= since
+        {
+            notes.push_str(", modified recently");
+        }
+        let candidates = [
+            rel.to_string(),
+            format!("{}/{rel}", self.root.trim_end_matches('/')),
+        ];
+        if let Some(pos) = self
+            .ctx
+            .included_position(entry.content_hash.as_ref(), &candidates)
+        {
+            notes.push_str(if pos > self.position {
+                ", included below"
+            } else {
+                ", included above"
+            });
+        }
+        notes
+    }
 }
 
 /// Escape XML special characters in attribute values.
-fn xml_escape(s: &str) -> String {
+pub(crate) fn xml_escape(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
@@ -375,10 +672,12 @@ fn xml_escape(s: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::block;
     use bcp_types::BlockType;
     use bcp_types::block::Block;
     use bcp_types::code::CodeBlock;
     use bcp_types::conversation::ConversationBlock;
+    use bcp_types::diff::{DiffBlock, DiffHunk};
     use bcp_types::enums::Lang;
     use bcp_wire::block_frame::BlockFlags;
 
@@ -456,4 +755,103 @@ mod tests {
         assert!(result.contains("Entry point: CLI args, config loading."));
         assert!(!result.contains("long content"));
     }
+
+    #[test]
+    fn xml_diff_unified_headers() {
+        let block = Block {
+            block_type: BlockType::Diff,
+            flags: BlockFlags::NONE,
+            summary: None,
+            content: BlockContent::Diff(DiffBlock {
+                path: "src/new.rs".to_string(),
+                hunks: vec![DiffHunk::new(10, 10, b" a\n-b\n+c\n+d\n".to_vec())],
+                old_path: Some("src/old.rs".to_string()),
+                status: DiffStatus::Renamed,
+            }),
+        };
+        let result = XmlRenderer::render_all(&[&block]).unwrap();
+        assert!(
+            result.contains("<diff path=\"src/new.rs\" status=\"renamed\" from=\"src/old.rs\">")
+        );
+        assert!(result.contains("@@ -10,2 +10,3 @@\n a\n-b\n+c\n+d\n</diff>"));
+    }
+
+    fn image_block(image: ImageBlock) -> Block {
+        block(BlockType::Image, BlockContent::Image(image))
+    }
+
+    #[test]
+    fn xml_image_reference_emits_src() {
+        let block = image_block(ImageBlock {
+            width: Some(640),
+            height: Some(480),
+            ..ImageBlock::from_uri(MediaType::Png, "Logo", "file:///assets/logo.png")
+        });
+        let result = XmlRenderer::render_all(&[&block]).unwrap();
+        assert!(result.contains(
+            "<image type=\"png\" alt=\"Logo\" width=\"640\" height=\"480\" src=\"file:///assets/logo.png\" />"
+        ));
+    }
+
+    #[test]
+    fn xml_inline_raster_image_emits_size_note() {
+        let block =
+            image_block(ImageBlock::from_bytes("Logo", b"\x89PNG\r\n\x1A\n\xFF\xFE").unwrap());
+        let result = XmlRenderer::render_all(&[&block]).unwrap();
+        assert!(
+            result.contains("<image type=\"png\" alt=\"Logo\">\n[png image, 10 bytes]\n</image>")
+        );
+    }
+
+    #[test]
+    fn unified_hunk_header_omits_count_of_one() {
+        let hunks = [DiffHunk::new(42, 42, b"-old\n+new".to_vec())];
+        assert_eq!(render_unified_hunks(&hunks), "@@ -42 +42 @@\n-old\n+new\n");
+    }
+
+    #[test]
+    fn xml_summary_for_every_block_type() {
+        let summary = Some(bcp_types::summary::Summary {
+            text: "Three tests failed.".to_string(),
+        });
+        let tool = Block {
+            block_type: BlockType::ToolResult,
+            flags: BlockFlags::NONE,
+            summary: summary.clone(),
+            content: BlockContent::ToolResult(bcp_types::tool_result::ToolResultBlock {
+                tool_name: "cargo test".to_string(),
+                status: Status::Error,
+                content: b"long test output".to_vec(),
+                schema_hint: None,
+            }),
+        };
+        let diff = Block {
+            block_type: BlockType::Diff,
+            flags: BlockFlags::NONE,
+            summary,
+            content: BlockContent::Diff(DiffBlock {
+                path: "src/lib.rs".to_string(),
+                hunks: vec![DiffHunk::new(1, 1, b"-old\n+new\n".to_vec())],
+                old_path: None,
+                status: DiffStatus::Modified,
+            }),
+        };
+        let result = XmlRenderer::render_all(&[&tool, &diff]).unwrap();
+        assert!(result.contains(
+            "<tool name=\"cargo test\" status=\"error\" summary=\"true\">\nThree tests failed.\n</tool>"
+        ));
+        assert!(
+            result.contains(
+                "<diff path=\"src/lib.rs\" summary=\"true\">\nThree tests failed.\n</diff>"
+            )
+        );
+        assert!(!result.contains("long test output"));
+        assert!(!result.contains("+new"));
+
+        // A Full decision still renders the content despite the summary.
+        let full =
+            XmlRenderer::render_all_with_decisions(&[(&tool, &RenderDecision::Full)]).unwrap();
+        assert!(full.contains("long test output"));
+        assert!(!full.contains("summary=\"true\""));
+    }
 }
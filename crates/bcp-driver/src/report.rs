@@ -0,0 +1,385 @@
+use std::ops::Range;
+
+use bcp_types::BlockType;
+
+use crate::budget::{RenderDecision, TokenEstimator, block_description};
+use crate::driver::RenderPlan;
+
+/// Rendered text together with a report of how each block was rendered.
+///
+/// Returned by [`BcpDriver::render_with_report`]. `text` is exactly what
+/// [`BcpDriver::render`] returns for the same input.
+///
+/// [`BcpDriver::render`]: crate::BcpDriver::render
+/// [`BcpDriver::render_with_report`]: crate::BcpDriver::render_with_report
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderOutput {
+    /// Model-ready text.
+    pub text: String,
+    /// What happened to each block, and the token totals.
+    pub report: RenderReport,
+}
+
+/// Per-block account of a render.
+///
+/// ```text
+/// ┌──────────────┬───────────────────────────────────────────────────┐
+/// │ Field        │ Purpose                                           │
+/// ├──────────────┼───────────────────────────────────────────────────┤
+/// │ blocks       │ One entry per content block, in stream order      │
+/// │ total_tokens │ Estimated tokens of the whole output text         │
+/// │ token_budget │ The budget the render ran under, if any           │
+/// └──────────────┴───────────────────────────────────────────────────┘
+/// ```
+///
+/// Token counts come from the same estimator the budget engine uses, so
+/// `total_tokens` is directly comparable to `token_budget`: it prices
+/// the text exactly as returned, separators and root element included.
+/// A block's `estimated_tokens` prices its own text, markup included,
+/// so the entries sum to a little less than `total_tokens`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderReport {
+    /// Every content block, including those dropped by the filters.
+    /// Annotation and End blocks are not listed.
+    pub blocks: Vec<BlockReport>,
+    /// Estimated tokens of the rendered text.
+    pub total_tokens: u32,
+    /// `DriverConfig::token_budget` at render time.
+    pub token_budget: Option<u32>,
+}
+
+impl RenderReport {
+    /// Tokens left under the budget: negative when the render went over
+    /// (Critical and High blocks and kept conversation turns can force
+    /// it), `None` without a budget.
+    #[must_use]
+    pub fn headroom(&self) -> Option<i64> {
+        self.token_budget
+            .map(|budget| i64::from(budget) - i64::from(self.total_tokens))
+    }
+}
+
+/// How one block was rendered.
+///
+/// ```text
+/// ┌──────────────────┬───────────────────────────────────────────────┐
+/// │ Field            │ Purpose                                       │
+/// ├──────────────────┼───────────────────────────────────────────────┤
+/// │ block_index      │ Position in the payload's block stream        │
+/// │ block_type       │ The block's type                              │
+/// │ description      │ Path, tool name, title, … (as in placeholders)│
+/// │ decision         │ Full / Summary / Outline / Truncated /        │
+/// │                  │ Placeholder / Omit                            │
+/// │ reason           │ Why the driver chose that decision            │
+/// │ estimated_tokens │ Estimated tokens of the block's text          │
+/// │ range            │ Byte range of the block in the output text    │
+/// │ query_score      │ Match against DriverConfig::query, 0..=1      │
+/// │ saved_tokens     │ Tokens DriverConfig::dedup saved on the block │
+/// └──────────────────┴───────────────────────────────────────────────┘
+/// ```
+///
+/// `range` covers the block's own text, without the separator before it
+/// or the root element around it. Blocks that produce no output get an
+/// empty range at the position where they would have appeared.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockReport {
+    /// Index of the block in the unfiltered block stream, the index
+    /// annotations target.
+    pub block_index: usize,
+    /// The block's type.
+    pub block_type: BlockType,
+    /// Human-readable identifier for the block.
+    pub description: String,
+    /// How the block was rendered.
+    pub decision: RenderDecision,
+    /// Why it was rendered that way.
+    pub reason: DecisionReason,
+    /// Estimated tokens of the text in `range`, or 0 for omitted
+    /// blocks.
+    pub estimated_tokens: u32,
+    /// Byte range of the block's text within `RenderOutput::text`.
+    pub range: Range<usize>,
+    /// How well the block matches `DriverConfig::query`, relative to
+    /// the best match (1.0). `None` without a query, and for blocks the
+    /// filters dropped.
+    pub query_score: Option<f32>,
+    /// Estimated tokens `DriverConfig::dedup` saved by replacing the
+    /// block's repeated content with back-references, net of what it
+    /// added to a merged fragment. 0 without dedup.
+    pub saved_tokens: u32,
+}
+
+/// Why a block received its [`RenderDecision`].
+///
+/// ```text
+/// ┌──────────────────┬───────────────────────────────────────────────┐
+/// │ Reason           │ Meaning                                       │
+/// ├──────────────────┼───────────────────────────────────────────────┤
+/// │ Filtered         │ Dropped by include_types, tags or expiry      │
+/// │ NoBudget         │ No token budget set: rendered in full         │
+/// │ FullVerbosity    │ Verbosity::Full: rendered in full             │
+/// │ SummaryVerbosity │ Verbosity::Summary: summary where available   │
+/// │ WithinBudget     │ Full content fit the remaining budget         │
+/// │ Critical         │ Critical priority: full regardless of budget  │
+/// │ KeptTurn         │ System or recent conversation turn: full      │
+/// │                  │ regardless of budget                          │
+/// │ ForcedOverBudget │ High priority: full although it did not fit   │
+/// │ LowPriority      │ Low / Background: capped at summary or        │
+/// │                  │ placeholder by priority, not by budget        │
+/// │ OverBudget       │ Degraded because a fuller form did not fit    │
+/// └──────────────────┴───────────────────────────────────────────────┘
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DecisionReason {
+    Filtered,
+    NoBudget,
+    FullVerbosity,
+    SummaryVerbosity,
+    WithinBudget,
+    Critical,
+    KeptTurn,
+    ForcedOverBudget,
+    LowPriority,
+    OverBudget,
+}
+
+impl DecisionReason {
+    /// Kebab-case name, as printed by `bcp decode --report`.
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Filtered => "filtered",
+            Self::NoBudget => "no-budget",
+            Self::FullVerbosity => "full-verbosity",
+            Self::SummaryVerbosity => "summary-verbosity",
+            Self::WithinBudget => "within-budget",
+            Self::Critical => "critical",
+            Self::KeptTurn => "kept-turn",
+            Self::ForcedOverBudget => "forced-over-budget",
+            Self::LowPriority => "low-priority",
+            Self::OverBudget => "over-budget",
+        }
+    }
+}
+
+/// Build the report for a rendered plan.
+///
+/// `ranges` holds one byte range per plan block, as returned by
+/// [`RenderPlan::render_ranged`]. Blocks the filters dropped are merged
+/// in at their stream position with an empty range at the offset the
+/// next rendered block starts from.
+pub(crate) fn build_report(
+    plan: &RenderPlan<'_>,
+    text: &str,
+    ranges: Vec<Range<usize>>,
+    estimator: &dyn TokenEstimator,
+    token_budget: Option<u32>,
+) -> RenderReport {
+    let mut blocks: Vec<BlockReport> = plan
+        .items()
+        .into_iter()
+        .zip(ranges)
+        .enumerate()
+        .map(|(pos, ((block, decision), range))| BlockReport {
+            block_index: plan.original_indices[pos],
+            block_type: block.block_type.clone(),
+            description: block_description(block),
+            decision: decision.clone(),
+            reason: plan.reasons[pos],
+            estimated_tokens: estimator.estimate(&text[range.clone()]),
+            range,
+            query_score: plan.query_scores.as_ref().map(|scores| scores[pos]),
+            saved_tokens: plan.saved_tokens[pos],
+        })
+        .collect();
+
+    for &(index, block) in &plan.filtered_out {
+        let at = blocks.partition_point(|b| b.block_index < index);
+        let offset = blocks.get(at).map_or_else(
+            || blocks.last().map_or(0, |b| b.range.end),
+            |b| b.range.start,
+        );
+        blocks.insert(
+            at,
+            BlockReport {
+                block_index: index,
+                block_type: block.block_type.clone(),
+                description: block_description(block),
+                decision: RenderDecision::Omit,
+                reason: DecisionReason::Filtered,
+                estimated_tokens: 0,
+                range: offset..offset,
+                query_score: None,
+                saved_tokens: 0,
+            },
+        );
+    }
+
+    RenderReport {
+        total_tokens: estimator.estimate(text),
+        blocks,
+        token_budget,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::budget::HeuristicEstimator;
+    use crate::config::{DriverConfig, OutputMode, RawEncoding, Verbosity};
+    use crate::driver::{BcpDriver, DefaultDriver};
+    use bcp_decoder::BcpDecoder;
+    use bcp_encoder::BcpEncoder;
+    use bcp_types::block::Block;
+    use bcp_types::enums::{Lang, Priority, Role, Status};
+
+    fn blocks() -> Vec<Block> {
+        let payload = BcpEncoder::new()
+            .add_code(Lang::Rust, "a.rs", b"fn a() {}")
+            .with_priority(Priority::Critical)
+            .unwrap()
+            .add_code(Lang::Rust, "b.rs", "x".repeat(400).as_bytes())
+            .with_summary("B.")
+            .unwrap()
+            .add_conversation(Role::User, b"stale question")
+            .with_expiry(5)
+            .unwrap()
+            .add_tool_result("rg", Status::Ok, b"3 matches")
+            .with_priority(Priority::Background)
+            .unwrap()
+            .encode()
+            .unwrap();
+        BcpDecoder::decode(&payload).unwrap().blocks
+    }
+
+    fn config(mode: OutputMode, token_budget: Option<u32>) -> DriverConfig {
+        DriverConfig {
+            mode: Some(mode),
+            token_budget,
+            current_time: Some(10),
+            estimator: Some(Arc::new(HeuristicEstimator)),
+            ..DriverConfig::default()
+        }
+    }
+
+    #[test]
+    fn report_text_and_ranges_match_render() {
+        let blocks = blocks();
+        let modes = [
+            OutputMode::Xml,
+            OutputMode::Markdown,
+            OutputMode::Minimal,
+            OutputMode::Raw(RawEncoding::Base64),
+        ];
+        for mode in modes {
+            for budget in [None, Some(60)] {
+                let config = config(mode, budget);
+                let out = DefaultDriver.render_with_report(&blocks, &config).unwrap();
+                assert_eq!(out.text, DefaultDriver.render(&blocks, &config).unwrap());
+
+                let indices: Vec<_> = out.report.blocks.iter().map(|b| b.block_index).collect();
+                assert_eq!(indices, [0, 2, 3, 5], "{mode:?}");
+                for b in &out.report.blocks {
+                    let text = &out.text[b.range.clone()];
+                    match b.decision {
+                        RenderDecision::Omit => assert!(text.is_empty()),
+                        _ => assert!(!text.is_empty() && text.trim() == text, "{mode:?}"),
+                    }
+                }
+            }
+        }
+
+        let out = DefaultDriver
+            .render_with_report(&blocks, &config(OutputMode::Minimal, None))
+            .unwrap();
+        let first = &out.report.blocks[0];
+        assert_eq!(
+            &out.text[first.range.clone()],
+            "--- a.rs [rust] ---\nfn a() {}"
+        );
+    }
+
+    #[test]
+    fn report_explains_budget_decisions() {
+        let blocks = blocks();
+        let unbudgeted = DefaultDriver
+            .render_with_report(&blocks, &config(OutputMode::Xml, None))
+            .unwrap()
+            .report;
+        let reasons: Vec<_> = unbudgeted.blocks.iter().map(|b| b.reason).collect();
+        assert_eq!(
+            reasons,
+            [
+                DecisionReason::NoBudget,
+                DecisionReason::NoBudget,
+                DecisionReason::Filtered,
+                DecisionReason::NoBudget,
+            ]
+        );
+        assert_eq!(unbudgeted.headroom(), None);
+
+        // Room for the critical block and the summary — what a summary
+        // render of the code blocks takes — but not for the rest.
+        let summarized = DriverConfig {
+            verbosity: Verbosity::Summary,
+            include_types: Some(vec![BlockType::Code]),
+            ..config(OutputMode::Xml, None)
+        };
+        let budget = DefaultDriver
+            .render_with_report(&blocks, &summarized)
+            .unwrap()
+            .report
+            .total_tokens
+            + 1;
+        let out = DefaultDriver
+            .render_with_report(&blocks, &config(OutputMode::Xml, Some(budget)))
+            .unwrap();
+        let report = &out.report;
+        let summary: Vec<_> = report
+            .blocks
+            .iter()
+            .map(|b| (b.decision.clone(), b.reason))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (RenderDecision::Full, DecisionReason::Critical),
+                (RenderDecision::Summary, DecisionReason::OverBudget),
+                (RenderDecision::Omit, DecisionReason::Filtered),
+                (RenderDecision::Omit, DecisionReason::OverBudget),
+            ]
+        );
+        assert_eq!(report.blocks[1].description, "b.rs");
+        assert_eq!(report.total_tokens, HeuristicEstimator.estimate(&out.text));
+        assert!(report.total_tokens <= budget);
+        assert_eq!(
+            report.headroom(),
+            Some(i64::from(budget) - i64::from(report.total_tokens))
+        );
+    }
+
+    #[test]
+    fn report_lists_query_scores() {
+        let blocks = blocks();
+        let scores = |query: Option<&str>| -> Vec<Option<f32>> {
+            let config = DriverConfig {
+                query: query.map(str::to_string),
+                ..config(OutputMode::Xml, None)
+            };
+            let report = DefaultDriver
+                .render_with_report(&blocks, &config)
+                .unwrap()
+                .report;
+            report.blocks.iter().map(|b| b.query_score).collect()
+        };
+
+        assert_eq!(scores(None), [None; 4]);
+        // The expired conversation was filtered out before scoring.
+        assert_eq!(
+            scores(Some("which rg matches?")),
+            [Some(0.0), Some(0.0), None, Some(1.0)]
+        );
+    }
+}
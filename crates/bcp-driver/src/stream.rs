@@ -0,0 +1,616 @@
+use std::collections::{HashMap, VecDeque};
+use std::slice;
+use std::sync::Arc;
+
+use bcp_decoder::{DecoderEvent, StreamingDecoder};
+use bcp_types::BlockType;
+use bcp_types::block::Block;
+use bcp_types::enums::Priority;
+use futures_core::Stream;
+use tokio::io::AsyncRead;
+
+use crate::budget::{
+    BlockPricer, RenderDecision, TokenEstimator, decide_block, resolve_priorities,
+    resolve_relevance, scan_blocks,
+};
+use crate::config::{DriverConfig, OutputMode, Verbosity};
+use crate::driver::{
+    DefaultDriver, RenderPlan, block_allowed, render_item, separator, summary_decision,
+};
+use crate::error::{DriverError, PayloadError};
+use crate::render_context::{BlockAnnotations, RenderContext, apply_annotation};
+
+/// One piece of streamed output.
+///
+/// Each chunk carries the separator that precedes it, and the first
+/// chunk carries the opening root tag in XML mode, so concatenating
+/// every chunk's `text` yields the complete rendered output. Under
+/// [`StreamStrategy::ScanFirst`] the first chunk also carries any
+/// declared path root (see [`PathPrefix`](crate::PathPrefix));
+/// `Greedy` never declares one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextChunk {
+    /// Rendered text, including any leading separator or root tag.
+    pub text: String,
+    /// Index of the rendered block in the payload's block stream, or
+    /// `None` for the closing root tag in XML mode.
+    pub block_index: Option<usize>,
+}
+
+/// How a [`RenderStream`] decides what each block costs against the
+/// token budget.
+///
+/// ```text
+/// ┌───────────┬──────────────────────────────────────────────────────┐
+/// │ Strategy  │ Behavior                                             │
+/// ├───────────┼──────────────────────────────────────────────────────┤
+/// │ Greedy    │ Render each block once the next content block has    │
+/// │           │ been decoded. The budget is spent in arrival order.  │
+/// │ ScanFirst │ Decode the whole stream, run the regular two-pass    │
+/// │           │ budget engine, then emit one chunk per block.        │
+/// └───────────┴──────────────────────────────────────────────────────┘
+/// ```
+///
+/// `Greedy` holds one block back so the annotations the encoder writes
+/// right after a block (priority, tags, expiry, …) reach it before it
+/// renders. Annotations that arrive later than that are not applied, a
+/// `FILE_TREE` can only mark files rendered above it as included, and a
+/// block that arrives after the budget is spent is degraded even if it
+/// outranks earlier blocks. Each block is priced with its framing, but
+/// the output is not re-estimated as a whole: an estimator that is not
+/// additive can take the total slightly over budget.
+///
+/// The [`DriverConfig`] settings that weigh the whole payload cannot be
+/// applied one block at a time, so `Greedy` ignores them:
+///
+/// ```text
+/// ┌────────────────────┬─────────────────────────────────────────────┐
+/// │ Setting            │ Needs                                       │
+/// ├────────────────────┼─────────────────────────────────────────────┤
+/// │ query              │ Every block, to rank each against the rest  │
+/// │ budget_strategy    │ Every block's cost, to allocate at once     │
+/// │ conversation       │ The last turn, to know which turns are kept │
+/// │ group_dependencies │ Both halves of a pair, to decide them once  │
+/// │ dedup              │ Every full block, to find repeated content  │
+/// │ ordering           │ Every block, to sort them                   │
+/// │ path_prefix        │ Every path, to find their common directory  │
+/// └────────────────────┴─────────────────────────────────────────────┘
+/// ```
+///
+/// Blocks therefore stream in arrival order, and `Greedy` never calls
+/// `RenderContext::factor_paths`: every path renders in full, even in
+/// Minimal mode, where `PathPrefix::Auto` would declare a root. The
+/// output can differ from `render` wherever one of these settings
+/// would change it. `ScanFirst` honours all of them.
+///
+/// `ScanFirst` produces exactly the output of
+/// [`BcpDriver::render`](crate::BcpDriver::render), split into chunks,
+/// but emits nothing until the END sentinel has been read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StreamStrategy {
+    #[default]
+    Greedy,
+    ScanFirst,
+}
+
+/// Asynchronous streaming renderer — decodes blocks from an async
+/// reader and yields their rendered text as soon as each is ready
+/// (RFC §5.2).
+///
+/// ```text
+/// AsyncRead ──▶ StreamingDecoder ──▶ RenderStream::next() ──▶ TextChunk
+///                                          │
+///                                    DriverConfig
+///                                    + StreamStrategy
+/// ```
+///
+/// Like [`StreamingDecoder`], this is a pull-based stream: call
+/// [`next`](Self::next) in a loop until it returns `None`, or adapt it
+/// with [`into_stream`](Self::into_stream) for `Stream` combinators.
+/// Each call reads only as much of the payload as it needs to produce
+/// the next chunk. Filtering, verbosity, model profiles and output modes behave
+/// as in [`DefaultDriver`]; see [`StreamStrategy`] for how the token
+/// budget is applied.
+///
+/// Decoded blocks are kept until the stream ends: annotations may refer
+/// to any earlier block.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use bcp_driver::{DefaultDriver, DriverConfig};
+/// use tokio::io::AsyncRead;
+///
+/// async fn forward(reader: impl AsyncRead + Unpin) {
+///     let config = DriverConfig::default();
+///     let mut stream = DefaultDriver::decode_stream(reader, &config);
+///     while let Some(chunk) = stream.next().await.transpose().unwrap() {
+///         print!("{}", chunk.text);
+///     }
+/// }
+/// ```
+pub struct RenderStream<'c, R> {
+    decoder: StreamingDecoder<R>,
+    config: &'c DriverConfig,
+    strategy: StreamStrategy,
+    estimator: Arc<dyn TokenEstimator>,
+    now: u64,
+    /// Every block decoded so far, annotations included.
+    blocks: Vec<Block>,
+    /// Greedy only: annotations, priorities and relevance resolved from
+    /// the ANNOTATION blocks decoded so far, by target block index.
+    annotations: HashMap<u32, BlockAnnotations>,
+    priorities: HashMap<u32, Priority>,
+    relevance: HashMap<u32, f32>,
+    /// Greedy only: cross-block context for the blocks rendered so far.
+    ctx: RenderContext,
+    /// Greedy only: the content block waiting for its annotations.
+    pending: Option<usize>,
+    /// Greedy only: how many blocks have passed the filters, which is
+    /// the output position of the next one.
+    rendered: usize,
+    /// Greedy only: budget left, when a budget is set, after the XML
+    /// root element.
+    remaining: Option<u32>,
+    /// Whether a block chunk has been queued yet.
+    started: bool,
+    queue: VecDeque<TextChunk>,
+    done: bool,
+}
+
+impl DefaultDriver {
+    /// Render a payload as it is read from `reader`, one chunk per block.
+    ///
+    /// Shorthand for [`RenderStream::new`] over a fresh
+    /// [`StreamingDecoder`] with the default [`StreamStrategy`]. Build
+    /// the decoder yourself to attach a content store.
+    ///
+    /// This is an inherent method of `DefaultDriver`, not part of
+    /// [`BcpDriver`](crate::BcpDriver): the trait renders blocks that
+    /// are already decoded and stays independent of the async runtime.
+    pub fn decode_stream<R: AsyncRead + Unpin>(
+        reader: R,
+        config: &DriverConfig,
+    ) -> RenderStream<'_, R> {
+        RenderStream::new(StreamingDecoder::new(reader), config)
+    }
+}
+
+impl<'c, R: AsyncRead + Unpin> RenderStream<'c, R> {
+    /// Create a streaming renderer over an existing decoder.
+    #[must_use]
+    pub fn new(decoder: StreamingDecoder<R>, config: &'c DriverConfig) -> Self {
+        let estimator = config.token_estimator();
+        let ctx = RenderContext::new(&[], Some(config));
+        let remaining = config.token_budget.map(|budget| {
+            let pricer = BlockPricer::new(config.output_mode(), &ctx, estimator.as_ref());
+            budget.saturating_sub(pricer.wrapper_tokens())
+        });
+        Self {
+            decoder,
+            config,
+            strategy: StreamStrategy::default(),
+            estimator,
+            now: config.now(),
+            blocks: Vec::new(),
+            annotations: HashMap::new(),
+            priorities: HashMap::new(),
+            relevance: HashMap::new(),
+            ctx,
+            pending: None,
+            rendered: 0,
+            remaining,
+            started: false,
+            queue: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Set the budget strategy. Defaults to [`StreamStrategy::Greedy`].
+    #[must_use]
+    pub fn with_strategy(mut self, strategy: StreamStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Read until the next chunk is ready and return it.
+    ///
+    /// Returns `Some(Ok(chunk))` for each chunk, `None` once the output
+    /// is complete, or `Some(Err(_))` on a decode or render error, after
+    /// which the stream ends. If no block survives filtering, the only
+    /// item is `Err(PayloadError::Render(DriverError::EmptyInput))`.
+    pub async fn next(&mut self) -> Option<Result<TextChunk, PayloadError>> {
+        loop {
+            if let Some(chunk) = self.queue.pop_front() {
+                return Some(Ok(chunk));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(err) = self.step().await {
+                self.done = true;
+                self.queue.clear();
+                return Some(Err(err));
+            }
+        }
+    }
+
+    /// Adapt this renderer into a [`Stream`] of the items
+    /// [`next`](Self::next) returns.
+    pub fn into_stream(self) -> impl Stream<Item = Result<TextChunk, PayloadError>> + 'c
+    where
+        R: 'c,
+    {
+        futures_util::stream::unfold(self, |mut stream| async move {
+            let item = stream.next().await?;
+            Some((item, stream))
+        })
+    }
+
+    /// Consume one decoder event.
+    async fn step(&mut self) -> Result<(), PayloadError> {
+        let block = match self.decoder.next().await {
+            None => return Ok(self.finish()?),
+            Some(event) => match event? {
+                DecoderEvent::Header(_) => return Ok(()),
+                DecoderEvent::Block(block) => block,
+            },
+        };
+        let is_content = !matches!(block.block_type, BlockType::Annotation | BlockType::End);
+        if self.strategy == StreamStrategy::Greedy && !is_content {
+            apply_annotation(&mut self.annotations, &block, &self.blocks);
+            let block = slice::from_ref(&block);
+            self.priorities.extend(resolve_priorities(block));
+            self.relevance.extend(resolve_relevance(block));
+        }
+        self.blocks.push(block);
+        if self.strategy == StreamStrategy::Greedy && is_content {
+            let index = self.blocks.len() - 1;
+            if let Some(ready) = self.pending.replace(index) {
+                self.render_greedy(ready)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush what is left once the END sentinel has been read.
+    fn finish(&mut self) -> Result<(), DriverError> {
+        self.done = true;
+        match self.strategy {
+            StreamStrategy::ScanFirst => self.render_scanned()?,
+            StreamStrategy::Greedy => {
+                if let Some(ready) = self.pending.take() {
+                    self.render_greedy(ready)?;
+                }
+            }
+        }
+        if !self.started {
+            return Err(DriverError::EmptyInput);
+        }
+        if self.config.output_mode() == OutputMode::Xml {
+            self.queue.push_back(TextChunk {
+                text: format!("\n</{}>", self.root()),
+                block_index: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// Decide and render the block at `index` with what is known so far.
+    fn render_greedy(&mut self, index: usize) -> Result<(), DriverError> {
+        let config = self.config;
+        let block = &self.blocks[index];
+        #[allow(clippy::cast_possible_truncation)]
+        let ann = self.annotations.get(&(index as u32)).cloned();
+        if !block_allowed(block, ann.as_ref(), config, self.now) {
+            return Ok(());
+        }
+
+        let decision = match (self.remaining.as_mut(), config.verbosity) {
+            (_, Verbosity::Summary) => summary_decision(block),
+            (Some(remaining), Verbosity::Adaptive) => {
+                // Priced on its own, with its annotations, as it will
+                // render.
+                let mut ctx = RenderContext::new(&[(block, &RenderDecision::Full)], Some(config));
+                if let Some(ann) = &ann {
+                    ctx.annotations.insert(0, ann.clone());
+                }
+                let pricer = BlockPricer::new(config.output_mode(), &ctx, self.estimator.as_ref());
+                let infos = scan_blocks(
+                    &[block],
+                    &self.priorities,
+                    &self.relevance,
+                    &pricer,
+                    &[index],
+                );
+                decide_block(&infos[0], block, remaining, self.estimator.as_ref()).0
+            }
+            _ => RenderDecision::Full,
+        };
+
+        let position = self.rendered;
+        self.ctx.include(position, block, &decision);
+        if let Some(ann) = ann {
+            self.ctx.annotations.insert(position, ann);
+        }
+        let text = render_item(config.output_mode(), block, &decision, position, &self.ctx)?;
+        let opening = self.ctx.opening(config.output_mode());
+        self.rendered += 1;
+
+        if let Some(text) = text {
+            self.push(text, index, &opening);
+        }
+        Ok(())
+    }
+
+    /// Run the full render pipeline over every decoded block and queue
+    /// one chunk per rendered block.
+    fn render_scanned(&mut self) -> Result<(), DriverError> {
+        let plan = RenderPlan::new(&self.blocks, self.config)?;
+        let mode = self.config.output_mode();
+        let opening = plan.ctx.opening(mode);
+        let mut texts = Vec::new();
+        for (position, (block, decision)) in plan.items().into_iter().enumerate() {
+            if let Some(text) = render_item(mode, block, decision, position, &plan.ctx)? {
+                texts.push((text, plan.original_indices[position]));
+            }
+        }
+        for (text, index) in texts {
+            self.push(text, index, &opening);
+        }
+        Ok(())
+    }
+
+    /// Queue a rendered block behind the separator, or for the first
+    /// block the `opening` root tag or path root, that belongs before it.
+    fn push(&mut self, mut text: String, block_index: usize, opening: &str) {
+        let prefix = if self.started {
+            separator(self.config.output_mode())
+        } else {
+            opening
+        };
+        self.started = true;
+        text.insert_str(0, prefix);
+        self.queue.push_back(TextChunk {
+            text,
+            block_index: Some(block_index),
+        });
+    }
+
+    /// The XML root element name from the model profile.
+    fn root(&self) -> &'static str {
+        self.config
+            .profile()
+            .map(|p| p.tags)
+            .unwrap_or_default()
+            .root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::config::RawEncoding;
+    use crate::driver::BcpDriver;
+    use bcp_decoder::BcpDecoder;
+    use bcp_encoder::BcpEncoder;
+    use bcp_types::enums::{Lang, Role};
+    use futures_util::StreamExt;
+    use tokio::io::AsyncWriteExt;
+    use tokio::time::timeout;
+
+    fn payload() -> Vec<u8> {
+        let mut enc = BcpEncoder::new();
+        enc.add_code(
+            Lang::Rust,
+            "src/main.rs",
+            b"fn main() {\n    let config = load();\n    run(config);\n}",
+        )
+        .with_summary("Entry point.")
+        .unwrap()
+        .add_code(Lang::Rust, "src/gen.rs", b"// generated code")
+        .with_expiry(1)
+        .unwrap()
+        .add_code(Lang::Rust, "src/run.rs", b"pub fn run() { loop {} }")
+        .with_priority(Priority::Critical)
+        .unwrap()
+        .add_conversation(Role::User, b"Why does it hang?");
+        enc.encode().unwrap()
+    }
+
+    async fn collect(stream: &mut RenderStream<'_, &[u8]>) -> Vec<TextChunk> {
+        let mut chunks = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            chunks.push(chunk.unwrap());
+        }
+        chunks
+    }
+
+    fn joined(chunks: &[TextChunk]) -> String {
+        chunks.iter().map(|c| c.text.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn chunks_concatenate_to_render_output() {
+        let payload = payload();
+        let blocks = BcpDecoder::decode(&payload).unwrap().blocks;
+        let modes = [
+            OutputMode::Xml,
+            OutputMode::Markdown,
+            OutputMode::Minimal,
+            OutputMode::Raw(RawEncoding::Base64),
+        ];
+        for mode in modes {
+            let config = DriverConfig {
+                mode: Some(mode),
+                current_time: Some(10),
+                ..DriverConfig::default()
+            };
+            let expected = DefaultDriver.render(&blocks, &config).unwrap();
+            for strategy in [StreamStrategy::Greedy, StreamStrategy::ScanFirst] {
+                let mut stream =
+                    DefaultDriver::decode_stream(&payload[..], &config).with_strategy(strategy);
+                let chunks = collect(&mut stream).await;
+                assert_eq!(joined(&chunks), expected, "{mode:?} {strategy:?}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn chunks_report_block_indices() {
+        let payload = payload();
+        let config = DriverConfig {
+            current_time: Some(10),
+            ..DriverConfig::default()
+        };
+        let mut stream = DefaultDriver::decode_stream(&payload[..], &config);
+        let indices: Vec<_> = collect(&mut stream)
+            .await
+            .iter()
+            .map(|c| c.block_index)
+            .collect();
+        // Block 1 has expired; 2 and 4 are annotations.
+        assert_eq!(indices, [Some(0), Some(3), Some(5), None]);
+    }
+
+    #[tokio::test]
+    async fn scan_first_declares_the_path_root() {
+        let mut enc = BcpEncoder::new();
+        for name in ["retry", "backoff", "jitter"] {
+            let path = format!("services/gateway/src/transport/{name}.rs");
+            enc.add_code(Lang::Rust, &path, b"pub fn policy() {}");
+        }
+        let payload = enc.encode().unwrap();
+        let blocks = BcpDecoder::decode(&payload).unwrap().blocks;
+        let config = DriverConfig {
+            mode: Some(OutputMode::Minimal),
+            ..DriverConfig::default()
+        };
+        let expected = DefaultDriver.render(&blocks, &config).unwrap();
+        assert!(expected.starts_with("--- root: services/gateway/src/transport ---"));
+
+        let mut stream = DefaultDriver::decode_stream(&payload[..], &config)
+            .with_strategy(StreamStrategy::ScanFirst);
+        let chunks = collect(&mut stream).await;
+        assert!(chunks[0].text.starts_with("--- root: "));
+        assert_eq!(joined(&chunks), expected);
+
+        // Greedy cannot know the root before the last path arrives.
+        let mut stream = DefaultDriver::decode_stream(&payload[..], &config);
+        let greedy = joined(&collect(&mut stream).await);
+        assert!(!greedy.contains("--- root: "));
+        assert!(greedy.contains("services/gateway/src/transport/retry.rs"));
+    }
+
+    #[tokio::test]
+    async fn into_stream_yields_the_same_chunks() {
+        let payload = payload();
+        let config = DriverConfig {
+            current_time: Some(10),
+            ..DriverConfig::default()
+        };
+        let mut stream = DefaultDriver::decode_stream(&payload[..], &config);
+        let expected = collect(&mut stream).await;
+        let chunks: Vec<_> = DefaultDriver::decode_stream(&payload[..], &config)
+            .into_stream()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!(chunks, expected);
+    }
+
+    #[tokio::test]
+    async fn greedy_budget_spends_in_arrival_order() {
+        let payload = payload();
+        let blocks = BcpDecoder::decode(&payload).unwrap().blocks;
+        let config = DriverConfig {
+            mode: Some(OutputMode::Minimal),
+            token_budget: Some(32),
+            current_time: Some(10),
+            ..DriverConfig::default()
+        };
+
+        // Greedy: main.rs arrives first and takes most of the budget;
+        // the critical run.rs renders anyway and the last turn is
+        // dropped.
+        let mut stream = DefaultDriver::decode_stream(&payload[..], &config);
+        let greedy = joined(&collect(&mut stream).await);
+        assert!(greedy.contains("run(config);"));
+        assert!(greedy.contains("pub fn run()"));
+        assert!(!greedy.contains("Why does it hang?"));
+
+        // ScanFirst: run.rs is allocated first, so main.rs is cut down —
+        // the same as a regular render.
+        let mut stream = DefaultDriver::decode_stream(&payload[..], &config)
+            .with_strategy(StreamStrategy::ScanFirst);
+        let scanned = joined(&collect(&mut stream).await);
+        assert_eq!(scanned, DefaultDriver.render(&blocks, &config).unwrap());
+        assert!(scanned.contains("[elided: lines 2-4"));
+        assert!(!scanned.contains("run(config);"));
+    }
+
+    #[tokio::test]
+    async fn greedy_emits_before_stream_ends() {
+        let payload = payload();
+        let config = DriverConfig {
+            mode: Some(OutputMode::Minimal),
+            current_time: Some(10),
+            ..DriverConfig::default()
+        };
+        let (mut writer, reader) = tokio::io::duplex(payload.len());
+        // Everything but the END sentinel's last byte: all blocks are
+        // complete, so all but the held-back last one can render.
+        writer
+            .write_all(&payload[..payload.len() - 1])
+            .await
+            .unwrap();
+
+        let mut stream = DefaultDriver::decode_stream(reader, &config);
+        let first = timeout(Duration::from_secs(5), stream.next())
+            .await
+            .expect("first chunk should not wait for END")
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.block_index, Some(0));
+
+        writer
+            .write_all(&payload[payload.len() - 1..])
+            .await
+            .unwrap();
+        drop(writer);
+        let mut rest = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            rest.push(chunk.unwrap().block_index);
+        }
+        assert_eq!(rest, [Some(3), Some(5)]);
+    }
+
+    #[tokio::test]
+    async fn empty_and_invalid_streams_error() {
+        let payload = BcpEncoder::new()
+            .add_code(Lang::Rust, "src/gen.rs", b"// generated")
+            .with_expiry(1)
+            .unwrap()
+            .encode()
+            .unwrap();
+        let config = DriverConfig {
+            current_time: Some(10),
+            ..DriverConfig::default()
+        };
+        let mut stream = DefaultDriver::decode_stream(&payload[..], &config);
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(PayloadError::Render(DriverError::EmptyInput)))
+        ));
+        assert!(stream.next().await.is_none());
+
+        let mut stream = DefaultDriver::decode_stream(&b"not bcp"[..], &config);
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(PayloadError::Decode(_)))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -0,0 +1,89 @@
+use bcp_types::BlockType;
+use bcp_types::annotation::AnnotationBlock;
+use bcp_types::block::{Block, BlockContent};
+use bcp_types::code::CodeBlock;
+use bcp_types::conversation::ConversationBlock;
+use bcp_types::enums::{AnnotationKind, Lang, Priority, Role, Status};
+use bcp_types::summary::Summary;
+use bcp_types::tool_result::ToolResultBlock;
+use bcp_wire::block_frame::BlockFlags;
+
+/// A block with no flags and no summary.
+pub(crate) fn block(block_type: BlockType, content: BlockContent) -> Block {
+    Block {
+        block_type,
+        flags: BlockFlags::NONE,
+        summary: None,
+        content,
+    }
+}
+
+/// `block` with a summary attached.
+pub(crate) fn with_summary(mut block: Block, summary: &str) -> Block {
+    block.flags = BlockFlags::HAS_SUMMARY;
+    block.summary = Some(Summary {
+        text: summary.to_string(),
+    });
+    block
+}
+
+/// A whole-file CODE block.
+pub(crate) fn code_block(lang: Lang, path: &str, content: impl AsRef<[u8]>) -> Block {
+    block(
+        BlockType::Code,
+        BlockContent::Code(CodeBlock {
+            lang,
+            path: path.to_string(),
+            content: content.as_ref().to_vec(),
+            line_range: None,
+        }),
+    )
+}
+
+/// A CONVERSATION turn with no tool call id.
+pub(crate) fn conversation_block(role: Role, content: impl AsRef<[u8]>) -> Block {
+    block(
+        BlockType::Conversation,
+        BlockContent::Conversation(ConversationBlock {
+            role,
+            content: content.as_ref().to_vec(),
+            tool_call_id: None,
+        }),
+    )
+}
+
+/// A successful `TOOL_RESULT` block.
+pub(crate) fn tool_result_block(name: &str, content: impl AsRef<[u8]>) -> Block {
+    block(
+        BlockType::ToolResult,
+        BlockContent::ToolResult(ToolResultBlock {
+            tool_name: name.to_string(),
+            status: Status::Ok,
+            content: content.as_ref().to_vec(),
+            schema_hint: None,
+        }),
+    )
+}
+
+/// An ANNOTATION block.
+pub(crate) fn annotation(annotation: AnnotationBlock) -> Block {
+    block(BlockType::Annotation, BlockContent::Annotation(annotation))
+}
+
+/// A `Priority` annotation on the block at `target`.
+pub(crate) fn priority_annotation(target: u32, priority: Priority) -> Block {
+    annotation(AnnotationBlock {
+        target_block_id: target,
+        kind: AnnotationKind::Priority,
+        value: vec![priority.to_wire_byte()],
+    })
+}
+
+/// A `Tag` annotation on the block at `target`.
+pub(crate) fn tag_annotation(target: u32, tag: &str) -> Block {
+    annotation(AnnotationBlock {
+        target_block_id: target,
+        kind: AnnotationKind::Tag,
+        value: tag.as_bytes().to_vec(),
+    })
+}
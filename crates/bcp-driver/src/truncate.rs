@@ -0,0 +1,298 @@
+use bcp_types::block::{Block, BlockContent};
+
+use crate::budget::TokenEstimator;
+use crate::config::OutputMode;
+use crate::placeholder::render_elision;
+
+/// Which lines of a block survive truncation.
+///
+/// ```text
+/// ┌──────────────────┬──────────┬─────────────────────────────────────┐
+/// │ Block type       │ Window   │ Rationale                           │
+/// ├──────────────────┼──────────┼─────────────────────────────────────┤
+/// │ Code             │ Head     │ Imports and signatures come first   │
+/// │ Document         │ Head     │ Headings and intros come first      │
+/// │ StructuredData   │ Head     │ Keys and schema shape come first    │
+/// │ ToolResult       │ HeadTail │ Errors and totals land at the end   │
+/// │ Conversation     │ HeadTail │ The ask opens, the conclusion ends  │
+/// │ (other)          │ —        │ Not truncatable                     │
+/// └──────────────────┴──────────┴─────────────────────────────────────┘
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Window {
+    Head,
+    HeadTail,
+}
+
+/// A line window that fits a token allowance.
+pub(crate) struct Truncation {
+    /// Lines kept from the start of the content.
+    pub head: usize,
+    /// Lines kept from the end of the content.
+    pub tail: usize,
+    /// Estimated tokens of the kept lines.
+    pub kept_tokens: u32,
+}
+
+/// The truncation window and text body of a block, if it has one.
+fn window(block: &Block) -> Option<(Window, &[u8])> {
+    match &block.content {
+        BlockContent::Code(c) => Some((Window::Head, &c.content)),
+        BlockContent::Document(d) => Some((Window::Head, &d.content)),
+        BlockContent::StructuredData(d) => Some((Window::Head, &d.content)),
+        BlockContent::ToolResult(t) => Some((Window::HeadTail, &t.content)),
+        BlockContent::Conversation(c) => Some((Window::HeadTail, &c.content)),
+        _ => None,
+    }
+}
+
+/// Pick the lines of `block` to keep within `allowance` tokens.
+///
+/// Head windows keep lines from the top until the allowance runs out.
+/// Head+tail windows spend half the allowance on the top and the rest
+/// on the bottom. Returns `None` when the block is not truncatable, its
+/// body is not UTF-8, not even one line fits, or every line fits (the
+/// block is not actually over the allowance).
+pub(crate) fn plan_truncation(
+    block: &Block,
+    allowance: u32,
+    estimator: &dyn TokenEstimator,
+) -> Option<Truncation> {
+    let (window, content) = window(block)?;
+    let text = std::str::from_utf8(content).ok()?;
+    // Price each line with its newline, so the kept lines cost what
+    // they would as part of the whole body.
+    let costs: Vec<u32> = text
+        .lines()
+        .map(|l| estimator.estimate(&format!("{l}\n")))
+        .collect();
+
+    let take = |costs: &mut dyn Iterator<Item = &u32>, limit: u32| {
+        let mut spent = 0;
+        let count = costs
+            .take_while(|&&c| {
+                let fits = spent + c <= limit;
+                if fits {
+                    spent += c;
+                }
+                fits
+            })
+            .count();
+        (count, spent)
+    };
+
+    let (head, tail, kept_tokens) = match window {
+        Window::Head => {
+            let (head, spent) = take(&mut costs.iter(), allowance);
+            (head, 0, spent)
+        }
+        Window::HeadTail => {
+            let (head, head_spent) = take(&mut costs.iter(), allowance / 2);
+            let (tail, tail_spent) = take(&mut costs[head..].iter().rev(), allowance - head_spent);
+            (head, tail, head_spent + tail_spent)
+        }
+    };
+
+    if head + tail == 0 || head + tail >= costs.len() {
+        return None;
+    }
+    Some(Truncation {
+        head,
+        tail,
+        kept_tokens,
+    })
+}
+
+/// Build the truncated copy of `block` that the renderers draw.
+///
+/// The body keeps the first `head` and last `tail` lines. For text modes
+/// the elision marker replaces the lines in between, so it sits inside
+/// the block's element, fence or section. Raw frames carry block bytes,
+/// so in Raw mode the body holds only the kept lines and the marker is
+/// returned separately, as a comment line for after the frame.
+///
+/// Marker line numbers count from `CodeBlock::line_range`'s start when
+/// the block is a fragment. A head-only code window also narrows
+/// `line_range` to the source lines kept, so a `lines="1-40"` header
+/// counts the kept lines only; the marker line after them is not part
+/// of the range.
+pub(crate) fn truncate_block(
+    block: &Block,
+    head: usize,
+    tail: usize,
+    omitted_tokens: u32,
+    mode: OutputMode,
+) -> (Block, Option<String>) {
+    let mut out = block.clone();
+    let offset = match &block.content {
+        BlockContent::Code(c) => c.line_range.map_or(0, |(start, _)| start.saturating_sub(1)),
+        _ => 0,
+    };
+    let Some((_, content)) = window(block) else {
+        return (out, None);
+    };
+    let text = String::from_utf8_lossy(content);
+    let lines: Vec<&str> = text.lines().collect();
+    let (head, tail) = (
+        head.min(lines.len()),
+        tail.min(lines.len() - head.min(lines.len())),
+    );
+
+    #[allow(clippy::cast_possible_truncation)]
+    let (first, last) = (
+        offset + head as u32 + 1,
+        offset + (lines.len() - tail) as u32,
+    );
+    let marker = render_elision(mode, first, last, omitted_tokens);
+
+    let mut kept: Vec<&str> = lines[..head].to_vec();
+    let trailer = if let OutputMode::Raw(_) = mode {
+        Some(marker)
+    } else {
+        kept.push(&marker);
+        None
+    };
+    kept.extend_from_slice(&lines[lines.len() - tail..]);
+    let body = kept.join("\n").into_bytes();
+
+    match &mut out.content {
+        BlockContent::Code(c) => {
+            c.content = body;
+            if tail == 0 && head > 0 {
+                c.line_range = Some((offset + 1, first - 1));
+            }
+        }
+        BlockContent::Document(d) => d.content = body,
+        BlockContent::StructuredData(d) => d.content = body,
+        BlockContent::ToolResult(t) => t.content = body,
+        BlockContent::Conversation(c) => c.content = body,
+        _ => {}
+    }
+    (out, trailer)
+}
+
+/// Build the outlined copy of `block` that the renderers draw for
+/// `RenderDecision::Outline`: the code's declarations with bodies
+/// elided (see [`bcp_types::outline::outline`]).
+///
+/// Non-code blocks, and code with no outline, come back unchanged and
+/// so render in full.
+pub(crate) fn outline_block(block: &Block) -> Block {
+    let mut out = block.clone();
+    if let BlockContent::Code(c) = &mut out.content
+        && let Some(outline) = c.outline()
+    {
+        c.content = outline.into_bytes();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::HeuristicEstimator;
+    use crate::test_support::block;
+    use bcp_types::BlockType;
+    use bcp_types::code::CodeBlock;
+    use bcp_types::enums::{Lang, Status};
+    use bcp_types::tool_result::ToolResultBlock;
+
+    /// Ten lines of 8 chars each, 2 heuristic tokens per line.
+    fn numbered() -> Vec<u8> {
+        (1..=10)
+            .map(|n| format!("line {n:03}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into_bytes()
+    }
+
+    fn code(line_range: Option<(u32, u32)>) -> Block {
+        block(
+            BlockType::Code,
+            BlockContent::Code(CodeBlock {
+                lang: Lang::Rust,
+                path: "src/big.rs".to_string(),
+                content: numbered(),
+                line_range,
+            }),
+        )
+    }
+
+    fn tool() -> Block {
+        block(
+            BlockType::ToolResult,
+            BlockContent::ToolResult(ToolResultBlock {
+                tool_name: "cargo test".to_string(),
+                status: Status::Error,
+                content: numbered(),
+                schema_hint: None,
+            }),
+        )
+    }
+
+    fn body(block: &Block) -> String {
+        String::from_utf8(window(block).unwrap().1.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn code_keeps_head_within_allowance() {
+        let t = plan_truncation(&code(None), 7, &HeuristicEstimator).unwrap();
+        assert_eq!((t.head, t.tail, t.kept_tokens), (3, 0, 6));
+
+        assert!(plan_truncation(&code(None), 1, &HeuristicEstimator).is_none());
+        assert!(plan_truncation(&code(None), 100, &HeuristicEstimator).is_none());
+    }
+
+    #[test]
+    fn tool_output_keeps_head_and_tail() {
+        let t = plan_truncation(&tool(), 8, &HeuristicEstimator).unwrap();
+        assert_eq!((t.head, t.tail, t.kept_tokens), (2, 2, 8));
+
+        let (block, trailer) = truncate_block(&tool(), 2, 2, 12, OutputMode::Minimal);
+        assert_eq!(
+            body(&block),
+            "line 001\nline 002\n[elided: lines 3-8 ~12tok]\nline 009\nline 010"
+        );
+        assert!(trailer.is_none());
+    }
+
+    #[test]
+    fn code_marker_follows_line_range() {
+        let (block, _) = truncate_block(&code(Some((41, 50))), 3, 0, 14, OutputMode::Xml);
+        assert_eq!(
+            body(&block),
+            "line 001\nline 002\nline 003\n<elided lines=\"44-50\" tokens=\"14\" />"
+        );
+
+        let (block, trailer) = truncate_block(
+            &code(Some((41, 50))),
+            3,
+            0,
+            14,
+            OutputMode::Raw(crate::config::RawEncoding::Base64),
+        );
+        assert_eq!(body(&block), "line 001\nline 002\nline 003");
+        let BlockContent::Code(c) = &block.content else {
+            unreachable!()
+        };
+        assert_eq!(c.line_range, Some((41, 43)));
+        assert_eq!(trailer.as_deref(), Some("# elided: lines 44-50 ~14tok"));
+    }
+
+    #[test]
+    fn head_window_line_range_excludes_marker() {
+        for mode in [OutputMode::Xml, OutputMode::Markdown, OutputMode::Minimal] {
+            let (block, _) = truncate_block(&code(Some((41, 50))), 3, 0, 14, mode);
+            let BlockContent::Code(c) = &block.content else {
+                unreachable!()
+            };
+            let (start, end) = c.line_range.unwrap();
+            let text = body(&block);
+            let (kept, marker) = text.rsplit_once('\n').unwrap();
+            // The range spans exactly the source lines before the marker,
+            // and the marker picks up at the next line.
+            assert_eq!(kept.lines().count(), (end - start + 1) as usize, "{mode:?}");
+            assert!(marker.contains(&format!("{}-50", end + 1)), "{mode:?}");
+        }
+    }
+}
@@ -38,7 +38,7 @@ fn full_pipeline_encode_decode_render() {
 
     // ── XML mode (full content, no budget) ──────────────────────────
     let xml_config = DriverConfig {
-        mode: OutputMode::Xml,
+        mode: Some(OutputMode::Xml),
         ..DriverConfig::default()
     };
     let xml = driver
@@ -61,7 +61,7 @@ fn full_pipeline_encode_decode_render() {
 
     // ── Markdown mode ─────────────────────────────────────────────
     let md_config = DriverConfig {
-        mode: OutputMode::Markdown,
+        mode: Some(OutputMode::Markdown),
         ..DriverConfig::default()
     };
     let md = driver
@@ -75,7 +75,7 @@ fn full_pipeline_encode_decode_render() {
 
     // ── Minimal mode ──────────────────────────────────────────────
     let min_config = DriverConfig {
-        mode: OutputMode::Minimal,
+        mode: Some(OutputMode::Minimal),
         ..DriverConfig::default()
     };
     let min = driver
@@ -110,7 +110,7 @@ fn pipeline_with_summary_verbosity() {
     let driver = DefaultDriver;
 
     let config = DriverConfig {
-        mode: OutputMode::Xml,
+        mode: Some(OutputMode::Xml),
         verbosity: Verbosity::Summary,
         ..DriverConfig::default()
     };
@@ -147,7 +147,7 @@ fn pipeline_with_type_filter() {
     let driver = DefaultDriver;
 
     let config = DriverConfig {
-        mode: OutputMode::Minimal,
+        mode: Some(OutputMode::Minimal),
         include_types: Some(vec![bcp_types::BlockType::Conversation]),
         ..DriverConfig::default()
     };
@@ -188,7 +188,7 @@ fn roundtrip_with_budget() {
     // Budget of 250: enough for one full block (~200 tokens) + one summary (~10),
     // but not enough for two full blocks (~400)
     let config = DriverConfig {
-        mode: OutputMode::Xml,
+        mode: Some(OutputMode::Xml),
         token_budget: Some(250),
         verbosity: Verbosity::Adaptive,
         ..DriverConfig::default()
@@ -228,12 +228,12 @@ fn adaptive_mode_without_budget_matches_full() {
     let driver = DefaultDriver;
 
     let adaptive_config = DriverConfig {
-        mode: OutputMode::Xml,
+        mode: Some(OutputMode::Xml),
         verbosity: Verbosity::Adaptive,
         ..DriverConfig::default()
     };
     let full_config = DriverConfig {
-        mode: OutputMode::Xml,
+        mode: Some(OutputMode::Xml),
         verbosity: Verbosity::Full,
         ..DriverConfig::default()
     };
@@ -277,7 +277,7 @@ fn mixed_priorities_budget_allocation() {
     // Budget of 250: enough for Critical (~133tok code) + High (~133tok) but
     // Normal and Background must degrade. Using CodeAwareEstimator (chars/3 for code).
     let config = DriverConfig {
-        mode: OutputMode::Minimal,
+        mode: Some(OutputMode::Minimal),
         token_budget: Some(250),
         verbosity: Verbosity::Adaptive,
         ..DriverConfig::default()
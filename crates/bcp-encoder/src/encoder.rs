@@ -1,7 +1,8 @@
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use bcp_types::BlockContent;
-use bcp_types::annotation::AnnotationBlock;
+use bcp_types::annotation::{AnnotationBlock, Provenance};
 use bcp_types::code::CodeBlock;
 use bcp_types::content_store::ContentStore;
 use bcp_types::conversation::ConversationBlock;
@@ -9,7 +10,8 @@ use bcp_types::diff::{DiffBlock, DiffHunk};
 use bcp_types::embedding_ref::EmbeddingRefBlock;
 use bcp_types::document::DocumentBlock;
 use bcp_types::enums::{
-    AnnotationKind, DataFormat, FormatHint, Lang, MediaType, Priority, Role, Status,
+    AnnotationKind, DataFormat, DiffStatus, FormatHint, Lang, LinkKind, MediaType, Priority, Role,
+    Status,
 };
 use bcp_types::extension::ExtensionBlock;
 use bcp_types::file_tree::{FileEntry, FileTreeBlock};
@@ -22,6 +24,7 @@ use bcp_wire::header::{HEADER_SIZE, HeaderFlags, BcpHeader};
 
 use crate::compression::{self, COMPRESSION_THRESHOLD};
 use crate::error::EncodeError;
+use crate::validation::{self, ValidationOptions};
 
 /// Maximum block body size (16 MiB). Blocks exceeding this limit produce
 /// an [`EncodeError::BlockTooLarge`] during `.encode()`.
@@ -135,6 +138,8 @@ pub struct BcpEncoder {
     /// When `true`, automatically content-address any block whose body
     /// has been seen before (hash already exists in the store).
     auto_dedup: bool,
+    /// Opt-in content checks run during `.encode()`.
+    validation: ValidationOptions,
 }
 
 /// Internal representation of a block awaiting serialization.
@@ -174,6 +179,7 @@ impl BcpEncoder {
             compress_all_blocks: false,
             content_store: None,
             auto_dedup: false,
+            validation: ValidationOptions::default(),
         }
     }
 
@@ -204,6 +210,15 @@ impl BcpEncoder {
         )
     }
 
+    /// Add a CODE block, detecting the language from `path` and `content`.
+    ///
+    /// Uses [`Lang::detect`]: the file name or extension first, then a
+    /// shebang or editor modeline in the content. Falls back to
+    /// [`Lang::Unknown`] when nothing matches.
+    pub fn add_code_auto(&mut self, path: &str, content: &[u8]) -> &mut Self {
+        self.add_code(Lang::detect(path, content), path, content)
+    }
+
     /// Add a CODE block with a line range.
     ///
     /// Same as [`add_code`](Self::add_code) but includes `line_start` and
@@ -346,10 +361,27 @@ impl BcpEncoder {
             BlockContent::Diff(DiffBlock {
                 path: path.to_string(),
                 hunks,
+                old_path: None,
+                status: DiffStatus::Modified,
             }),
         )
     }
 
+    /// Add a pre-built DIFF block.
+    ///
+    /// Use this for diffs that carry a rename (`old_path`) or a non-default
+    /// [`DiffStatus`], typically the output of
+    /// [`DiffBlock::parse_unified`](bcp_types::diff::DiffBlock::parse_unified):
+    ///
+    /// ```rust,ignore
+    /// for diff in DiffBlock::parse_unified(&git_diff_output)? {
+    ///     encoder.add_diff_block(diff);
+    /// }
+    /// ```
+    pub fn add_diff_block(&mut self, diff: DiffBlock) -> &mut Self {
+        self.push_block(block_type::DIFF, BlockContent::Diff(diff))
+    }
+
     /// Add an ANNOTATION block.
     ///
     /// Annotations are metadata overlays that target another block by its
@@ -414,10 +446,38 @@ impl BcpEncoder {
                 media_type,
                 alt_text: alt_text.to_string(),
                 data: data.to_vec(),
+                uri: None,
+                width: None,
+                height: None,
             }),
         )
     }
 
+    /// Add an IMAGE block that references external pixels by URI.
+    ///
+    /// The `uri` is a location (`file://`, `https://`) or a content-store
+    /// hash (`blake3:<hex>`). No image bytes are carried in the payload.
+    pub fn add_image_uri(&mut self, media_type: MediaType, alt_text: &str, uri: &str) -> &mut Self {
+        self.add_image_block(ImageBlock::from_uri(media_type, alt_text, uri))
+    }
+
+    /// Add a pre-built IMAGE block.
+    ///
+    /// Use this for images that carry dimensions or whose media type was
+    /// sniffed with [`ImageBlock::from_bytes`]:
+    ///
+    /// ```rust,ignore
+    /// let image = ImageBlock {
+    ///     width: Some(640),
+    ///     height: Some(480),
+    ///     ..ImageBlock::from_bytes("logo", &png)?
+    /// };
+    /// encoder.add_image_block(image);
+    /// ```
+    pub fn add_image_block(&mut self, image: ImageBlock) -> &mut Self {
+        self.push_block(block_type::IMAGE, BlockContent::Image(image))
+    }
+
     /// Add an EXTENSION block.
     ///
     /// User-defined block type for custom payloads. The `namespace` and
@@ -460,10 +520,38 @@ impl BcpEncoder {
         Ok(self)
     }
 
+    /// Attach the outline of the most recently added CODE block as its
+    /// summary.
+    ///
+    /// The outline keeps the code's signatures, type definitions,
+    /// imports and doc comments, eliding function bodies (see
+    /// [`bcp_types::outline::outline`]), so a file without an authored
+    /// summary still degrades to its API surface rather than a
+    /// placeholder. Blocks that are not code, or have no outline, are
+    /// left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`EncodeError::NoBlockTarget`] if no blocks have been
+    /// added yet.
+    pub fn with_outline_summary(&mut self) -> Result<&mut Self, EncodeError> {
+        let block = self.blocks.last_mut().ok_or(EncodeError::NoBlockTarget {
+            method: "with_outline_summary",
+        })?;
+        if let BlockContent::Code(code) = &block.content
+            && let Some(outline) = code.outline()
+        {
+            block.summary = Some(outline);
+        }
+        Ok(self)
+    }
+
     /// Attach a priority annotation to the most recently added block.
     ///
     /// This is a convenience method that appends an ANNOTATION block
     /// with `kind=Priority` targeting the last added block's index.
+    /// Annotations are skipped when finding that block, so this chains
+    /// with the other annotation modifiers below.
     /// The annotation's value is the priority byte (e.g. `0x02` for
     /// `Priority::High`).
     ///
@@ -472,22 +560,130 @@ impl BcpEncoder {
     /// Returns [`EncodeError::NoBlockTarget`] if no blocks have been
     /// added yet.
     pub fn with_priority(&mut self, priority: Priority) -> Result<&mut Self, EncodeError> {
+        self.annotate_last("with_priority", |target_block_id| AnnotationBlock {
+            target_block_id,
+            kind: AnnotationKind::Priority,
+            value: vec![priority.to_wire_byte()],
+        })
+    }
+
+    /// Attach a relevance score to the most recently added block.
+    ///
+    /// `score` runs from `0.0` (barely relevant) to `1.0` (exactly what
+    /// was asked for) and is clamped to that range. The budget engine
+    /// uses it to order blocks within the same priority tier.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`EncodeError::NoBlockTarget`] if no blocks have been
+    /// added yet.
+    pub fn with_relevance(&mut self, score: f32) -> Result<&mut Self, EncodeError> {
+        self.annotate_last("with_relevance", |target| {
+            AnnotationBlock::relevance(target, score)
+        })
+    }
+
+    /// Record where the most recently added block's content came from.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`EncodeError::NoBlockTarget`] if no blocks have been
+    /// added yet.
+    pub fn with_provenance(&mut self, source: &Provenance) -> Result<&mut Self, EncodeError> {
+        self.annotate_last("with_provenance", |target| {
+            AnnotationBlock::provenance(target, source)
+        })
+    }
+
+    /// Mark the most recently added block as stale from `expires_at`
+    /// (seconds since the Unix epoch). The driver drops expired blocks.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`EncodeError::NoBlockTarget`] if no blocks have been
+    /// added yet.
+    pub fn with_expiry(&mut self, expires_at: u64) -> Result<&mut Self, EncodeError> {
+        self.annotate_last("with_expiry", |target| {
+            AnnotationBlock::expiry(target, expires_at)
+        })
+    }
+
+    /// Mark the most recently added block as stale `ttl` from now.
+    ///
+    /// Shorthand for [`with_expiry`](Self::with_expiry) with the current
+    /// system time plus `ttl`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`EncodeError::NoBlockTarget`] if no blocks have been
+    /// added yet.
+    pub fn with_ttl(&mut self, ttl: Duration) -> Result<&mut Self, EncodeError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.annotate_last("with_ttl", |target| {
+            AnnotationBlock::expiry(target, now.saturating_add(ttl.as_secs()))
+        })
+    }
+
+    /// Link the most recently added block to block `to`, e.g. a test
+    /// file that [`LinkKind::Tests`] an earlier CODE block.
+    ///
+    /// `to` is a stream index as returned by
+    /// [`next_block_id`](Self::next_block_id). Use
+    /// [`add_link`](Self::add_link) to link blocks added out of order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`EncodeError::NoBlockTarget`] if no blocks have been
+    /// added yet.
+    pub fn with_link(&mut self, kind: LinkKind, to: u32) -> Result<&mut Self, EncodeError> {
+        self.annotate_last("with_link", |target| AnnotationBlock::link(target, kind, to))
+    }
+
+    /// Add a `Link` annotation between two arbitrary blocks.
+    pub fn add_link(&mut self, from: u32, kind: LinkKind, to: u32) -> &mut Self {
+        self.push_block(
+            block_type::ANNOTATION,
+            BlockContent::Annotation(AnnotationBlock::link(from, kind, to)),
+        )
+    }
+
+    /// Stream index the next added block will have.
+    ///
+    /// Annotations occupy stream positions too, so this is the count of
+    /// all blocks added so far. Record it before an `.add_*()` call to
+    /// refer to that block from a later link or annotation.
+    #[must_use]
+    pub fn next_block_id(&self) -> u32 {
+        #[allow(clippy::cast_possible_truncation)]
+        let id = self.blocks.len() as u32;
+        id
+    }
+
+    /// Append an ANNOTATION built by `make` that targets the most
+    /// recently added non-annotation block.
+    ///
+    /// Skipping annotations lets modifiers chain: in
+    /// `.add_code(..).with_priority(..)?.with_relevance(..)?` both
+    /// annotations target the CODE block, not each other.
+    fn annotate_last(
+        &mut self,
+        method: &'static str,
+        make: impl FnOnce(u32) -> AnnotationBlock,
+    ) -> Result<&mut Self, EncodeError> {
         let target_index = self
             .blocks
-            .len()
-            .checked_sub(1)
-            .ok_or(EncodeError::NoBlockTarget { method: "with_priority" })?;
+            .iter()
+            .rposition(|b| b.block_type != block_type::ANNOTATION)
+            .ok_or(EncodeError::NoBlockTarget { method })?;
 
         #[allow(clippy::cast_possible_truncation)]
         let target_id = target_index as u32;
 
         self.push_block(
             block_type::ANNOTATION,
-            BlockContent::Annotation(AnnotationBlock {
-                target_block_id: target_id,
-                kind: AnnotationKind::Priority,
-                value: vec![priority.to_wire_byte()],
-            }),
+            BlockContent::Annotation(make(target_id)),
         );
         Ok(self)
     }
@@ -621,6 +817,19 @@ impl BcpEncoder {
         self
     }
 
+    /// Validate `STRUCTURED_DATA` content against its declared format.
+    ///
+    /// When set, `.encode()` parses every `STRUCTURED_DATA` block with
+    /// [`validation::validate`](crate::validation::validate) and fails
+    /// with [`EncodeError::InvalidStructuredData`] on the first block that
+    /// does not parse. JSON, NDJSON and XML are checked; YAML, TOML and
+    /// CSV pass through unchecked. Off by default, since parsing large
+    /// payloads has a cost the caller may not want to pay.
+    pub fn validate_structured_data(&mut self) -> &mut Self {
+        self.validation.structured_data = true;
+        self
+    }
+
     // ── Encode ──────────────────────────────────────────────────────────
 
     /// Serialize all accumulated blocks into a complete BCP payload.
@@ -655,6 +864,11 @@ impl BcpEncoder {
     /// - [`EncodeError::BlockTooLarge`] if any block body exceeds 16 MiB.
     /// - [`EncodeError::MissingContentStore`] if content addressing is
     ///   requested but no store has been configured.
+    /// - [`EncodeError::InvalidStructuredData`] if
+    ///   [`validate_structured_data`](Self::validate_structured_data) is
+    ///   set and a `STRUCTURED_DATA` block does not parse.
+    /// - [`EncodeError::InvalidImage`] if an inline image's magic bytes
+    ///   identify a different format than its declared `media_type`.
     /// - [`EncodeError::Wire`] if the underlying wire serialization fails.
     /// - [`EncodeError::Io`] if writing to the output buffer fails.
     pub fn encode(&self) -> Result<Vec<u8>, EncodeError> {
@@ -669,6 +883,30 @@ impl BcpEncoder {
             return Err(EncodeError::MissingContentStore);
         }
 
+        // Checking image signatures only reads a few magic bytes, so
+        // unlike structured-data validation it is always on.
+        for (block_index, pending) in self.blocks.iter().enumerate() {
+            if let BlockContent::Image(ref image) = pending.content {
+                image
+                    .check_media_type()
+                    .map_err(|source| EncodeError::InvalidImage { block_index, source })?;
+            }
+        }
+
+        if self.validation.structured_data {
+            for (block_index, pending) in self.blocks.iter().enumerate() {
+                if let BlockContent::StructuredData(ref data) = pending.content {
+                    validation::validate(data.format, &data.content).map_err(|reason| {
+                        EncodeError::InvalidStructuredData {
+                            block_index,
+                            format: data.format,
+                            reason,
+                        }
+                    })?;
+                }
+            }
+        }
+
         // Pre-allocate: 8 bytes header + estimated block data + END sentinel.
         let estimated_size = HEADER_SIZE + self.blocks.len() * 256 + 3;
         let mut output = Vec::with_capacity(estimated_size);
@@ -841,8 +1079,8 @@ impl Default for BcpEncoder {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bcp_types::file_tree::FileEntryKind;
     use bcp_wire::header::BCP_MAGIC;
+    use bcp_types::error::TypeError;
 
     // ── Helper ──────────────────────────────────────────────────────────
 
@@ -932,6 +1170,49 @@ mod tests {
         assert_eq!(annotation.value, vec![Priority::High.to_wire_byte()]);
     }
 
+    #[test]
+    fn typed_annotation_modifiers_target_last_block() {
+        let mut encoder = BcpEncoder::new();
+        let lib = encoder.next_block_id();
+        encoder.add_code(Lang::Rust, "src/lib.rs", b"pub fn f() {}");
+        let test = encoder.next_block_id();
+        encoder
+            .add_code(Lang::Rust, "tests/f.rs", b"#[test] fn t() {}")
+            .with_relevance(0.9).unwrap()
+            .with_provenance(&Provenance::Commit("9d19b6b".into())).unwrap()
+            .with_expiry(1_700_000_000).unwrap()
+            .with_link(LinkKind::Tests, lib).unwrap();
+        assert_eq!((lib, test), (0, 1));
+        let payload = encoder.encode().unwrap();
+
+        let mut cursor = HEADER_SIZE;
+        let mut annotations = Vec::new();
+        while let Some((frame, n)) = BlockFrame::read_from(&payload[cursor..]).unwrap() {
+            if frame.block_type == block_type::ANNOTATION {
+                annotations.push(AnnotationBlock::decode_body(&frame.body).unwrap());
+            }
+            cursor += n;
+        }
+        assert_eq!(annotations.len(), 4);
+        assert!(annotations.iter().all(|a| a.target_block_id == test));
+        assert_eq!(annotations[0].as_relevance(), Some(0.9));
+        assert_eq!(
+            annotations[1].as_provenance(),
+            Some(Provenance::Commit("9d19b6b".into()))
+        );
+        assert_eq!(annotations[2].as_expiry(), Some(1_700_000_000));
+        assert_eq!(annotations[3].as_link(), Some((LinkKind::Tests, lib)));
+    }
+
+    #[test]
+    fn typed_annotation_modifiers_require_a_block() {
+        let result = BcpEncoder::new().with_relevance(0.5).map(|_| ());
+        assert!(matches!(
+            result,
+            Err(EncodeError::NoBlockTarget { method: "with_relevance" })
+        ));
+    }
+
     #[test]
     fn empty_encoder_returns_empty_payload_error() {
         let result = BcpEncoder::new().encode();
@@ -955,12 +1236,7 @@ mod tests {
             .add_conversation(Role::User, b"hello")
             .add_file_tree(
                 "/project",
-                vec![FileEntry {
-                    name: "lib.rs".to_string(),
-                    kind: FileEntryKind::File,
-                    size: 100,
-                    children: vec![],
-                }],
+                vec![FileEntry::file("lib.rs", 100)],
             )
             .add_tool_result("rg", Status::Ok, b"found 3 matches")
             .add_document("README", b"# Title", FormatHint::Markdown)
@@ -969,7 +1245,9 @@ mod tests {
                 "src/lib.rs",
                 vec![DiffHunk {
                     old_start: 1,
+                    old_lines: 0,
                     new_start: 1,
+                    new_lines: 1,
                     lines: b"+new line\n".to_vec(),
                 }],
             )
@@ -1037,6 +1315,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn add_code_auto_detects_language() {
+        let payload = BcpEncoder::new()
+            .add_code_auto("infra/main.tf", b"resource \"x\" \"y\" {}")
+            .add_code_auto("bin/deploy", b"#!/usr/bin/env bash\nset -e\n")
+            .encode()
+            .unwrap();
+
+        let (first, n) = BlockFrame::read_from(&payload[HEADER_SIZE..])
+            .unwrap()
+            .unwrap();
+        let (second, _) = BlockFrame::read_from(&payload[HEADER_SIZE + n..])
+            .unwrap()
+            .unwrap();
+        assert_eq!(CodeBlock::decode_body(&first.body).unwrap().lang, Lang::Hcl);
+        assert_eq!(CodeBlock::decode_body(&second.body).unwrap().lang, Lang::Shell);
+    }
+
     #[test]
     fn optional_fields_omitted_when_none() {
         // CODE block without line_range
@@ -1121,6 +1417,39 @@ mod tests {
         assert_eq!(code.content, b"fn main() {}");
     }
 
+    #[test]
+    fn with_outline_summary_summarizes_code() {
+        let source = b"/// Entry point.\nfn main() {\n    run();\n}\n";
+        let payload = BcpEncoder::new()
+            .add_code(Lang::Rust, "main.rs", source)
+            .with_outline_summary().unwrap()
+            .add_document("Notes", b"# Notes\ntext", FormatHint::Markdown)
+            .with_outline_summary().unwrap()
+            .encode()
+            .unwrap();
+
+        let (frame, n) = BlockFrame::read_from(&payload[HEADER_SIZE..])
+            .unwrap()
+            .unwrap();
+        assert!(frame.flags.has_summary());
+        let (summary, consumed) = Summary::decode(&frame.body).unwrap();
+        assert_eq!(summary.text, "/// Entry point.\nfn main() { ... }");
+        let code = CodeBlock::decode_body(&frame.body[consumed..]).unwrap();
+        assert_eq!(code.content, source);
+
+        // Only code is outlined.
+        let (frame, _) = BlockFrame::read_from(&payload[HEADER_SIZE + n..])
+            .unwrap()
+            .unwrap();
+        assert!(!frame.flags.has_summary());
+
+        let result = BcpEncoder::new().with_outline_summary().map(|_| ());
+        assert!(matches!(
+            result,
+            Err(EncodeError::NoBlockTarget { method: "with_outline_summary" })
+        ));
+    }
+
     #[test]
     fn rfc_example_encodes_successfully() {
         // Reproduces the example from RFC §12.1 / SPEC_03 §1
@@ -1336,6 +1665,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn validate_structured_data_rejects_malformed_xml() {
+        let result = BcpEncoder::new()
+            .add_code(Lang::Rust, "main.rs", b"fn main() {}")
+            .add_structured_data(DataFormat::Xml, b"<config><a></config>")
+            .validate_structured_data()
+            .encode();
+
+        assert!(matches!(
+            result,
+            Err(EncodeError::InvalidStructuredData {
+                block_index: 1,
+                format: DataFormat::Xml,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn image_media_type_mismatch_rejected() {
+        let result = BcpEncoder::new()
+            .add_image(MediaType::Jpeg, "logo", b"\x89PNG\r\n\x1A\n\0\0\0\rIHDR")
+            .encode();
+
+        assert!(matches!(
+            result,
+            Err(EncodeError::InvalidImage {
+                block_index: 0,
+                source: TypeError::MediaTypeMismatch {
+                    declared: MediaType::Jpeg,
+                    detected: MediaType::Png,
+                },
+            })
+        ));
+    }
+
+    #[test]
+    fn structured_data_unvalidated_by_default() {
+        let result = BcpEncoder::new()
+            .add_structured_data(DataFormat::Ndjson, b"{not json}\n")
+            .encode();
+        assert!(result.is_ok());
+    }
+
     #[test]
     fn auto_dedup_detects_duplicate_blocks() {
         let store = Arc::new(crate::MemoryContentStore::new());
@@ -1448,6 +1821,7 @@ mod tests {
         // A realistic 50-line Rust file should compress by >= 20%.
         let rust_code = r#"use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub struct Config {
     pub name: String,
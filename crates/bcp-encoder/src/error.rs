@@ -1,3 +1,5 @@
+use bcp_types::enums::DataFormat;
+use bcp_types::error::TypeError;
 use bcp_wire::WireError;
 
 /// Errors specific to zstd compression and decompression.
@@ -38,6 +40,8 @@ pub enum CompressionError {
 ///   ├── BlockTooLarge        ← single block body exceeds size limit
 ///   ├── NoBlockTarget        ← modifier called with no preceding block
 ///   ├── MissingContentStore  ← content addressing enabled without a store
+///   ├── InvalidStructuredData ← opt-in format validation failed
+///   ├── InvalidImage         ← inline bytes contradict the media type
 ///   ├── Compression(…)       ← from zstd compress/decompress
 ///   ├── Wire(WireError)      ← from bcp-wire serialization
 ///   └── Io(std::io::Error)   ← from underlying I/O writes
@@ -56,6 +60,19 @@ pub enum EncodeError {
     #[error("content addressing requires a content store (call set_content_store first)")]
     MissingContentStore,
 
+    #[error("block {block_index}: invalid {format:?} structured data: {reason}")]
+    InvalidStructuredData {
+        block_index: usize,
+        format: DataFormat,
+        reason: String,
+    },
+
+    #[error("block {block_index}: {source}")]
+    InvalidImage {
+        block_index: usize,
+        source: TypeError,
+    },
+
     #[error(transparent)]
     Compression(#[from] CompressionError),
 
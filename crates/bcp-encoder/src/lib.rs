@@ -5,6 +5,7 @@ pub mod compression;
 pub mod content_store;
 pub mod encoder;
 pub mod error;
+pub mod validation;
 
 pub use content_store::MemoryContentStore;
 pub use encoder::BcpEncoder;
@@ -0,0 +1,179 @@
+use bcp_types::enums::DataFormat;
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+/// Opt-in content checks the encoder runs during `.encode()`.
+///
+/// All checks are off by default; each is switched on by its builder
+/// method on [`BcpEncoder`](crate::BcpEncoder).
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ValidationOptions {
+    /// Parse `STRUCTURED_DATA` content against its declared format.
+    pub structured_data: bool,
+}
+
+/// Check that `STRUCTURED_DATA` content parses as its declared format.
+///
+/// Used by [`BcpEncoder::validate_structured_data`](crate::BcpEncoder::validate_structured_data)
+/// to reject mislabelled payloads before they reach a model.
+///
+/// ```text
+/// ┌────────┬──────────────────────────────────────────────┐
+/// │ Format │ Check                                        │
+/// ├────────┼──────────────────────────────────────────────┤
+/// │ Json   │ Parses as a single JSON value (serde_json)   │
+/// │ Ndjson │ Every non-blank line parses as a JSON value  │
+/// │ Xml    │ Well-formed (quick-xml), one root element    │
+/// │ Yaml   │ Not checked                                  │
+/// │ Toml   │ Not checked                                  │
+/// │ Csv    │ Not checked                                  │
+/// └────────┴──────────────────────────────────────────────┘
+/// ```
+///
+/// The XML check covers syntax and element nesting only — it does not
+/// resolve entities, validate against a DTD, or apply a schema.
+///
+/// # Errors
+///
+/// Returns a human-readable reason when the content does not parse.
+pub fn validate(format: DataFormat, content: &[u8]) -> Result<(), String> {
+    match format {
+        DataFormat::Json => serde_json::from_slice::<serde_json::Value>(content)
+            .map(drop)
+            .map_err(|e| e.to_string()),
+        DataFormat::Ndjson => validate_ndjson(as_utf8(content)?),
+        DataFormat::Xml => validate_xml(as_utf8(content)?),
+        DataFormat::Yaml | DataFormat::Toml | DataFormat::Csv => Ok(()),
+    }
+}
+
+fn as_utf8(content: &[u8]) -> Result<&str, String> {
+    std::str::from_utf8(content).map_err(|e| format!("not valid UTF-8: {e}"))
+}
+
+fn validate_ndjson(text: &str) -> Result<(), String> {
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        serde_json::from_str::<serde_json::Value>(line)
+            .map_err(|e| format!("line {}: {e}", i + 1))?;
+    }
+    Ok(())
+}
+
+fn validate_xml(text: &str) -> Result<(), String> {
+    let mut reader = Reader::from_str(text);
+    // End-tag names are matched against `open` below so the error can
+    // name both tags.
+    reader.config_mut().check_end_names = false;
+    let mut open: Vec<String> = Vec::new();
+    let mut roots = 0usize;
+
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| format!("at byte {}: {e}", reader.error_position()))?;
+        match event {
+            Event::Start(ref tag) | Event::Empty(ref tag) => {
+                for attr in tag.attributes() {
+                    attr.map_err(|e| e.to_string())?;
+                }
+                if open.is_empty() {
+                    roots += 1;
+                    if roots > 1 {
+                        return Err("multiple root elements".to_string());
+                    }
+                }
+                if let Event::Start(_) = event {
+                    open.push(String::from_utf8_lossy(tag.name().as_ref()).into_owned());
+                }
+            }
+            Event::End(ref tag) => {
+                let name = String::from_utf8_lossy(tag.name().as_ref()).into_owned();
+                // quick-xml itself rejects a close tag with nothing open.
+                if let Some(expected) = open.pop()
+                    && expected != name
+                {
+                    return Err(format!("mismatched </{name}>, expected </{expected}>"));
+                }
+            }
+            Event::Text(ref t) if open.is_empty() && !t.iter().all(u8::is_ascii_whitespace) => {
+                return Err("text outside the root element".to_string());
+            }
+            Event::CData(_) if open.is_empty() => {
+                return Err("text outside the root element".to_string());
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if let Some(name) = open.last() {
+        return Err(format!("unclosed <{name}>"));
+    }
+    if roots == 0 {
+        return Err("no root element".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_and_ndjson() {
+        assert!(validate(DataFormat::Json, b"{\"a\": [1, 2]}").is_ok());
+        assert!(validate(DataFormat::Json, b"{\"a\": ").is_err());
+
+        let ndjson = b"{\"id\": 1}\n\n{\"id\": 2}\n";
+        assert!(validate(DataFormat::Ndjson, ndjson).is_ok());
+        let err = validate(DataFormat::Ndjson, b"{\"id\": 1}\n{oops}\n").unwrap_err();
+        assert!(err.starts_with("line 2:"), "{err}");
+    }
+
+    #[test]
+    fn xml_well_formed() {
+        let doc = br#"<?xml version="1.0"?>
+<!-- config -->
+<config env="a>b">
+  <item id='1'/>
+  <note><![CDATA[<not a tag>]]></note>
+</config>
+"#;
+        assert!(validate(DataFormat::Xml, doc).is_ok());
+    }
+
+    #[test]
+    fn xml_syntax_errors() {
+        let cases: [&[u8]; 3] = [
+            b"<a><!-- never closed </a>",
+            b"<a><![CDATA[ open </a>",
+            b"<a x=\"1\" x=\"2\"/>",
+        ];
+        for doc in cases {
+            assert!(validate(DataFormat::Xml, doc).is_err(), "{doc:?}");
+        }
+    }
+
+    #[test]
+    fn xml_malformed() {
+        let cases: [(&[u8], &str); 5] = [
+            (b"<a><b></a></b>", "mismatched </a>, expected </b>"),
+            (b"<a>", "unclosed <a>"),
+            (b"<a/><b/>", "multiple root elements"),
+            (b"hello <a/>", "text outside the root element"),
+            (b"", "no root element"),
+        ];
+        for (doc, reason) in cases {
+            assert_eq!(validate(DataFormat::Xml, doc).unwrap_err(), reason);
+        }
+    }
+
+    #[test]
+    fn unchecked_formats_pass() {
+        assert!(validate(DataFormat::Yaml, b": : :").is_ok());
+        assert!(validate(DataFormat::Csv, b"a,b\n1").is_ok());
+    }
+}
@@ -31,7 +31,7 @@ fn bench_estimate_heuristic(c: &mut Criterion) {
     let payload = representative_payload();
     let decoded = BcpDecoder::decode(&payload).unwrap();
     let config = DriverConfig {
-        mode: OutputMode::Minimal,
+        mode: Some(OutputMode::Minimal),
         ..Default::default()
     };
     let output = DefaultDriver.render(&decoded.blocks, &config).unwrap();
@@ -46,7 +46,7 @@ fn bench_estimate_code_aware(c: &mut Criterion) {
     let payload = representative_payload();
     let decoded = BcpDecoder::decode(&payload).unwrap();
     let config = DriverConfig {
-        mode: OutputMode::Minimal,
+        mode: Some(OutputMode::Minimal),
         ..Default::default()
     };
     let output = DefaultDriver.render(&decoded.blocks, &config).unwrap();
@@ -70,7 +70,7 @@ fn bench_full_pipeline(c: &mut Criterion) {
 
             let decoded = BcpDecoder::decode(&payload).unwrap();
             let config = DriverConfig {
-                mode: OutputMode::Minimal,
+                mode: Some(OutputMode::Minimal),
                 ..Default::default()
             };
             let output = DefaultDriver.render(&decoded.blocks, &config).unwrap();
@@ -39,7 +39,7 @@ use bcp_types::diff::DiffHunk;
 use bcp_types::enums::{
     AnnotationKind, DataFormat, FormatHint, Lang, MediaType, Priority, Role, Status,
 };
-use bcp_types::file_tree::{FileEntry, FileEntryKind};
+use bcp_types::file_tree::FileEntry;
 
 fn main() {
     let manifest_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -163,18 +163,8 @@ fn generate_mixed_blocks(golden: &Path) {
         .add_file_tree(
             "src/",
             vec![
-                FileEntry {
-                    name: lib_name.to_string(),
-                    kind: FileEntryKind::File,
-                    size: 45,
-                    children: vec![],
-                },
-                FileEntry {
-                    name: main_name.to_string(),
-                    kind: FileEntryKind::File,
-                    size: 120,
-                    children: vec![],
-                },
+                FileEntry::file(lib_name, 45),
+                FileEntry::file(main_name, 120),
             ],
         )
         .encode()
@@ -402,12 +392,7 @@ fn generate_all_block_types(golden: &Path) {
         .add_conversation(Role::System, b"You are a helpful assistant.")
         .add_file_tree(
             "/",
-            vec![FileEntry {
-                name: go_file.clone(),
-                kind: FileEntryKind::File,
-                size: 12,
-                children: vec![],
-            }],
+            vec![FileEntry::file(go_file.clone(), 12)],
         )
         .add_tool_result("ls", Status::Ok, go_file.as_bytes())
         .add_document("README", "# BCP".as_bytes(), FormatHint::Markdown)
@@ -416,7 +401,9 @@ fn generate_all_block_types(golden: &Path) {
             &go_file,
             vec![DiffHunk {
                 old_start: 1,
+                old_lines: 1,
                 new_start: 1,
+                new_lines: 1,
                 lines: diff_old.into_bytes(),
             }],
         )
@@ -13,10 +13,13 @@
 //!   - 400 chars → 100 tokens
 
 use bcp_decoder::BcpDecoder;
-use bcp_driver::{DefaultDriver, DriverConfig, BcpDriver, OutputMode};
+use bcp_driver::{
+    BcpDriver, BudgetStrategy, DecisionReason, DefaultDriver, DriverConfig, DriverError,
+    OutputMode, RawEncoding,
+};
 use bcp_encoder::BcpEncoder;
 use bcp_types::BlockType;
-use bcp_types::enums::{Lang, Priority, Role};
+use bcp_types::enums::{Lang, Priority, Role, Status};
 
 // ── Test 1: Critical blocks always render even under extreme budget pressure ──
 
@@ -46,7 +49,7 @@ fn budget_critical_always_included() {
 
     let decoded = BcpDecoder::decode(&payload).unwrap();
     let config = DriverConfig {
-        mode: OutputMode::Xml,
+        mode: Some(OutputMode::Xml),
         token_budget: Some(10),
         ..Default::default()
     };
@@ -70,9 +73,11 @@ fn budget_critical_always_included() {
 
 #[test]
 fn budget_background_omitted_first() {
-    // Each block body is ~40 chars → ~10 tokens with HeuristicEstimator.
-    // Budget of 25 can fit ~2 full blocks. BACKGROUND should be the first
-    // casualty, so its unique marker should not appear as full content.
+    // Each block body is ~40 chars → ~10 tokens with HeuristicEstimator,
+    // about twice that once wrapped in its <code> element. Budget of 40
+    // fits the <context> root and ~1-2 full blocks. BACKGROUND should be
+    // the first casualty, so its unique marker should not appear as full
+    // content.
     //
     // Note: the driver uses CodeAwareEstimator (not Heuristic). Unindented
     // single-line code estimates at chars/4, same ratio as heuristic for
@@ -93,8 +98,8 @@ fn budget_background_omitted_first() {
 
     let decoded = BcpDecoder::decode(&payload).unwrap();
     let config = DriverConfig {
-        mode: OutputMode::Xml,
-        token_budget: Some(22),
+        mode: Some(OutputMode::Xml),
+        token_budget: Some(40),
         ..Default::default()
     };
     let output = DefaultDriver.render(&decoded.blocks, &config).unwrap();
@@ -162,7 +167,7 @@ fn budget_summary_used_under_pressure() {
 
     let decoded = BcpDecoder::decode(&payload).unwrap();
     let config = DriverConfig {
-        mode: OutputMode::Xml,
+        mode: Some(OutputMode::Xml),
         token_budget: Some(20),
         ..Default::default()
     };
@@ -190,7 +195,7 @@ fn budget_type_filter_independent_of_budget() {
 
     let decoded = BcpDecoder::decode(&payload).unwrap();
     let config = DriverConfig {
-        mode: OutputMode::Minimal,
+        mode: Some(OutputMode::Minimal),
         include_types: Some(vec![BlockType::Code]),
         token_budget: Some(30),
         ..Default::default()
@@ -239,7 +244,7 @@ fn budget_priority_ordering_verified() {
 
     let decoded = BcpDecoder::decode(&payload).unwrap();
     let config = DriverConfig {
-        mode: OutputMode::Minimal,
+        mode: Some(OutputMode::Minimal),
         token_budget: Some(110),
         ..Default::default()
     };
@@ -256,3 +261,102 @@ fn budget_priority_ordering_verified() {
         "BACKGROUND block full content must not appear when budget is tight; output:\n{output}"
     );
 }
+
+// ── Test 7: Relevance breaks ties within a priority tier ─────────────────────
+
+#[test]
+fn budget_relevance_orders_within_tier() {
+    // Both bodies are ~40 chars → ~10 tokens, ~17 with the `--- path ---`
+    // header line, and share the NORMAL tier. Budget of 24 fits only one
+    // in full. The later block carries a higher
+    // relevance score, so it must win the slot despite its stream position.
+    let payload = BcpEncoder::new()
+        .add_code(Lang::Rust, "first.rs", b"fn first() { /* UNSCORED_MARKER_1 */ }")
+        .add_code(Lang::Rust, "second.rs", b"fn second() { /* RELEVANT_MARKER_2 */ }")
+        .with_relevance(0.9).unwrap()
+        .encode()
+        .unwrap();
+
+    let decoded = BcpDecoder::decode(&payload).unwrap();
+    let config = DriverConfig {
+        mode: Some(OutputMode::Minimal),
+        token_budget: Some(24),
+        ..Default::default()
+    };
+    let output = DefaultDriver.render(&decoded.blocks, &config).unwrap();
+
+    assert!(
+        output.contains("RELEVANT_MARKER_2"),
+        "higher-relevance block must render in full; output:\n{output}"
+    );
+    assert!(
+        !output.contains("UNSCORED_MARKER_1"),
+        "unscored block must yield its slot to the relevant one; output:\n{output}"
+    );
+}
+
+// ── Test 8: Rendered output stays within budget, framing included ────────────
+
+#[test]
+fn budget_output_never_exceeds_budget() {
+    // Many small blocks: the markup around each (tags, fences, headers,
+    // separators) costs about as much as its body, so a budget engine that
+    // priced content alone would overshoot badly here. The estimate of the
+    // final text must stay within budget in every mode and strategy, unless
+    // a Critical or High block was forced in over it.
+    let mut encoder = BcpEncoder::new();
+    encoder
+        .add_code(Lang::Rust, "src/high.rs", b"pub fn high() -> u32 { 42 }")
+        .with_summary("Returns the answer.").unwrap()
+        .with_priority(Priority::High).unwrap();
+    for i in 0..12 {
+        let path = format!("src/m{i}.rs");
+        let body = format!("pub fn m{i}() -> u32 {{\n    {i}\n}}\n").repeat(1 + i % 3);
+        encoder.add_code(Lang::Rust, &path, body.as_bytes());
+        match i % 4 {
+            0 => { encoder.with_priority(Priority::Low).unwrap(); }
+            1 => { encoder.with_priority(Priority::Background).unwrap(); }
+            2 => { encoder.with_summary(&format!("Module {i}.")).unwrap(); }
+            _ => {}
+        }
+    }
+    encoder
+        .add_conversation(Role::User, b"Why do the modules disagree?")
+        .add_tool_result("cargo test", Status::Error, b"test m3 ... FAILED\n".repeat(6).as_slice());
+    let payload = encoder.encode().unwrap();
+    let decoded = BcpDecoder::decode(&payload).unwrap();
+
+    let modes = [
+        OutputMode::Xml,
+        OutputMode::Markdown,
+        OutputMode::Minimal,
+        OutputMode::Raw(RawEncoding::Base64),
+    ];
+    for mode in modes {
+        for strategy in [BudgetStrategy::Greedy, BudgetStrategy::Optimal] {
+            for budget in [10, 25, 50, 100, 200, 400] {
+                let config = DriverConfig {
+                    mode: Some(mode),
+                    token_budget: Some(budget),
+                    budget_strategy: strategy,
+                    ..Default::default()
+                };
+                let out = match DefaultDriver.render_with_report(&decoded.blocks, &config) {
+                    Ok(out) => out,
+                    Err(DriverError::EmptyInput) => continue,
+                    Err(err) => panic!("{mode:?} {strategy:?} {budget}: {err}"),
+                };
+                let forced = out.report.blocks.iter().any(|b| {
+                    matches!(b.reason, DecisionReason::Critical | DecisionReason::ForcedOverBudget)
+                });
+                assert_eq!(out.report.total_tokens, config.token_estimator().estimate(&out.text));
+                assert!(
+                    forced || out.report.total_tokens <= budget,
+                    "{mode:?} {strategy:?}: {} tokens over a budget of {budget}; output:\n{}",
+                    out.report.total_tokens,
+                    out.text
+                );
+            }
+        }
+    }
+}
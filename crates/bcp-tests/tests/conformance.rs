@@ -50,7 +50,7 @@ fn render(payload: &[u8], mode: OutputMode) -> String {
     let decoded = BcpDecoder::decode(payload)
         .unwrap_or_else(|e| panic!("decode failed for mode {mode:?}: {e}"));
     let config = DriverConfig {
-        mode,
+        mode: Some(mode),
         ..DriverConfig::default()
     };
     DefaultDriver
@@ -224,7 +224,7 @@ fn decode_content_addressed() -> Vec<bcp_types::block::Block> {
 fn content_addressed_xml() {
     let blocks = decode_content_addressed();
     let config = DriverConfig {
-        mode: OutputMode::Xml,
+        mode: Some(OutputMode::Xml),
         ..DriverConfig::default()
     };
     let output = DefaultDriver
@@ -237,7 +237,7 @@ fn content_addressed_xml() {
 fn content_addressed_markdown() {
     let blocks = decode_content_addressed();
     let config = DriverConfig {
-        mode: OutputMode::Markdown,
+        mode: Some(OutputMode::Markdown),
         ..DriverConfig::default()
     };
     let output = DefaultDriver
@@ -250,7 +250,7 @@ fn content_addressed_markdown() {
 fn content_addressed_minimal() {
     let blocks = decode_content_addressed();
     let config = DriverConfig {
-        mode: OutputMode::Minimal,
+        mode: Some(OutputMode::Minimal),
         ..DriverConfig::default()
     };
     let output = DefaultDriver
@@ -268,7 +268,7 @@ fn budget_render(mode: OutputMode, token_budget: u32) -> String {
     let payload = golden_payload("budget_constrained");
     let decoded = BcpDecoder::decode(&payload).expect("budget_constrained decode failed");
     let config = DriverConfig {
-        mode,
+        mode: Some(mode),
         token_budget: Some(token_budget),
         ..DriverConfig::default()
     };
@@ -309,7 +309,7 @@ fn all_block_types_text_only(mode: OutputMode) -> String {
     let payload = golden_payload("all_block_types");
     let decoded = BcpDecoder::decode(&payload).expect("all_block_types decode failed");
     let config = DriverConfig {
-        mode,
+        mode: Some(mode),
         include_types: Some(vec![
             BlockType::Code,
             BlockType::Conversation,
@@ -26,7 +26,7 @@ use bcp_types::diff::DiffHunk;
 use bcp_types::enums::{
     AnnotationKind, DataFormat, FormatHint, Lang, MediaType, Role, Status,
 };
-use bcp_types::file_tree::{FileEntry, FileEntryKind};
+use bcp_types::file_tree::FileEntry;
 
 // ── encode_from_blocks helper ────────────────────────────────────────────────
 
@@ -164,23 +164,8 @@ fn roundtrip_conversation_tool() {
 #[test]
 fn roundtrip_file_tree() {
     let entries = vec![
-        FileEntry {
-            name: "main.rs".to_string(),
-            kind: FileEntryKind::File,
-            size: 512,
-            children: vec![],
-        },
-        FileEntry {
-            name: "lib".to_string(),
-            kind: FileEntryKind::Directory,
-            size: 0,
-            children: vec![FileEntry {
-                name: "utils.rs".to_string(),
-                kind: FileEntryKind::File,
-                size: 128,
-                children: vec![],
-            }],
-        },
+        FileEntry::file("main.rs", 512),
+        FileEntry::dir("lib", vec![FileEntry::file("utils.rs", 128)]),
     ];
 
     let original = BcpEncoder::new()
@@ -241,7 +226,9 @@ fn roundtrip_structured_data() {
 fn roundtrip_diff() {
     let hunks = vec![DiffHunk {
         old_start: 5,
+        old_lines: 1,
         new_start: 5,
+        new_lines: 1,
         lines: b"-    old_value: u32,\n+    new_value: u64,\n".to_vec(),
     }];
 
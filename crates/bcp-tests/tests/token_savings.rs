@@ -20,14 +20,17 @@
 //! - `token_savings_vs_markdown`: HeuristicEstimator, asserts ≥30% savings
 //! - `code_aware_estimator_savings`: CodeAwareEstimator, asserts ≥25% savings
 //! - `xml_mode_vs_markdown`: XML mode vs markdown, asserts ≥5% savings
+//! - `path_prefix_factoring_savings`: monorepo paths declared once as a
+//!   common root, asserts ≥3% savings in Minimal and XML mode
 
 use bcp_decoder::BcpDecoder;
 use bcp_driver::{
     CodeAwareEstimator, DefaultDriver, DriverConfig, HeuristicEstimator, BcpDriver, OutputMode,
-    TokenEstimator,
+    PathPrefix, TokenEstimator,
 };
 use bcp_encoder::BcpEncoder;
 use bcp_types::block::{Block, BlockContent};
+use bcp_types::diff::DiffHunk;
 use bcp_types::enums::{Lang, Role, Status};
 use bcp_types::file_tree::{FileEntry, FileEntryKind};
 
@@ -148,29 +151,9 @@ func main() {\n\
 /// token-savings tests.
 fn build_representative_payload() -> Vec<u8> {
     let file_tree = vec![
-        FileEntry {
-            name: "main.rs".to_string(),
-            kind: FileEntryKind::File,
-            size: 312,
-            children: vec![],
-        },
-        FileEntry {
-            name: "lib.rs".to_string(),
-            kind: FileEntryKind::File,
-            size: 256,
-            children: vec![],
-        },
-        FileEntry {
-            name: "tests".to_string(),
-            kind: FileEntryKind::Directory,
-            size: 0,
-            children: vec![FileEntry {
-                name: "integration.rs".to_string(),
-                kind: FileEntryKind::File,
-                size: 128,
-                children: vec![],
-            }],
-        },
+        FileEntry::file("main.rs", 312),
+        FileEntry::file("lib.rs", 256),
+        FileEntry::dir("tests", vec![FileEntry::file("integration.rs", 128)]),
     ];
 
     BcpEncoder::new()
@@ -197,6 +180,32 @@ fn build_representative_payload() -> Vec<u8> {
         .unwrap()
 }
 
+/// Build a monorepo payload: the code files of the representative payload,
+/// all under one deep service directory, with a diff and a tool result.
+fn build_monorepo_payload() -> Vec<u8> {
+    BcpEncoder::new()
+        .add_code(Lang::Rust, "services/billing/src/server/main.rs", RUST_MAIN)
+        .add_code(Lang::Rust, "services/billing/src/pool/lib.rs", RUST_LIB)
+        .add_code(Lang::TypeScript, "services/billing/src/web/index.ts", TS_INDEX)
+        .add_code(Lang::Python, "services/billing/src/scripts/deploy.py", PY_DEPLOY)
+        .add_code(Lang::Go, "services/billing/src/cmd/server.go", GO_SERVER)
+        .add_diff(
+            "services/billing/src/pool/lib.rs",
+            vec![DiffHunk::new(
+                5,
+                5,
+                b"-    timeout_ms: u32,\n+    timeout_ms: u64,\n".to_vec(),
+            )],
+        )
+        .add_tool_result(
+            "cargo_test",
+            Status::Ok,
+            b"running 42 tests\ntest result: ok. 42 passed; 0 failed; 0 ignored",
+        )
+        .encode()
+        .unwrap()
+}
+
 // ── Markdown reference renderer ───────────────────────────────────────────────
 
 /// Render the same semantic content as conventional markdown.
@@ -355,6 +364,10 @@ fn render_markdown_tree(entries: &[FileEntry], depth: usize, out: &mut String) {
             FileEntryKind::File => {
                 out.push_str(&format!("{indent}{} ({} bytes)\n", entry.name, entry.size));
             }
+            FileEntryKind::Symlink => {
+                let target = entry.link_target.as_deref().unwrap_or("?");
+                out.push_str(&format!("{indent}{} -> {target}\n", entry.name));
+            }
         }
     }
 }
@@ -379,6 +392,20 @@ fn lang_name(lang: Lang) -> &'static str {
         Lang::Yaml => "yaml",
         Lang::Toml => "toml",
         Lang::Markdown => "markdown",
+        Lang::Kotlin => "kotlin",
+        Lang::Swift => "swift",
+        Lang::CSharp => "csharp",
+        Lang::Php => "php",
+        Lang::Scala => "scala",
+        Lang::Haskell => "haskell",
+        Lang::Elixir => "elixir",
+        Lang::Zig => "zig",
+        Lang::Lua => "lua",
+        Lang::Dockerfile => "dockerfile",
+        Lang::Makefile => "makefile",
+        Lang::Hcl => "hcl",
+        Lang::Protobuf => "protobuf",
+        Lang::GraphQL => "graphql",
         Lang::Unknown | Lang::Other(_) => "text",
     }
 }
@@ -392,7 +419,7 @@ fn token_savings_vs_markdown() {
     let estimator = HeuristicEstimator;
 
     let minimal_config = DriverConfig {
-        mode: OutputMode::Minimal,
+        mode: Some(OutputMode::Minimal),
         ..DriverConfig::default()
     };
     let minimal_output = DefaultDriver.render(&decoded.blocks, &minimal_config).unwrap();
@@ -427,7 +454,7 @@ fn code_aware_estimator_savings() {
     let estimator = CodeAwareEstimator;
 
     let minimal_config = DriverConfig {
-        mode: OutputMode::Minimal,
+        mode: Some(OutputMode::Minimal),
         ..DriverConfig::default()
     };
     let minimal_output = DefaultDriver.render(&decoded.blocks, &minimal_config).unwrap();
@@ -459,7 +486,7 @@ fn xml_mode_vs_markdown() {
     let estimator = HeuristicEstimator;
 
     let xml_config = DriverConfig {
-        mode: OutputMode::Xml,
+        mode: Some(OutputMode::Xml),
         ..DriverConfig::default()
     };
     let xml_output = DefaultDriver.render(&decoded.blocks, &xml_config).unwrap();
@@ -481,3 +508,53 @@ fn xml_mode_vs_markdown() {
          Markdown:\n{markdown}"
     );
 }
+
+#[test]
+fn path_prefix_factoring_savings() {
+    // Every header repeats `services/billing/src/`. Factoring declares it
+    // once — `--- root: services/billing/src ---` in Minimal mode (on by
+    // default), `<context root="...">` in XML mode (opt-in) — and shows
+    // each path relative to it. The code bodies dominate the token count,
+    // so the saving grows with the number of blocks and the path depth.
+    let payload = build_monorepo_payload();
+    let decoded = BcpDecoder::decode(&payload).unwrap();
+    let estimator = HeuristicEstimator;
+    let render = |mode: OutputMode, path_prefix: PathPrefix| {
+        let config = DriverConfig {
+            mode: Some(mode),
+            path_prefix,
+            ..DriverConfig::default()
+        };
+        DefaultDriver.render(&decoded.blocks, &config).unwrap()
+    };
+
+    for (mode, factor) in [
+        (OutputMode::Minimal, PathPrefix::Auto),
+        (OutputMode::Xml, PathPrefix::Factor),
+    ] {
+        let full = render(mode, PathPrefix::Full);
+        let factored = render(mode, factor);
+        assert!(factored.contains("root") && factored.contains("services/billing/src"));
+        assert_eq!(factored.matches("services/billing/src").count(), 1);
+
+        let full_tokens = estimator.estimate(&full);
+        let factored_tokens = estimator.estimate(&factored);
+        let savings_pct = (1.0 - factored_tokens as f64 / full_tokens as f64) * 100.0;
+
+        println!("{mode:?} full paths:     {full_tokens}");
+        println!("{mode:?} factored paths: {factored_tokens}");
+        println!("Savings:               {savings_pct:.1}%");
+
+        assert!(
+            savings_pct >= 3.0,
+            "Expected ≥3% savings from path factoring in {mode:?}, got {savings_pct:.1}%\n\
+             Factored output:\n{factored}"
+        );
+    }
+
+    // Auto leaves XML paths alone.
+    assert_eq!(
+        render(OutputMode::Xml, PathPrefix::Auto),
+        render(OutputMode::Xml, PathPrefix::Full)
+    );
+}
@@ -1,4 +1,8 @@
-use crate::enums::AnnotationKind;
+use std::fmt;
+
+use bcp_wire::varint::{decode_varint, encode_varint};
+
+use crate::enums::{AnnotationKind, LinkKind};
 use crate::error::TypeError;
 use crate::fields::{
     decode_bytes_value, decode_field_header, decode_varint_value, encode_bytes_field,
@@ -15,6 +19,18 @@ use crate::fields::{
 /// - `Priority`: value is a [`Priority`](crate::enums::Priority) byte
 /// - `Summary`: value is UTF-8 text summarizing the target block
 /// - `Tag`: value is a UTF-8 label/tag string
+/// - `Relevance`: value is an `f32` score in `0.0..=1.0`, little-endian
+/// - `Provenance`: value is a UTF-8 [`Provenance`] such as `tool:ripgrep`
+/// - `Expiry`: value is a `u64` Unix timestamp (seconds), little-endian
+/// - `Link`: value is a [`LinkKind`] byte followed by the varint index of
+///   the linked block
+///
+/// The typed constructors ([`relevance`](Self::relevance),
+/// [`provenance`](Self::provenance), [`expiry`](Self::expiry),
+/// [`link`](Self::link)) build these values, and the matching `as_*`
+/// accessors read them back. Accessors return `None` for a different
+/// kind or a malformed value, so consumers can skip bad annotations
+/// without failing the whole payload.
 ///
 /// Field layout within body:
 ///
@@ -35,6 +51,96 @@ pub struct AnnotationBlock {
 }
 
 impl AnnotationBlock {
+    /// A `Relevance` annotation. `score` is clamped to `0.0..=1.0`.
+    #[must_use]
+    pub fn relevance(target_block_id: u32, score: f32) -> Self {
+        Self {
+            target_block_id,
+            kind: AnnotationKind::Relevance,
+            value: score.clamp(0.0, 1.0).to_le_bytes().to_vec(),
+        }
+    }
+
+    /// A `Provenance` annotation recording where the target's content
+    /// came from.
+    #[must_use]
+    pub fn provenance(target_block_id: u32, source: &Provenance) -> Self {
+        Self {
+            target_block_id,
+            kind: AnnotationKind::Provenance,
+            value: source.to_string().into_bytes(),
+        }
+    }
+
+    /// An `Expiry` annotation. `expires_at` is in seconds since the Unix
+    /// epoch; the target is stale from that instant on.
+    #[must_use]
+    pub fn expiry(target_block_id: u32, expires_at: u64) -> Self {
+        Self {
+            target_block_id,
+            kind: AnnotationKind::Expiry,
+            value: expires_at.to_le_bytes().to_vec(),
+        }
+    }
+
+    /// A `Link` annotation: block `target_block_id` *kind* block `to`.
+    #[must_use]
+    pub fn link(target_block_id: u32, kind: LinkKind, to: u32) -> Self {
+        let mut value = vec![kind.to_wire_byte()];
+        let mut varint = [0u8; 10];
+        let n = encode_varint(u64::from(to), &mut varint);
+        value.extend_from_slice(&varint[..n]);
+        Self {
+            target_block_id,
+            kind: AnnotationKind::Link,
+            value,
+        }
+    }
+
+    /// The score of a well-formed `Relevance` annotation.
+    #[must_use]
+    pub fn as_relevance(&self) -> Option<f32> {
+        if self.kind != AnnotationKind::Relevance {
+            return None;
+        }
+        let score = f32::from_le_bytes(self.value.as_slice().try_into().ok()?);
+        (0.0..=1.0).contains(&score).then_some(score)
+    }
+
+    /// The source of a well-formed `Provenance` annotation.
+    #[must_use]
+    pub fn as_provenance(&self) -> Option<Provenance> {
+        if self.kind != AnnotationKind::Provenance {
+            return None;
+        }
+        Provenance::parse(std::str::from_utf8(&self.value).ok()?)
+    }
+
+    /// The expiry time of a well-formed `Expiry` annotation.
+    #[must_use]
+    pub fn as_expiry(&self) -> Option<u64> {
+        if self.kind != AnnotationKind::Expiry {
+            return None;
+        }
+        Some(u64::from_le_bytes(self.value.as_slice().try_into().ok()?))
+    }
+
+    /// The relationship and linked block index of a well-formed `Link`
+    /// annotation.
+    #[must_use]
+    pub fn as_link(&self) -> Option<(LinkKind, u32)> {
+        if self.kind != AnnotationKind::Link {
+            return None;
+        }
+        let (&kind, rest) = self.value.split_first()?;
+        let kind = LinkKind::from_wire_byte(kind).ok()?;
+        let (to, n) = decode_varint(rest).ok()?;
+        if n != rest.len() {
+            return None;
+        }
+        Some((kind, u32::try_from(to).ok()?))
+    }
+
     /// Serialize this block's fields into a TLV-encoded body.
     pub fn encode_body(&self) -> Vec<u8> {
         let mut buf = Vec::new();
@@ -87,6 +193,59 @@ impl AnnotationBlock {
     }
 }
 
+/// Where a block's content came from, carried by a `Provenance`
+/// annotation.
+///
+/// On the wire the value is `scheme:source` text:
+///
+/// ```text
+/// ┌─────────┬────────────────────────────────────┐
+/// │ Scheme  │ Example                            │
+/// ├─────────┼────────────────────────────────────┤
+/// │ tool    │ tool:ripgrep                       │
+/// │ url     │ url:https://docs.rs/serde          │
+/// │ commit  │ commit:9d19b6b                     │
+/// └─────────┴────────────────────────────────────┘
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Provenance {
+    /// Output of a named tool invocation.
+    Tool(String),
+    /// Fetched from a URL.
+    Url(String),
+    /// Taken from a VCS commit (hash or ref).
+    Commit(String),
+}
+
+impl Provenance {
+    /// Parse `scheme:source` text. Returns `None` for an unknown scheme
+    /// or an empty source.
+    #[must_use]
+    pub fn parse(text: &str) -> Option<Self> {
+        let (scheme, source) = text.split_once(':')?;
+        if source.is_empty() {
+            return None;
+        }
+        let source = source.to_string();
+        match scheme {
+            "tool" => Some(Self::Tool(source)),
+            "url" => Some(Self::Url(source)),
+            "commit" => Some(Self::Commit(source)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tool(s) => write!(f, "tool:{s}"),
+            Self::Url(s) => write!(f, "url:{s}"),
+            Self::Commit(s) => write!(f, "commit:{s}"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -126,4 +285,57 @@ mod tests {
         let decoded = AnnotationBlock::decode_body(&body).unwrap();
         assert_eq!(decoded, block);
     }
+
+    #[test]
+    fn typed_annotations_roundtrip() {
+        let relevance = AnnotationBlock::relevance(1, 0.75);
+        let provenance =
+            AnnotationBlock::provenance(2, &Provenance::Url("https://x.dev/a:b".into()));
+        let expiry = AnnotationBlock::expiry(3, 1_700_000_000);
+        let link = AnnotationBlock::link(4, LinkKind::Tests, 300);
+
+        for block in [&relevance, &provenance, &expiry, &link] {
+            let decoded = AnnotationBlock::decode_body(&block.encode_body()).unwrap();
+            assert_eq!(&decoded, block);
+        }
+        assert_eq!(relevance.as_relevance(), Some(0.75));
+        assert_eq!(
+            provenance.as_provenance(),
+            Some(Provenance::Url("https://x.dev/a:b".into()))
+        );
+        assert_eq!(expiry.as_expiry(), Some(1_700_000_000));
+        assert_eq!(link.as_link(), Some((LinkKind::Tests, 300)));
+
+        // Accessors are kind-checked.
+        assert_eq!(relevance.as_expiry(), None);
+        assert_eq!(AnnotationBlock::relevance(0, 7.0).as_relevance(), Some(1.0));
+    }
+
+    #[test]
+    fn malformed_typed_values_are_ignored() {
+        let bad = |kind, value: &[u8]| AnnotationBlock {
+            target_block_id: 0,
+            kind,
+            value: value.to_vec(),
+        };
+        assert_eq!(
+            bad(AnnotationKind::Relevance, b"\x00\x00").as_relevance(),
+            None
+        );
+        assert_eq!(
+            bad(AnnotationKind::Relevance, &f32::NAN.to_le_bytes()).as_relevance(),
+            None
+        );
+        assert_eq!(
+            bad(AnnotationKind::Provenance, b"ftp:x").as_provenance(),
+            None
+        );
+        assert_eq!(
+            bad(AnnotationKind::Provenance, b"tool:").as_provenance(),
+            None
+        );
+        assert_eq!(bad(AnnotationKind::Expiry, b"\x01").as_expiry(), None);
+        assert_eq!(bad(AnnotationKind::Link, b"\x09\x01").as_link(), None);
+        assert_eq!(bad(AnnotationKind::Link, b"\x01\x01\x02").as_link(), None);
+    }
 }
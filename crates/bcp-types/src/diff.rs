@@ -1,7 +1,8 @@
+use crate::enums::DiffStatus;
 use crate::error::TypeError;
 use crate::fields::{
     decode_bytes_value, decode_field_header, decode_varint_value, encode_bytes_field,
-    encode_nested_field, encode_varint_field, skip_field,
+    encode_nested_field, encode_varint_field, narrow_varint, skip_field,
 };
 
 /// DIFF block — represents code changes for a single file.
@@ -13,20 +14,30 @@ use crate::fields::{
 /// Field layout within body:
 ///
 /// ```text
-/// ┌──────────┬───────────┬───────┬───────────────────────────────┐
-/// │ Field ID │ Wire Type │ Name  │ Description                   │
-/// ├──────────┼───────────┼───────┼───────────────────────────────┤
-/// │ 1        │ Bytes     │ path  │ File path                     │
-/// │ 2        │ Nested    │ hunks │ Repeated DiffHunk             │
-/// └──────────┴───────────┴───────┴───────────────────────────────┘
+/// ┌──────────┬───────────┬──────────┬────────────────────────────────┐
+/// │ Field ID │ Wire Type │ Name     │ Description                    │
+/// ├──────────┼───────────┼──────────┼────────────────────────────────┤
+/// │ 1        │ Bytes     │ path     │ File path (post-change)        │
+/// │ 2        │ Nested    │ hunks    │ Repeated DiffHunk              │
+/// │ 3        │ Bytes     │ old_path │ Pre-rename path (optional)     │
+/// │ 4        │ Varint    │ status   │ DiffStatus byte (optional)     │
+/// └──────────┴───────────┴──────────┴────────────────────────────────┘
 /// ```
 ///
 /// Multiple hunks produce multiple field-2 occurrences (repeated field
 /// pattern, same as `FileEntry` in FILE_TREE).
+///
+/// Field 3 is only encoded when `old_path` is `Some`, and field 4 only
+/// when `status` is not [`DiffStatus::Modified`]. Payloads written before
+/// these fields existed therefore decode as plain in-place modifications.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DiffBlock {
     pub path: String,
     pub hunks: Vec<DiffHunk>,
+    /// Path before the change, set for renames and copies.
+    pub old_path: Option<String>,
+    /// File-level change status (added, deleted, renamed, modified).
+    pub status: DiffStatus,
 }
 
 /// A single contiguous range of changes within a diff.
@@ -40,25 +51,49 @@ pub struct DiffBlock {
 /// │ 1        │ Varint    │ old_start │ Start line in old file     │
 /// │ 2        │ Varint    │ new_start │ Start line in new file     │
 /// │ 3        │ Bytes     │ lines     │ Hunk content (unified fmt) │
+/// │ 4        │ Varint    │ old_lines │ Line count in old file     │
+/// │ 5        │ Varint    │ new_lines │ Line count in new file     │
 /// └──────────┴───────────┴───────────┴────────────────────────────┘
 /// ```
 ///
 /// The `lines` field contains the hunk body in unified diff format:
 /// lines prefixed with `+` (added), `-` (removed), or ` ` (context).
+///
+/// Together the four numeric fields form the `@@ -old_start,old_lines
+/// +new_start,new_lines @@` hunk header. When fields 4 and 5 are absent
+/// (payloads from older encoders) the counts are recomputed from `lines`.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DiffHunk {
     pub old_start: u32,
+    pub old_lines: u32,
     pub new_start: u32,
+    pub new_lines: u32,
     pub lines: Vec<u8>,
 }
 
 impl DiffHunk {
+    /// Build a hunk from its start lines and body, deriving the old/new
+    /// line counts from the `+`, `-` and ` ` prefixes in `lines`.
+    #[must_use]
+    pub fn new(old_start: u32, new_start: u32, lines: Vec<u8>) -> Self {
+        let (old_lines, new_lines) = count_hunk_lines(&lines);
+        Self {
+            old_start,
+            old_lines,
+            new_start,
+            new_lines,
+            lines,
+        }
+    }
+
     /// Encode this hunk into TLV bytes (used as nested field payload).
     fn encode(&self) -> Vec<u8> {
         let mut buf = Vec::new();
         encode_varint_field(&mut buf, 1, u64::from(self.old_start));
         encode_varint_field(&mut buf, 2, u64::from(self.new_start));
         encode_bytes_field(&mut buf, 3, &self.lines);
+        encode_varint_field(&mut buf, 4, u64::from(self.old_lines));
+        encode_varint_field(&mut buf, 5, u64::from(self.new_lines));
         buf
     }
 
@@ -67,6 +102,8 @@ impl DiffHunk {
         let mut old_start: Option<u32> = None;
         let mut new_start: Option<u32> = None;
         let mut lines: Option<Vec<u8>> = None;
+        let mut old_lines: Option<u32> = None;
+        let mut new_lines: Option<u32> = None;
 
         while !buf.is_empty() {
             let (header, n) = decode_field_header(buf)?;
@@ -88,6 +125,16 @@ impl DiffHunk {
                     buf = &buf[n..];
                     lines = Some(data.to_vec());
                 }
+                4 => {
+                    let (v, n) = decode_varint_value(buf)?;
+                    buf = &buf[n..];
+                    old_lines = Some(narrow_varint(v, "old_lines")?);
+                }
+                5 => {
+                    let (v, n) = decode_varint_value(buf)?;
+                    buf = &buf[n..];
+                    new_lines = Some(narrow_varint(v, "new_lines")?);
+                }
                 _ => {
                     let n = skip_field(buf, header.wire_type)?;
                     buf = &buf[n..];
@@ -95,10 +142,15 @@ impl DiffHunk {
             }
         }
 
+        let lines = lines.ok_or(TypeError::MissingRequiredField { field: "lines" })?;
+        let (counted_old, counted_new) = count_hunk_lines(&lines);
+
         Ok(Self {
             old_start: old_start.ok_or(TypeError::MissingRequiredField { field: "old_start" })?,
+            old_lines: old_lines.unwrap_or(counted_old),
             new_start: new_start.ok_or(TypeError::MissingRequiredField { field: "new_start" })?,
-            lines: lines.ok_or(TypeError::MissingRequiredField { field: "lines" })?,
+            new_lines: new_lines.unwrap_or(counted_new),
+            lines,
         })
     }
 }
@@ -111,6 +163,12 @@ impl DiffBlock {
         for hunk in &self.hunks {
             encode_nested_field(&mut buf, 2, &hunk.encode());
         }
+        if let Some(ref old_path) = self.old_path {
+            encode_bytes_field(&mut buf, 3, old_path.as_bytes());
+        }
+        if self.status != DiffStatus::Modified {
+            encode_varint_field(&mut buf, 4, u64::from(self.status.to_wire_byte()));
+        }
         buf
     }
 
@@ -118,6 +176,8 @@ impl DiffBlock {
     pub fn decode_body(mut buf: &[u8]) -> Result<Self, TypeError> {
         let mut path: Option<String> = None;
         let mut hunks = Vec::new();
+        let mut old_path: Option<String> = None;
+        let mut status = DiffStatus::Modified;
 
         while !buf.is_empty() {
             let (header, n) = decode_field_header(buf)?;
@@ -134,6 +194,16 @@ impl DiffBlock {
                     buf = &buf[n..];
                     hunks.push(DiffHunk::decode(data)?);
                 }
+                3 => {
+                    let (data, n) = decode_bytes_value(buf)?;
+                    buf = &buf[n..];
+                    old_path = Some(String::from_utf8_lossy(data).into_owned());
+                }
+                4 => {
+                    let (v, n) = decode_varint_value(buf)?;
+                    buf = &buf[n..];
+                    status = DiffStatus::from_wire_byte(narrow_varint(v, "status")?)?;
+                }
                 _ => {
                     let n = skip_field(buf, header.wire_type)?;
                     buf = &buf[n..];
@@ -144,8 +214,268 @@ impl DiffBlock {
         Ok(Self {
             path: path.ok_or(TypeError::MissingRequiredField { field: "path" })?,
             hunks,
+            old_path,
+            status,
         })
     }
+
+    /// Parse the text of a unified diff (e.g. `git diff` output) into one
+    /// `DiffBlock` per file.
+    ///
+    /// Understands both git-style diffs (with `diff --git`, `new file mode`,
+    /// `deleted file mode`, `rename from` / `rename to` extended headers)
+    /// and plain `diff -u` output that only has `---` / `+++` headers.
+    /// Leading `a/` and `b/` prefixes are stripped from paths.
+    ///
+    /// Only git headers mark a file `Renamed`. Plain `diff -u` names
+    /// often differ between `---` and `+++` (`foo.c.orig` / `foo.c`,
+    /// `orig/x` / `new/x`), so such a file is `Modified` under its `+++`
+    /// path.
+    ///
+    /// ```text
+    /// diff --git a/src/old.rs b/src/new.rs      → new block
+    /// rename from src/old.rs                   → status = Renamed
+    /// --- a/src/old.rs                         → old_path
+    /// +++ b/src/new.rs                         → path
+    /// @@ -10,3 +10,4 @@ fn main() {           → new hunk (10,3 → 10,4)
+    ///  context / -removed / +added lines       → hunk.lines
+    /// ```
+    ///
+    /// Hunk bodies are consumed by the counts in their `@@` header, so a
+    /// removed line that happens to start with `--` is not mistaken for a
+    /// file header. Text outside any file section (commit messages from
+    /// `git format-patch`, `index` lines, binary-file notices) is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TypeError::InvalidDiff`] if a hunk header cannot be
+    /// parsed, a hunk appears before any file header, or a hunk body ends
+    /// before its declared line counts are satisfied.
+    pub fn parse_unified(text: &str) -> Result<Vec<Self>, TypeError> {
+        let mut blocks: Vec<Self> = Vec::new();
+        let mut current: Option<Self> = None;
+        // Remaining (old, new) lines expected by the open hunk.
+        let mut remaining = (0u32, 0u32);
+
+        for (idx, raw) in text.split_inclusive('\n').enumerate() {
+            let line_no = idx + 1;
+            let line = raw.trim_end_matches(['\n', '\r']);
+
+            if remaining != (0, 0) {
+                consume_hunk_line(&mut remaining, line, line_no)?;
+                // Some tools strip the single space from empty context
+                // lines; restore it so the stored body stays well-formed.
+                if let Some(hunk) = current.as_mut().and_then(|b| b.hunks.last_mut()) {
+                    if line.is_empty() {
+                        hunk.lines.push(b' ');
+                    }
+                    hunk.lines.extend_from_slice(raw.as_bytes());
+                }
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("diff --git ") {
+                blocks.extend(current.take());
+                let (old, new) = split_git_header_paths(rest);
+                current = Some(Self {
+                    path: new.to_string(),
+                    hunks: Vec::new(),
+                    old_path: (old != new).then(|| old.to_string()),
+                    status: if old == new {
+                        DiffStatus::Modified
+                    } else {
+                        DiffStatus::Renamed
+                    },
+                });
+            } else if current
+                .as_mut()
+                .is_some_and(|block| block.apply_extended_header(line))
+            {
+                // `new file mode`, `deleted file mode`, `rename from/to`.
+            } else if let Some(old) = line.strip_prefix("--- ") {
+                let old = strip_diff_path(old, "a/");
+                // A `---` header after hunks (or with no `diff --git` line
+                // at all) starts a new file in plain `diff -u` output.
+                if current.as_ref().is_some_and(|b| !b.hunks.is_empty()) {
+                    blocks.extend(current.take());
+                }
+                let block = current.get_or_insert_with(|| Self {
+                    path: old.to_string(),
+                    hunks: Vec::new(),
+                    old_path: None,
+                    status: DiffStatus::Modified,
+                });
+                if old == "/dev/null" {
+                    block.status = DiffStatus::Added;
+                } else if block.status != DiffStatus::Renamed {
+                    block.path = old.to_string();
+                }
+            } else if let Some(new) = line.strip_prefix("+++ ") {
+                let new = strip_diff_path(new, "b/");
+                let Some(block) = current.as_mut() else {
+                    continue;
+                };
+                if new == "/dev/null" {
+                    block.status = DiffStatus::Deleted;
+                } else {
+                    block.path = new.to_string();
+                }
+            } else if line.starts_with("@@") {
+                let Some(block) = current.as_mut() else {
+                    return Err(TypeError::InvalidDiff {
+                        line: line_no,
+                        reason: "hunk before any file header",
+                    });
+                };
+                let hunk = parse_hunk_header(line).ok_or(TypeError::InvalidDiff {
+                    line: line_no,
+                    reason: "malformed @@ hunk header",
+                })?;
+                remaining = (hunk.old_lines, hunk.new_lines);
+                block.hunks.push(hunk);
+            } else if line.starts_with('\\') {
+                // "\ No newline at end of file" trails the final body line
+                // and may arrive after the hunk counts are exhausted.
+                if let Some(hunk) = current.as_mut().and_then(|b| b.hunks.last_mut()) {
+                    hunk.lines.extend_from_slice(raw.as_bytes());
+                }
+            }
+        }
+
+        if remaining != (0, 0) {
+            return Err(TypeError::InvalidDiff {
+                line: text.lines().count(),
+                reason: "hunk body shorter than its header",
+            });
+        }
+
+        blocks.extend(current);
+        Ok(blocks)
+    }
+}
+
+impl DiffBlock {
+    /// Apply a git extended header line to this block, returning `false`
+    /// if `line` is not one of the headers that affect status or paths.
+    fn apply_extended_header(&mut self, line: &str) -> bool {
+        if line.starts_with("new file mode") {
+            self.status = DiffStatus::Added;
+        } else if line.starts_with("deleted file mode") {
+            self.status = DiffStatus::Deleted;
+        } else if let Some(from) = line.strip_prefix("rename from ") {
+            self.old_path = Some(from.to_string());
+            self.status = DiffStatus::Renamed;
+        } else if let Some(to) = line.strip_prefix("rename to ") {
+            self.path = to.to_string();
+        } else {
+            return false;
+        }
+        true
+    }
+}
+
+/// Account for one hunk body line against the open hunk's remaining
+/// (old, new) line counts.
+fn consume_hunk_line(
+    remaining: &mut (u32, u32),
+    line: &str,
+    line_no: usize,
+) -> Result<(), TypeError> {
+    let err = |reason| TypeError::InvalidDiff {
+        line: line_no,
+        reason,
+    };
+    match line.as_bytes().first() {
+        // An empty line is a context line whose leading space was stripped.
+        Some(b' ') | None => {
+            if remaining.0 == 0 || remaining.1 == 0 {
+                return Err(err("context line exceeds hunk counts"));
+            }
+            remaining.0 -= 1;
+            remaining.1 -= 1;
+        }
+        Some(b'-') => {
+            if remaining.0 == 0 {
+                return Err(err("removed line exceeds hunk old count"));
+            }
+            remaining.0 -= 1;
+        }
+        Some(b'+') => {
+            if remaining.1 == 0 {
+                return Err(err("added line exceeds hunk new count"));
+            }
+            remaining.1 -= 1;
+        }
+        Some(b'\\') => {}
+        Some(_) => return Err(err("hunk body shorter than its header")),
+    }
+    Ok(())
+}
+
+/// Count the old-side and new-side lines in a unified hunk body.
+///
+/// Context lines (` `) count toward both sides, `-` only the old side and
+/// `+` only the new side. `\ No newline at end of file` markers count
+/// toward neither.
+fn count_hunk_lines(lines: &[u8]) -> (u32, u32) {
+    let mut old = 0u32;
+    let mut new = 0u32;
+    for line in lines.split(|&b| b == b'\n') {
+        match line.first() {
+            Some(b' ') => {
+                old += 1;
+                new += 1;
+            }
+            Some(b'-') => old += 1,
+            Some(b'+') => new += 1,
+            _ => {}
+        }
+    }
+    (old, new)
+}
+
+/// Parse `@@ -a[,b] +c[,d] @@ [section]` into an empty hunk with the
+/// header's starts and counts. An omitted count means 1, per the format.
+fn parse_hunk_header(line: &str) -> Option<DiffHunk> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (ranges, _section) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let parse_range = |s: &str| -> Option<(u32, u32)> {
+        match s.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((s.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_lines) = parse_range(old)?;
+    let (new_start, new_lines) = parse_range(new)?;
+    Some(DiffHunk {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        lines: Vec::new(),
+    })
+}
+
+/// Split the `a/<old> b/<new>` tail of a `diff --git` line into paths.
+///
+/// Splits on the last ` b/` so that paths containing spaces survive as
+/// long as the new path itself does not contain ` b/`.
+fn split_git_header_paths(rest: &str) -> (&str, &str) {
+    match rest.rfind(" b/") {
+        Some(pos) => (
+            strip_diff_path(&rest[..pos], "a/"),
+            strip_diff_path(&rest[pos + 1..], "b/"),
+        ),
+        None => (rest, rest),
+    }
+}
+
+/// Strip the `a/` or `b/` prefix and any trailing tab-separated timestamp
+/// (as emitted by `diff -u`) from a file header path.
+fn strip_diff_path<'a>(path: &'a str, prefix: &str) -> &'a str {
+    let path = path.split('\t').next().unwrap_or(path);
+    path.strip_prefix(prefix).unwrap_or(path)
 }
 
 #[cfg(test)]
@@ -158,10 +488,14 @@ mod tests {
             path: "src/main.rs".to_string(),
             hunks: vec![DiffHunk {
                 old_start: 10,
+                old_lines: 3,
                 new_start: 10,
+                new_lines: 3,
                 lines: b" fn main() {\n-    println!(\"old\");\n+    println!(\"new\");\n }\n"
                     .to_vec(),
             }],
+            old_path: None,
+            status: DiffStatus::Modified,
         };
         let body = block.encode_body();
         let decoded = DiffBlock::decode_body(&body).unwrap();
@@ -175,15 +509,21 @@ mod tests {
             hunks: vec![
                 DiffHunk {
                     old_start: 1,
+                    old_lines: 0,
                     new_start: 1,
+                    new_lines: 1,
                     lines: b"+use std::io;\n".to_vec(),
                 },
                 DiffHunk {
                     old_start: 50,
+                    old_lines: 1,
                     new_start: 51,
+                    new_lines: 1,
                     lines: b"-    old_call();\n+    new_call();\n".to_vec(),
                 },
             ],
+            old_path: None,
+            status: DiffStatus::Modified,
         };
         let body = block.encode_body();
         let decoded = DiffBlock::decode_body(&body).unwrap();
@@ -195,9 +535,222 @@ mod tests {
         let block = DiffBlock {
             path: "empty.rs".to_string(),
             hunks: vec![],
+            old_path: None,
+            status: DiffStatus::Modified,
         };
         let body = block.encode_body();
         let decoded = DiffBlock::decode_body(&body).unwrap();
         assert_eq!(decoded, block);
     }
+
+    #[test]
+    fn roundtrip_rename_with_old_path() {
+        let block = DiffBlock {
+            path: "src/new.rs".to_string(),
+            hunks: vec![DiffHunk::new(1, 1, b"-a\n+b\n".to_vec())],
+            old_path: Some("src/old.rs".to_string()),
+            status: DiffStatus::Renamed,
+        };
+        let body = block.encode_body();
+        let decoded = DiffBlock::decode_body(&body).unwrap();
+        assert_eq!(decoded, block);
+    }
+
+    #[test]
+    fn legacy_hunk_without_counts_derives_them() {
+        // Body written by an encoder that predates fields 4 and 5.
+        let mut hunk = Vec::new();
+        encode_varint_field(&mut hunk, 1, 7);
+        encode_varint_field(&mut hunk, 2, 7);
+        encode_bytes_field(&mut hunk, 3, b" ctx\n-old\n+new\n+more\n");
+        let mut body = Vec::new();
+        encode_bytes_field(&mut body, 1, b"a.rs");
+        encode_nested_field(&mut body, 2, &hunk);
+
+        let decoded = DiffBlock::decode_body(&body).unwrap();
+        assert_eq!(decoded.status, DiffStatus::Modified);
+        assert_eq!(decoded.old_path, None);
+        assert_eq!(decoded.hunks[0].old_lines, 2);
+        assert_eq!(decoded.hunks[0].new_lines, 3);
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        // Status 257 would wrap to 0x01 (added) if truncated to a byte.
+        let mut body = Vec::new();
+        encode_bytes_field(&mut body, 1, b"a.rs");
+        encode_varint_field(&mut body, 4, 257);
+        let err = DiffBlock::decode_body(&body).unwrap_err();
+        assert!(matches!(
+            err,
+            TypeError::FieldOutOfRange {
+                field: "status",
+                value: 257
+            }
+        ));
+
+        let mut hunk = Vec::new();
+        encode_varint_field(&mut hunk, 1, 1);
+        encode_varint_field(&mut hunk, 2, 1);
+        encode_bytes_field(&mut hunk, 3, b"+x\n");
+        encode_varint_field(&mut hunk, 4, u64::from(u32::MAX) + 1);
+        let mut body = Vec::new();
+        encode_bytes_field(&mut body, 1, b"a.rs");
+        encode_nested_field(&mut body, 2, &hunk);
+        let err = DiffBlock::decode_body(&body).unwrap_err();
+        assert!(matches!(
+            err,
+            TypeError::FieldOutOfRange {
+                field: "old_lines",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_git_diff_multiple_files() {
+        let text = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 3b18e51..a9c2f4d 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,4 @@ mod config;
+ use std::io;
++use std::fs;
+
+ fn main() {
+@@ -20 +21 @@
+--- old comment
++// new comment
+diff --git a/README.md b/README.md
+new file mode 100644
+index 0000000..e69de29
+--- /dev/null
++++ b/README.md
+@@ -0,0 +1,2 @@
++# Title
++body
+\\ No newline at end of file
+";
+        let blocks = DiffBlock::parse_unified(text).unwrap();
+        assert_eq!(blocks.len(), 2);
+
+        assert_eq!(blocks[0].path, "src/lib.rs");
+        assert_eq!(blocks[0].status, DiffStatus::Modified);
+        assert_eq!(blocks[0].hunks.len(), 2);
+        assert_eq!(
+            (blocks[0].hunks[0].old_start, blocks[0].hunks[0].old_lines),
+            (1, 3)
+        );
+        assert_eq!(
+            (blocks[0].hunks[0].new_start, blocks[0].hunks[0].new_lines),
+            (1, 4)
+        );
+        // The empty context line is restored to a single space.
+        assert_eq!(
+            blocks[0].hunks[0].lines,
+            b" use std::io;\n+use std::fs;\n \n fn main() {\n".to_vec()
+        );
+        // `--- old comment` is a removed line, not a file header.
+        assert_eq!(
+            blocks[0].hunks[1].lines,
+            b"--- old comment\n+// new comment\n".to_vec()
+        );
+        assert_eq!(
+            (blocks[0].hunks[1].old_lines, blocks[0].hunks[1].new_lines),
+            (1, 1)
+        );
+
+        assert_eq!(blocks[1].path, "README.md");
+        assert_eq!(blocks[1].status, DiffStatus::Added);
+        assert!(
+            blocks[1].hunks[0]
+                .lines
+                .ends_with(b"\\ No newline at end of file\n")
+        );
+    }
+
+    #[test]
+    fn parse_git_rename_and_delete() {
+        let text = "\
+diff --git a/old.rs b/new.rs
+similarity index 90%
+rename from old.rs
+rename to new.rs
+--- a/old.rs
++++ b/new.rs
+@@ -1 +1 @@
+-fn a() {}
++fn b() {}
+diff --git a/gone.rs b/gone.rs
+deleted file mode 100644
+--- a/gone.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+";
+        let blocks = DiffBlock::parse_unified(text).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].path, "new.rs");
+        assert_eq!(blocks[0].old_path.as_deref(), Some("old.rs"));
+        assert_eq!(blocks[0].status, DiffStatus::Renamed);
+        assert_eq!(blocks[1].path, "gone.rs");
+        assert_eq!(blocks[1].status, DiffStatus::Deleted);
+        assert_eq!(blocks[1].hunks[0].new_lines, 0);
+    }
+
+    #[test]
+    fn parse_plain_diff_u() {
+        let text = "\
+--- a.txt\t2024-01-01 00:00:00
++++ a.txt\t2024-01-02 00:00:00
+@@ -1,2 +1,2 @@
+ same
+-old
++new
+";
+        let blocks = DiffBlock::parse_unified(text).unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].path, "a.txt");
+        assert_eq!(blocks[0].status, DiffStatus::Modified);
+        assert_eq!(blocks[0].hunks[0].lines, b" same\n-old\n+new\n".to_vec());
+    }
+
+    #[test]
+    fn parse_plain_diff_u_with_different_names_is_not_a_rename() {
+        let text = "\
+--- foo.c.orig
++++ foo.c
+@@ -1 +1 @@
+-int a;
++int b;
+--- orig/x
++++ new/x
+@@ -1 +1 @@
+-a
++b
+";
+        let blocks = DiffBlock::parse_unified(text).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].path, "foo.c");
+        assert_eq!(blocks[1].path, "new/x");
+        for block in &blocks {
+            assert_eq!(block.status, DiffStatus::Modified);
+            assert_eq!(block.old_path, None);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_truncated_hunk() {
+        let text = "--- a/x\n+++ b/x\n@@ -1,3 +1,3 @@\n a\n";
+        let err = DiffBlock::parse_unified(text).unwrap_err();
+        assert!(matches!(err, TypeError::InvalidDiff { .. }));
+    }
+
+    #[test]
+    fn parse_rejects_malformed_header() {
+        let text = "--- a/x\n+++ b/x\n@@ -x +1 @@\n";
+        let err = DiffBlock::parse_unified(text).unwrap_err();
+        assert!(matches!(err, TypeError::InvalidDiff { line: 3, .. }));
+    }
 }
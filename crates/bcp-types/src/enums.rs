@@ -56,7 +56,7 @@ macro_rules! wire_enum {
 ///
 /// ```text
 /// ┌──────┬────────────┐
-/// │ Wire │ Language    │
+/// │ Wire │ Language   │
 /// ├──────┼────────────┤
 /// │ 0x01 │ Rust       │
 /// │ 0x02 │ TypeScript │
@@ -75,6 +75,20 @@ macro_rules! wire_enum {
 /// │ 0x0F │ Yaml       │
 /// │ 0x10 │ Toml       │
 /// │ 0x11 │ Markdown   │
+/// │ 0x12 │ Kotlin     │
+/// │ 0x13 │ Swift      │
+/// │ 0x14 │ CSharp     │
+/// │ 0x15 │ Php        │
+/// │ 0x16 │ Scala      │
+/// │ 0x17 │ Haskell    │
+/// │ 0x18 │ Elixir     │
+/// │ 0x19 │ Zig        │
+/// │ 0x1A │ Lua        │
+/// │ 0x1B │ Dockerfile │
+/// │ 0x1C │ Makefile   │
+/// │ 0x1D │ Hcl        │
+/// │ 0x1E │ Protobuf   │
+/// │ 0x1F │ GraphQL    │
 /// │ 0xFF │ Unknown    │
 /// └──────┴────────────┘
 /// ```
@@ -101,6 +115,20 @@ pub enum Lang {
     Yaml,
     Toml,
     Markdown,
+    Kotlin,
+    Swift,
+    CSharp,
+    Php,
+    Scala,
+    Haskell,
+    Elixir,
+    Zig,
+    Lua,
+    Dockerfile,
+    Makefile,
+    Hcl,
+    Protobuf,
+    GraphQL,
     Unknown,
     /// Forward-compatible catch-all. Preserves the raw wire byte for
     /// language IDs this version doesn't recognize.
@@ -128,6 +156,20 @@ impl Lang {
             Self::Yaml => 0x0F,
             Self::Toml => 0x10,
             Self::Markdown => 0x11,
+            Self::Kotlin => 0x12,
+            Self::Swift => 0x13,
+            Self::CSharp => 0x14,
+            Self::Php => 0x15,
+            Self::Scala => 0x16,
+            Self::Haskell => 0x17,
+            Self::Elixir => 0x18,
+            Self::Zig => 0x19,
+            Self::Lua => 0x1A,
+            Self::Dockerfile => 0x1B,
+            Self::Makefile => 0x1C,
+            Self::Hcl => 0x1D,
+            Self::Protobuf => 0x1E,
+            Self::GraphQL => 0x1F,
             Self::Unknown => 0xFF,
             Self::Other(id) => id,
         }
@@ -157,10 +199,222 @@ impl Lang {
             0x0F => Self::Yaml,
             0x10 => Self::Toml,
             0x11 => Self::Markdown,
+            0x12 => Self::Kotlin,
+            0x13 => Self::Swift,
+            0x14 => Self::CSharp,
+            0x15 => Self::Php,
+            0x16 => Self::Scala,
+            0x17 => Self::Haskell,
+            0x18 => Self::Elixir,
+            0x19 => Self::Zig,
+            0x1A => Self::Lua,
+            0x1B => Self::Dockerfile,
+            0x1C => Self::Makefile,
+            0x1D => Self::Hcl,
+            0x1E => Self::Protobuf,
+            0x1F => Self::GraphQL,
             0xFF => Self::Unknown,
             other => Self::Other(other),
         }
     }
+
+    /// Look up a language by name or common alias (case-insensitive).
+    ///
+    /// Accepts the names used in manifests, markdown fences, shebang
+    /// interpreters and editor modelines (`rust`, `py`, `c++`, `bash`,
+    /// `terraform`, ...). Unrecognized names return [`Lang::Unknown`].
+    #[must_use]
+    pub fn from_name(name: &str) -> Self {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "rust" | "rs" => Self::Rust,
+            "typescript" | "ts" | "tsx" => Self::TypeScript,
+            "javascript" | "js" | "jsx" | "node" => Self::JavaScript,
+            "python" | "py" | "python3" => Self::Python,
+            "go" | "golang" => Self::Go,
+            "java" => Self::Java,
+            "c" => Self::C,
+            "cpp" | "c++" | "cxx" => Self::Cpp,
+            "ruby" | "rb" => Self::Ruby,
+            "shell" | "sh" | "bash" | "zsh" | "ksh" | "dash" => Self::Shell,
+            "sql" => Self::Sql,
+            "html" => Self::Html,
+            "css" => Self::Css,
+            "json" => Self::Json,
+            "yaml" | "yml" => Self::Yaml,
+            "toml" => Self::Toml,
+            "markdown" | "md" => Self::Markdown,
+            "kotlin" | "kt" => Self::Kotlin,
+            "swift" => Self::Swift,
+            "csharp" | "c#" | "cs" => Self::CSharp,
+            "php" => Self::Php,
+            "scala" => Self::Scala,
+            "haskell" | "hs" => Self::Haskell,
+            "elixir" | "ex" | "exs" => Self::Elixir,
+            "zig" => Self::Zig,
+            "lua" => Self::Lua,
+            "dockerfile" | "docker" => Self::Dockerfile,
+            "makefile" | "make" => Self::Makefile,
+            "hcl" | "terraform" | "tf" => Self::Hcl,
+            "protobuf" | "proto" => Self::Protobuf,
+            "graphql" | "gql" => Self::GraphQL,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Infer a language from a file path's name or extension.
+    ///
+    /// Well-known extensionless filenames (`Dockerfile`, `Makefile`,
+    /// `Gemfile`, ...) are matched first, then the extension. Returns
+    /// [`Lang::Unknown`] when neither is recognized.
+    #[must_use]
+    pub fn from_path(path: &str) -> Self {
+        let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        match file_name {
+            "Dockerfile" | "Containerfile" => return Self::Dockerfile,
+            "Makefile" | "makefile" | "GNUmakefile" => return Self::Makefile,
+            "Gemfile" | "Rakefile" => return Self::Ruby,
+            _ if file_name.starts_with("Dockerfile.") => return Self::Dockerfile,
+            _ => {}
+        }
+        let Some((_, ext)) = file_name.rsplit_once('.') else {
+            return Self::Unknown;
+        };
+        match ext.to_ascii_lowercase().as_str() {
+            "rs" => Self::Rust,
+            "ts" | "tsx" | "mts" | "cts" => Self::TypeScript,
+            "js" | "jsx" | "mjs" | "cjs" => Self::JavaScript,
+            "py" | "pyi" => Self::Python,
+            "go" => Self::Go,
+            "java" => Self::Java,
+            "c" | "h" => Self::C,
+            "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => Self::Cpp,
+            "rb" => Self::Ruby,
+            "sh" | "bash" | "zsh" => Self::Shell,
+            "sql" => Self::Sql,
+            "html" | "htm" => Self::Html,
+            "css" => Self::Css,
+            "json" => Self::Json,
+            "yaml" | "yml" => Self::Yaml,
+            "toml" => Self::Toml,
+            "md" | "markdown" => Self::Markdown,
+            "kt" | "kts" => Self::Kotlin,
+            "swift" => Self::Swift,
+            "cs" => Self::CSharp,
+            "php" => Self::Php,
+            "scala" | "sc" => Self::Scala,
+            "hs" | "lhs" => Self::Haskell,
+            "ex" | "exs" => Self::Elixir,
+            "zig" => Self::Zig,
+            "lua" => Self::Lua,
+            "dockerfile" => Self::Dockerfile,
+            "mk" => Self::Makefile,
+            "tf" | "tfvars" | "hcl" => Self::Hcl,
+            "proto" => Self::Protobuf,
+            "graphql" | "gql" => Self::GraphQL,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Infer a language from file content.
+    ///
+    /// Checks, in order:
+    ///
+    /// 1. A shebang on the first line (`#!/usr/bin/env python3`,
+    ///    `#!/bin/bash`). Trailing version numbers on the interpreter
+    ///    are ignored.
+    /// 2. A vim modeline (`vim: set ft=lua:`) or emacs mode line
+    ///    (`-*- mode: ruby -*-`) within the first or last five lines.
+    ///
+    /// Returns [`Lang::Unknown`] when neither yields a known language.
+    #[must_use]
+    pub fn from_content(content: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(content);
+        let lines: Vec<&str> = text.lines().collect();
+
+        if let Some(lang) = lines.first().and_then(|l| shebang_lang(l)) {
+            return lang;
+        }
+
+        let tail_start = lines.len().saturating_sub(5).max(5);
+        lines
+            .iter()
+            .take(5)
+            .chain(lines.iter().skip(tail_start))
+            .find_map(|l| modeline_lang(l))
+            .unwrap_or(Self::Unknown)
+    }
+
+    /// Infer a language from the path, falling back to the content.
+    #[must_use]
+    pub fn detect(path: &str, content: &[u8]) -> Self {
+        match Self::from_path(path) {
+            Self::Unknown => Self::from_content(content),
+            lang => lang,
+        }
+    }
+}
+
+/// Language named by a `#!` interpreter line, if any.
+///
+/// `/usr/bin/env` is skipped (along with its `-S`-style flags) so the
+/// real interpreter is used; `python3.12` is looked up as `python`.
+fn shebang_lang(line: &str) -> Option<Lang> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|w| !w.starts_with('-'))?;
+    }
+    let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let lang = match name {
+        "runghc" | "runhaskell" | "stack" => Lang::Haskell,
+        "deno" | "bun" | "nodejs" => Lang::JavaScript,
+        "ts-node" | "tsx" => Lang::TypeScript,
+        "kscript" => Lang::Kotlin,
+        "luajit" => Lang::Lua,
+        "iex" => Lang::Elixir,
+        other => Lang::from_name(other),
+    };
+    (lang != Lang::Unknown).then_some(lang)
+}
+
+/// Language named by a vim or emacs modeline on this line, if any.
+fn modeline_lang(line: &str) -> Option<Lang> {
+    // Emacs: `-*- mode: python -*-` or the short form `-*- python -*-`.
+    if let Some((_, rest)) = line.split_once("-*-")
+        && let Some((vars, _)) = rest.split_once("-*-")
+    {
+        let name = vars
+            .split(';')
+            .find_map(|v| v.trim().strip_prefix("mode:"))
+            .unwrap_or(vars);
+        let lang = Lang::from_name(name);
+        if lang != Lang::Unknown {
+            return Some(lang);
+        }
+    }
+
+    // Vim: `vim: set ft=lua:`, `vi: filetype=sh`, `ex: syntax=make`.
+    // The marker must open the line or follow whitespace, so words that
+    // merely end in it (`index:`, `navi:`) are not mistaken for one.
+    let settings = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        line.match_indices(marker)
+            .find(|(i, _)| {
+                line[..*i]
+                    .chars()
+                    .next_back()
+                    .is_none_or(char::is_whitespace)
+            })
+            .map(|(i, _)| &line[i + marker.len()..])
+    })?;
+    settings
+        .split([' ', ':', '\t'])
+        .find_map(|opt| {
+            opt.strip_prefix("ft=")
+                .or_else(|| opt.strip_prefix("filetype="))
+                .or_else(|| opt.strip_prefix("syntax="))
+        })
+        .map(Lang::from_name)
+        .filter(|lang| *lang != Lang::Unknown)
 }
 
 // ── Role ──────────────────────────────────────────────────────────────
@@ -280,21 +534,28 @@ wire_enum! {
   /// so the renderer can syntax-highlight or parse it appropriately.
   ///
   /// ```text
-  /// ┌──────┬──────┐
-  /// │ Wire │ Fmt  │
-  /// ├──────┼──────┤
-  /// │ 0x01 │ Json │
-  /// │ 0x02 │ Yaml │
-  /// │ 0x03 │ Toml │
-  /// │ 0x04 │ Csv  │
-  /// └──────┴──────┘
+  /// ┌──────┬────────┐
+  /// │ Wire │ Fmt    │
+  /// ├──────┼────────┤
+  /// │ 0x01 │ Json   │
+  /// │ 0x02 │ Yaml   │
+  /// │ 0x03 │ Toml   │
+  /// │ 0x04 │ Csv    │
+  /// │ 0x05 │ Xml    │
+  /// │ 0x06 │ Ndjson │
+  /// └──────┴────────┘
   /// ```
+  ///
+  /// `Ndjson` is newline-delimited JSON (also known as JSON Lines): one
+  /// JSON value per line, blank lines ignored.
   #[derive(Clone, Copy, Debug, PartialEq, Eq)]
   pub enum DataFormat {
     Json = 0x01,
     Yaml = 0x02,
     Toml = 0x03,
     Csv = 0x04,
+    Xml = 0x05,
+    Ndjson = 0x06,
   }
 }
 
@@ -306,22 +567,84 @@ wire_enum! {
   /// Determines how the annotation's `value` field should be interpreted.
   ///
   /// ```text
-  /// ┌──────┬──────────┐
-  /// │ Wire │ Kind     │
-  /// ├──────┼──────────┤
-  /// │ 0x01 │ Priority │
-  /// │ 0x02 │ Summary  │
-  /// │ 0x03 │ Tag      │
-  /// └──────┴──────────┘
+  /// ┌──────┬────────────┬──────────────────────────────────────────┐
+  /// │ Wire │ Kind       │ Value                                    │
+  /// ├──────┼────────────┼──────────────────────────────────────────┤
+  /// │ 0x01 │ Priority   │ Priority byte                            │
+  /// │ 0x02 │ Summary    │ UTF-8 text                               │
+  /// │ 0x03 │ Tag        │ UTF-8 label                              │
+  /// │ 0x04 │ Relevance  │ f32 little-endian, 0.0–1.0               │
+  /// │ 0x05 │ Provenance │ UTF-8 `tool:…`, `url:…` or `commit:…`    │
+  /// │ 0x06 │ Expiry     │ u64 little-endian Unix seconds           │
+  /// │ 0x07 │ Link       │ LinkKind byte + varint target block id   │
+  /// └──────┴────────────┴──────────────────────────────────────────┘
   /// ```
+  ///
+  /// See [`AnnotationBlock`](crate::annotation::AnnotationBlock) for typed
+  /// constructors and accessors for each value layout.
   #[derive(Clone, Copy, Debug, PartialEq, Eq)]
   pub enum AnnotationKind {
     Priority = 0x01,
     Summary = 0x02,
     Tag = 0x03,
+    Relevance = 0x04,
+    Provenance = 0x05,
+    Expiry = 0x06,
+    Link = 0x07,
   }
 }
 
+// ── LinkKind ──────────────────────────────────────────────────────────
+
+wire_enum! {
+  /// Relationship carried by a `Link` annotation.
+  ///
+  /// A link reads "annotated block *kind* target block": a test file
+  /// `Tests` the code it exercises, a CODE block `DependsOn` the module
+  /// it imports, a document `Explains` the code it describes.
+  ///
+  /// ```text
+  /// ┌──────┬───────────┐
+  /// │ Wire │ Kind      │
+  /// ├──────┼───────────┤
+  /// │ 0x01 │ DependsOn │
+  /// │ 0x02 │ Tests     │
+  /// │ 0x03 │ Explains  │
+  /// └──────┴───────────┘
+  /// ```
+  #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+  pub enum LinkKind {
+    DependsOn = 0x01,
+    Tests = 0x02,
+    Explains = 0x03,
+  }
+}
+
+impl LinkKind {
+    /// Kebab-case name used in manifests and rendered output
+    /// (`depends-on`, `tests`, `explains`).
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::DependsOn => "depends-on",
+            Self::Tests => "tests",
+            Self::Explains => "explains",
+        }
+    }
+
+    /// Parse a name produced by [`name`](Self::name). Underscores are
+    /// accepted in place of hyphens.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('_', "-").as_str() {
+            "depends-on" => Some(Self::DependsOn),
+            "tests" => Some(Self::Tests),
+            "explains" => Some(Self::Explains),
+            _ => None,
+        }
+    }
+}
+
 // ── MediaType ─────────────────────────────────────────────────────────
 
 wire_enum! {
@@ -351,6 +674,66 @@ wire_enum! {
   }
 }
 
+impl MediaType {
+    /// Identify a raster image format from its leading magic bytes.
+    ///
+    /// ```text
+    /// ┌──────┬──────────────────────────────────────┐
+    /// │ Type │ Signature                            │
+    /// ├──────┼──────────────────────────────────────┤
+    /// │ Png  │ 89 50 4E 47 0D 0A 1A 0A              │
+    /// │ Jpeg │ FF D8 FF                             │
+    /// │ Gif  │ "GIF87a" or "GIF89a"                 │
+    /// │ Webp │ "RIFF" <4-byte size> "WEBP"          │
+    /// └──────┴──────────────────────────────────────┘
+    /// ```
+    ///
+    /// Returns `None` when no signature matches. SVG is text and has no
+    /// fixed signature, so it is never sniffed.
+    #[must_use]
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"\x89PNG\r\n\x1A\n") {
+            Some(Self::Png)
+        } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+            Some(Self::Webp)
+        } else {
+            None
+        }
+    }
+}
+
+// ── DiffStatus ────────────────────────────────────────────────────────
+
+wire_enum! {
+  /// File-level change status for DIFF blocks.
+  ///
+  /// Mirrors the status letters `git diff --name-status` reports. A
+  /// `Renamed` diff carries the pre-rename path in `DiffBlock::old_path`.
+  ///
+  /// ```text
+  /// ┌──────┬──────────┐
+  /// │ Wire │ Status   │
+  /// ├──────┼──────────┤
+  /// │ 0x01 │ Modified │
+  /// │ 0x02 │ Added    │
+  /// │ 0x03 │ Deleted  │
+  /// │ 0x04 │ Renamed  │
+  /// └──────┴──────────┘
+  /// ```
+  #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+  pub enum DiffStatus {
+    #[default]
+    Modified = 0x01,
+    Added = 0x02,
+    Deleted = 0x03,
+    Renamed = 0x04,
+  }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -377,6 +760,20 @@ mod tests {
             (Lang::Yaml, 0x0F),
             (Lang::Toml, 0x10),
             (Lang::Markdown, 0x11),
+            (Lang::Kotlin, 0x12),
+            (Lang::Swift, 0x13),
+            (Lang::CSharp, 0x14),
+            (Lang::Php, 0x15),
+            (Lang::Scala, 0x16),
+            (Lang::Haskell, 0x17),
+            (Lang::Elixir, 0x18),
+            (Lang::Zig, 0x19),
+            (Lang::Lua, 0x1A),
+            (Lang::Dockerfile, 0x1B),
+            (Lang::Makefile, 0x1C),
+            (Lang::Hcl, 0x1D),
+            (Lang::Protobuf, 0x1E),
+            (Lang::GraphQL, 0x1F),
             (Lang::Unknown, 0xFF),
         ];
         for (variant, wire) in cases {
@@ -385,6 +782,77 @@ mod tests {
         }
     }
 
+    #[test]
+    fn lang_from_path_extensions_and_filenames() {
+        let cases = [
+            ("src/main.rs", Lang::Rust),
+            ("app/Main.kt", Lang::Kotlin),
+            ("Sources/App.swift", Lang::Swift),
+            ("Program.cs", Lang::CSharp),
+            ("index.php", Lang::Php),
+            ("build.sc", Lang::Scala),
+            ("Main.hs", Lang::Haskell),
+            ("lib/app.ex", Lang::Elixir),
+            ("build.zig", Lang::Zig),
+            ("init.lua", Lang::Lua),
+            ("infra/main.tf", Lang::Hcl),
+            ("api/v1/user.proto", Lang::Protobuf),
+            ("schema.graphql", Lang::GraphQL),
+            ("docker/Dockerfile", Lang::Dockerfile),
+            ("Dockerfile.dev", Lang::Dockerfile),
+            ("Makefile", Lang::Makefile),
+            ("rules.mk", Lang::Makefile),
+            ("Gemfile", Lang::Ruby),
+            ("include\\util.HPP", Lang::Cpp),
+            ("LICENSE", Lang::Unknown),
+            ("notes.txt", Lang::Unknown),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(Lang::from_path(path), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn lang_from_content_shebangs() {
+        let cases: [(&[u8], Lang); 6] = [
+            (b"#!/usr/bin/env python3\nprint(1)", Lang::Python),
+            (b"#!/usr/bin/python3.12\n", Lang::Python),
+            (b"#!/bin/bash\nset -e", Lang::Shell),
+            (b"#!/usr/bin/env -S deno run\n", Lang::JavaScript),
+            (b"#!/usr/bin/env elixir\n", Lang::Elixir),
+            (b"#!/usr/bin/env frobnicate\n", Lang::Unknown),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(Lang::from_content(content), expected);
+        }
+    }
+
+    #[test]
+    fn lang_from_content_modelines() {
+        assert_eq!(
+            Lang::from_content(b"-- vim: set ft=lua:\nlocal x = 1"),
+            Lang::Lua
+        );
+        assert_eq!(
+            Lang::from_content(b"# -*- mode: ruby; coding: utf-8 -*-\nputs 1"),
+            Lang::Ruby
+        );
+        let tail = format!("{}# vi: filetype=sh\n", "echo\n".repeat(20));
+        assert_eq!(Lang::from_content(tail.as_bytes()), Lang::Shell);
+        assert_eq!(Lang::from_content(b"plain text\n"), Lang::Unknown);
+        // Markers embedded in words are not modelines.
+        assert_eq!(
+            Lang::from_content(b"see index: ft=lua\nnavi: syntax=make\n"),
+            Lang::Unknown
+        );
+    }
+
+    #[test]
+    fn lang_detect_prefers_path() {
+        assert_eq!(Lang::detect("run.py", b"#!/bin/bash\n"), Lang::Python);
+        assert_eq!(Lang::detect("bin/run", b"#!/bin/bash\n"), Lang::Shell);
+    }
+
     #[test]
     fn lang_other_preserved() {
         let lang = Lang::from_wire_byte(0x42);
@@ -484,6 +952,8 @@ mod tests {
             (DataFormat::Yaml, 0x02),
             (DataFormat::Toml, 0x03),
             (DataFormat::Csv, 0x04),
+            (DataFormat::Xml, 0x05),
+            (DataFormat::Ndjson, 0x06),
         ];
         for (variant, wire) in cases {
             assert_eq!(variant.to_wire_byte(), wire);
@@ -499,6 +969,10 @@ mod tests {
             (AnnotationKind::Priority, 0x01),
             (AnnotationKind::Summary, 0x02),
             (AnnotationKind::Tag, 0x03),
+            (AnnotationKind::Relevance, 0x04),
+            (AnnotationKind::Provenance, 0x05),
+            (AnnotationKind::Expiry, 0x06),
+            (AnnotationKind::Link, 0x07),
         ];
         for (variant, wire) in cases {
             assert_eq!(variant.to_wire_byte(), wire);
@@ -506,6 +980,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn link_kind_roundtrip_and_names() {
+        let cases = [
+            (LinkKind::DependsOn, 0x01, "depends-on"),
+            (LinkKind::Tests, 0x02, "tests"),
+            (LinkKind::Explains, 0x03, "explains"),
+        ];
+        for (variant, wire, name) in cases {
+            assert_eq!(variant.to_wire_byte(), wire);
+            assert_eq!(LinkKind::from_wire_byte(wire).unwrap(), variant);
+            assert_eq!(LinkKind::from_name(name), Some(variant));
+        }
+        assert_eq!(LinkKind::from_name("depends_on"), Some(LinkKind::DependsOn));
+        assert_eq!(LinkKind::from_name("imports"), None);
+    }
+
     // ── MediaType tests ───────────────────────────────────────────────
 
     #[test]
@@ -522,4 +1012,36 @@ mod tests {
             assert_eq!(MediaType::from_wire_byte(wire).unwrap(), variant);
         }
     }
+
+    #[test]
+    fn media_type_sniff_magic_bytes() {
+        let cases: [(&[u8], Option<MediaType>); 7] = [
+            (b"\x89PNG\r\n\x1A\n\0\0\0\rIHDR", Some(MediaType::Png)),
+            (&[0xFF, 0xD8, 0xFF, 0xE0], Some(MediaType::Jpeg)),
+            (b"GIF89a\x01\x00", Some(MediaType::Gif)),
+            (b"RIFF\x24\x00\x00\x00WEBPVP8 ", Some(MediaType::Webp)),
+            (b"RIFF\x24\x00\x00\x00WAVEfmt ", None),
+            (b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>", None),
+            (b"\x89PN", None),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(MediaType::sniff(data), expected);
+        }
+    }
+
+    // ── DiffStatus tests ──────────────────────────────────────────────
+
+    #[test]
+    fn diff_status_roundtrip() {
+        let cases = [
+            (DiffStatus::Modified, 0x01),
+            (DiffStatus::Added, 0x02),
+            (DiffStatus::Deleted, 0x03),
+            (DiffStatus::Renamed, 0x04),
+        ];
+        for (variant, wire) in cases {
+            assert_eq!(variant.to_wire_byte(), wire);
+            assert_eq!(DiffStatus::from_wire_byte(wire).unwrap(), variant);
+        }
+    }
 }
@@ -1,5 +1,7 @@
 use bcp_wire::WireError;
 
+use crate::enums::MediaType;
+
 /// Errors that can occur when encoding or decoding typed block bodies.
 ///
 /// These are higher-level than [`WireError`] — they deal with semantic
@@ -15,7 +17,10 @@ use bcp_wire::WireError;
 /// │   ├── wraps WireError for low-level parse failures  │
 /// │   ├── UnknownFieldWireType for bad TLV wire types   │
 /// │   ├── MissingRequiredField for incomplete blocks     │
-/// │   └── InvalidEnumValue for out-of-range enum bytes  │
+/// │   ├── InvalidEnumValue for out-of-range enum bytes  │
+/// │   ├── InvalidFieldLength for fixed-size byte fields │
+/// │   ├── InvalidDiff for malformed unified diff text   │
+/// │   └── MediaTypeMismatch / UnrecognizedImageFormat   │
 /// └─────────────────────────────────────────────────────┘
 /// ```
 #[derive(Debug, thiserror::Error)]
@@ -43,6 +48,50 @@ pub enum TypeError {
     #[error("invalid {enum_name} value: {value:#04X}")]
     InvalidEnumValue { enum_name: &'static str, value: u8 },
 
+    /// A fixed-size bytes field had the wrong length.
+    ///
+    /// For example, a `FileEntry` content hash must be exactly 32 bytes
+    /// (a BLAKE3 digest). Shorter or longer values indicate corruption.
+    #[error("invalid length for {field}: expected {expected} bytes, got {actual}")]
+    InvalidFieldLength {
+        field: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+
+    /// A varint field held a value too large for its type.
+    ///
+    /// For example, a hunk line count above `u32::MAX`, or a DIFF status
+    /// of 257. Such values are rejected rather than truncated, so they
+    /// cannot wrap around to a valid value.
+    #[error("value {value} out of range for {field}")]
+    FieldOutOfRange { field: &'static str, value: u64 },
+
+    /// Unified diff text could not be parsed into DIFF blocks.
+    ///
+    /// Raised by `DiffBlock::parse_unified` when a hunk header is
+    /// malformed or a hunk body is shorter than its `@@` header claims.
+    /// `line` is the 1-indexed line number in the input text.
+    #[error("invalid unified diff at line {line}: {reason}")]
+    InvalidDiff { line: usize, reason: &'static str },
+
+    /// Inline image bytes do not match the declared media type.
+    ///
+    /// Raised by `ImageBlock::check_media_type` when the magic bytes
+    /// identify a different format than the block's `media_type`.
+    #[error("image media type mismatch: declared {declared:?}, detected {detected:?}")]
+    MediaTypeMismatch {
+        declared: MediaType,
+        detected: MediaType,
+    },
+
+    /// Inline image bytes carry no recognised format signature.
+    ///
+    /// Raised by `ImageBlock::from_bytes`, which needs the magic bytes to
+    /// fill in `media_type`.
+    #[error("unrecognized image format")]
+    UnrecognizedImageFormat,
+
     /// An underlying wire-level error occurred while parsing within a body.
     ///
     /// This typically surfaces when a varint inside the block body is
@@ -163,6 +163,17 @@ pub fn decode_varint_value(buf: &[u8]) -> Result<(u64, usize), TypeError> {
     Ok((value, n))
 }
 
+/// Narrow a varint payload value to the field's type, rejecting
+/// values that would otherwise be silently truncated.
+///
+/// # Errors
+///
+/// Returns `TypeError::FieldOutOfRange` naming `field` when `value`
+/// does not fit in `T`.
+pub fn narrow_varint<T: TryFrom<u64>>(value: u64, field: &'static str) -> Result<T, TypeError> {
+    T::try_from(value).map_err(|_| TypeError::FieldOutOfRange { field, value })
+}
+
 /// Read a length-prefixed byte payload from the body.
 ///
 /// Call this after `decode_field_header` returns `FieldWireType::Bytes`
@@ -1,7 +1,7 @@
 use crate::error::TypeError;
 use crate::fields::{
     decode_bytes_value, decode_field_header, decode_varint_value, encode_bytes_field,
-    encode_nested_field, encode_varint_field, skip_field,
+    encode_nested_field, encode_varint_field, narrow_varint, skip_field,
 };
 
 /// FILE_TREE block — represents a directory structure.
@@ -30,39 +30,111 @@ pub struct FileTreeBlock {
     pub entries: Vec<FileEntry>,
 }
 
-/// A single entry in a file tree — either a file or a directory.
+/// A single entry in a file tree — a file, directory, or symlink.
 ///
 /// Nested fields within a `FileEntry`:
 ///
 /// ```text
-/// ┌──────────┬───────────┬──────────┬─────────────────────────────┐
-/// │ Field ID │ Wire Type │ Name     │ Description                 │
-/// ├──────────┼───────────┼──────────┼─────────────────────────────┤
-/// │ 1        │ Bytes     │ name     │ Entry name (not full path)  │
-/// │ 2        │ Varint    │ kind     │ 0=file, 1=directory         │
-/// │ 3        │ Varint    │ size     │ File size in bytes          │
-/// │ 4        │ Nested    │ children │ Repeated FileEntry (dirs)   │
-/// └──────────┴───────────┴──────────┴─────────────────────────────┘
+/// ┌──────────┬───────────┬──────────────┬──────────────────────────────┐
+/// │ Field ID │ Wire Type │ Name         │ Description                  │
+/// ├──────────┼───────────┼──────────────┼──────────────────────────────┤
+/// │ 1        │ Bytes     │ name         │ Entry name (not full path)   │
+/// │ 2        │ Varint    │ kind         │ 0=file, 1=directory, 2=link  │
+/// │ 3        │ Varint    │ size         │ File size in bytes           │
+/// │ 4        │ Nested    │ children     │ Repeated FileEntry (dirs)    │
+/// │ 5        │ Varint    │ mode         │ Unix permission bits (opt)   │
+/// │ 6        │ Varint    │ mtime        │ Modified time, Unix secs     │
+/// │ 7        │ Bytes     │ content_hash │ BLAKE3 hash, 32 bytes (opt)  │
+/// │ 8        │ Bytes     │ link_target  │ Symlink target path (opt)    │
+/// └──────────┴───────────┴──────────────┴──────────────────────────────┘
 /// ```
 ///
 /// The `children` field is recursive: a directory entry contains nested
 /// `FileEntry` values, each encoded as a nested TLV sub-message.
+///
+/// Fields 5–8 are optional and only encoded when `Some`. The
+/// `content_hash` uses the same BLAKE3 digest as content-addressed
+/// blocks, so a renderer can tell when a file in the tree is also
+/// present as a CODE block in the same payload.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct FileEntry {
     pub name: String,
     pub kind: FileEntryKind,
     pub size: u64,
     pub children: Vec<FileEntry>,
+    /// Unix permission bits (e.g. `0o755`).
+    pub mode: Option<u32>,
+    /// Last modification time in seconds since the Unix epoch.
+    pub mtime: Option<u64>,
+    /// BLAKE3 hash of the file contents.
+    pub content_hash: Option<[u8; 32]>,
+    /// Target path for `FileEntryKind::Symlink` entries.
+    pub link_target: Option<String>,
 }
 
-/// Whether a file tree entry is a regular file or a directory.
+/// Whether a file tree entry is a regular file, a directory, or a symlink.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum FileEntryKind {
     File = 0,
     Directory = 1,
+    Symlink = 2,
 }
 
 impl FileEntry {
+    /// A regular file of `size` bytes, with no optional metadata.
+    #[must_use]
+    pub fn file(name: impl Into<String>, size: u64) -> Self {
+        Self::new(name, FileEntryKind::File, size, Vec::new())
+    }
+
+    /// A directory holding `children`, with no optional metadata.
+    #[must_use]
+    pub fn dir(name: impl Into<String>, children: Vec<FileEntry>) -> Self {
+        Self::new(name, FileEntryKind::Directory, 0, children)
+    }
+
+    /// A symbolic link pointing at `target`.
+    #[must_use]
+    pub fn symlink(name: impl Into<String>, target: impl Into<String>) -> Self {
+        let mut entry = Self::new(name, FileEntryKind::Symlink, 0, Vec::new());
+        entry.link_target = Some(target.into());
+        entry
+    }
+
+    fn new(name: impl Into<String>, kind: FileEntryKind, size: u64, children: Vec<Self>) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            size,
+            children,
+            mode: None,
+            mtime: None,
+            content_hash: None,
+            link_target: None,
+        }
+    }
+
+    /// Set the Unix permission bits.
+    #[must_use]
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Set the modification time, in seconds since the Unix epoch.
+    #[must_use]
+    pub fn with_mtime(mut self, mtime: u64) -> Self {
+        self.mtime = Some(mtime);
+        self
+    }
+
+    /// Set the BLAKE3 hash of the file contents.
+    #[must_use]
+    pub fn with_content_hash(mut self, hash: [u8; 32]) -> Self {
+        self.content_hash = Some(hash);
+        self
+    }
+
     /// Encode this entry into TLV bytes (used as nested field payload).
     fn encode(&self) -> Vec<u8> {
         let mut buf = Vec::new();
@@ -72,6 +144,18 @@ impl FileEntry {
         for child in &self.children {
             encode_nested_field(&mut buf, 4, &child.encode());
         }
+        if let Some(mode) = self.mode {
+            encode_varint_field(&mut buf, 5, u64::from(mode));
+        }
+        if let Some(mtime) = self.mtime {
+            encode_varint_field(&mut buf, 6, mtime);
+        }
+        if let Some(ref hash) = self.content_hash {
+            encode_bytes_field(&mut buf, 7, hash);
+        }
+        if let Some(ref target) = self.link_target {
+            encode_bytes_field(&mut buf, 8, target.as_bytes());
+        }
         buf
     }
 
@@ -81,6 +165,10 @@ impl FileEntry {
         let mut kind: Option<FileEntryKind> = None;
         let mut size: u64 = 0;
         let mut children = Vec::new();
+        let mut mode: Option<u32> = None;
+        let mut mtime: Option<u64> = None;
+        let mut content_hash: Option<[u8; 32]> = None;
+        let mut link_target: Option<String> = None;
 
         while !buf.is_empty() {
             let (header, n) = decode_field_header(buf)?;
@@ -98,6 +186,7 @@ impl FileEntry {
                     kind = Some(match v {
                         0 => FileEntryKind::File,
                         1 => FileEntryKind::Directory,
+                        2 => FileEntryKind::Symlink,
                         other => {
                             return Err(TypeError::InvalidEnumValue {
                                 enum_name: "FileEntryKind",
@@ -116,6 +205,31 @@ impl FileEntry {
                     buf = &buf[n..];
                     children.push(FileEntry::decode(data)?);
                 }
+                5 => {
+                    let (v, n) = decode_varint_value(buf)?;
+                    buf = &buf[n..];
+                    mode = Some(narrow_varint(v, "mode")?);
+                }
+                6 => {
+                    let (v, n) = decode_varint_value(buf)?;
+                    buf = &buf[n..];
+                    mtime = Some(v);
+                }
+                7 => {
+                    let (data, n) = decode_bytes_value(buf)?;
+                    buf = &buf[n..];
+                    content_hash =
+                        Some(data.try_into().map_err(|_| TypeError::InvalidFieldLength {
+                            field: "content_hash",
+                            expected: 32,
+                            actual: data.len(),
+                        })?);
+                }
+                8 => {
+                    let (data, n) = decode_bytes_value(buf)?;
+                    buf = &buf[n..];
+                    link_target = Some(String::from_utf8_lossy(data).into_owned());
+                }
                 _ => {
                     let n = skip_field(buf, header.wire_type)?;
                     buf = &buf[n..];
@@ -128,6 +242,10 @@ impl FileEntry {
             kind: kind.ok_or(TypeError::MissingRequiredField { field: "kind" })?,
             size,
             children,
+            mode,
+            mtime,
+            content_hash,
+            link_target,
         })
     }
 }
@@ -186,18 +304,8 @@ mod tests {
         let block = FileTreeBlock {
             root_path: "/project".to_string(),
             entries: vec![
-                FileEntry {
-                    name: "Cargo.toml".to_string(),
-                    kind: FileEntryKind::File,
-                    size: 256,
-                    children: vec![],
-                },
-                FileEntry {
-                    name: "README.md".to_string(),
-                    kind: FileEntryKind::File,
-                    size: 1024,
-                    children: vec![],
-                },
+                FileEntry::file("Cargo.toml", 256),
+                FileEntry::file("README.md", 1024),
             ],
         };
         let body = block.encode_body();
@@ -209,30 +317,13 @@ mod tests {
     fn roundtrip_nested_directories() {
         let block = FileTreeBlock {
             root_path: "/app".to_string(),
-            entries: vec![FileEntry {
-                name: "src".to_string(),
-                kind: FileEntryKind::Directory,
-                size: 0,
-                children: vec![
-                    FileEntry {
-                        name: "main.rs".to_string(),
-                        kind: FileEntryKind::File,
-                        size: 512,
-                        children: vec![],
-                    },
-                    FileEntry {
-                        name: "lib".to_string(),
-                        kind: FileEntryKind::Directory,
-                        size: 0,
-                        children: vec![FileEntry {
-                            name: "utils.rs".to_string(),
-                            kind: FileEntryKind::File,
-                            size: 128,
-                            children: vec![],
-                        }],
-                    },
+            entries: vec![FileEntry::dir(
+                "src",
+                vec![
+                    FileEntry::file("main.rs", 512),
+                    FileEntry::dir("lib", vec![FileEntry::file("utils.rs", 128)]),
                 ],
-            }],
+            )],
         };
         let body = block.encode_body();
         let decoded = FileTreeBlock::decode_body(&body).unwrap();
@@ -249,4 +340,59 @@ mod tests {
         let decoded = FileTreeBlock::decode_body(&body).unwrap();
         assert_eq!(decoded, block);
     }
+
+    #[test]
+    fn roundtrip_entry_metadata() {
+        let block = FileTreeBlock {
+            root_path: "/repo".to_string(),
+            entries: vec![
+                FileEntry::file("build.sh", 64)
+                    .with_mode(0o755)
+                    .with_mtime(1_700_000_000)
+                    .with_content_hash([0xAB; 32]),
+                FileEntry::symlink("latest", "releases/v2"),
+            ],
+        };
+        let body = block.encode_body();
+        let decoded = FileTreeBlock::decode_body(&body).unwrap();
+        assert_eq!(decoded, block);
+    }
+
+    #[test]
+    fn content_hash_wrong_length_rejected() {
+        let mut entry = Vec::new();
+        encode_bytes_field(&mut entry, 1, b"a.rs");
+        encode_varint_field(&mut entry, 2, 0);
+        encode_bytes_field(&mut entry, 7, &[0u8; 16]);
+        let mut body = Vec::new();
+        encode_bytes_field(&mut body, 1, b"/");
+        encode_nested_field(&mut body, 2, &entry);
+
+        let result = FileTreeBlock::decode_body(&body);
+        assert!(matches!(
+            result,
+            Err(TypeError::InvalidFieldLength {
+                field: "content_hash",
+                expected: 32,
+                actual: 16,
+            })
+        ));
+    }
+
+    #[test]
+    fn mode_out_of_range_rejected() {
+        let mut entry = Vec::new();
+        encode_bytes_field(&mut entry, 1, b"a.sh");
+        encode_varint_field(&mut entry, 2, 0);
+        encode_varint_field(&mut entry, 5, u64::from(u32::MAX) + 1);
+        let mut body = Vec::new();
+        encode_bytes_field(&mut body, 1, b"/");
+        encode_nested_field(&mut body, 2, &entry);
+
+        let result = FileTreeBlock::decode_body(&body);
+        assert!(matches!(
+            result,
+            Err(TypeError::FieldOutOfRange { field: "mode", .. })
+        ));
+    }
 }
@@ -2,43 +2,154 @@ use crate::enums::MediaType;
 use crate::error::TypeError;
 use crate::fields::{
     decode_bytes_value, decode_field_header, decode_varint_value, encode_bytes_field,
-    encode_varint_field, skip_field,
+    encode_varint_field, narrow_varint, skip_field,
 };
 
+/// URI scheme for images stored in a [`ContentStore`](crate::ContentStore).
+///
+/// A reference URI of the form `blake3:<64 hex chars>` names an image by
+/// the BLAKE3 hash of its bytes rather than by location.
+pub const CONTENT_HASH_SCHEME: &str = "blake3:";
+
 /// IMAGE block — image content or reference.
 ///
 /// Can carry either inline image bytes or a URI pointing to an external
 /// image. The `media_type` field tells the decoder how to interpret the
-/// `data` payload (PNG, JPEG, SVG, etc.).
+/// pixels, wherever they live (PNG, JPEG, SVG, etc.).
 ///
 /// Field layout within body:
 ///
 /// ```text
-/// ┌──────────┬───────────┬────────────┬──────────────────────────┐
-/// │ Field ID │ Wire Type │ Name       │ Description              │
-/// ├──────────┼───────────┼────────────┼──────────────────────────┤
-/// │ 1        │ Varint    │ media_type │ MediaType enum byte      │
-/// │ 2        │ Bytes     │ alt_text   │ Alt text description     │
-/// │ 3        │ Bytes     │ data       │ Image bytes or URI       │
-/// └──────────┴───────────┴────────────┴──────────────────────────┘
+/// ┌──────────┬───────────┬────────────┬──────────────────────────────┐
+/// │ Field ID │ Wire Type │ Name       │ Description                  │
+/// ├──────────┼───────────┼────────────┼──────────────────────────────┤
+/// │ 1        │ Varint    │ media_type │ MediaType enum byte          │
+/// │ 2        │ Bytes     │ alt_text   │ Alt text description         │
+/// │ 3        │ Bytes     │ data       │ Inline image bytes           │
+/// │ 4        │ Bytes     │ uri        │ External reference (opt)     │
+/// │ 5        │ Varint    │ width      │ Width in pixels (opt)        │
+/// │ 6        │ Varint    │ height     │ Height in pixels (opt)       │
+/// └──────────┴───────────┴────────────┴──────────────────────────────┘
 /// ```
+///
+/// A reference image sets `uri` and leaves `data` empty. The URI is
+/// either a location (`file://`, `https://`) or a content-store hash
+/// (`blake3:<hex>`, see [`CONTENT_HASH_SCHEME`]). Field 3 is always
+/// written so older decoders that require it still accept the block.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ImageBlock {
     pub media_type: MediaType,
     pub alt_text: String,
-    /// Raw image bytes (inline) or a UTF-8 URI string (reference).
-    /// The block's `IS_REFERENCE` flag in `BlockFlags` distinguishes
-    /// between inline data and a URI reference.
+    /// Raw image bytes. Empty when the image is a `uri` reference.
     pub data: Vec<u8>,
+    /// External image location, set instead of inline `data`.
+    pub uri: Option<String>,
+    /// Image width in pixels.
+    pub width: Option<u32>,
+    /// Image height in pixels.
+    pub height: Option<u32>,
 }
 
 impl ImageBlock {
+    /// Build an inline image, filling in `media_type` from the magic bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TypeError::UnrecognizedImageFormat`] when `data` does
+    /// not start with a PNG, JPEG, GIF, or WebP signature.
+    pub fn from_bytes(alt_text: &str, data: &[u8]) -> Result<Self, TypeError> {
+        let media_type = MediaType::sniff(data).ok_or(TypeError::UnrecognizedImageFormat)?;
+        Ok(Self {
+            media_type,
+            alt_text: alt_text.to_string(),
+            data: data.to_vec(),
+            uri: None,
+            width: None,
+            height: None,
+        })
+    }
+
+    /// Build an image that references external pixels by URI.
+    #[must_use]
+    pub fn from_uri(media_type: MediaType, alt_text: &str, uri: &str) -> Self {
+        Self {
+            media_type,
+            alt_text: alt_text.to_string(),
+            data: Vec::new(),
+            uri: Some(uri.to_string()),
+            width: None,
+            height: None,
+        }
+    }
+
+    /// Build an image that references content-store bytes by BLAKE3 hash.
+    #[must_use]
+    pub fn from_content_hash(media_type: MediaType, alt_text: &str, hash: &[u8; 32]) -> Self {
+        use std::fmt::Write;
+        let mut uri = String::from(CONTENT_HASH_SCHEME);
+        for byte in hash {
+            let _ = write!(uri, "{byte:02x}");
+        }
+        Self::from_uri(media_type, alt_text, &uri)
+    }
+
+    /// Whether the image pixels live outside this block.
+    #[must_use]
+    pub fn is_reference(&self) -> bool {
+        self.uri.is_some()
+    }
+
+    /// The BLAKE3 hash named by a `blake3:<hex>` reference URI.
+    ///
+    /// Returns `None` for inline images, other URI schemes, and
+    /// malformed hex.
+    #[must_use]
+    pub fn content_hash(&self) -> Option<[u8; 32]> {
+        let hex = self.uri.as_deref()?.strip_prefix(CONTENT_HASH_SCHEME)?;
+        if hex.len() != 64 || !hex.is_ascii() {
+            return None;
+        }
+        let mut hash = [0u8; 32];
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        Some(hash)
+    }
+
+    /// Check inline bytes against the declared `media_type`.
+    ///
+    /// References and bytes without a recognised signature (including
+    /// SVG markup) pass unchecked.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TypeError::MediaTypeMismatch`] when the magic bytes
+    /// identify a different format.
+    pub fn check_media_type(&self) -> Result<(), TypeError> {
+        match MediaType::sniff(&self.data) {
+            Some(detected) if detected != self.media_type => Err(TypeError::MediaTypeMismatch {
+                declared: self.media_type,
+                detected,
+            }),
+            _ => Ok(()),
+        }
+    }
+
     /// Serialize this block's fields into a TLV-encoded body.
     pub fn encode_body(&self) -> Vec<u8> {
         let mut buf = Vec::new();
         encode_varint_field(&mut buf, 1, u64::from(self.media_type.to_wire_byte()));
         encode_bytes_field(&mut buf, 2, self.alt_text.as_bytes());
         encode_bytes_field(&mut buf, 3, &self.data);
+        if let Some(ref uri) = self.uri {
+            encode_bytes_field(&mut buf, 4, uri.as_bytes());
+        }
+        if let Some(width) = self.width {
+            encode_varint_field(&mut buf, 5, u64::from(width));
+        }
+        if let Some(height) = self.height {
+            encode_varint_field(&mut buf, 6, u64::from(height));
+        }
         buf
     }
 
@@ -47,6 +158,9 @@ impl ImageBlock {
         let mut media_type: Option<MediaType> = None;
         let mut alt_text: Option<String> = None;
         let mut data: Option<Vec<u8>> = None;
+        let mut uri: Option<String> = None;
+        let mut width: Option<u32> = None;
+        let mut height: Option<u32> = None;
 
         while !buf.is_empty() {
             let (header, n) = decode_field_header(buf)?;
@@ -56,7 +170,7 @@ impl ImageBlock {
                 1 => {
                     let (v, n) = decode_varint_value(buf)?;
                     buf = &buf[n..];
-                    media_type = Some(MediaType::from_wire_byte(v as u8)?);
+                    media_type = Some(MediaType::from_wire_byte(narrow_varint(v, "media_type")?)?);
                 }
                 2 => {
                     let (d, n) = decode_bytes_value(buf)?;
@@ -68,6 +182,21 @@ impl ImageBlock {
                     buf = &buf[n..];
                     data = Some(d.to_vec());
                 }
+                4 => {
+                    let (d, n) = decode_bytes_value(buf)?;
+                    buf = &buf[n..];
+                    uri = Some(String::from_utf8_lossy(d).into_owned());
+                }
+                5 => {
+                    let (v, n) = decode_varint_value(buf)?;
+                    buf = &buf[n..];
+                    width = Some(narrow_varint(v, "width")?);
+                }
+                6 => {
+                    let (v, n) = decode_varint_value(buf)?;
+                    buf = &buf[n..];
+                    height = Some(narrow_varint(v, "height")?);
+                }
                 _ => {
                     let n = skip_field(buf, header.wire_type)?;
                     buf = &buf[n..];
@@ -75,12 +204,22 @@ impl ImageBlock {
             }
         }
 
+        // A reference image may omit the inline data field entirely.
+        let data = match data {
+            Some(d) => d,
+            None if uri.is_some() => Vec::new(),
+            None => return Err(TypeError::MissingRequiredField { field: "data" }),
+        };
+
         Ok(Self {
             media_type: media_type.ok_or(TypeError::MissingRequiredField {
                 field: "media_type",
             })?,
             alt_text: alt_text.ok_or(TypeError::MissingRequiredField { field: "alt_text" })?,
-            data: data.ok_or(TypeError::MissingRequiredField { field: "data" })?,
+            data,
+            uri,
+            width,
+            height,
         })
     }
 }
@@ -89,12 +228,17 @@ impl ImageBlock {
 mod tests {
     use super::*;
 
+    const PNG_HEADER: &[u8] = b"\x89PNG\r\n\x1A\n\0\0\0\rIHDR";
+
     #[test]
     fn roundtrip_inline_png() {
         let block = ImageBlock {
             media_type: MediaType::Png,
             alt_text: "A screenshot of the app".to_string(),
             data: vec![0x89, 0x50, 0x4E, 0x47], // PNG magic bytes (truncated)
+            uri: None,
+            width: None,
+            height: None,
         };
         let body = block.encode_body();
         let decoded = ImageBlock::decode_body(&body).unwrap();
@@ -104,12 +248,75 @@ mod tests {
     #[test]
     fn roundtrip_svg_reference() {
         let block = ImageBlock {
-            media_type: MediaType::Svg,
-            alt_text: "Architecture diagram".to_string(),
-            data: b"https://example.com/diagram.svg".to_vec(),
+            width: Some(800),
+            height: Some(600),
+            ..ImageBlock::from_uri(
+                MediaType::Svg,
+                "Architecture diagram",
+                "https://example.com/diagram.svg",
+            )
         };
         let body = block.encode_body();
         let decoded = ImageBlock::decode_body(&body).unwrap();
         assert_eq!(decoded, block);
+        assert!(decoded.is_reference());
+    }
+
+    #[test]
+    fn reference_without_data_field_decodes() {
+        let mut body = Vec::new();
+        encode_varint_field(&mut body, 1, u64::from(MediaType::Png.to_wire_byte()));
+        encode_bytes_field(&mut body, 2, b"logo");
+        encode_bytes_field(&mut body, 4, b"file:///assets/logo.png");
+        let decoded = ImageBlock::decode_body(&body).unwrap();
+        assert!(decoded.data.is_empty());
+        assert_eq!(decoded.uri.as_deref(), Some("file:///assets/logo.png"));
+    }
+
+    #[test]
+    fn content_hash_uri_roundtrip() {
+        let hash = [0x5Au8; 32];
+        let block = ImageBlock::from_content_hash(MediaType::Png, "cached", &hash);
+        assert_eq!(block.uri.as_deref().map(str::len), Some(7 + 64));
+        assert_eq!(block.content_hash(), Some(hash));
+
+        let file = ImageBlock::from_uri(MediaType::Png, "local", "file:///tmp/a.png");
+        assert_eq!(file.content_hash(), None);
+    }
+
+    #[test]
+    fn from_bytes_sniffs_media_type() {
+        let block = ImageBlock::from_bytes("logo", PNG_HEADER).unwrap();
+        assert_eq!(block.media_type, MediaType::Png);
+        assert!(matches!(
+            ImageBlock::from_bytes("notes", b"plain text"),
+            Err(TypeError::UnrecognizedImageFormat)
+        ));
+    }
+
+    #[test]
+    fn check_media_type_detects_mismatch() {
+        let mut block = ImageBlock::from_bytes("logo", PNG_HEADER).unwrap();
+        assert!(block.check_media_type().is_ok());
+
+        block.media_type = MediaType::Jpeg;
+        assert!(matches!(
+            block.check_media_type(),
+            Err(TypeError::MediaTypeMismatch {
+                declared: MediaType::Jpeg,
+                detected: MediaType::Png,
+            })
+        ));
+    }
+
+    #[test]
+    fn width_out_of_range_rejected() {
+        let mut body =
+            ImageBlock::from_uri(MediaType::Png, "wide", "file:///wide.png").encode_body();
+        encode_varint_field(&mut body, 5, u64::from(u32::MAX) + 1);
+        assert!(matches!(
+            ImageBlock::decode_body(&body),
+            Err(TypeError::FieldOutOfRange { field: "width", .. })
+        ));
     }
 }
@@ -16,6 +16,7 @@ pub mod extension;
 pub mod fields;
 pub mod file_tree;
 pub mod image;
+pub mod outline;
 pub mod structured_data;
 pub mod summary;
 pub mod tool_result;
@@ -23,7 +24,10 @@ pub mod tool_result;
 pub use block::{Block, BlockContent};
 pub use block_type::BlockType;
 pub use content_store::{ContentStore, REFERENCE_BODY_SIZE};
-pub use enums::{AnnotationKind, DataFormat, FormatHint, Lang, MediaType, Priority, Role, Status};
+pub use enums::{
+    AnnotationKind, DataFormat, DiffStatus, FormatHint, Lang, LinkKind, MediaType, Priority, Role,
+    Status,
+};
 pub use error::TypeError;
 pub use fields::FieldWireType;
 pub use summary::Summary;
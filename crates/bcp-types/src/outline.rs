@@ -0,0 +1,674 @@
+use crate::code::CodeBlock;
+use crate::enums::Lang;
+
+/// Stands in for an elided body.
+const ELIDED: &str = "...";
+
+/// Header words that make a `{ … }` body part of the outline: the
+/// braces hold declarations rather than statements.
+const CONTAINERS: &[&str] = &[
+    "class",
+    "enum",
+    "extension",
+    "extern",
+    "impl",
+    "import",
+    "input",
+    "interface",
+    "message",
+    "mod",
+    "module",
+    "namespace",
+    "object",
+    "protocol",
+    "record",
+    "service",
+    "struct",
+    "trait",
+    "type",
+    "union",
+    "use",
+];
+
+/// Container words that also start C-style return types
+/// (`struct node *make(void) {`): only a container if no `(` follows.
+const TYPE_CONTAINERS: &[&str] = &["enum", "struct", "union"];
+
+/// Header words that make a `{ … }` body a function body, even if a
+/// container word appears too (`fn iter() -> impl Iterator {`).
+const FUNCTIONS: &[&str] = &["def", "fn", "fun", "func", "function"];
+
+/// Python statements whose indented body is elided.
+const PYTHON_BLOCKS: &[&str] = &[
+    "async", "def", "elif", "else", "except", "finally", "for", "if", "try", "while", "with",
+];
+
+/// Openers of an `end`-terminated body in Ruby, Lua and Elixir.
+const END_BLOCKS: &[&str] = &[
+    "def",
+    "defmacro",
+    "defmacrop",
+    "defp",
+    "function",
+    "local function",
+];
+
+/// How a language marks up comments and strings, for the brace scanner.
+struct Syntax {
+    /// Line comment openers. A `#` only opens a comment at the start of
+    /// a word, so `$#` and `${#x}` in shell are code.
+    line: &'static [&'static str],
+    /// Whether `/* … */` is a comment.
+    block: bool,
+    /// Characters that open a string closed by the same character.
+    quotes: &'static str,
+    /// Whether `'` opens a character literal rather than a string. A `'`
+    /// that doesn't close within a character (a Rust lifetime) is code.
+    char_literals: bool,
+}
+
+/// Outline `source`: its declarations — signatures, type definitions,
+/// imports and comments — with function bodies elided.
+///
+/// Uses lightweight lexical rules per language family, not a parser:
+///
+/// ```text
+/// ┌──────────────────────────────┬───────────────────────────────────┐
+/// │ Languages                    │ Rule                              │
+/// ├──────────────────────────────┼───────────────────────────────────┤
+/// │ Rust, TypeScript, JavaScript │ `{ … }` bodies become `{ ... }`,  │
+/// │ Go, Java, C, C++, Kotlin,    │ unless the header before them     │
+/// │ Swift, C#, PHP, Scala, Zig,  │ declares a type, module or import │
+/// │ Shell, CSS, HCL, Protobuf,   │ (`struct`, `impl`, `class`, …),   │
+/// │ GraphQL                      │ whose members are outlined in turn│
+/// │ Python                       │ indented blocks after `def`, `if`,│
+/// │                              │ … become `...`; classes are kept  │
+/// │                              │ and docstrings survive            │
+/// │ Ruby, Lua, Elixir            │ `def`/`function` bodies become    │
+/// │                              │ `...`, keeping their `end`        │
+/// │ Haskell                      │ unindented declarations and type  │
+/// │                              │ signatures                        │
+/// │ Markdown                     │ headings                          │
+/// └──────────────────────────────┴───────────────────────────────────┘
+/// ```
+///
+/// Returns `None` for other languages, and when the outline would be
+/// empty or elide nothing.
+///
+/// ```text
+/// pub struct Config {                pub struct Config {
+///     pub port: u16,                     pub port: u16,
+/// }                                  }
+///                            ──►
+/// impl Config {                      impl Config {
+///     /// Parse a config file.           /// Parse a config file.
+///     pub fn load(p: &Path) -> Self {    pub fn load(p: &Path) -> Self { ... }
+///         let raw = read(p);         }
+///         toml::from_str(&raw)
+///     }
+/// }
+/// ```
+#[must_use]
+pub fn outline(lang: Lang, source: &str) -> Option<String> {
+    let raw = match lang {
+        Lang::Python => indented(source),
+        Lang::Ruby | Lang::Lua | Lang::Elixir => end_blocks(source),
+        Lang::Haskell => haskell(source),
+        Lang::Markdown => headings(source),
+        lang => braces(source, &syntax(lang)?),
+    };
+    let text = tidy(&raw);
+    (!text.is_empty() && text.len() < source.trim().len()).then_some(text)
+}
+
+impl CodeBlock {
+    /// Outline this block's source; see [`outline`].
+    ///
+    /// Returns `None` for non-UTF-8 content, unsupported languages, and
+    /// sources with nothing to elide.
+    #[must_use]
+    pub fn outline(&self) -> Option<String> {
+        let source = std::str::from_utf8(&self.content).ok()?;
+        outline(self.lang, source)
+    }
+}
+
+fn syntax(lang: Lang) -> Option<Syntax> {
+    let c_like = Syntax {
+        line: &["//"],
+        block: true,
+        quotes: "\"",
+        char_literals: true,
+    };
+    Some(match lang {
+        Lang::Rust
+        | Lang::C
+        | Lang::Cpp
+        | Lang::Java
+        | Lang::Kotlin
+        | Lang::Swift
+        | Lang::CSharp
+        | Lang::Scala
+        | Lang::Zig
+        | Lang::Protobuf => c_like,
+        Lang::Go => Syntax {
+            quotes: "\"`",
+            ..c_like
+        },
+        Lang::TypeScript | Lang::JavaScript => Syntax {
+            quotes: "\"'`",
+            char_literals: false,
+            ..c_like
+        },
+        Lang::Php => Syntax {
+            line: &["//", "#"],
+            quotes: "\"'",
+            char_literals: false,
+            ..c_like
+        },
+        Lang::Css => Syntax {
+            line: &[],
+            quotes: "\"'",
+            char_literals: false,
+            ..c_like
+        },
+        Lang::Hcl => Syntax {
+            line: &["#", "//"],
+            char_literals: false,
+            ..c_like
+        },
+        Lang::Shell => Syntax {
+            line: &["#"],
+            block: false,
+            quotes: "\"'",
+            char_literals: false,
+        },
+        Lang::GraphQL => Syntax {
+            line: &["#"],
+            block: false,
+            char_literals: false,
+            ..c_like
+        },
+        _ => return None,
+    })
+}
+
+/// Whether `rest` starts with `s`.
+fn starts(rest: &[char], s: &str) -> bool {
+    s.chars().count() <= rest.len() && s.chars().zip(rest).all(|(a, b)| a == *b)
+}
+
+/// Length of the comment or string starting at `chars[i]`, if one does.
+/// Line comments stop before their newline.
+fn skip_literal(chars: &[char], i: usize, syntax: &Syntax) -> Option<usize> {
+    let rest = &chars[i..];
+    let until = |from: usize, end: &str| {
+        (from..rest.len())
+            .find(|&j| starts(&rest[j..], end))
+            .map_or(rest.len(), |j| j + end.len())
+    };
+
+    let word_start = i == 0 || chars[i - 1].is_whitespace();
+    if syntax
+        .line
+        .iter()
+        .any(|l| starts(rest, l) && (*l != "#" || word_start))
+    {
+        return Some(rest.iter().position(|&c| c == '\n').unwrap_or(rest.len()));
+    }
+    if syntax.block && starts(rest, "/*") {
+        return Some(until(2, "*/"));
+    }
+    let c = rest[0];
+    if c == '\'' && syntax.char_literals {
+        return match rest {
+            ['\'', '\\', ..] => Some(until(2, "'")),
+            ['\'', _, '\'', ..] => Some(3),
+            _ => None,
+        };
+    }
+    if syntax.quotes.contains(c) {
+        let mut j = 1;
+        while j < rest.len() && rest[j] != c {
+            j += if rest[j] == '\\' { 2 } else { 1 };
+        }
+        return Some((j + 1).min(rest.len()));
+    }
+    None
+}
+
+/// Whether a `{` after `header` opens declarations to keep.
+fn is_container(header: &str) -> bool {
+    if header.contains("=>") {
+        return false;
+    }
+    let mut words: Vec<(usize, &str)> = Vec::new();
+    let mut start = None;
+    for (at, c) in header.char_indices().chain([(header.len(), ' ')]) {
+        match (start, c.is_alphanumeric() || c == '_') {
+            (None, true) => start = Some(at),
+            (Some(s), false) => {
+                words.push((s, &header[s..at]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if words.iter().any(|(_, w)| FUNCTIONS.contains(w)) {
+        return false;
+    }
+    header.trim() == "export"
+        || words.iter().any(|&(at, w)| {
+            CONTAINERS.contains(&w) && !(TYPE_CONTAINERS.contains(&w) && header[at..].contains('('))
+        })
+}
+
+/// Outline a brace-delimited language: copy declarations, collapsing
+/// each function body to `{ ... }`.
+fn braces(source: &str, syntax: &Syntax) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let mut out = String::new();
+    // Code of the statement being read, for `is_container`.
+    let mut header = String::new();
+    let mut line_blank = true;
+    // Inside an elided body: its brace depth and what it held.
+    let mut body: Option<(usize, String)> = None;
+
+    let mut i = 0;
+    while i < chars.len() {
+        if let Some(len) = skip_literal(&chars, i, syntax) {
+            let literal: String = chars[i..i + len].iter().collect();
+            if let Some((_, text)) = &mut body {
+                text.push_str(&literal);
+            } else {
+                out.push_str(&literal);
+                header.push(' ');
+                line_blank = false;
+            }
+            i += len;
+            continue;
+        }
+
+        let c = chars[i];
+        i += 1;
+        if let Some((depth, text)) = &mut body {
+            match c {
+                '{' => *depth += 1,
+                '}' if *depth == 1 => {
+                    if text.trim().is_empty() {
+                        out.push_str(text);
+                        out.push('}');
+                    } else {
+                        out.push(' ');
+                        out.push_str(ELIDED);
+                        out.push_str(" }");
+                    }
+                    body = None;
+                    continue;
+                }
+                '}' => *depth -= 1,
+                _ => {}
+            }
+            text.push(c);
+            continue;
+        }
+
+        out.push(c);
+        match c {
+            '{' => {
+                if !is_container(&header) {
+                    body = Some((1, String::new()));
+                }
+                header.clear();
+            }
+            '}' | ';' => header.clear(),
+            '\n' => {
+                if line_blank {
+                    header.clear();
+                }
+                header.push(c);
+                line_blank = true;
+            }
+            c => {
+                header.push(c);
+                line_blank &= c.is_whitespace();
+            }
+        }
+    }
+    if body.is_some() {
+        out.push(' ');
+        out.push_str(ELIDED);
+    }
+    out
+}
+
+/// Leading whitespace width of `line`.
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// First word of `line`, up to whitespace or punctuation.
+fn first_word(line: &str) -> &str {
+    let line = line.trim_start();
+    let end = line
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+/// Outline Python: keep `class` bodies and each block's header,
+/// replacing the bodies of functions and control flow with `...` after
+/// any docstring.
+fn indented(source: &str) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        out.push(line.to_string());
+        i += 1;
+        if !PYTHON_BLOCKS.contains(&first_word(line)) {
+            continue;
+        }
+        // A signature may wrap; the block opens at the line ending `:`.
+        let mut open = line.trim_end().ends_with(':');
+        let mut parens = bracket_balance(line);
+        while !open && parens > 0 && i < lines.len() {
+            out.push(lines[i].to_string());
+            parens += bracket_balance(lines[i]);
+            open = lines[i].trim_end().ends_with(':');
+            i += 1;
+        }
+        if !open {
+            continue;
+        }
+
+        let outer = indent(line);
+        let end = (i..lines.len())
+            .find(|&j| {
+                !lines[j].trim().is_empty() && indent(lines[j]) <= outer && !in_string(&lines, i, j)
+            })
+            .unwrap_or(lines.len());
+        let Some(first) = (i..end).find(|&j| !lines[j].trim().is_empty()) else {
+            continue;
+        };
+        let pad = &lines[first][..indent(lines[first])];
+        let body = lines[first].trim_start();
+        let quote = ["\"\"\"", "'''"]
+            .into_iter()
+            .find(|q| body.trim_start_matches(['r', 'b', 'u']).starts_with(q));
+        let mut rest = first;
+        if let Some(quote) = quote {
+            let closes_at = if body.matches(quote).count() >= 2 {
+                first
+            } else {
+                (first + 1..end)
+                    .find(|&j| lines[j].contains(quote))
+                    .unwrap_or(end - 1)
+            };
+            out.extend(lines[first..=closes_at].iter().map(|l| (*l).to_string()));
+            rest = closes_at + 1;
+        }
+        if (rest..end).any(|j| !lines[j].trim().is_empty()) {
+            out.push(format!("{pad}{ELIDED}"));
+        }
+        // Blank lines after the body separate it from what follows.
+        i = (rest..end)
+            .rev()
+            .find(|&j| !lines[j].trim().is_empty())
+            .map_or(end, |j| j + 1);
+    }
+    out.join("\n")
+}
+
+/// Net `(`, `[` and `{` left open on `line`.
+fn bracket_balance(line: &str) -> i32 {
+    line.chars()
+        .map(|c| match c {
+            '(' | '[' | '{' => 1,
+            ')' | ']' | '}' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Whether line `j` continues a triple-quoted string opened between
+/// lines `from` and `j`.
+fn in_string(lines: &[&str], from: usize, j: usize) -> bool {
+    let quotes: usize = lines[from..j]
+        .iter()
+        .map(|l| l.matches("\"\"\"").count() + l.matches("'''").count())
+        .sum();
+    quotes % 2 == 1
+}
+
+/// Outline Ruby, Lua or Elixir: replace each `def`/`function` body,
+/// up to the `end` at its own indentation, with `...`.
+fn end_blocks(source: &str) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        out.push(line.to_string());
+        i += 1;
+        let trimmed = line.trim();
+        let opens = END_BLOCKS
+            .iter()
+            .any(|k| trimmed.starts_with(k) && trimmed[k.len()..].starts_with(' '))
+            || trimmed.contains("= function(");
+        let one_line = trimmed.ends_with(" end") || trimmed.contains(", do:");
+        if !opens || one_line {
+            continue;
+        }
+        let outer = indent(line);
+        let end = (i..lines.len())
+            .find(|&j| !lines[j].trim().is_empty() && indent(lines[j]) <= outer)
+            .unwrap_or(lines.len());
+        if let Some(first) = (i..end).find(|&j| !lines[j].trim().is_empty()) {
+            let pad = &lines[first][..indent(lines[first])];
+            out.push(format!("{pad}{ELIDED}"));
+        }
+        i = end;
+    }
+    out.join("\n")
+}
+
+/// Outline Haskell: module header, imports, type signatures and
+/// declarations — every unindented line that doesn't define a value.
+fn haskell(source: &str) -> String {
+    let mut out: Vec<&str> = Vec::new();
+    let mut in_decl = false;
+    for line in source.lines() {
+        if line.trim().is_empty() {
+            out.push(line);
+            continue;
+        }
+        let top = indent(line) == 0;
+        let word = first_word(line);
+        if top {
+            in_decl = matches!(
+                word,
+                "data" | "newtype" | "class" | "module" | "import" | "type"
+            ) || line.contains("::");
+            if in_decl || line.starts_with("--") || line.starts_with("{-") {
+                out.push(line);
+            }
+        } else if in_decl {
+            out.push(line);
+        }
+    }
+    out.join("\n")
+}
+
+/// Outline Markdown: its headings, skipping fenced code.
+fn headings(source: &str) -> String {
+    let mut fenced = false;
+    let mut out = Vec::new();
+    for line in source.lines() {
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+        } else if !fenced && line.starts_with('#') {
+            out.push(line);
+        }
+    }
+    out.join("\n")
+}
+
+/// Trim trailing whitespace, collapse runs of blank lines, and drop
+/// blank lines at either end.
+fn tidy(text: &str) -> String {
+    let mut out: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim_end) {
+        if !(line.is_empty() && out.last().is_none_or(|l| l.is_empty())) {
+            out.push(line);
+        }
+    }
+    while out.last() == Some(&"") {
+        out.pop();
+    }
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rust_elides_function_bodies() {
+        let source = r#"use std::path::{Path, PathBuf};
+
+/// A config.
+#[derive(Debug)]
+pub struct Config<'a> {
+    pub name: &'a str,
+}
+
+impl<'a> Config<'a> {
+    /// Load it.
+    pub fn load(path: &Path) -> Self {
+        let brace = '{';
+        let text = "}";
+        Self { name: "x" }
+    }
+
+    fn empty() {}
+}
+
+fn iter() -> impl Iterator<Item = u8> {
+    [1, 2].into_iter()
+}
+"#;
+        assert_eq!(
+            outline(Lang::Rust, source).unwrap(),
+            r"use std::path::{Path, PathBuf};
+
+/// A config.
+#[derive(Debug)]
+pub struct Config<'a> {
+    pub name: &'a str,
+}
+
+impl<'a> Config<'a> {
+    /// Load it.
+    pub fn load(path: &Path) -> Self { ... }
+
+    fn empty() {}
+}
+
+fn iter() -> impl Iterator<Item = u8> { ... }"
+        );
+    }
+
+    #[test]
+    fn brace_languages_keep_declarations() {
+        let ts = "import { a, b } from \"x\";\nexport class A {\n  run(x: string): void {\n    log(`}${x}`);\n  }\n}\n";
+        assert_eq!(
+            outline(Lang::TypeScript, ts).unwrap(),
+            "import { a, b } from \"x\";\nexport class A {\n  run(x: string): void { ... }\n}"
+        );
+
+        let c =
+            "struct node {\n  int v;\n};\n\nstruct node *make(void) {\n  return 0; /* } */\n}\n";
+        assert_eq!(
+            outline(Lang::C, c).unwrap(),
+            "struct node {\n  int v;\n};\n\nstruct node *make(void) { ... }"
+        );
+
+        let sh = "# Build.\nbuild() {\n  echo \"${#args}\" # }\n}\n";
+        assert_eq!(
+            outline(Lang::Shell, sh).unwrap(),
+            "# Build.\nbuild() { ... }"
+        );
+    }
+
+    #[test]
+    fn python_keeps_classes_and_docstrings() {
+        let source = r#"import os
+
+class Loader:
+    """Loads things."""
+
+    def load(self,
+             path):
+        """Read `path`.
+
+        Returns bytes.
+        """
+        with open(path) as f:
+            return f.read()
+
+    def name(self): return "loader"
+
+if __name__ == "__main__":
+    Loader().load("x")
+"#;
+        assert_eq!(
+            outline(Lang::Python, source).unwrap(),
+            r#"import os
+
+class Loader:
+    """Loads things."""
+
+    def load(self,
+             path):
+        """Read `path`.
+
+        Returns bytes.
+        """
+        ...
+
+    def name(self): return "loader"
+
+if __name__ == "__main__":
+    ..."#
+        );
+    }
+
+    #[test]
+    fn end_languages_keep_end() {
+        let ruby = "class A\n  def run(x)\n    x + 1\n  end\nend\n";
+        assert_eq!(
+            outline(Lang::Ruby, ruby).unwrap(),
+            "class A\n  def run(x)\n    ...\n  end\nend"
+        );
+    }
+
+    #[test]
+    fn other_languages_and_nothing_to_elide() {
+        assert_eq!(outline(Lang::Json, "{\"a\": {\"b\": 1}}"), None);
+        assert_eq!(outline(Lang::Rust, "struct A {\n    x: u8,\n}\n"), None);
+        assert_eq!(
+            outline(Lang::Markdown, "# A\ntext\n```\n# not\n```\n## B\n").unwrap(),
+            "# A\n## B"
+        );
+        assert_eq!(
+            outline(
+                Lang::Haskell,
+                "module M where\n\nf :: Int -> Int\nf x = x + 1\n"
+            )
+            .unwrap(),
+            "module M where\n\nf :: Int -> Int"
+        );
+    }
+}